@@ -0,0 +1,348 @@
+//! Benchmarks of the most common operations, on some representative worlds.
+//!
+//! Run them with `cargo bench`.
+
+use amethyst_core::math::{Isometry3, Point3, Vector3};
+use amethyst_nphysics::{
+    servers_storage::{ServersStorage, ServersStorages},
+    AreaNpServer, ExplosionDesc, ExplosionFalloff, JointNpServer, RBodyNpServer, ShapeNpServer,
+    WorldNpServer,
+};
+use amethyst_physics::{
+    objects::*,
+    servers::{
+        AreaDesc, AreaPhysicsServerTrait, BodyMode, JointDesc, JointPhysicsServerTrait,
+        JointPosition, RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc, ShapePhysicsServerTrait,
+        WorldPhysicsServerTrait,
+    },
+};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+/// Holds the servers and the handles, so the objects are not dropped while measuring.
+struct BenchWorld {
+    storages: ServersStorages<f32>,
+    world: WorldNpServer<f32>,
+    bodies: RBodyNpServer<f32>,
+    areas: AreaNpServer<f32>,
+    shapes: ShapeNpServer<f32>,
+    joints: JointNpServer<f32>,
+    body_handles: Vec<PhysicsHandle<PhysicsRigidBodyTag>>,
+    area_handles: Vec<PhysicsHandle<PhysicsAreaTag>>,
+    shape_handles: Vec<PhysicsHandle<PhysicsShapeTag>>,
+    joint_handles: Vec<PhysicsHandle<PhysicsJointTag>>,
+}
+
+impl BenchWorld {
+    fn new() -> Self {
+        let storages = ServersStorage::new();
+        BenchWorld {
+            world: WorldNpServer::new(storages.clone()),
+            bodies: RBodyNpServer::new(storages.clone()),
+            areas: AreaNpServer::new(storages.clone()),
+            shapes: ShapeNpServer::new(storages.clone()),
+            joints: JointNpServer::new(storages.clone()),
+            storages,
+            body_handles: Vec::new(),
+            area_handles: Vec::new(),
+            shape_handles: Vec::new(),
+            joint_handles: Vec::new(),
+        }
+    }
+
+    fn add_shape(&mut self, desc: &ShapeDesc<f32>) -> PhysicsShapeTag {
+        let handle = self.shapes.create(desc);
+        let tag = handle.get();
+        self.shape_handles.push(handle);
+        tag
+    }
+
+    fn add_body(
+        &mut self,
+        mode: BodyMode,
+        shape: PhysicsShapeTag,
+        position: Vector3<f32>,
+    ) -> PhysicsRigidBodyTag {
+        let mut desc = RigidBodyDesc::default();
+        desc.mode = mode;
+        let handle = self.bodies.create(&desc);
+        let tag = handle.get();
+        self.bodies.set_shape(tag, Some(shape));
+        self.bodies.set_transform(
+            tag,
+            &Isometry3::translation(position.x, position.y, position.z),
+        );
+        self.body_handles.push(handle);
+        tag
+    }
+
+    fn add_ground(&mut self) {
+        let ground = self.add_shape(&ShapeDesc::Cube {
+            half_extents: Vector3::new(100.0, 1.0, 100.0),
+        });
+        self.add_body(BodyMode::Static, ground, Vector3::new(0.0, -1.0, 0.0));
+    }
+
+    /// Steps the world until the bodies are settled enough to measure the steady state.
+    fn warm_up(&self) {
+        for _ in 0..10 {
+            self.world.step();
+        }
+    }
+}
+
+/// 1000 boxes falling on a ground.
+fn falling_boxes() -> BenchWorld {
+    let mut w = BenchWorld::new();
+    w.add_ground();
+    let cube = w.add_shape(&ShapeDesc::Cube {
+        half_extents: Vector3::repeat(0.5),
+    });
+    for i in 0..1000 {
+        let position = Vector3::new(
+            (i % 10) as f32 * 1.5,
+            2.0 + (i / 100) as f32 * 1.5,
+            ((i / 10) % 10) as f32 * 1.5,
+        );
+        w.add_body(BodyMode::Dynamic, cube, position);
+    }
+    w
+}
+
+/// 200 spheres rolling on a trimesh terrain.
+fn trimesh_terrain() -> BenchWorld {
+    const SIZE: usize = 64;
+
+    let mut points = Vec::with_capacity(SIZE * SIZE);
+    for z in 0..SIZE {
+        for x in 0..SIZE {
+            let height = ((x as f32) * 0.3).sin() + ((z as f32) * 0.2).cos();
+            points.push(Point3::new(x as f32, height, z as f32));
+        }
+    }
+    let mut indices = Vec::with_capacity((SIZE - 1) * (SIZE - 1) * 2);
+    for z in 0..SIZE - 1 {
+        for x in 0..SIZE - 1 {
+            let i = z * SIZE + x;
+            indices.push(Point3::new(i, i + SIZE, i + 1));
+            indices.push(Point3::new(i + 1, i + SIZE, i + SIZE + 1));
+        }
+    }
+
+    let mut w = BenchWorld::new();
+    let terrain = w.add_shape(&ShapeDesc::TriMesh { points, indices });
+    w.add_body(BodyMode::Static, terrain, Vector3::zeros());
+    let sphere = w.add_shape(&ShapeDesc::Sphere { radius: 0.5 });
+    for i in 0..200 {
+        let position = Vector3::new(
+            (i % 20) as f32 * 3.0 + 1.0,
+            5.0,
+            (i / 20) as f32 * 6.0 + 1.0,
+        );
+        w.add_body(BodyMode::Dynamic, sphere, position);
+    }
+    w
+}
+
+/// 20 chains of 20 boxes, connected by joints.
+fn joint_chains() -> BenchWorld {
+    let mut w = BenchWorld::new();
+    w.add_ground();
+    let cube = w.add_shape(&ShapeDesc::Cube {
+        half_extents: Vector3::repeat(0.25),
+    });
+    for chain in 0..20 {
+        let mut previous = w.add_body(
+            BodyMode::Static,
+            cube,
+            Vector3::new(chain as f32 * 2.0, 12.0, 0.0),
+        );
+        for link in 1..20 {
+            let body = w.add_body(
+                BodyMode::Dynamic,
+                cube,
+                Vector3::new(chain as f32 * 2.0, 12.0, link as f32 * 0.5),
+            );
+            let joint = w.joints.create(&JointDesc::Fixed, JointPosition::Middle);
+            w.joints.insert_rigid_body(joint.get(), previous);
+            w.joints.insert_rigid_body(joint.get(), body);
+            w.joint_handles.push(joint);
+            previous = body;
+        }
+    }
+    w
+}
+
+/// 200 areas crossed by 500 falling spheres.
+fn sensor_heavy() -> BenchWorld {
+    let mut w = BenchWorld::new();
+    w.add_ground();
+    let area_shape = w.add_shape(&ShapeDesc::Cube {
+        half_extents: Vector3::new(1.0, 5.0, 1.0),
+    });
+    for i in 0..200 {
+        let handle = w.areas.create(&AreaDesc::default());
+        w.areas.set_shape(handle.get(), Some(area_shape));
+        w.areas.set_transform(
+            handle.get(),
+            &Isometry3::translation((i % 20) as f32 * 2.0, 5.0, (i / 20) as f32 * 2.0),
+        );
+        w.area_handles.push(handle);
+    }
+    let sphere = w.add_shape(&ShapeDesc::Sphere { radius: 0.3 });
+    for i in 0..500 {
+        let position = Vector3::new(
+            (i % 25) as f32 * 1.6,
+            12.0 + (i / 250) as f32,
+            ((i / 25) % 10) as f32 * 2.0,
+        );
+        w.add_body(BodyMode::Dynamic, sphere, position);
+    }
+    w
+}
+
+fn bench_step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("step");
+    group.sample_size(20);
+
+    let scenes: [(&str, fn() -> BenchWorld); 4] = [
+        ("falling_boxes", falling_boxes),
+        ("trimesh_terrain", trimesh_terrain),
+        ("joint_chains", joint_chains),
+        ("sensor_heavy", sensor_heavy),
+    ];
+    for (name, scene) in scenes.iter() {
+        let w = scene();
+        w.warm_up();
+        group.bench_function(*name, |b| b.iter(|| w.world.step()));
+    }
+    group.finish();
+}
+
+fn bench_creation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("creation");
+
+    group.bench_function("create_1000_bodies", |b| {
+        b.iter_batched(
+            BenchWorld::new,
+            |w| {
+                (0..1000)
+                    .map(|_| w.bodies.create(&RigidBodyDesc::default()))
+                    .collect::<Vec<_>>()
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("create_many_1000_bodies", |b| {
+        let descs = vec![RigidBodyDesc::default(); 1000];
+        b.iter_batched(
+            BenchWorld::new,
+            |w| w.bodies.create_many(&descs),
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("create_many_with_shape_1000_bodies", |b| {
+        b.iter_batched(
+            || {
+                let mut w = BenchWorld::new();
+                let shape = w.add_shape(&ShapeDesc::Sphere { radius: 0.5 });
+                let spawns: Vec<_> = (0..1000)
+                    .map(|i| {
+                        let position =
+                            Isometry3::translation((i % 32) as f32, (i / 32) as f32, 0.0);
+                        (RigidBodyDesc::default(), shape, position)
+                    })
+                    .collect();
+                (w, spawns)
+            },
+            |(w, spawns)| {
+                let handles = w.bodies.create_many_with_shape(&spawns);
+                w.world.register_inserted_colliders();
+                (w, handles)
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("set_shape_1000_bodies", |b| {
+        b.iter_batched(
+            || {
+                let mut w = BenchWorld::new();
+                let shape = w.add_shape(&ShapeDesc::Sphere { radius: 0.5 });
+                for _ in 0..1000 {
+                    let handle = w.bodies.create(&RigidBodyDesc::default());
+                    w.body_handles.push(handle);
+                }
+                (w, shape)
+            },
+            |(w, shape)| {
+                for handle in w.body_handles.iter() {
+                    w.bodies.set_shape(handle.get(), Some(shape));
+                }
+                w
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+fn bench_queries(c: &mut Criterion) {
+    let mut group = c.benchmark_group("queries");
+
+    let w = falling_boxes();
+    w.warm_up();
+
+    group.bench_function("transforms", |b| {
+        b.iter(|| {
+            for handle in w.body_handles.iter() {
+                criterion::black_box(w.bodies.transform(handle.get()));
+            }
+        })
+    });
+
+    group.bench_function("transforms_snapshot", |b| {
+        b.iter(|| {
+            let snapshot = w.world.transforms_snapshot();
+            for handle in w.body_handles.iter() {
+                criterion::black_box(snapshot.get(handle.get()));
+            }
+        })
+    });
+
+    group.bench_function("island", |b| {
+        let body = w.body_handles[500].get();
+        b.iter(|| w.world.island(body))
+    });
+
+    group.bench_function("usage", |b| b.iter(|| w.storages.usage()));
+
+    // The explosion changes the world, so it's measured on a new one each time.
+    group.bench_function("explode", |b| {
+        b.iter_batched(
+            || {
+                let w = falling_boxes();
+                w.warm_up();
+                w
+            },
+            |w| {
+                w.world.explode(&ExplosionDesc {
+                    center: Point3::new(7.0, 0.0, 7.0),
+                    radius: 10.0,
+                    strength: 20.0,
+                    falloff: ExplosionFalloff::Linear,
+                    occlusion: true,
+                });
+                w
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_step, bench_creation, bench_queries);
+criterion_main!(benches);
@@ -0,0 +1,107 @@
+use amethyst_core::math::Point3;
+use amethyst_physics::PtReal;
+use ncollide3d::bounding_volume::AABB;
+
+use crate::{body::BodyData, body_storage::BodyStorage, WorldNpServer};
+
+// Activation regions
+impl<N: PtReal> WorldNpServer<N> {
+    /// Set the regions where the dynamic rigid bodies are simulated.
+    ///
+    /// The dynamic rigid bodies outside all the regions are kept asleep, and are woken up as soon as
+    /// they are inside a region again.
+    /// Pass an empty `Vec` (default) to simulate all the bodies.
+    pub fn set_activation_regions(&self, regions: Vec<AABB<N>>) {
+        if regions.is_empty() {
+            // Wake up the bodies put to sleep by the regions.
+            let bodies = self.storages.bodies_r();
+            for (key, _) in bodies.iter() {
+                let mut body = bodies.get_body(key).unwrap();
+                if body.out_of_region {
+                    body.out_of_region = false;
+                    body.activate();
+                }
+            }
+        }
+        *self.activation_regions.write().unwrap() = regions;
+    }
+
+    pub fn activation_regions(&self) -> Vec<AABB<N>> {
+        self.activation_regions.read().unwrap().clone()
+    }
+
+    /// Puts to sleep the dynamic rigid bodies outside the activation regions, and wakes up the ones
+    /// that entered a region.
+    pub(crate) fn apply_activation_regions(&self, bodies: &BodyStorage<N>) {
+        let regions = self.activation_regions.read().unwrap();
+        if regions.is_empty() {
+            return;
+        }
+
+        for (key, _) in bodies.iter() {
+            let mut body = bodies.get_body(key).unwrap();
+            if !matches!(body.body_data, BodyData::Rigid { .. }) {
+                continue;
+            }
+
+            if !body.np_body.is_dynamic() {
+                continue;
+            }
+            let position = if let Some(transform) = body.body_transform() {
+                Point3::from(transform.translation.vector)
+            } else {
+                continue;
+            };
+
+            if regions.iter().any(|r| contains(r, &position)) {
+                if body.out_of_region {
+                    body.out_of_region = false;
+                    body.activate();
+                }
+            } else {
+                body.out_of_region = true;
+                body.np_body.deactivate();
+            }
+        }
+    }
+}
+
+pub(crate) fn contains<N: PtReal>(region: &AABB<N>, point: &Point3<N>) -> bool {
+    (0..3).all(|i| region.mins()[i] <= point[i] && point[i] <= region.maxs()[i])
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Point3};
+    use amethyst_physics::servers::{RigidBodyDesc, ShapeDesc};
+    use ncollide3d::bounding_volume::AABB;
+
+    use crate::test_world::TestWorld;
+
+    #[test]
+    fn bodies_outside_the_regions_kept_asleep() {
+        let test = TestWorld::<f32>::new();
+        test.world.set_activation_regions(vec![AABB::new(
+            Point3::new(-5.0, -50.0, -5.0),
+            Point3::new(5.0, 50.0, 5.0),
+        )]);
+
+        let sphere = ShapeDesc::Sphere { radius: 0.5 };
+        let inside = test.create_body(&RigidBodyDesc::default(), &sphere, &Isometry3::identity());
+        let outside = test.create_body(
+            &RigidBodyDesc::default(),
+            &sphere,
+            &Isometry3::translation(20.0, 0.0, 0.0),
+        );
+
+        test.step_n(10);
+        assert!(test.position(inside.get()).y < 0.0);
+        assert_eq!(test.position(outside.get()).y, 0.0);
+        assert!(test.is_sleeping(outside.get()));
+
+        test.world.set_activation_regions(Vec::new());
+        test.step_n(10);
+        assert!(!test.is_sleeping(outside.get()));
+        assert!(test.position(outside.get()).y < 0.0);
+    }
+}
@@ -12,14 +12,17 @@ use nphysics3d::object::{
 };
 
 use crate::{
-    body::{Body, BodyData},
+    body::{Body, BodyData, OverlapDetail},
     conversors::*,
+    error::{LogError, PhysicsError, PhysicsResult},
+    extended_groups::ExtendedGroups,
+    replay::ReplayCall,
     servers_storage::*,
     shape::RigidShape,
     storage::StoreKey,
-    utils::*,
 };
 
+#[allow(missing_debug_implementations)]
 pub struct AreaNpServer<N: PtReal> {
     storages: ServersStorages<N>,
 }
@@ -85,7 +88,7 @@ impl<N: PtReal> AreaNpServer<N> {
         colliders: &mut CollidersStorageWrite<'_, N>,
     ) {
         let mut collider = collider_desc.build(NpBodyPartHandle(area.self_key.unwrap(), 0));
-        AreaNpServer::update_user_data(&mut collider, area);
+        area.set_user_data_to(&mut collider);
 
         let key = colliders.insert_collider(collider);
         area.collider_key = Some(key);
@@ -99,19 +102,16 @@ impl<N: PtReal> AreaNpServer<N> {
         }
     }
 
-    pub fn update_user_data(collider: &mut NpCollider<N, StoreKey>, area: &Body<N>) {
-        collider.set_user_data(Some(Box::new(UserData::new(
-            ObjectType::Area,
-            area.self_key.unwrap(),
-            area.entity,
-        ))));
-    }
-
     pub fn create_collider_desc(body: &Body<N>, shape: &RigidShape<N>) -> NpColliderDesc<N> {
-        NpColliderDesc::new(shape.shape_handle().clone())
+        let mut collider_desc = NpColliderDesc::new(shape.shape_handle().clone())
             .collision_groups(body.np_collision_groups)
             .density(zero())
-            .sensor(true)
+            .sensor(true);
+        // The sensors use the linear prediction as proximity margin.
+        if let Some(margin) = body.proximity_margin {
+            collider_desc.set_linear_prediction(margin);
+        }
+        collider_desc
     }
 }
 
@@ -128,6 +128,232 @@ impl<N: crate::PtReal> AreaNpServer<N> {
     }
 }
 
+// Stale tags
+impl<N: PtReal> AreaNpServer<N> {
+    /// Returns `true` if the tag refers to an existing area.
+    pub fn is_valid(&self, area_tag: PhysicsAreaTag) -> bool {
+        let bodies = self.storages.bodies_r();
+        bodies
+            .get_body(area_tag_to_store_key(area_tag))
+            .map_or(false, |area| matches!(area.body_data, BodyData::Area(_)))
+    }
+
+    /// Converts the tag to the storage key, reporting it when it's stale and the report is on.
+    fn checked_key(&self, area_tag: PhysicsAreaTag) -> StoreKey {
+        #[cfg(feature = "validation")]
+        self.storages.validate();
+
+        if self.storages.report_stale_tags() && !self.is_valid(area_tag) {
+            self.storages.report_stale_tag("area");
+        }
+        area_tag_to_store_key(area_tag)
+    }
+}
+
+// Overlap details
+impl<N: PtReal> AreaNpServer<N> {
+    /// Returns the overlap events of the last step, like `overlap_events`, with the collider and
+    /// the body part that triggered each event.
+    pub fn overlap_details(&self, area_tag: PhysicsAreaTag) -> Vec<OverlapDetail> {
+        let area_key = self.checked_key(area_tag);
+        let bodies = self.storages.bodies_r();
+
+        if let Some(area) = bodies.get_body(area_key) {
+            if let BodyData::Area(e) = &area.body_data {
+                return e.to_vec();
+            }
+        }
+        Vec::new()
+    }
+}
+
+// Extended groups
+impl<N: PtReal> AreaNpServer<N> {
+    /// Set the masks checked after the collision groups, for more than the 30 groups supported by
+    /// NPhysics.
+    pub fn set_extended_groups(&self, area_tag: PhysicsAreaTag, groups: ExtendedGroups) {
+        self.with_area(area_tag, |area| {
+            area.extended_groups = groups;
+            area.sync_collider_user_data(&self.storages.colliders_r());
+            // Setting the collision groups again makes the broad phase filter the pairs again.
+            self.update_collider_collision_groups(area);
+        })
+        .or_report(&self.storages);
+    }
+
+    pub fn extended_groups(&self, area_tag: PhysicsAreaTag) -> ExtendedGroups {
+        self.storages
+            .bodies_r()
+            .get_body(area_tag_to_store_key(area_tag))
+            .map_or(ExtendedGroups::ALL, |area| area.extended_groups)
+    }
+}
+
+// Proximity margin
+impl<N: PtReal> AreaNpServer<N> {
+    /// Set the distance within which the area detects the overlaps; e.g. a door volume that opens
+    /// slightly before the player reaches it, or 0 to detect only the exact overlaps.
+    ///
+    /// The collider is rebuilt, so the overlapping bodies are detected again.
+    pub fn set_proximity_margin(&self, area_tag: PhysicsAreaTag, margin: N) {
+        self.with_area(area_tag, |area| {
+            area.proximity_margin = Some(margin);
+
+            // NPhysics reads the margin only when the collider is built.
+            let shapes = self.storages.shapes_r();
+            if let Some(shape) = area.shape_key.and_then(|key| shapes.get(key)) {
+                let mut colliders = self.storages.colliders_w();
+                let collider_desc = Self::create_collider_desc(area, &shape);
+                Self::drop_collider(area, &mut colliders);
+                Self::install_collider(area, &collider_desc, &mut colliders);
+            }
+        })
+        .or_report(&self.storages);
+    }
+
+    /// Returns the proximity margin set with `set_proximity_margin`, if any.
+    pub fn proximity_margin(&self, area_tag: PhysicsAreaTag) -> Option<N> {
+        self.storages
+            .bodies_r()
+            .get_body(area_tag_to_store_key(area_tag))
+            .and_then(|area| area.proximity_margin)
+    }
+}
+
+// Raw access
+impl<N: PtReal> AreaNpServer<N> {
+    /// Runs `f` with the NPhysics collider of the area, to reach the features not wrapped by the
+    /// server.
+    ///
+    /// This is an advanced API: the server is not aware of the changes done through it, and
+    /// overwrites them the next time it rebuilds or updates the collider. Fails with
+    /// `ShapeMissing` when the area has no shape.
+    pub fn with_np_collider<R>(
+        &self,
+        area_tag: PhysicsAreaTag,
+        f: impl FnOnce(&mut NpCollider<N, StoreKey>) -> R,
+    ) -> PhysicsResult<R> {
+        self.checked_key(area_tag);
+        self.with_area(area_tag, |area| {
+            let collider_key = area.collider_key.ok_or(PhysicsError::ShapeMissing)?;
+            let colliders = self.storages.colliders_r();
+            let mut collider = colliders
+                .get_collider(collider_key)
+                .ok_or(PhysicsError::ShapeMissing)?;
+            Ok(f(&mut *collider))
+        })?
+    }
+}
+
+// Fallible functions
+impl<N: PtReal> AreaNpServer<N> {
+    /// Calls `f` with the area of the tag, or returns the reason why it can't.
+    fn with_area<R>(
+        &self,
+        area_tag: PhysicsAreaTag,
+        f: impl FnOnce(&mut Body<N>) -> R,
+    ) -> PhysicsResult<R> {
+        let bodies = self.storages.bodies_r();
+        let mut area = bodies
+            .get_body(area_tag_to_store_key(area_tag))
+            .ok_or(PhysicsError::BodyNotFound)?;
+        if !matches!(area.body_data, BodyData::Area(_)) {
+            return Err(PhysicsError::WrongBodyType);
+        }
+        Ok(f(&mut *area))
+    }
+
+    /// Like `set_entity`, but returns an error when the area doesn't exist.
+    pub fn try_set_entity(
+        &self,
+        area_tag: PhysicsAreaTag,
+        entity: Option<Entity>,
+    ) -> PhysicsResult<()> {
+        self.with_area(area_tag, |area| {
+            area.entity = entity;
+            area.sync_collider_user_data(&self.storages.colliders_r());
+        })
+    }
+
+    /// Like `set_shape`, but returns an error when the area or the shape doesn't exist.
+    pub fn try_set_shape(
+        &self,
+        area_tag: PhysicsAreaTag,
+        shape_tag: Option<PhysicsShapeTag>,
+    ) -> PhysicsResult<()> {
+        self.with_area(area_tag, |_| ())?;
+        if let Some(shape_tag) = shape_tag {
+            if !self
+                .storages
+                .shapes_r()
+                .has(shape_tag_to_store_key(shape_tag))
+            {
+                return Err(PhysicsError::ShapeMissing);
+            }
+        }
+        self.set_shape(area_tag, shape_tag);
+        Ok(())
+    }
+
+    /// Like `set_transform`, but returns an error when the area doesn't exist.
+    pub fn try_set_transform(
+        &self,
+        area_tag: PhysicsAreaTag,
+        transf: &Isometry3<N>,
+    ) -> PhysicsResult<()> {
+        self.with_area(area_tag, |area| {
+            area.set_body_transform(transf)?;
+            self.storages.track_static_move(area);
+            Ok(())
+        })?
+    }
+
+    /// Like `transform`, but returns an error when the area doesn't exist.
+    pub fn try_transform(&self, area_tag: PhysicsAreaTag) -> PhysicsResult<Isometry3<N>> {
+        self.with_area(area_tag, |area| {
+            area.body_transform()
+                .copied()
+                .ok_or(PhysicsError::WrongBodyType)
+        })?
+    }
+
+    /// Like `overlap_events`, but returns an error when the area doesn't exist.
+    pub fn try_overlap_events(&self, area_tag: PhysicsAreaTag) -> PhysicsResult<Vec<OverlapEvent>> {
+        self.with_area(area_tag, |area| {
+            if let BodyData::Area(e) = &area.body_data {
+                e.iter().map(|d| d.event.clone()).collect()
+            } else {
+                Vec::new()
+            }
+        })
+    }
+}
+
+// Collision layers
+impl<N: PtReal> AreaNpServer<N> {
+    /// Like `set_belong_to`, but the groups are resolved by name.
+    pub fn set_belong_to_layers(
+        &self,
+        area_tag: PhysicsAreaTag,
+        names: &[&str],
+    ) -> PhysicsResult<()> {
+        let groups = self.storages.collision_layers_r().groups(names)?;
+        self.set_belong_to(area_tag, groups);
+        Ok(())
+    }
+
+    /// Like `set_collide_with`, but the groups are resolved by name.
+    pub fn set_collide_with_layers(
+        &self,
+        area_tag: PhysicsAreaTag,
+        names: &[&str],
+    ) -> PhysicsResult<()> {
+        let groups = self.storages.collision_layers_r().groups(names)?;
+        self.set_collide_with(area_tag, groups);
+        Ok(())
+    }
+}
+
 impl<N> AreaPhysicsServerTrait<N> for AreaNpServer<N>
 where
     N: PtReal,
@@ -149,33 +375,30 @@ where
         let mut area = bodies_storage.get_body(a_key).unwrap();
         area.self_key = Some(a_key);
 
-        PhysicsHandle::new(store_key_to_area_tag(a_key), self.storages.gc.clone())
+        let area_tag = store_key_to_area_tag(a_key);
+        self.storages.replay_recorder.record(|| {
+            ReplayCall::CreateArea(
+                area_tag,
+                area_desc.belong_to.clone(),
+                area_desc.collide_with.clone(),
+            )
+        });
+        PhysicsHandle::new(area_tag, self.storages.gc())
     }
 
     fn set_entity(&self, area_tag: PhysicsAreaTag, entity: Option<Entity>) {
-        let area_key = area_tag_to_store_key(area_tag);
-        let bodies = self.storages.bodies_r();
-
-        let area = bodies.get_body(area_key);
-        if let Some(mut area) = area {
-            fail_cond!(!matches!(area.body_data, BodyData::Area(_)));
-            area.entity = entity;
-
-            if let Some(collider_key) = area.collider_key {
-                let colliders = self.storages.colliders_r();
-
-                let collider = colliders.get_collider(collider_key);
-                if let Some(mut collider) = collider {
-                    AreaNpServer::update_user_data(&mut *collider, &*area);
-                } else {
-                    error!("A body is assigned to a collider, but the collider doesn't exist!")
-                }
+        self.checked_key(area_tag);
+        match self.try_set_entity(area_tag, entity) {
+            // Like the other setters, a missing area is reported only as a stale tag.
+            Err(PhysicsError::BodyNotFound) => {}
+            result => {
+                result.or_report(&self.storages);
             }
         }
     }
 
     fn entity(&self, area_tag: PhysicsAreaTag) -> Option<Entity> {
-        let area_key = area_tag_to_store_key(area_tag);
+        let area_key = self.checked_key(area_tag);
         let bodies = self.storages.bodies_r();
 
         let area = bodies.get_body(area_key);
@@ -187,8 +410,11 @@ where
     }
 
     fn set_shape(&self, area_tag: PhysicsAreaTag, shape_tag: Option<PhysicsShapeTag>) {
-        let area_key = area_tag_to_store_key(area_tag);
+        let area_key = self.checked_key(area_tag);
         let shape_key = shape_tag.map(shape_tag_to_store_key);
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::SetAreaShape(area_tag, shape_tag));
 
         let bodies = self.storages.bodies_r();
 
@@ -230,7 +456,7 @@ where
     }
 
     fn shape(&self, area_tag: PhysicsAreaTag) -> Option<PhysicsShapeTag> {
-        let area_key = area_tag_to_store_key(area_tag);
+        let area_key = self.checked_key(area_tag);
         let bodies = self.storages.bodies_r();
 
         let area = bodies.get_body(area_key);
@@ -242,88 +468,221 @@ where
     }
 
     fn set_transform(&self, area_tag: PhysicsAreaTag, transf: &Isometry3<N>) {
-        let body_key = area_tag_to_store_key(area_tag);
+        let body_key = self.checked_key(area_tag);
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::SetAreaTransform(area_tag, *transf));
         let bodies = self.storages.bodies_r();
 
         let area = bodies.get_body(body_key);
         if let Some(mut area) = area {
-            area.set_body_transform(transf);
+            if area
+                .set_body_transform(transf)
+                .or_report(&self.storages)
+                .is_some()
+            {
+                self.storages.track_static_move(&area);
+            }
         }
     }
 
     fn transform(&self, area_tag: PhysicsAreaTag) -> Isometry3<N> {
-        let area_key = area_tag_to_store_key(area_tag);
+        let area_key = self.checked_key(area_tag);
         let bodies = self.storages.bodies_r();
 
         let area = bodies.get_body(area_key);
-        if let Some(area) = area {
-            *area.body_transform()
-        } else {
-            Isometry3::identity()
-        }
+        area.and_then(|area| area.body_transform().copied())
+            .unwrap_or_else(Isometry3::identity)
     }
 
     fn set_belong_to(&self, area_tag: PhysicsAreaTag, groups: Vec<CollisionGroup>) {
-        let area_key = area_tag_to_store_key(area_tag);
+        let area_key = self.checked_key(area_tag);
         let bodies = self.storages.bodies_r();
 
         let area = bodies.get_body(area_key);
         if let Some(mut area) = area {
-            let (_, collide_with) =
-                collision_group_conversor::from_nphysics(&area.np_collision_groups);
-            area.np_collision_groups =
-                collision_group_conversor::to_nphysics(&groups, &collide_with);
+            area.set_belong_to(groups);
             self.update_collider_collision_groups(&area);
         }
     }
 
     fn belong_to(&self, area_tag: PhysicsAreaTag) -> Vec<CollisionGroup> {
-        let area_key = area_tag_to_store_key(area_tag);
+        let area_key = self.checked_key(area_tag);
         let bodies = self.storages.bodies_r();
 
         let area = bodies.get_body(area_key);
         if let Some(area) = area {
-            collision_group_conversor::from_nphysics(&area.np_collision_groups).0
+            area.belong_to.clone()
         } else {
             Vec::new()
         }
     }
 
     fn set_collide_with(&self, area_tag: PhysicsAreaTag, groups: Vec<CollisionGroup>) {
-        let area_key = area_tag_to_store_key(area_tag);
+        let area_key = self.checked_key(area_tag);
         let bodies = self.storages.bodies_r();
 
         let area = bodies.get_body(area_key);
         if let Some(mut area) = area {
-            let (belong_to, _) =
-                collision_group_conversor::from_nphysics(&area.np_collision_groups);
-            area.np_collision_groups = collision_group_conversor::to_nphysics(&belong_to, &groups);
+            area.set_collide_with(groups);
             self.update_collider_collision_groups(&area);
         }
     }
 
     fn collide_with(&self, area_tag: PhysicsAreaTag) -> Vec<CollisionGroup> {
-        let area_key = area_tag_to_store_key(area_tag);
+        let area_key = self.checked_key(area_tag);
         let bodies = self.storages.bodies_r();
 
         let area = bodies.get_body(area_key);
         if let Some(area) = area {
-            collision_group_conversor::from_nphysics(&area.np_collision_groups).1
+            area.collide_with.clone()
         } else {
             Vec::new()
         }
     }
 
     fn overlap_events(&self, area_tag: PhysicsAreaTag) -> Vec<OverlapEvent> {
-        let area_key = area_tag_to_store_key(area_tag);
+        let area_key = self.checked_key(area_tag);
         let bodies = self.storages.bodies_r();
 
         let area = bodies.get_body(area_key);
         if let Some(area) = area {
             if let BodyData::Area(e) = &area.body_data {
-                return e.to_vec();
+                return e.iter().map(|d| d.event.clone()).collect();
             }
         }
         Vec::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Vector3};
+    use amethyst_physics::servers::{
+        AreaDesc, AreaPhysicsServerTrait, OverlapEvent, RBodyPhysicsServerTrait, RigidBodyDesc,
+        ShapeDesc, ShapePhysicsServerTrait, WorldPhysicsServerTrait,
+    };
+
+    use crate::{conversors::rigid_tag_to_store_key, test_world::TestWorld, PhysicsObject};
+
+    #[test]
+    fn overlap_events_cleared_at_the_next_step() {
+        let test = TestWorld::<f32>::new();
+        test.world.set_gravity(&Vector3::zeros());
+
+        let sphere = test.create_body(
+            &RigidBodyDesc::default(),
+            &ShapeDesc::Sphere { radius: 0.5 },
+            &Isometry3::identity(),
+        );
+        let area_shape = test.shapes.create(&ShapeDesc::Cube {
+            half_extents: Vector3::repeat(1.0),
+        });
+        let area = test.areas.create(&AreaDesc::default());
+        test.areas.set_shape(area.get(), Some(area_shape.get()));
+        let empty = test.areas.create(&AreaDesc::default());
+        test.areas.set_shape(empty.get(), Some(area_shape.get()));
+        test.areas
+            .set_transform(empty.get(), &Isometry3::translation(10.0, 0.0, 0.0));
+
+        test.step();
+        let events = test.areas.overlap_events(area.get());
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], OverlapEvent::Enter(..)));
+        test.step();
+        assert!(test.areas.overlap_events(area.get()).is_empty());
+
+        test.bodies
+            .set_transform(sphere.get(), &Isometry3::translation(0.0, 5.0, 0.0));
+        test.step();
+        let events = test.areas.overlap_events(area.get());
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], OverlapEvent::Exit(..)));
+        test.step();
+        assert!(test.areas.overlap_events(area.get()).is_empty());
+        assert!(test.areas.overlap_events(empty.get()).is_empty());
+    }
+
+    #[test]
+    fn overlap_events_delivered_to_their_areas() {
+        let test = TestWorld::<f32>::new();
+        test.world.set_gravity(&Vector3::zeros());
+
+        let area_shape = test.shapes.create(&ShapeDesc::Cube {
+            half_extents: Vector3::repeat(1.0),
+        });
+        let sphere = ShapeDesc::Sphere { radius: 0.2 };
+        let mut areas = Vec::new();
+        let mut expected = Vec::new();
+        for i in 0..3 {
+            let x = i as f32 * 10.0;
+            let area = test.areas.create(&AreaDesc::default());
+            test.areas.set_shape(area.get(), Some(area_shape.get()));
+            test.areas
+                .set_transform(area.get(), &Isometry3::translation(x, 0.0, 0.0));
+
+            let bodies: Vec<_> = [-0.5, 0.5]
+                .iter()
+                .map(|z| {
+                    test.create_body(
+                        &RigidBodyDesc::default(),
+                        &sphere,
+                        &Isometry3::translation(x, 0.0, *z),
+                    )
+                })
+                .collect();
+            expected.push(bodies.iter().map(|b| b.get()).collect::<Vec<_>>());
+            areas.push((area, bodies));
+        }
+        test.step();
+
+        for ((area, _), expected) in areas.iter().zip(expected) {
+            let mut entered: Vec<_> = test
+                .areas
+                .overlap_events(area.get())
+                .into_iter()
+                .map(|e| match e {
+                    OverlapEvent::Enter(body, _) => body,
+                    OverlapEvent::Exit(..) => panic!("No body exited"),
+                })
+                .collect();
+            entered.sort_by_key(|tag| expected.iter().position(|e| e == tag));
+            assert_eq!(entered, expected);
+        }
+    }
+
+    #[test]
+    fn overlap_details_name_the_collider() {
+        let test = TestWorld::<f32>::new();
+        test.world.set_gravity(&Vector3::zeros());
+
+        let sphere = test.create_body(
+            &RigidBodyDesc::default(),
+            &ShapeDesc::Sphere { radius: 0.5 },
+            &Isometry3::identity(),
+        );
+        let area_shape = test.shapes.create(&ShapeDesc::Cube {
+            half_extents: Vector3::repeat(1.0),
+        });
+        let area = test.areas.create(&AreaDesc::default());
+        test.areas.set_shape(area.get(), Some(area_shape.get()));
+        test.step();
+
+        let collider = test
+            .storages
+            .bodies_r()
+            .get_body(rigid_tag_to_store_key(sphere.get()))
+            .unwrap()
+            .collider_key
+            .unwrap();
+        let details = test.areas.overlap_details(area.get());
+        assert_eq!(details.len(), 1);
+        assert!(matches!(details[0].event, OverlapEvent::Enter(body, _) if body == sphere.get()));
+        assert_eq!(details[0].collider, collider);
+        assert_eq!(details[0].body_part, 0);
+        assert_eq!(
+            details[0].other.object,
+            PhysicsObject::RigidBody(sphere.get())
+        );
+    }
+}
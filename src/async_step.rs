@@ -0,0 +1,109 @@
+use std::thread;
+
+use amethyst_physics::PtReal;
+use log::error;
+
+use crate::WorldNpServer;
+
+// Async step
+impl<N: PtReal> WorldNpServer<N> {
+    /// Starts the step on a background thread, and returns immediately.
+    ///
+    /// **No server can be used until `wait_step_done` is called**: a change made meanwhile would
+    /// be applied randomly before or after the step, so in the debug builds the servers panic.
+    /// The only exceptions are `step`, `step_with_delta`, `step_async`, `rewind`, `resimulate`,
+    /// `state_checksum`, `snapshot`, `restore`, `compact`, `explode` and `resize_capsule`, that
+    /// wait for the step before doing anything, and the rigid body getters read from the side
+    /// table: `entity`, `shape`, `belong_to`, `collide_with` and `is_valid`.
+    ///
+    /// To not stall the game thread, do other work and then call `wait_step_done` before reading
+    /// the transforms.
+    pub fn step_async(&self) {
+        self.wait_step_done();
+
+        let world_server = self.clone();
+        let handle = thread::Builder::new()
+            .name("physics_step".to_string())
+            .spawn(move || world_server.step_world(None));
+
+        match handle {
+            Ok(handle) => {
+                self.storages
+                    .set_async_step_thread(Some(handle.thread().id()));
+                *self.async_step.lock().unwrap() = Some(handle);
+            }
+            Err(e) => {
+                error!(
+                    "Was not possible to spawn the step thread, stepping synchronously. {}",
+                    e
+                );
+                self.step_world(None);
+            }
+        }
+    }
+
+    /// Waits for the step started by `step_async` to finish.
+    ///
+    /// Returns immediately when no step is running.
+    pub fn wait_step_done(&self) {
+        let handle = self.async_step.lock().unwrap().take();
+        if let Some(handle) = handle {
+            if handle.join().is_err() {
+                error!("The async physics step panicked.");
+            }
+            self.storages.set_async_step_thread(None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_physics::servers::{RBodyPhysicsServerTrait, RigidBodyDesc};
+
+    use crate::test_world::TestWorld;
+
+    #[test]
+    fn rewind_waits_the_async_step() {
+        let test = TestWorld::<f32>::new();
+        test.world.set_rollback_capacity(5);
+        let body = test.bodies.create(&RigidBodyDesc::default());
+
+        test.step_n(2);
+        let position = test.position(body.get());
+
+        test.world.step_async();
+        assert!(test.world.rewind(1));
+        assert_eq!(test.position(body.get()), position);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "wait_step_done")]
+    fn servers_used_during_the_async_step() {
+        let test = TestWorld::<f32>::new();
+        let body = test.bodies.create(&RigidBodyDesc::default());
+
+        test.world.step_async();
+        test.bodies.transform(body.get());
+    }
+
+    #[test]
+    fn state_readers_wait_the_async_step() {
+        let stepped = TestWorld::<f32>::new();
+        let _body = stepped.bodies.create(&RigidBodyDesc::default());
+        stepped.step();
+
+        let test = TestWorld::<f32>::new();
+        let body = test.bodies.create(&RigidBodyDesc::default());
+        test.world.step_async();
+        assert_eq!(test.world.state_checksum(), stepped.world.state_checksum());
+
+        test.world.step_async();
+        let snapshot = test.world.snapshot();
+        assert_eq!(snapshot.bodies.len(), 1);
+        assert_eq!(
+            snapshot.bodies[0].transform,
+            test.bodies.transform(body.get())
+        );
+    }
+}
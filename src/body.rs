@@ -3,22 +3,36 @@ use amethyst_core::{
     math::{zero, Isometry3},
 };
 use amethyst_physics::{
+    objects::CollisionGroup,
     servers::{ContactEvent, OverlapEvent},
     PtReal,
 };
+use log::error;
 use ncollide3d::pipeline::object::CollisionGroups as NpCollisionGroups;
 use nphysics3d::{
     material::{BasicMaterial, MaterialHandle},
-    object::{Body as NpBody, RigidBody as NpRigidBody},
+    object::{Body as NpBody, Collider as NpCollider, RigidBody as NpRigidBody},
 };
+use smallvec::SmallVec;
 
-use crate::storage::StoreKey;
+use crate::{
+    collider_storage::ColliderStorage,
+    conversors::collision_group_conversor,
+    error::{PhysicsError, PhysicsResult},
+    extended_groups::ExtendedGroups,
+    query::QueryHit,
+    soft_body::SoftBodyAttachment,
+    stable_id::StableId,
+    storage::StoreKey,
+    utils::{ColliderTag, ObjectType, UserData},
+};
 
 /// Store information about a body
 ///
 /// A body is:
 /// - Rigid - RigidBody(Disabled, Dynamic, Static, Kinematic)
 /// - Area - RigidBody(Static)
+/// - Soft - MassSpringSystem
 #[allow(missing_debug_implementations)]
 pub struct Body<N: PtReal> {
     pub self_key: Option<StoreKey>,
@@ -26,9 +40,42 @@ pub struct Body<N: PtReal> {
     pub body_data: BodyData<N>,
     pub collider_key: Option<StoreKey>,
     pub shape_key: Option<StoreKey>,
+    /// The cheap shape used when the body is far from the LOD focus points.
+    pub lod_shape_key: Option<StoreKey>,
+    /// `true` when the collider is using the LOD shape.
+    pub lod_active: bool,
+    /// The joints that constraint this body.
+    pub joints: Vec<StoreKey>,
     pub entity: Option<Entity>,
     pub material_handle: MaterialHandle<N>, // TODO share this material across many bodies
+    pub friction: N,
+    pub bounciness: N,
+    /// The sleep state of the last step, used to notify its changes.
+    pub sleeping: bool,
+    /// `true` when the body is kept asleep because it's outside the activation regions.
+    pub out_of_region: bool,
+    /// The maximum distance the body is moved each step to get out of the overlapping geometry.
+    pub depenetration: Option<N>,
+    /// `true` when the body follows the kinematic platform it's standing on.
+    pub carried_by_platforms: bool,
+    /// `true` when the kinematic body reports the contacts with the not dynamic bodies.
+    pub report_static_contacts: bool,
+    /// `true` when the transform changed since it was last read with
+    /// `WorldNpServer::sync_moved_bodies`; set it with `BodyStorage::mark_moved`, that queues the
+    /// body to be read.
+    pub moved: bool,
     pub np_collision_groups: NpCollisionGroups,
+    /// The groups of `np_collision_groups`, kept to not convert them at each read.
+    pub belong_to: Vec<CollisionGroup>,
+    pub collide_with: Vec<CollisionGroup>,
+    /// The masks checked by the broad phase, after the collision groups.
+    pub extended_groups: ExtendedGroups,
+    /// The tag returned with the contacts and the overlaps of the body collider.
+    pub collider_tag: Option<ColliderTag>,
+    /// The distance within which the area detects the overlaps; `None` uses the NPhysics default.
+    pub proximity_margin: Option<N>,
+    /// The id assigned by the `BodyStorage`, that doesn't change across the snapshots.
+    pub stable_id: StableId,
 }
 
 impl<N: PtReal> Body<N> {
@@ -40,18 +87,37 @@ impl<N: PtReal> Body<N> {
         np_collision_groups: NpCollisionGroups,
         contacts_to_report: usize,
     ) -> Self {
+        let (belong_to, collide_with) =
+            collision_group_conversor::from_nphysics(&np_collision_groups);
         Body {
             self_key: None,
             np_body: np_rigid_body,
             body_data: BodyData::Rigid {
                 contacts_to_report,
-                contacts: Vec::new(),
+                contacts: ContactEvents::new(),
             },
             collider_key: None,
             shape_key: None,
+            lod_shape_key: None,
+            lod_active: false,
+            joints: Vec::new(),
             entity: None,
             material_handle: MaterialHandle::new(BasicMaterial::new(bounciness, friction)),
+            friction,
+            bounciness,
+            sleeping: false,
+            out_of_region: false,
+            depenetration: None,
+            carried_by_platforms: false,
+            report_static_contacts: false,
+            moved: true,
             np_collision_groups,
+            belong_to,
+            collide_with,
+            extended_groups: ExtendedGroups::ALL,
+            collider_tag: None,
+            proximity_margin: None,
+            stable_id: StableId::default(),
         }
     }
 
@@ -60,15 +126,74 @@ impl<N: PtReal> Body<N> {
         np_rigid_body: Box<NpRigidBody<N>>,
         np_collision_groups: NpCollisionGroups,
     ) -> Self {
+        let (belong_to, collide_with) =
+            collision_group_conversor::from_nphysics(&np_collision_groups);
         Body {
             self_key: None,
             np_body: np_rigid_body,
-            body_data: BodyData::Area(Vec::new()),
+            body_data: BodyData::Area(OverlapEvents::new()),
+            collider_key: None,
+            shape_key: None,
+            lod_shape_key: None,
+            lod_active: false,
+            joints: Vec::new(),
+            entity: None,
+            material_handle: MaterialHandle::new(BasicMaterial::new(zero(), zero())),
+            friction: zero(),
+            bounciness: zero(),
+            sleeping: false,
+            out_of_region: false,
+            depenetration: None,
+            carried_by_platforms: false,
+            report_static_contacts: false,
+            moved: true,
+            np_collision_groups,
+            belong_to,
+            collide_with,
+            extended_groups: ExtendedGroups::ALL,
+            collider_tag: None,
+            proximity_margin: None,
+            stable_id: StableId::default(),
+        }
+    }
+
+    /// Creates a Soft Body `Body`
+    pub(crate) fn new_soft_body(
+        np_body: Box<dyn NpBody<N>>,
+        indices: Vec<[usize; 3]>,
+        np_collision_groups: NpCollisionGroups,
+    ) -> Self {
+        let (belong_to, collide_with) =
+            collision_group_conversor::from_nphysics(&np_collision_groups);
+        Body {
+            self_key: None,
+            np_body,
+            body_data: BodyData::Soft {
+                indices,
+                attachments: Vec::new(),
+            },
             collider_key: None,
             shape_key: None,
+            lod_shape_key: None,
+            lod_active: false,
+            joints: Vec::new(),
             entity: None,
             material_handle: MaterialHandle::new(BasicMaterial::new(zero(), zero())),
+            friction: zero(),
+            bounciness: zero(),
+            sleeping: false,
+            out_of_region: false,
+            depenetration: None,
+            carried_by_platforms: false,
+            report_static_contacts: false,
+            moved: true,
             np_collision_groups,
+            belong_to,
+            collide_with,
+            extended_groups: ExtendedGroups::ALL,
+            collider_tag: None,
+            proximity_margin: None,
+            stable_id: StableId::default(),
         }
     }
 
@@ -86,34 +211,120 @@ impl<N: PtReal> Body<N> {
         self.np_body.downcast_mut::<NpRigidBody<N>>()
     }
 
+    /// Set the groups the body belongs to, and update the `np_collision_groups`.
+    pub fn set_belong_to(&mut self, groups: Vec<CollisionGroup>) {
+        self.belong_to = collision_group_conversor::normalize(groups);
+        self.np_collision_groups =
+            collision_group_conversor::to_nphysics(&self.belong_to, &self.collide_with);
+    }
+
+    /// Set the groups the body collides with, and update the `np_collision_groups`.
+    pub fn set_collide_with(&mut self, groups: Vec<CollisionGroup>) {
+        self.collide_with = collision_group_conversor::normalize(groups);
+        self.np_collision_groups =
+            collision_group_conversor::to_nphysics(&self.belong_to, &self.collide_with);
+    }
+
     pub fn activate(&mut self) {
         self.np_body.activate();
     }
 
-    /// Set body transform.
-    pub fn set_body_transform(&mut self, transf: &Isometry3<N>) {
-        match self.body_data {
-            BodyData::Rigid { .. } | BodyData::Area(..) => {
-                if let Some(body) = self.rigid_body_mut() {
-                    body.set_position(*transf);
-                } else {
-                    panic!("Failed to cast the body, to a Rigid Body!");
-                }
+    /// Set the user data, that identifies this body during the collision detection, to the
+    /// collider.
+    ///
+    /// Every collider of the body must get it when it's created.
+    pub fn set_user_data_to(&self, collider: &mut NpCollider<N, StoreKey>) {
+        let object_type = match self.body_data {
+            BodyData::Rigid { .. } => ObjectType::RigidBody,
+            BodyData::Area(..) => ObjectType::Area,
+            BodyData::Soft { .. } => ObjectType::SoftBody,
+        };
+        collider.set_user_data(Some(Box::new(UserData::new(
+            object_type,
+            self.self_key.unwrap(),
+            self.entity,
+            self.extended_groups,
+            self.collider_tag,
+        ))));
+    }
+
+    /// Regenerates the user data of the body collider.
+    ///
+    /// Must be called each time the `entity` changes.
+    pub fn sync_collider_user_data(&self, colliders: &ColliderStorage<N, StoreKey>) {
+        if let Some(collider_key) = self.collider_key {
+            if let Some(mut collider) = colliders.get_collider(collider_key) {
+                self.set_user_data_to(&mut *collider);
+            } else {
+                error!("A body is assigned to a collider, but the collider doesn't exist!");
             }
         }
     }
 
+    /// Set body transform.
+    ///
+    /// Fails with `WrongBodyType` if the body doesn't have a transform, like the soft bodies.
+    pub fn set_body_transform(&mut self, transf: &Isometry3<N>) -> PhysicsResult<()> {
+        let body = self.rigid_body_mut().ok_or(PhysicsError::WrongBodyType)?;
+        body.set_position(*transf);
+        Ok(())
+    }
+
     /// Get body transform.
-    pub fn body_transform(&self) -> &Isometry3<N> {
-        match self.body_data {
-            BodyData::Rigid { .. } | BodyData::Area(..) => {
-                if let Some(body) = self.rigid_body() {
-                    body.position()
-                } else {
-                    panic!("Failed to cast the body, to a Rigid Body!");
-                }
-            }
-        }
+    ///
+    /// Returns `None` if the body doesn't have a transform, like the soft bodies.
+    pub fn body_transform(&self) -> Option<&Isometry3<N>> {
+        self.rigid_body().map(|body| body.position())
+    }
+}
+
+/// Most of the bodies have few contacts each step, so they are stored inline to not allocate.
+pub type ContactEvents<N> = SmallVec<[ContactDetail<N>; 4]>;
+/// Most of the areas have few overlap events each step, so they are stored inline to not allocate.
+pub type OverlapEvents = SmallVec<[OverlapDetail; 4]>;
+
+/// An overlap event, with the collider of the body that triggered it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlapDetail {
+    pub event: OverlapEvent,
+    /// The collider that entered or exited the area.
+    ///
+    /// A body has a single collider, but it's replaced when the body switches to its LOD shape,
+    /// or when its shape changes kind.
+    pub collider: StoreKey,
+    /// The index of the body part the collider is attached to; always 0 for the rigid bodies.
+    pub body_part: usize,
+    /// The body that entered or exited the area, with its entity and collider tag.
+    pub other: QueryHit,
+}
+
+/// The state of a contact, compared to the previous step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContactState {
+    /// The bodies started to touch during the last step.
+    Started,
+    /// The bodies were already touching in the previous step.
+    Persisted,
+    /// The bodies stopped to touch during the last step; the event is the last known contact.
+    Stopped,
+}
+
+/// A contact event, with its state.
+///
+/// The contacts are identified by the other body, so a contact is `Persisted` as long as the two
+/// bodies keep touching, even if the contact point moves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContactDetail<N: PtReal> {
+    pub event: ContactEvent<N>,
+    pub state: ContactState,
+    /// The other body, with its entity and collider tag.
+    pub other: QueryHit,
+}
+
+impl<N: PtReal> ContactDetail<N> {
+    /// Returns `true` if the bodies are touching; `false` if the contact is `Stopped`.
+    pub fn is_touching(&self) -> bool {
+        self.state != ContactState::Stopped
     }
 }
 
@@ -122,7 +333,91 @@ impl<N: PtReal> Body<N> {
 pub enum BodyData<N: PtReal> {
     Rigid {
         contacts_to_report: usize,
-        contacts: Vec<ContactEvent<N>>,
+        contacts: ContactEvents<N>,
     },
-    Area(Vec<OverlapEvent>),
+    Area(OverlapEvents),
+    Soft {
+        /// The triangles of the surface.
+        indices: Vec<[usize; 3]>,
+        attachments: Vec<SoftBodyAttachment<N>>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::{
+        ecs::{Builder, World, WorldExt},
+        math::{Isometry3, Point3, Vector2},
+    };
+    use amethyst_physics::servers::{RBodyPhysicsServerTrait, RigidBodyDesc};
+
+    use crate::{
+        error::PhysicsError, test_world::TestWorld, ExplosionDesc, ExplosionFalloff, SoftBodyDesc,
+        SoftBodyKind, SoftBodyNpServer, SoftBodyShape,
+    };
+
+    #[test]
+    fn collider_user_data_follows_the_entity() {
+        let test = TestWorld::<f32>::new();
+        let ground = test.create_ground();
+        let mut sphere_desc = RigidBodyDesc::default();
+        sphere_desc.contacts_to_report = 1;
+        let sphere = test.create_sphere_on_ground(&sphere_desc);
+
+        // The entity is set after the collider is created.
+        let entity = World::new().create_entity().build();
+        test.bodies.set_entity(ground.get(), Some(entity));
+        test.step_n(30);
+
+        let mut contacts = Vec::new();
+        test.bodies.contact_events(sphere.get(), &mut contacts);
+        assert_eq!(contacts.len(), 1);
+        assert_eq!(contacts[0].other_entity, Some(entity));
+    }
+
+    #[test]
+    fn soft_body_without_transform() {
+        let test = TestWorld::<f32>::new();
+        let soft_bodies = SoftBodyNpServer::new(test.storages.clone());
+        let cloth = soft_bodies.create(&SoftBodyDesc {
+            kind: SoftBodyKind::MassSpring,
+            shape: SoftBodyShape::Grid {
+                position: Isometry3::translation(0.0, 2.0, 0.0),
+                extents: Vector2::new(1.0, 1.0),
+                subdivisions: (2, 2),
+            },
+            mass: 1.0,
+            stiffness: 1000.0,
+            damping_ratio: 0.75,
+            pinned: Vec::new(),
+            margin: 0.05,
+            belong_to: RigidBodyDesc::<f32>::default().belong_to,
+            collide_with: RigidBodyDesc::<f32>::default().collide_with,
+        });
+        let body = test.bodies.create(&RigidBodyDesc::default());
+
+        {
+            let bodies = test.storages.bodies_r();
+            let mut soft_body = bodies.get_body(cloth.0).unwrap();
+            assert!(soft_body.body_transform().is_none());
+            assert_eq!(
+                soft_body.set_body_transform(&Isometry3::identity()),
+                Err(PhysicsError::WrongBodyType)
+            );
+        }
+
+        // The functions that visit all the bodies skip it.
+        test.step();
+        test.world.explode(&ExplosionDesc {
+            center: Point3::new(0.0, 2.0, 0.0),
+            radius: 5.0,
+            strength: 1.0,
+            falloff: ExplosionFalloff::Constant,
+            occlusion: false,
+        });
+        test.step();
+        let snapshot = test.world.transforms_snapshot();
+        assert_eq!(snapshot.len(), 1);
+        assert!(snapshot.get(body.get()).is_some());
+    }
 }
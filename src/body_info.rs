@@ -0,0 +1,150 @@
+use std::sync::RwLock;
+
+use amethyst_core::ecs::Entity;
+use amethyst_physics::{objects::CollisionGroup, PtReal};
+
+use crate::{body::Body, storage::StoreKey};
+
+/// A copy of the rigid body fields that change rarely.
+///
+/// The bodies storage is locked during the whole step, so these fields are kept also in a side
+/// table that the getters can read without locking the storage and the body.
+#[derive(Debug, Clone)]
+pub struct BodyInfo {
+    pub entity: Option<Entity>,
+    pub shape_key: Option<StoreKey>,
+    pub belong_to: Vec<CollisionGroup>,
+    pub collide_with: Vec<CollisionGroup>,
+}
+
+impl BodyInfo {
+    pub fn new<N: PtReal>(body: &Body<N>) -> Self {
+        BodyInfo {
+            entity: body.entity,
+            shape_key: body.shape_key,
+            belong_to: body.belong_to.clone(),
+            collide_with: body.collide_with.clone(),
+        }
+    }
+}
+
+/// The `BodyInfo` of each rigid body, stored in the slot of the body in the bodies arena.
+///
+/// Each slot has its own lock, so changing the info of a body doesn't block the readers of the
+/// others; the lock of the slots list is taken for write only to grow it.
+#[derive(Debug, Default)]
+pub struct BodiesInfo {
+    slots: RwLock<Vec<RwLock<Option<(StoreKey, BodyInfo)>>>>,
+}
+
+impl BodiesInfo {
+    pub fn new() -> Self {
+        BodiesInfo::default()
+    }
+
+    pub fn insert(&self, key: StoreKey, info: BodyInfo) {
+        let (index, _) = key.into_raw_parts();
+        {
+            let slots = self.slots.read().unwrap();
+            if let Some(slot) = slots.get(index) {
+                *slot.write().unwrap() = Some((key, info));
+                return;
+            }
+        }
+
+        let mut slots = self.slots.write().unwrap();
+        if slots.len() <= index {
+            slots.resize_with(index + 1, || RwLock::new(None));
+        }
+        *slots[index].write().unwrap() = Some((key, info));
+    }
+
+    /// Removes the info of the body, returning it.
+    pub fn remove(&self, key: StoreKey) -> Option<BodyInfo> {
+        self.with_slot(key, |slot| slot.take().map(|(_, info)| info))
+            .flatten()
+    }
+
+    /// Calls `f` with the info of the body, if it exists.
+    pub fn get<R>(&self, key: StoreKey, f: impl FnOnce(&BodyInfo) -> R) -> Option<R> {
+        let (index, _) = key.into_raw_parts();
+        let slots = self.slots.read().unwrap();
+        let slot = slots.get(index)?.read().unwrap();
+        match &*slot {
+            Some((slot_key, info)) if *slot_key == key => Some(f(info)),
+            _ => None,
+        }
+    }
+
+    pub fn contains_key(&self, key: StoreKey) -> bool {
+        self.get(key, |_| ()).is_some()
+    }
+
+    /// Returns the keys of all the bodies that have the info.
+    pub fn keys(&self) -> Vec<StoreKey> {
+        self.slots
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|slot| slot.read().unwrap().as_ref().map(|(key, _)| *key))
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.slots.write().unwrap().clear();
+    }
+
+    /// Calls `f` with the slot of the body, when it holds the info of this body and not of one
+    /// previously stored in the same slot.
+    fn with_slot<R>(
+        &self,
+        key: StoreKey,
+        f: impl FnOnce(&mut Option<(StoreKey, BodyInfo)>) -> R,
+    ) -> Option<R> {
+        let (index, _) = key.into_raw_parts();
+        let slots = self.slots.read().unwrap();
+        let mut slot = slots.get(index)?.write().unwrap();
+        if matches!(&*slot, Some((slot_key, _)) if *slot_key == key) {
+            Some(f(&mut slot))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info() -> BodyInfo {
+        BodyInfo {
+            entity: None,
+            shape_key: None,
+            belong_to: vec![CollisionGroup::new(1)],
+            collide_with: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn info_of_a_reused_slot_not_returned() {
+        let bodies_info = BodiesInfo::new();
+        let old_key = StoreKey::from_raw_parts(3, 0);
+        let new_key = StoreKey::from_raw_parts(3, 1);
+
+        bodies_info.insert(old_key, info());
+        assert!(bodies_info.contains_key(old_key));
+        assert!(!bodies_info.contains_key(new_key));
+        assert_eq!(bodies_info.remove(new_key).map(|_| ()), None);
+
+        bodies_info.insert(new_key, info());
+        assert!(!bodies_info.contains_key(old_key));
+        assert_eq!(
+            bodies_info.get(new_key, |info| info.belong_to.len()),
+            Some(1)
+        );
+        assert_eq!(bodies_info.keys(), vec![new_key]);
+
+        assert!(bodies_info.remove(new_key).is_some());
+        assert!(bodies_info.keys().is_empty());
+    }
+}
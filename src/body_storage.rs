@@ -1,12 +1,13 @@
-use std::{cell::UnsafeCell, sync::Mutex};
+use std::{collections::HashMap, sync::Mutex};
 
 use amethyst_physics::PtReal;
-use generational_arena::{Iter, IterMut};
 use nphysics3d::object::{Body as NpBody, BodySet};
 
 use crate::{
     body::Body,
-    storage::{Storage, StorageGuard, StoreKey},
+    stable_id::StableId,
+    statistics::StorageUsage,
+    storage::{Iter, IterMut, Storage, StorageGuard, StoreKey},
 };
 
 #[allow(missing_debug_implementations)]
@@ -14,6 +15,13 @@ pub struct BodyStorage<N: PtReal> {
     storage: Storage<Body<N>>,
     /// A list of removed ID, this list is decremented only when the function `pop_removal_event` is called
     removed: Vec<StoreKey>,
+    /// The bodies by `StableId`.
+    stable_ids: HashMap<StableId, StoreKey>,
+    /// The id assigned to the next inserted body; `0` is never assigned.
+    next_stable_id: u64,
+    /// The bodies with `Body::moved` set, so the moved bodies are synchronized without iterating
+    /// all of them.
+    moved: Mutex<Vec<StoreKey>>,
 }
 
 impl<N: PtReal> BodyStorage<N> {
@@ -21,6 +29,21 @@ impl<N: PtReal> BodyStorage<N> {
         BodyStorage {
             storage: Storage::new(50, 50),
             removed: Vec::new(),
+            stable_ids: HashMap::new(),
+            next_stable_id: 1,
+            moved: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Create a storage that can hold `capacity` objects before growing, and then grows by the
+    /// same amount.
+    pub fn with_capacity(capacity: usize) -> Self {
+        BodyStorage {
+            storage: Storage::new(capacity, capacity.max(1)),
+            removed: Vec::new(),
+            stable_ids: HashMap::new(),
+            next_stable_id: 1,
+            moved: Mutex::new(Vec::new()),
         }
     }
 }
@@ -32,13 +55,96 @@ impl<N: PtReal> Default for BodyStorage<N> {
 }
 
 impl<N: PtReal> BodyStorage<N> {
-    pub fn insert_body(&mut self, body: Body<N>) -> StoreKey {
-        self.storage.insert(body)
+    pub fn insert_body(&mut self, mut body: Body<N>) -> StoreKey {
+        let stable_id = StableId(self.next_stable_id);
+        self.next_stable_id += 1;
+        body.stable_id = stable_id;
+        let moved = body.moved;
+        let key = self.storage.insert(body);
+        self.stable_ids.insert(stable_id, key);
+        if moved {
+            self.moved.get_mut().unwrap().push(key);
+        }
+        key
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.storage.reserve(additional);
     }
 
     pub fn drop_body(&mut self, key: StoreKey) {
-        self.storage.remove(key);
-        self.removed.push(key);
+        if let Some(body) = self.storage.remove(key) {
+            self.stable_ids.remove(&body.stable_id);
+            self.removed.push(key);
+        }
+    }
+
+    /// Drop all the bodies, without notifying their removal.
+    ///
+    /// This must be used only when the `MechanicalWorld` is rebuilt.
+    pub fn clear(&mut self) {
+        self.storage.clear();
+        self.removed.clear();
+        self.stable_ids.clear();
+        self.moved.get_mut().unwrap().clear();
+    }
+
+    /// Releases the memory of the empty storage; see `Storage::shrink_to`.
+    pub fn shrink_to(&mut self, capacity: usize) {
+        self.storage.shrink_to(capacity);
+    }
+
+    /// Sets `Body::moved`, and queues the body for the next `take_moved`.
+    pub fn mark_moved(&self, key: StoreKey, body: &mut Body<N>) {
+        if !body.moved {
+            body.moved = true;
+            self.moved.lock().unwrap().push(key);
+        }
+    }
+
+    /// Returns the keys of the bodies marked as moved since the last call.
+    ///
+    /// The keys of the bodies dropped meanwhile are included, and `Body::moved` is not cleared.
+    /// There is a single queue, so the keys are returned only to the first caller.
+    pub fn take_moved(&self) -> Vec<StoreKey> {
+        std::mem::take(&mut *self.moved.lock().unwrap())
+    }
+
+    /// Returns the key of the body with this `StableId`.
+    pub fn stable_key(&self, stable_id: StableId) -> Option<StoreKey> {
+        self.stable_ids.get(&stable_id).copied()
+    }
+
+    /// Gives back to a restored body the id that it had when the snapshot was taken.
+    pub(crate) fn set_stable_id(&mut self, key: StoreKey, stable_id: StableId) {
+        let old_id = match self.storage.get(key) {
+            Some(mut body) => std::mem::replace(&mut body.stable_id, stable_id),
+            None => return,
+        };
+        self.stable_ids.remove(&old_id);
+        self.stable_ids.insert(stable_id, key);
+        self.next_stable_id = self.next_stable_id.max(stable_id.0 + 1);
+    }
+
+    pub(crate) fn next_stable_id(&self) -> u64 {
+        self.next_stable_id
+    }
+
+    /// Makes sure that the ids given to the new bodies were never given before.
+    pub(crate) fn reserve_stable_ids(&mut self, next_stable_id: u64) {
+        self.next_stable_id = self.next_stable_id.max(next_stable_id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.storage.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.storage.is_empty()
+    }
+
+    pub fn usage(&self) -> StorageUsage {
+        self.storage.usage()
     }
 
     /// Returns a `Mutex` guarded body that can be used safely to get or set data.
@@ -46,11 +152,11 @@ impl<N: PtReal> BodyStorage<N> {
         self.storage.get(key)
     }
 
-    pub fn iter(&self) -> Iter<'_, (UnsafeCell<Body<N>>, Mutex<()>)> {
+    pub fn iter(&self) -> Iter<'_, Body<N>> {
         self.storage.iter()
     }
 
-    pub fn iter_mut(&mut self) -> IterMut<'_, (UnsafeCell<Body<N>>, Mutex<()>)> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, Body<N>> {
         self.storage.iter_mut()
     }
 }
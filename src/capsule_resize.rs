@@ -0,0 +1,211 @@
+use amethyst_core::math::{Isometry3, Translation3, Vector3};
+use amethyst_physics::{objects::*, servers::ShapeDesc, PtReal};
+use ncollide3d::{
+    bounding_volume,
+    pipeline::object::CollisionGroups,
+    query,
+    shape::{Capsule as NcCapsule, Shape as NcShape},
+};
+use nphysics3d::world::GeometricalWorld;
+
+use crate::{
+    collider_storage::ColliderStorage,
+    conversors::*,
+    error::{LogError, PhysicsError, PhysicsResult},
+    replay::ReplayCall,
+    storage::StoreKey,
+    RBodyNpServer, WorldNpServer,
+};
+
+/// The penetration that the resized capsule can gain, before being considered blocked.
+const CLEARANCE_TOLERANCE: f64 = 0.01;
+
+// Capsule resize
+impl<N: PtReal> WorldNpServer<N> {
+    /// Changes the half height of the body capsule, keeping its bottom in place.
+    ///
+    /// The collider is updated in place, so the contacts are not lost. Before resizing, an overlap
+    /// query checks that the new capsule doesn't penetrate the surrounding geometry, like when a
+    /// crouching character stands up under a ceiling; in that case nothing is changed and `false`
+    /// is returned.
+    ///
+    /// The capsule shape must be used only by this body. The running async step is waited, so the
+    /// overlap query sees the stepped positions.
+    pub fn resize_capsule(&self, body_tag: PhysicsRigidBodyTag, half_height: N) -> bool {
+        self.try_resize_capsule(body_tag, half_height)
+            .or_report(&self.storages)
+            .unwrap_or(false)
+    }
+
+    /// Like `resize_capsule`, but returns an error when the body can't be resized.
+    pub fn try_resize_capsule(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+        half_height: N,
+    ) -> PhysicsResult<bool> {
+        self.wait_step_done();
+        let body_key = rigid_tag_to_store_key(body_tag);
+
+        let gw = self.geometrical_world.read().unwrap();
+        let bodies = self.storages.bodies_r();
+        let colliders = self.storages.colliders_r();
+        let shapes = self.storages.shapes_r();
+
+        let mut body = bodies
+            .get_body(body_key)
+            .ok_or(PhysicsError::BodyNotFound)?;
+        if body.lod_active {
+            return Err(PhysicsError::LodActive);
+        }
+        if body.shape_key.is_none() || body.collider_key.is_none() {
+            return Err(PhysicsError::ShapeMissing);
+        }
+
+        let mut shape = shapes.get(body.shape_key.unwrap()).unwrap();
+        if shape.bodies().len() != 1 {
+            return Err(PhysicsError::ShapeShared);
+        }
+        let (old_half_height, radius) = match &*shape.shape_desc() {
+            ShapeDesc::Capsule {
+                half_height,
+                radius,
+            } => (*half_height, *radius),
+            _ => return Err(PhysicsError::WrongShapeType),
+        };
+
+        let collider_key = body.collider_key.unwrap();
+        let (old_position, old_capsule) = {
+            let collider = colliders.get_collider(collider_key).unwrap();
+            (
+                *collider.position(),
+                NcCapsule::new(old_half_height, radius),
+            )
+        };
+
+        // The capsule axis is Y, moving the center by the half height change keeps the bottom in
+        // place.
+        let transform = *body.body_transform().ok_or(PhysicsError::WrongBodyType)?;
+        let offset =
+            Translation3::from(transform.rotation * Vector3::y() * (half_height - old_half_height));
+        let new_position = offset * old_position;
+        let new_capsule = NcCapsule::new(half_height, radius);
+
+        let blocked = is_blocked(
+            &gw,
+            &colliders,
+            body_key,
+            (&old_position, &old_capsule),
+            (&new_position, &new_capsule),
+        );
+        if blocked {
+            return Ok(false);
+        }
+
+        let old_shape_handle = shape.shape_handle().clone();
+        shape.update(&ShapeDesc::Capsule {
+            half_height,
+            radius,
+        });
+        RBodyNpServer::update_collider_shape(
+            &mut *body,
+            &old_shape_handle,
+            &*shape,
+            &colliders,
+            true,
+        );
+
+        body.set_body_transform(&(offset * transform))?;
+        bodies.mark_moved(body_key, &mut *body);
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::ResizeCapsule(body_tag, half_height));
+        Ok(true)
+    }
+}
+
+/// Returns `true` if the new capsule penetrates the geometry of the other bodies more than the old
+/// one.
+fn is_blocked<N: PtReal>(
+    gw: &GeometricalWorld<N, StoreKey, StoreKey>,
+    colliders: &ColliderStorage<N, StoreKey>,
+    body_key: StoreKey,
+    old: (&Isometry3<N>, &NcCapsule<N>),
+    new: (&Isometry3<N>, &NcCapsule<N>),
+) -> bool {
+    let depth = |position: &Isometry3<N>,
+                 capsule: &NcCapsule<N>,
+                 other_position: &Isometry3<N>,
+                 other_shape: &dyn NcShape<N>| {
+        query::contact(position, capsule, other_position, other_shape, N::from(0.0))
+            .map_or(N::from(0.0), |contact| contact.depth)
+    };
+
+    let aabb = bounding_volume::aabb(new.1, new.0);
+    let groups = CollisionGroups::new();
+    for (_, other) in gw.interferences_with_aabb(colliders, &aabb, &groups) {
+        if other.body() == body_key || other.is_sensor() {
+            continue;
+        }
+
+        let old_depth = depth(old.0, old.1, other.position(), other.shape());
+        let new_depth = depth(new.0, new.1, other.position(), other.shape());
+        if new_depth > old_depth + N::from(CLEARANCE_TOLERANCE) {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Vector3};
+    use amethyst_physics::servers::{BodyMode, RigidBodyDesc, ShapeDesc};
+
+    use crate::{error::PhysicsError, test_world::TestWorld};
+
+    #[test]
+    fn capsule_grows_until_the_ceiling() {
+        let test = TestWorld::<f32>::new();
+        let _ground = test.create_ground();
+
+        let mut static_desc = RigidBodyDesc::default();
+        static_desc.mode = BodyMode::Static;
+        let _ceiling = test.create_body(
+            &static_desc,
+            &ShapeDesc::Cube {
+                half_extents: Vector3::new(5.0, 0.5, 5.0),
+            },
+            &Isometry3::translation(0.0, 3.0, 0.0),
+        );
+
+        let mut character_desc = RigidBodyDesc::default();
+        character_desc.mode = BodyMode::Kinematic;
+        let character = test.create_body(
+            &character_desc,
+            &ShapeDesc::Capsule {
+                half_height: 0.5,
+                radius: 0.5,
+            },
+            &Isometry3::translation(0.0, 1.0, 0.0),
+        );
+        let sphere = test.create_body(
+            &RigidBodyDesc::default(),
+            &ShapeDesc::Sphere { radius: 0.5 },
+            &Isometry3::translation(3.0, 0.6, 0.0),
+        );
+        test.step();
+
+        // The top goes from 2.0 to 2.4, below the ceiling at 2.5.
+        assert!(test.world.resize_capsule(character.get(), 0.7));
+        test.assert_position(character.get(), &Vector3::new(0.0, 1.2, 0.0), 0.001);
+
+        // The top would go to 3.0, inside the ceiling.
+        assert!(!test.world.resize_capsule(character.get(), 1.0));
+        test.assert_position(character.get(), &Vector3::new(0.0, 1.2, 0.0), 0.001);
+
+        assert_eq!(
+            test.world.try_resize_capsule(sphere.get(), 1.0),
+            Err(PhysicsError::WrongShapeType)
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use amethyst_core::math::{try_convert, Isometry3, Vector3};
+use amethyst_physics::PtReal;
+
+use crate::storage::StoreKey;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Hasher used to compute the world state checksum.
+///
+/// It's an FNV-1a hasher, that is used in place of the `std` one because the checksum must be the
+/// same across platforms, compiler versions and executions.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct StateHasher {
+    hash: u64,
+}
+
+impl StateHasher {
+    pub(crate) fn new() -> Self {
+        StateHasher {
+            hash: FNV_OFFSET_BASIS,
+        }
+    }
+
+    pub(crate) fn write_u64(&mut self, value: u64) {
+        for byte in value.to_le_bytes().iter() {
+            self.hash ^= u64::from(*byte);
+            self.hash = self.hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub(crate) fn write_key(&mut self, key: StoreKey) {
+        let (index, generation) = key.into_raw_parts();
+        self.write_u64(index as u64);
+        self.write_u64(generation);
+    }
+
+    pub(crate) fn write_real<N: PtReal>(&mut self, value: N) {
+        let value: f64 = try_convert(value).unwrap_or(0.0);
+        // Make sure that `-0.0` and `0.0` produce the same hash.
+        let value = if value == 0.0 { 0.0 } else { value };
+        self.write_u64(value.to_bits());
+    }
+
+    pub(crate) fn write_vector<N: PtReal>(&mut self, vector: &Vector3<N>) {
+        for v in vector.iter() {
+            self.write_real(*v);
+        }
+    }
+
+    pub(crate) fn write_isometry<N: PtReal>(&mut self, isometry: &Isometry3<N>) {
+        self.write_vector(&isometry.translation.vector);
+        for v in isometry.rotation.coords.iter() {
+            self.write_real(*v);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::Vector3;
+
+    use super::StateHasher;
+
+    #[test]
+    fn same_state_same_checksum() {
+        let mut a = StateHasher::new();
+        let mut b = StateHasher::new();
+        a.write_vector(&Vector3::new(1.0f32, -0.0, 3.0));
+        b.write_vector(&Vector3::new(1.0f32, 0.0, 3.0));
+        assert_eq!(a.finish(), b.finish());
+
+        b.write_real(1.0f32);
+        assert_ne!(a.finish(), b.finish());
+    }
+}
@@ -5,7 +5,10 @@ use nphysics3d::object::{
     ColliderRemovalData as NpColliderRemovalData, ColliderSet,
 };
 
-use crate::storage::{Storage, StorageGuard, StoreKey};
+use crate::{
+    statistics::StorageUsage,
+    storage::{Storage, StorageGuard, StoreKey},
+};
 
 #[allow(missing_debug_implementations)]
 pub struct ColliderStorage<N: PtReal, BH: NpBodyHandle> {
@@ -24,6 +27,16 @@ impl<N: PtReal, BH: NpBodyHandle> ColliderStorage<N, BH> {
             removed: Vec::new(),
         }
     }
+
+    /// Create a storage that can hold `capacity` objects before growing, and then grows by the
+    /// same amount.
+    pub fn with_capacity(capacity: usize) -> Self {
+        ColliderStorage {
+            storage: Storage::new(capacity, capacity.max(1)),
+            inserted: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
 }
 
 impl<N: PtReal, BH: NpBodyHandle> Default for ColliderStorage<N, BH> {
@@ -39,6 +52,16 @@ impl<N: PtReal, BH: NpBodyHandle> ColliderStorage<N, BH> {
         key
     }
 
+    pub fn reserve(&mut self, additional: usize) {
+        self.storage.reserve(additional);
+        self.inserted.reserve(additional);
+    }
+
+    /// Returns the number of colliders not yet registered in the `GeometricalWorld`.
+    pub fn pending_insertions(&self) -> usize {
+        self.inserted.len()
+    }
+
     pub fn drop_collider(&mut self, key: StoreKey) {
         let res = self.storage.remove(key);
         if let Some(data) = res {
@@ -48,6 +71,32 @@ impl<N: PtReal, BH: NpBodyHandle> ColliderStorage<N, BH> {
         }
     }
 
+    pub fn len(&self) -> usize {
+        self.storage.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.storage.is_empty()
+    }
+
+    pub fn usage(&self) -> StorageUsage {
+        self.storage.usage()
+    }
+
+    /// Drop all the colliders, without notifying their insertion or removal.
+    ///
+    /// This must be used only when the `GeometricalWorld` is rebuilt.
+    pub fn clear(&mut self) {
+        self.storage.clear();
+        self.inserted.clear();
+        self.removed.clear();
+    }
+
+    /// Releases the memory of the empty storage; see `Storage::shrink_to`.
+    pub fn shrink_to(&mut self, capacity: usize) {
+        self.storage.shrink_to(capacity);
+    }
+
     /// Returns a `Mutex` guarded collider that can be used safely to get or set data.
     pub fn get_collider(&self, key: StoreKey) -> Option<StorageGuard<'_, NpCollider<N, BH>>> {
         self.storage.get(key)
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use amethyst_physics::{objects::CollisionGroup, PtReal};
+use ncollide3d::pipeline::object::CollisionGroups as NcCollisionGroups;
+
+use crate::{
+    error::{PhysicsError, PhysicsResult},
+    WorldNpServer,
+};
+
+/// The names of the collision groups, so the groups can be referred by name rather than by index.
+///
+/// Each group has at most one name; registering a new name for a group replaces the old one.
+#[derive(Debug, Clone, Default)]
+pub struct CollisionLayers {
+    groups: HashMap<String, u8>,
+}
+
+impl CollisionLayers {
+    /// Names the `group`.
+    ///
+    /// Fails with `GroupOutOfRange` if NPhysics doesn't support the group index.
+    pub fn register(&mut self, name: &str, group: CollisionGroup) -> PhysicsResult<()> {
+        if usize::from(group.get()) > NcCollisionGroups::max_group_id() {
+            return Err(PhysicsError::GroupOutOfRange);
+        }
+        self.groups.retain(|_, g| *g != group.get());
+        self.groups.insert(name.to_owned(), group.get());
+        Ok(())
+    }
+
+    /// Returns the group registered with this `name`.
+    pub fn group(&self, name: &str) -> Option<CollisionGroup> {
+        self.groups.get(name).map(|g| CollisionGroup::new(*g))
+    }
+
+    /// Returns the groups registered with these `names`.
+    ///
+    /// Fails with `LayerNotFound` if a name is not registered.
+    pub fn groups(&self, names: &[&str]) -> PhysicsResult<Vec<CollisionGroup>> {
+        names
+            .iter()
+            .map(|name| self.group(name).ok_or(PhysicsError::LayerNotFound))
+            .collect()
+    }
+
+    /// Returns the name of the `group`, if any.
+    pub fn name(&self, group: CollisionGroup) -> Option<&str> {
+        self.groups
+            .iter()
+            .find(|(_, g)| **g == group.get())
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+// Collision layers
+impl<N: PtReal> WorldNpServer<N> {
+    /// Names the collision `group`, so it can be resolved with `collision_layer`.
+    ///
+    /// The names are shared by all the servers of these storages, and are kept when the world is
+    /// restored from a snapshot.
+    pub fn register_collision_layer(&self, name: &str, group: CollisionGroup) -> PhysicsResult<()> {
+        self.storages.collision_layers_w().register(name, group)
+    }
+
+    /// Returns the group registered with this `name`.
+    pub fn collision_layer(&self, name: &str) -> Option<CollisionGroup> {
+        self.storages.collision_layers_r().group(name)
+    }
+
+    /// Returns the groups registered with these `names`; useful to fill the `belong_to` and
+    /// `collide_with` of the descriptors.
+    pub fn collision_layers(&self, names: &[&str]) -> PhysicsResult<Vec<CollisionGroup>> {
+        self.storages.collision_layers_r().groups(names)
+    }
+
+    /// Returns the name of the collision `group`, if any.
+    pub fn collision_layer_name(&self, group: CollisionGroup) -> Option<String> {
+        self.storages
+            .collision_layers_r()
+            .name(group)
+            .map(str::to_owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_physics::{
+        objects::CollisionGroup,
+        servers::{RBodyPhysicsServerTrait, RigidBodyDesc},
+    };
+
+    use crate::{error::PhysicsError, test_world::TestWorld};
+
+    #[test]
+    fn collision_layers_by_name() {
+        let test = TestWorld::<f32>::new();
+
+        test.world
+            .register_collision_layer("player", CollisionGroup::new(1))
+            .unwrap();
+        test.world
+            .register_collision_layer("enemy", CollisionGroup::new(2))
+            .unwrap();
+        assert_eq!(
+            test.world
+                .register_collision_layer("too_far", CollisionGroup::new(40)),
+            Err(PhysicsError::GroupOutOfRange)
+        );
+        assert_eq!(
+            test.world.collision_layer_name(CollisionGroup::new(2)),
+            Some("enemy".to_owned())
+        );
+        assert_eq!(
+            test.world.collision_layers(&["player", "ghost"]),
+            Err(PhysicsError::LayerNotFound)
+        );
+
+        let mut desc = RigidBodyDesc::default();
+        desc.belong_to = test.world.collision_layers(&["player"]).unwrap();
+        let body = test.bodies.create(&desc);
+        test.bodies
+            .set_collide_with_layers(body.get(), &["enemy", "player"])
+            .unwrap();
+
+        let groups = |g: Vec<CollisionGroup>| g.iter().map(|g| g.get()).collect::<Vec<_>>();
+        assert_eq!(groups(test.bodies.belong_to(body.get())), vec![1]);
+        assert_eq!(groups(test.bodies.collide_with(body.get())), vec![1, 2]);
+    }
+}
@@ -0,0 +1,188 @@
+use std::sync::{
+    mpsc::{channel, Receiver, Sender},
+    Arc, Mutex,
+};
+
+use amethyst_core::math::{Isometry3, Vector3};
+use amethyst_physics::{
+    objects::*,
+    servers::{BodyMode, RBodyPhysicsServerTrait},
+    PtReal,
+};
+
+use crate::{RBodyNpServer, WorldNpServer};
+
+/// A deferred call to the `RBodyNpServer`.
+#[derive(Debug, Clone)]
+pub enum PhysicsCommand<N: PtReal> {
+    SetTransform(PhysicsRigidBodyTag, Isometry3<N>),
+    SetMode(PhysicsRigidBodyTag, BodyMode),
+    SetLinearVelocity(PhysicsRigidBodyTag, Vector3<N>),
+    SetAngularVelocity(PhysicsRigidBodyTag, Vector3<N>),
+    ClearForces(PhysicsRigidBodyTag),
+    ApplyForce(PhysicsRigidBodyTag, Vector3<N>),
+    ApplyTorque(PhysicsRigidBodyTag, Vector3<N>),
+    /// Force and position.
+    ApplyForceAtPosition(PhysicsRigidBodyTag, Vector3<N>, Vector3<N>),
+    ApplyImpulse(PhysicsRigidBodyTag, Vector3<N>),
+    ApplyAngularImpulse(PhysicsRigidBodyTag, Vector3<N>),
+    /// Impulse and position.
+    ApplyImpulseAtPosition(PhysicsRigidBodyTag, Vector3<N>, Vector3<N>),
+}
+
+/// A queue of commands that are applied at the beginning of the next step.
+///
+/// Pushing a command doesn't take any lock: the command is sent through a channel, so many
+/// systems can push their commands in parallel without waiting each other. Only the step takes
+/// the lock of the receiving end, to drain it.
+/// The commands pushed by a thread keep their order, while the ones pushed by different threads
+/// interleave; also the ones of a single `extend`.
+/// Clone it to share the same queue.
+///
+/// The getters of the servers don't see the queued commands until the next step.
+///
+/// Only the calls that change the state of a rigid body can be queued; the areas, the shapes and
+/// the joints are changed through their servers, that take the storage locks.
+#[derive(Clone)]
+#[allow(missing_debug_implementations)]
+pub struct CommandQueue<N: PtReal> {
+    sender: Sender<PhysicsCommand<N>>,
+    receiver: Arc<Mutex<Receiver<PhysicsCommand<N>>>>,
+}
+
+impl<N: PtReal> CommandQueue<N> {
+    pub(crate) fn new() -> Self {
+        let (sender, receiver) = channel();
+        CommandQueue {
+            sender,
+            receiver: Arc::new(Mutex::new(receiver)),
+        }
+    }
+
+    pub fn push(&self, command: PhysicsCommand<N>) {
+        // Each clone keeps the receiver alive, so the send can't fail.
+        let _ = self.sender.send(command);
+    }
+
+    pub fn extend<I: IntoIterator<Item = PhysicsCommand<N>>>(&self, commands: I) {
+        for command in commands {
+            self.push(command);
+        }
+    }
+
+    fn take(&self) -> Vec<PhysicsCommand<N>> {
+        self.receiver.lock().unwrap().try_iter().collect()
+    }
+}
+
+// Commands
+impl<N: PtReal> WorldNpServer<N> {
+    /// Returns the queue of the commands applied at the beginning of each step.
+    pub fn command_queue(&self) -> CommandQueue<N> {
+        self.storages.command_queue.clone()
+    }
+
+    /// Applies the queued commands, in the order they were pushed.
+    pub(crate) fn apply_commands(&self) {
+        let commands = self.storages.command_queue.take();
+        if commands.is_empty() {
+            return;
+        }
+
+        let server = RBodyNpServer::new(self.storages.clone());
+        for command in commands {
+            apply_command(&server, command);
+        }
+    }
+}
+
+/// Calls the server function of the command.
+pub(crate) fn apply_command<N: PtReal>(server: &RBodyNpServer<N>, command: PhysicsCommand<N>) {
+    match command {
+        PhysicsCommand::SetTransform(tag, transform) => server.set_transform(tag, &transform),
+        PhysicsCommand::SetMode(tag, mode) => server.set_mode(tag, mode),
+        PhysicsCommand::SetLinearVelocity(tag, velocity) => {
+            server.set_linear_velocity(tag, &velocity)
+        }
+        PhysicsCommand::SetAngularVelocity(tag, velocity) => {
+            server.set_angular_velocity(tag, &velocity)
+        }
+        PhysicsCommand::ClearForces(tag) => server.clear_forces(tag),
+        PhysicsCommand::ApplyForce(tag, force) => server.apply_force(tag, &force),
+        PhysicsCommand::ApplyTorque(tag, torque) => server.apply_torque(tag, &torque),
+        PhysicsCommand::ApplyForceAtPosition(tag, force, position) => {
+            server.apply_force_at_position(tag, &force, &position)
+        }
+        PhysicsCommand::ApplyImpulse(tag, impulse) => server.apply_impulse(tag, &impulse),
+        PhysicsCommand::ApplyAngularImpulse(tag, impulse) => {
+            server.apply_angular_impulse(tag, &impulse)
+        }
+        PhysicsCommand::ApplyImpulseAtPosition(tag, impulse, position) => {
+            server.apply_impulse_at_position(tag, &impulse, &position)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Vector3};
+    use amethyst_physics::servers::{RBodyPhysicsServerTrait, RigidBodyDesc};
+
+    use super::PhysicsCommand;
+    use crate::test_world::TestWorld;
+
+    #[test]
+    fn commands_applied_in_order_at_the_step() {
+        let test = TestWorld::<f32>::new();
+        let body = test.bodies.create(&RigidBodyDesc::default());
+
+        let queue = test.world.command_queue();
+        queue.push(PhysicsCommand::SetTransform(
+            body.get(),
+            Isometry3::translation(0.0, 5.0, 0.0),
+        ));
+        queue.extend(vec![
+            PhysicsCommand::SetTransform(body.get(), Isometry3::translation(0.0, 10.0, 0.0)),
+            PhysicsCommand::SetLinearVelocity(body.get(), Vector3::new(2.0, 0.0, 0.0)),
+            PhysicsCommand::SetLinearVelocity(body.get(), Vector3::new(0.0, 0.0, 0.0)),
+        ]);
+        test.assert_position(body.get(), &Vector3::zeros(), 0.0);
+
+        test.step();
+        test.assert_position(body.get(), &Vector3::new(0.0, 10.0, 0.0), 0.1);
+        assert_eq!(test.bodies.linear_velocity(body.get()).x, 0.0);
+    }
+
+    #[test]
+    fn commands_pushed_by_many_threads() {
+        let test = TestWorld::<f32>::new();
+        let bodies: Vec<_> = (0..4)
+            .map(|_| test.bodies.create(&RigidBodyDesc::default()))
+            .collect();
+
+        let threads: Vec<_> = bodies
+            .iter()
+            .enumerate()
+            .map(|(i, body)| {
+                let queue = test.world.command_queue();
+                let tag = body.get();
+                std::thread::spawn(move || {
+                    for y in 0..=i {
+                        queue.push(PhysicsCommand::SetTransform(
+                            tag,
+                            Isometry3::translation(0.0, y as f32, 0.0),
+                        ));
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        test.step();
+        for (i, body) in bodies.iter().enumerate() {
+            test.assert_position(body.get(), &Vector3::new(0.0, i as f32, 0.0), 0.1);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use amethyst_physics::PtReal;
+
+use crate::{
+    body::BodyData,
+    error::{PhysicsError, PhysicsResult},
+    servers_storage::StorageCapacities,
+    world_snapshot::RestoredHandles,
+    WorldNpServer,
+};
+
+// Compaction
+impl<N: PtReal> WorldNpServer<N> {
+    /// Rebuilds the storages, releasing the memory of the destroyed objects; useful after a level
+    /// unload, since the storages never shrink.
+    ///
+    /// The world is rebuilt from a `snapshot`, so the rules of `restore` apply: all the tags become
+    /// invalid, and the returned `RestoredHandles` maps the old tags to the new objects.
+    /// Dropping the old handles doesn't affect the new objects.
+    ///
+    /// The soft bodies and the force generators, also the ones of the wheels, are not part of the
+    /// snapshot, so the compaction fails with `NotSnapshotable` while the world contains any.
+    /// The running async step is waited before checking them.
+    pub fn compact(&self) -> PhysicsResult<RestoredHandles> {
+        self.wait_step_done();
+        let has_soft_bodies = {
+            let bodies = self.storages.bodies_r();
+            bodies.iter().any(|(key, _)| {
+                bodies.get_body(key).map_or(false, |body| {
+                    matches!(body.body_data, BodyData::Soft { .. })
+                })
+            })
+        };
+        if has_soft_bodies || self.storages.force_generator_r().usage().len > 0 {
+            return Err(PhysicsError::NotSnapshotable);
+        }
+
+        let snapshot = self.snapshot();
+
+        let defaults = StorageCapacities::default();
+        let bodies = snapshot.bodies.len() + snapshot.areas.len();
+        let capacities = StorageCapacities {
+            bodies: bodies.max(defaults.bodies),
+            colliders: bodies.max(defaults.colliders),
+            shapes: snapshot.shapes.len().max(defaults.shapes),
+            joints: snapshot.joints.len().max(defaults.joints),
+            force_generators: defaults.force_generators,
+        };
+
+        Ok(self.restore_with_capacities(&snapshot, Some(capacities)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Vector3};
+    use amethyst_physics::servers::{BodyMode, RBodyPhysicsServerTrait, RigidBodyDesc};
+
+    use crate::{error::PhysicsError, test_world::TestWorld, ForceGeneratorNpServer, WindDesc};
+
+    #[test]
+    fn compaction_releases_the_destroyed_bodies_memory() {
+        let test = TestWorld::<f32>::new();
+        let mut desc = RigidBodyDesc::default();
+        desc.mode = BodyMode::Static;
+
+        let mut bodies: Vec<_> = (0..200).map(|_| test.bodies.create(&desc)).collect();
+        let kept = bodies.remove(0);
+        test.bodies
+            .set_transform(kept.get(), &Isometry3::translation(1.0, 2.0, 3.0));
+        drop(bodies);
+        test.step();
+        assert!(test.storages.usage().bodies.capacity >= 200);
+
+        let restored = test.world.compact().unwrap();
+        let usage = test.storages.usage();
+        assert_eq!(usage.bodies.len, 1);
+        assert_eq!(usage.bodies.capacity, 50);
+
+        assert_eq!(restored.bodies.len(), 1);
+        let (old_tag, new_handle) = &restored.bodies[0];
+        assert_eq!(*old_tag, kept.get());
+        test.assert_position(new_handle.get(), &Vector3::new(1.0, 2.0, 3.0), 0.0);
+    }
+
+    #[test]
+    fn old_tags_invalid_after_the_compaction() {
+        let test = TestWorld::<f32>::new();
+        let body = test.bodies.create(&RigidBodyDesc::default());
+
+        let restored = test.world.compact().unwrap();
+        assert!(!test.bodies.is_valid(body.get()));
+        assert!(test.bodies.is_valid(restored.bodies[0].1.get()));
+
+        // The new objects take the freed slots, but not the old tags.
+        drop(restored);
+        test.step();
+        let new_body = test.bodies.create(&RigidBodyDesc::default());
+        assert_ne!(new_body.get(), body.get());
+        assert!(!test.bodies.is_valid(body.get()));
+    }
+
+    #[test]
+    fn compaction_refused_with_the_force_generators() {
+        let test = TestWorld::<f32>::new();
+        let _body = test.bodies.create(&RigidBodyDesc::default());
+        let force_generators = ForceGeneratorNpServer::new(test.storages.clone());
+        force_generators.create_wind(&WindDesc::default());
+
+        assert_eq!(
+            test.world.compact().err(),
+            Some(PhysicsError::NotSnapshotable)
+        );
+        assert_eq!(test.storages.usage().force_generators.len, 1);
+    }
+
+    #[test]
+    fn stable_ids_survive_the_compaction() {
+        let test = TestWorld::<f32>::new();
+
+        let body_a = test.bodies.create(&RigidBodyDesc::default());
+        let body_b = test.bodies.create(&RigidBodyDesc::default());
+        let id_a = test.world.rigid_body_stable_id(body_a.get()).unwrap();
+        let id_b = test.world.rigid_body_stable_id(body_b.get()).unwrap();
+        assert_ne!(id_a, id_b);
+
+        drop(body_a);
+        test.step();
+        let handles = test.world.compact().unwrap();
+
+        assert_eq!(test.world.rigid_body_by_stable_id(id_a), None);
+        let restored_b = test.world.rigid_body_by_stable_id(id_b).unwrap();
+        let (_, handle_b) = handles
+            .bodies
+            .iter()
+            .find(|(old_tag, _)| *old_tag == body_b.get())
+            .unwrap();
+        assert_eq!(restored_b, handle_b.get());
+
+        // The ids are never given again.
+        let body_c = test.bodies.create(&RigidBodyDesc::default());
+        let id_c = test.world.rigid_body_stable_id(body_c.get()).unwrap();
+        assert!(id_c > id_a && id_c > id_b);
+    }
+}
@@ -12,6 +12,9 @@ macro_rules! matches {
 /// the functions processing print a message on the console.
 ///
 /// You can use the macro fail_cond(expression, return) if you need to return a value in case of fail.
+///
+/// The crate doesn't use them anymore: the internal functions return a `PhysicsError`, that is
+/// logged only by the functions that can't return it. They are kept for compatibility.
 #[macro_export]
 macro_rules! fail_cond {
     ($x:expr) => {{
@@ -0,0 +1,189 @@
+use amethyst_core::{
+    ecs::{Entity, System, Write},
+    shrev::EventChannel,
+};
+use amethyst_physics::{objects::*, PtReal};
+use ncollide3d::pipeline::narrow_phase::ContactEvent as NcContactEvent;
+use nphysics3d::world::GeometricalWorld;
+
+use crate::{
+    conversors::*,
+    servers_storage::CollidersStorageWrite,
+    storage::StoreKey,
+    utils::{ColliderTag, ObjectType, UserData},
+    WorldNpServer,
+};
+
+/// The two rigid bodies of a contact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContactPair {
+    pub body_1: PhysicsRigidBodyTag,
+    pub entity_1: Option<Entity>,
+    pub collider_tag_1: Option<ColliderTag>,
+    pub body_2: PhysicsRigidBodyTag,
+    pub entity_2: Option<Entity>,
+    pub collider_tag_2: Option<ColliderTag>,
+}
+
+/// Notifies when two rigid bodies start or stop to touch each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContactStatusEvent {
+    Started(ContactPair),
+    Stopped(ContactPair),
+}
+
+/// This system publishes the `ContactStatusEvent`s, of all the steps performed since its last run,
+/// to the `EventChannel<ContactStatusEvent>` resource.
+///
+/// Create it using `WorldNpServer::contact_events_system`, and add it after the physics systems.
+#[allow(missing_debug_implementations)]
+pub struct ContactEventsSystem<N: PtReal> {
+    world_server: WorldNpServer<N>,
+}
+
+impl<'a, N: PtReal> System<'a> for ContactEventsSystem<N> {
+    type SystemData = Write<'a, EventChannel<ContactStatusEvent>>;
+
+    fn run(&mut self, mut channel: Self::SystemData) {
+        let events = self.world_server.take_contact_status_events();
+        channel.iter_write(events);
+    }
+}
+
+// Contact events
+impl<N: PtReal> WorldNpServer<N> {
+    /// Returns the system that publishes the contact started and stopped events.
+    ///
+    /// The events are collected only after this function is called.
+    pub fn contact_events_system(&self) -> ContactEventsSystem<N> {
+        let mut events = self.contact_status_events.write().unwrap();
+        if events.is_none() {
+            *events = Some(Vec::new());
+        }
+        ContactEventsSystem {
+            world_server: self.clone(),
+        }
+    }
+
+    fn take_contact_status_events(&self) -> Vec<ContactStatusEvent> {
+        if let Some(events) = &mut *self.contact_status_events.write().unwrap() {
+            std::mem::replace(events, Vec::new())
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub(crate) fn collect_contact_status_events(
+        &self,
+        g_world: &GeometricalWorld<N, StoreKey, StoreKey>,
+        colliders: &CollidersStorageWrite<'_, N>,
+    ) {
+        let mut events = self.contact_status_events.write().unwrap();
+        let events = if let Some(events) = &mut *events {
+            events
+        } else {
+            return;
+        };
+
+        let body_of = |collider_key: StoreKey| {
+            let collider = colliders.get_collider(collider_key)?;
+            let user_data = collider.user_data()?.downcast_ref::<UserData>()?;
+            match user_data.object_type() {
+                ObjectType::RigidBody => Some((
+                    store_key_to_rigid_tag(user_data.store_key()),
+                    user_data.entity(),
+                    user_data.collider_tag(),
+                )),
+                ObjectType::Area | ObjectType::SoftBody => None,
+            }
+        };
+
+        for e in g_world.contact_events() {
+            let (c1, c2, started) = match e {
+                NcContactEvent::Started(c1, c2) => (*c1, *c2, true),
+                NcContactEvent::Stopped(c1, c2) => (*c1, *c2, false),
+            };
+
+            // The colliders removed during this step are skipped.
+            if let (
+                Some((body_1, entity_1, collider_tag_1)),
+                Some((body_2, entity_2, collider_tag_2)),
+            ) = (body_of(c1), body_of(c2))
+            {
+                let pair = ContactPair {
+                    body_1,
+                    entity_1,
+                    collider_tag_1,
+                    body_2,
+                    entity_2,
+                    collider_tag_2,
+                };
+                events.push(if started {
+                    ContactStatusEvent::Started(pair)
+                } else {
+                    ContactStatusEvent::Stopped(pair)
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::{
+        ecs::{RunNow, World, WorldExt},
+        math::Isometry3,
+        shrev::EventChannel,
+    };
+    use amethyst_physics::servers::{RBodyPhysicsServerTrait, RigidBodyDesc};
+
+    use super::ContactStatusEvent;
+    use crate::test_world::TestWorld;
+
+    #[test]
+    fn contact_status_published_to_the_channel() {
+        let test = TestWorld::<f32>::new();
+        let mut world = World::new();
+        world.insert(EventChannel::<ContactStatusEvent>::new());
+        let mut reader = world
+            .fetch_mut::<EventChannel<ContactStatusEvent>>()
+            .register_reader();
+        let mut system = test.world.contact_events_system();
+
+        let ground = test.create_ground();
+        let sphere = test.create_sphere_on_ground(&RigidBodyDesc::default());
+        let touches = |e: &ContactStatusEvent| {
+            let pair = match e {
+                ContactStatusEvent::Started(pair) | ContactStatusEvent::Stopped(pair) => pair,
+            };
+            (pair.body_1, pair.body_2) == (ground.get(), sphere.get())
+                || (pair.body_1, pair.body_2) == (sphere.get(), ground.get())
+        };
+
+        test.step_n(30);
+        system.run_now(&world);
+        let events: Vec<_> = world
+            .fetch::<EventChannel<ContactStatusEvent>>()
+            .read(&mut reader)
+            .copied()
+            .collect();
+        assert!(matches!(
+            events.first(),
+            Some(ContactStatusEvent::Started(_))
+        ));
+        assert!(events.iter().all(touches));
+
+        test.bodies
+            .set_transform(sphere.get(), &Isometry3::translation(0.0, 10.0, 0.0));
+        test.step();
+        system.run_now(&world);
+        let events: Vec<_> = world
+            .fetch::<EventChannel<ContactStatusEvent>>()
+            .read(&mut reader)
+            .copied()
+            .collect();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], ContactStatusEvent::Stopped(_)));
+        assert!(touches(&events[0]));
+    }
+}
@@ -56,6 +56,13 @@ pub mod collision_group_conversor {
         collision_groups
     }
 
+    /// Sorts the groups and removes the duplicates, like `from_nphysics` returns them.
+    pub fn normalize(mut groups: Vec<CollisionGroup>) -> Vec<CollisionGroup> {
+        groups.sort_by_key(|v| v.get());
+        groups.dedup_by_key(|v| v.get());
+        groups
+    }
+
     pub fn from_nphysics(groups: &NcCollisionGroups) -> (Vec<CollisionGroup>, Vec<CollisionGroup>) {
         let mut belong_to = Vec::<CollisionGroup>::with_capacity(NcCollisionGroups::max_group_id());
         let mut collide_with =
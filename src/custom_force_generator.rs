@@ -0,0 +1,102 @@
+use amethyst_physics::{objects::*, PtReal};
+use nphysics3d::{
+    force_generator::ForceGenerator as NpForceGenerator,
+    object::{Body as NpBody, BodySet as NpBodySet},
+    solver::IntegrationParameters,
+};
+
+use crate::{conversors::*, storage::StoreKey};
+
+/// A user defined force generator.
+///
+/// It's called by the solver, during each step, for each body that it targets.
+/// It's implemented for any `FnMut(PhysicsRigidBodyTag, &mut dyn Body<N>, N)`, where the last
+/// parameter is the step delta time.
+pub trait BodyForceGenerator<N: PtReal>: Send + Sync {
+    fn apply(&mut self, body_tag: PhysicsRigidBodyTag, body: &mut dyn NpBody<N>, delta_time: N);
+}
+
+impl<N, F> BodyForceGenerator<N> for F
+where
+    N: PtReal,
+    F: FnMut(PhysicsRigidBodyTag, &mut dyn NpBody<N>, N) + Send + Sync,
+{
+    fn apply(&mut self, body_tag: PhysicsRigidBodyTag, body: &mut dyn NpBody<N>, delta_time: N) {
+        self(body_tag, body, delta_time)
+    }
+}
+
+/// Adapts a `BodyForceGenerator` to the NPhysics force generator.
+pub(crate) struct CustomForceGenerator<N: PtReal> {
+    bodies: Vec<StoreKey>,
+    generator: Box<dyn BodyForceGenerator<N>>,
+}
+
+impl<N: PtReal> CustomForceGenerator<N> {
+    pub(crate) fn new(bodies: Vec<StoreKey>, generator: Box<dyn BodyForceGenerator<N>>) -> Self {
+        CustomForceGenerator { bodies, generator }
+    }
+
+    pub(crate) fn remove_body(&mut self, body: StoreKey) {
+        self.bodies.retain(|&b| b != body);
+    }
+}
+
+impl<N: PtReal> NpForceGenerator<N, StoreKey> for CustomForceGenerator<N> {
+    fn apply(
+        &mut self,
+        parameters: &IntegrationParameters<N>,
+        bodies: &mut dyn NpBodySet<N, Handle = StoreKey>,
+    ) {
+        let delta_time = parameters.dt();
+        for body_key in self.bodies.iter() {
+            if let Some(body) = bodies.get_mut(*body_key) {
+                self.generator
+                    .apply(store_key_to_rigid_tag(*body_key), body, delta_time);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use amethyst_core::math::{Isometry3, Vector3};
+    use amethyst_physics::{
+        objects::PhysicsRigidBodyTag,
+        servers::{RigidBodyDesc, ShapeDesc, WorldPhysicsServerTrait},
+    };
+    use nphysics3d::{
+        math::{Force, ForceType},
+        object::Body as NpBody,
+    };
+
+    use crate::{test_world::TestWorld, ForceGeneratorNpServer};
+
+    #[test]
+    fn custom_generator_called_for_its_bodies() {
+        let test = TestWorld::<f32>::new();
+        test.world.set_gravity(&Vector3::zeros());
+
+        let sphere = ShapeDesc::Sphere { radius: 0.5 };
+        let desc = RigidBodyDesc::default();
+        let target = test.create_body(&desc, &sphere, &Isometry3::identity());
+        let other = test.create_body(&desc, &sphere, &Isometry3::translation(5.0, 0.0, 0.0));
+
+        let called = Arc::new(Mutex::new(Vec::new()));
+        let generator_called = called.clone();
+        ForceGeneratorNpServer::new(test.storages.clone()).create_custom(
+            &[target.get()],
+            move |tag: PhysicsRigidBodyTag, body: &mut dyn NpBody<f32>, _delta_time: f32| {
+                generator_called.lock().unwrap().push(tag);
+                body.apply_force(0, &Force::linear(Vector3::y()), ForceType::Force, true);
+            },
+        );
+        test.step_n(5);
+
+        assert_eq!(*called.lock().unwrap(), vec![target.get(); 5]);
+        assert!(test.position(target.get()).y > 0.0);
+        assert_eq!(test.position(other.get()).y, 0.0);
+    }
+}
@@ -0,0 +1,301 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use amethyst_core::math::{convert, Isometry3, Point3, Vector3};
+use amethyst_physics::PtReal;
+use log::error;
+use ncollide3d::{
+    procedural::TriMesh as ProceduralTriMesh,
+    shape::{
+        Ball as NcBall, Capsule as NcCapsule, Compound as NcCompound, ConvexHull as NcConvexHull,
+        Cuboid as NcCuboid, Plane as NcPlane, Shape as NcShape, TriMesh as NcTriMesh,
+    },
+    transformation::ToTriMesh,
+};
+use nphysics3d::object::ColliderSet;
+
+use crate::WorldNpServer;
+
+/// Subdivisions used to tessellate the spheres and the capsules.
+const ROUND_SUBDIVISIONS: (u32, u32) = (16, 8);
+
+/// The planes are infinite, so they are drawn as a square of this half extent.
+const PLANE_HALF_EXTENT: f64 = 50.0;
+
+/// The triangle mesh of a collider, in world space.
+#[derive(Debug, Clone)]
+pub struct DebugMesh<N: PtReal> {
+    pub vertices: Vec<Point3<N>>,
+    pub indices: Vec<[u32; 3]>,
+    /// `true` when the collider belongs to an area.
+    pub is_sensor: bool,
+}
+
+impl<N: PtReal> DebugMesh<N> {
+    fn new(is_sensor: bool) -> Self {
+        DebugMesh {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            is_sensor,
+        }
+    }
+
+    /// Returns the edges of the mesh, each edge is returned once.
+    pub fn lines(&self) -> Vec<DebugLine<N>> {
+        let mut edges = HashSet::new();
+        let mut lines = Vec::new();
+        for triangle in self.indices.iter() {
+            for i in 0..3 {
+                let a = triangle[i];
+                let b = triangle[(i + 1) % 3];
+                if edges.insert((a.min(b), a.max(b))) {
+                    lines.push(DebugLine {
+                        start: self.vertices[a as usize],
+                        end: self.vertices[b as usize],
+                        is_sensor: self.is_sensor,
+                    });
+                }
+            }
+        }
+        lines
+    }
+
+    fn append(&mut self, position: &Isometry3<N>, vertices: &[Point3<N>], indices: &[[u32; 3]]) {
+        let offset = self.vertices.len() as u32;
+        self.vertices
+            .extend(vertices.iter().map(|v| position.transform_point(v)));
+        self.indices.extend(
+            indices
+                .iter()
+                .map(|t| [t[0] + offset, t[1] + offset, t[2] + offset]),
+        );
+    }
+
+    fn append_procedural(&mut self, position: &Isometry3<N>, mut mesh: ProceduralTriMesh<N>) {
+        mesh.unify_index_buffer();
+        let indices: Vec<[u32; 3]> = mesh
+            .indices
+            .unwrap_unified()
+            .iter()
+            .map(|t| [t.x, t.y, t.z])
+            .collect();
+        self.append(position, &mesh.coords, &indices);
+    }
+
+    /// Tessellates the shape and appends its triangles.
+    ///
+    /// The shapes that are not supported, like the cylinders, are skipped and an error is logged.
+    fn append_shape(&mut self, position: &Isometry3<N>, shape: &dyn NcShape<N>) {
+        if let Some(s) = shape.as_shape::<NcBall<N>>() {
+            self.append_procedural(position, s.to_trimesh(ROUND_SUBDIVISIONS));
+        } else if let Some(s) = shape.as_shape::<NcCuboid<N>>() {
+            self.append_procedural(position, s.to_trimesh(()));
+        } else if let Some(s) = shape.as_shape::<NcCapsule<N>>() {
+            self.append_procedural(position, s.to_trimesh(ROUND_SUBDIVISIONS));
+        } else if let Some(s) = shape.as_shape::<NcConvexHull<N>>() {
+            self.append_procedural(position, s.to_trimesh(()));
+        } else if let Some(s) = shape.as_shape::<NcTriMesh<N>>() {
+            let indices: Vec<[u32; 3]> = s
+                .faces()
+                .iter()
+                .map(|f| [f.indices.x as u32, f.indices.y as u32, f.indices.z as u32])
+                .collect();
+            self.append(position, s.points(), &indices);
+        } else if let Some(s) = shape.as_shape::<NcPlane<N>>() {
+            let normal = s.normal().into_inner();
+            let axis = if normal.x.abs() < convert(0.9) {
+                Vector3::x()
+            } else {
+                Vector3::y()
+            };
+            let half_extent: N = convert(PLANE_HALF_EXTENT);
+            let tangent = axis.cross(&normal).normalize() * half_extent;
+            let bitangent = normal.cross(&tangent);
+            let vertices = [
+                Point3::from(-tangent - bitangent),
+                Point3::from(tangent - bitangent),
+                Point3::from(tangent + bitangent),
+                Point3::from(-tangent + bitangent),
+            ];
+            self.append(position, &vertices, &[[0, 1, 2], [0, 2, 3]]);
+        } else if let Some(s) = shape.as_shape::<NcCompound<N>>() {
+            for (delta, sub_shape) in s.shapes() {
+                self.append_shape(&(position * delta), sub_shape.as_ref());
+            }
+        } else {
+            error!("The debug geometry of this shape is not supported, so it's skipped.");
+        }
+    }
+}
+
+/// A line, in world space, ready to be drawn with the debug lines.
+#[derive(Debug, Clone, Copy)]
+pub struct DebugLine<N: PtReal> {
+    pub start: Point3<N>,
+    pub end: Point3<N>,
+    /// `true` when the collider belongs to an area.
+    pub is_sensor: bool,
+}
+
+// Debug geometry
+impl<N: PtReal> WorldNpServer<N> {
+    /// Returns the triangle mesh of each collider, in world space.
+    ///
+    /// This is meant to visualize the collider placement, so the round shapes are approximated
+    /// and the planes are drawn as a finite square.
+    pub fn debug_meshes(&self) -> Vec<DebugMesh<N>> {
+        let colliders = self.storages.colliders_r();
+
+        let mut meshes = Vec::new();
+        ColliderSet::foreach(&*colliders, |_, collider| {
+            let mut mesh = DebugMesh::new(collider.is_sensor());
+            mesh.append_shape(collider.position(), collider.shape());
+            meshes.push(mesh);
+        });
+        meshes
+    }
+
+    /// Returns the wireframe of all the colliders, in world space.
+    pub fn debug_lines(&self) -> Vec<DebugLine<N>> {
+        self.debug_meshes()
+            .iter()
+            .flat_map(|mesh| mesh.lines())
+            .collect()
+    }
+
+    /// Writes the geometry of all the colliders, in world space, using the Wavefront OBJ format.
+    ///
+    /// Each collider is written as a separate object, the ones that belong to an area are named
+    /// `sensor_*` while the others `collider_*`.
+    pub fn write_obj<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        // The OBJ indices are 1 based and global to the file.
+        let mut offset = 1;
+        for (i, mesh) in self.debug_meshes().iter().enumerate() {
+            if mesh.is_sensor {
+                writeln!(writer, "o sensor_{}", i)?;
+            } else {
+                writeln!(writer, "o collider_{}", i)?;
+            }
+            for v in mesh.vertices.iter() {
+                writeln!(writer, "v {} {} {}", v.x, v.y, v.z)?;
+            }
+            for t in mesh.indices.iter() {
+                writeln!(
+                    writer,
+                    "f {} {} {}",
+                    t[0] as usize + offset,
+                    t[1] as usize + offset,
+                    t[2] as usize + offset
+                )?;
+            }
+            offset += mesh.vertices.len();
+        }
+        Ok(())
+    }
+
+    /// Exports the geometry of all the colliders to an OBJ file.
+    pub fn export_obj<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_obj(&mut writer)?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Point3, Vector3};
+    use amethyst_physics::servers::{
+        AreaDesc, AreaPhysicsServerTrait, BodyMode, RigidBodyDesc, ShapeDesc,
+        ShapePhysicsServerTrait,
+    };
+
+    use crate::test_world::TestWorld;
+
+    #[test]
+    fn cube_and_sphere_exported() {
+        let test = TestWorld::<f32>::new();
+
+        let mut desc = RigidBodyDesc::default();
+        desc.mode = BodyMode::Static;
+        let _cube = test.create_body(
+            &desc,
+            &ShapeDesc::Cube {
+                half_extents: Vector3::new(1.0, 1.0, 1.0),
+            },
+            &Isometry3::identity(),
+        );
+        let center = Point3::new(3.0, 0.0, 0.0);
+        let _sphere = test.create_body(
+            &desc,
+            &ShapeDesc::Sphere { radius: 0.5 },
+            &Isometry3::translation(center.x, center.y, center.z),
+        );
+        test.step();
+
+        let meshes = test.world.debug_meshes();
+        assert_eq!(meshes.len(), 2);
+        let (cubes, spheres): (Vec<_>, Vec<_>) = meshes
+            .iter()
+            .partition(|mesh| mesh.vertices.iter().all(|v| v.x < 2.0));
+
+        let cube = cubes[0];
+        assert_eq!(cube.indices.len(), 12);
+        assert!(cube.vertices.len() >= 8);
+        assert!(cube
+            .vertices
+            .iter()
+            .all(|v| v.coords.iter().all(|c| (c.abs() - 1.0).abs() < 1e-5)));
+
+        let sphere = spheres[0];
+        assert!(!sphere.indices.is_empty());
+        assert!(sphere
+            .vertices
+            .iter()
+            .all(|v| ((v - center).norm() - 0.5).abs() < 1e-5));
+
+        let mut obj = Vec::new();
+        test.world.write_obj(&mut obj).unwrap();
+        let obj = String::from_utf8(obj).unwrap();
+        let count = |prefix| obj.lines().filter(|l| l.starts_with(prefix)).count();
+        assert_eq!(count("o "), 2);
+        assert_eq!(count("v "), cube.vertices.len() + sphere.vertices.len());
+        assert_eq!(count("f "), cube.indices.len() + sphere.indices.len());
+    }
+
+    #[test]
+    fn sensors_named_in_the_exported_file() {
+        let test = TestWorld::<f32>::new();
+
+        let _ground = test.create_ground();
+        let shape = test.shapes.create(&ShapeDesc::Sphere { radius: 1.0 });
+        let area = test.areas.create(&AreaDesc::default());
+        test.areas.set_shape(area.get(), Some(shape.get()));
+        test.step();
+
+        let path = std::env::temp_dir().join("amethyst_nphysics_sensors_named.obj");
+        test.world.export_obj(&path).unwrap();
+        let obj = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let objects: Vec<&str> = obj.lines().filter(|l| l.starts_with("o ")).collect();
+        assert_eq!(objects.len(), 2);
+        assert_eq!(
+            objects
+                .iter()
+                .filter(|o| o.starts_with("o sensor_"))
+                .count(),
+            1
+        );
+        assert_eq!(
+            objects
+                .iter()
+                .filter(|o| o.starts_with("o collider_"))
+                .count(),
+            1
+        );
+    }
+}
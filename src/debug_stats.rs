@@ -0,0 +1,119 @@
+use std::fmt;
+
+use amethyst_core::ecs::{System, Write};
+use amethyst_physics::PtReal;
+
+use crate::{
+    statistics::{PhysicsStatistics, StepTimings},
+    WorldNpServer,
+};
+
+/// The health of the physics world, published as a resource by the `PhysicsDebugStatsSystem`
+/// so the UI overlays can show it without touching the storages.
+///
+/// It implements `Display`, that formats it on a few lines ready to be shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhysicsDebugStats {
+    pub statistics: PhysicsStatistics,
+    pub timings: StepTimings,
+    /// Number of events of the last step.
+    pub step_events: usize,
+    /// Number of events of all the steps.
+    pub total_events: u64,
+}
+
+impl fmt::Display for PhysicsDebugStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |d: std::time::Duration| d.as_secs_f64() * 1000.0;
+        let s = &self.statistics;
+        writeln!(
+            f,
+            "Rigid bodies: {} ({} awake), areas: {}, soft bodies: {}",
+            s.rigid_bodies, s.active_rigid_bodies, s.areas, s.soft_bodies
+        )?;
+        writeln!(
+            f,
+            "Colliders: {}, shapes: {}, joints: {}",
+            s.colliders, s.shapes, s.joints
+        )?;
+        writeln!(
+            f,
+            "Pairs: {} in contact, {} intersecting",
+            s.contact_pairs, s.proximity_pairs
+        )?;
+        writeln!(
+            f,
+            "Step: {:.2} ms (garbage collection {:.2} ms, simulation {:.2} ms, events {:.2} ms)",
+            ms(self.timings.total),
+            ms(self.timings.garbage_collection),
+            ms(self.timings.mechanical_step),
+            ms(self.timings.fetch_events)
+        )?;
+        write!(
+            f,
+            "Events: {} last step, {} total",
+            self.step_events, self.total_events
+        )
+    }
+}
+
+/// This system writes the `PhysicsDebugStats` of the last step to its resource.
+///
+/// Create it using `WorldNpServer::debug_stats_system`, and add it after the physics systems.
+#[allow(missing_debug_implementations)]
+pub struct PhysicsDebugStatsSystem<N: PtReal> {
+    world_server: WorldNpServer<N>,
+}
+
+impl<'a, N: PtReal> System<'a> for PhysicsDebugStatsSystem<N> {
+    type SystemData = Write<'a, PhysicsDebugStats>;
+
+    fn run(&mut self, mut stats: Self::SystemData) {
+        *stats = self.world_server.debug_stats();
+    }
+}
+
+// Debug stats
+impl<N: PtReal> WorldNpServer<N> {
+    /// Returns the statistics, the timings and the events count of the last step.
+    pub fn debug_stats(&self) -> PhysicsDebugStats {
+        let (step_events, total_events) = self.step_events();
+        PhysicsDebugStats {
+            statistics: self.statistics(),
+            timings: self.step_timings(),
+            step_events,
+            total_events,
+        }
+    }
+
+    /// Returns the system that publishes the `PhysicsDebugStats` resource.
+    pub fn debug_stats_system(&self) -> PhysicsDebugStatsSystem<N> {
+        PhysicsDebugStatsSystem {
+            world_server: self.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_physics::servers::{RBodyPhysicsServerTrait, RigidBodyDesc};
+
+    use crate::test_world::TestWorld;
+
+    #[test]
+    fn debug_stats_count_the_events() {
+        let test = TestWorld::<f32>::new();
+
+        let body = test.bodies.create(&RigidBodyDesc::default());
+        test.step();
+        drop(body);
+        test.step();
+        test.step();
+
+        let stats = test.world.debug_stats();
+        assert_eq!(stats.statistics.rigid_bodies, 0);
+        assert_eq!(stats.step_events, 0);
+        assert!(stats.total_events >= 1);
+        assert!(stats.to_string().starts_with("Rigid bodies: 0"));
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use amethyst_core::math::{Translation3, Vector3};
+use amethyst_physics::PtReal;
+use nphysics3d::world::GeometricalWorld;
+
+use crate::{
+    body_storage::BodyStorage, collider_storage::ColliderStorage, storage::StoreKey, WorldNpServer,
+};
+
+// Depenetration
+impl<N: PtReal> WorldNpServer<N> {
+    /// Moves the bodies with the depenetration enabled out of the geometry they are overlapping,
+    /// along the contact normals.
+    pub(crate) fn apply_depenetration(
+        gw: &GeometricalWorld<N, StoreKey, StoreKey>,
+        bodies: &BodyStorage<N>,
+        colliders: &ColliderStorage<N, StoreKey>,
+    ) {
+        let mut corrections: HashMap<StoreKey, Vector3<N>> = HashMap::new();
+        let mut correct = |body_key: StoreKey, correction: Vector3<N>| {
+            let enabled = bodies
+                .get_body(body_key)
+                .map_or(false, |body| body.depenetration.is_some());
+            if enabled {
+                *corrections.entry(body_key).or_insert_with(Vector3::zeros) += correction;
+            }
+        };
+
+        for (_, collider_1, _, collider_2, _, manifold) in gw.contact_pairs(colliders, true) {
+            if collider_1.body() == collider_2.body() {
+                continue;
+            }
+            if let Some(contact) = manifold.deepest_contact() {
+                if contact.contact.depth <= N::from(0.0) {
+                    continue;
+                }
+                // The normal goes from the first to the second collider.
+                let correction = contact.contact.normal.into_inner() * contact.contact.depth;
+                correct(collider_1.body(), -correction);
+                correct(collider_2.body(), correction);
+            }
+        }
+
+        for (body_key, correction) in corrections {
+            let mut body = bodies.get_body(body_key).unwrap();
+            let max_distance = body.depenetration.unwrap();
+
+            let distance = correction.norm();
+            let correction = if distance > max_distance {
+                correction * (max_distance / distance)
+            } else {
+                correction
+            };
+
+            let transform = if let Some(transform) = body.body_transform() {
+                Translation3::from(correction) * *transform
+            } else {
+                continue;
+            };
+            if body.set_body_transform(&transform).is_ok() {
+                body.activate();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Vector3};
+    use amethyst_physics::servers::{RigidBodyDesc, ShapeDesc, WorldPhysicsServerTrait};
+
+    use crate::test_world::TestWorld;
+
+    #[test]
+    fn overlapping_body_moved_out_by_the_max_distance() {
+        let test = TestWorld::<f32>::new();
+        test.world.set_gravity(&Vector3::zeros());
+        let _ground = test.create_ground();
+
+        // Both spheres are 0.4 inside the ground.
+        let sphere = ShapeDesc::Sphere { radius: 0.5 };
+        let desc = RigidBodyDesc::default();
+        let recovered = test.create_body(&desc, &sphere, &Isometry3::translation(-2.0, 0.1, 0.0));
+        let stuck = test.create_body(&desc, &sphere, &Isometry3::translation(2.0, 0.1, 0.0));
+        test.bodies.set_depenetration(recovered.get(), Some(0.3));
+        assert_eq!(test.bodies.depenetration(recovered.get()), Some(0.3));
+        assert_eq!(test.bodies.depenetration(stuck.get()), None);
+
+        test.step();
+        let recovered_by = test.position(recovered.get()).y - test.position(stuck.get()).y;
+        assert!(
+            recovered_by > 0.25 && recovered_by < 0.31,
+            "Moved out by {}",
+            recovered_by
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use amethyst_physics::{objects::*, PtReal};
+
+use crate::WorldNpServer;
+
+/// The objects to destroy at once, like when a level is unloaded.
+#[derive(Debug, Clone, Default)]
+pub struct DestroyBatch {
+    pub bodies: Vec<PhysicsRigidBodyTag>,
+    pub areas: Vec<PhysicsAreaTag>,
+    pub shapes: Vec<PhysicsShapeTag>,
+    pub joints: Vec<PhysicsJointTag>,
+}
+
+// Batch destruction
+impl<N: PtReal> WorldNpServer<N> {
+    /// Pushes all the objects of the batch to the garbage collector at once, and drops them right
+    /// away when `flush` is `true`; otherwise they are dropped before the next step.
+    ///
+    /// The objects are destroyed even if their `PhysicsHandle`s are still alive; dropping the
+    /// handles later is harmless, since the garbage collector ignores the objects that don't exist
+    /// anymore.
+    pub fn destroy_batch(&self, batch: DestroyBatch, flush: bool) {
+        {
+            let gc = self.storages.gc();
+            let mut gc = gc.write().unwrap();
+            gc.bodies.extend(batch.bodies);
+            gc.areas.extend(batch.areas);
+            gc.shapes.extend(batch.shapes);
+            gc.joints.extend(batch.joints);
+        }
+
+        if flush {
+            self.flush_destroyed();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_physics::servers::{
+        AreaDesc, AreaPhysicsServerTrait, JointDesc, JointPhysicsServerTrait, JointPosition,
+        RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc, ShapePhysicsServerTrait,
+    };
+
+    use super::DestroyBatch;
+    use crate::test_world::TestWorld;
+
+    #[test]
+    fn batch_destroyed_while_the_handles_alive() {
+        let test = TestWorld::<f32>::new();
+
+        let body = test.bodies.create(&RigidBodyDesc::default());
+        let kept = test.bodies.create(&RigidBodyDesc::default());
+        let area = test.areas.create(&AreaDesc::default());
+        let shape = test.shapes.create(&ShapeDesc::Sphere { radius: 1.0 });
+        let joint = test.joints.create(&JointDesc::Fixed, JointPosition::Middle);
+
+        test.world.destroy_batch(
+            DestroyBatch {
+                bodies: vec![body.get()],
+                areas: vec![area.get()],
+                shapes: vec![shape.get()],
+                joints: vec![joint.get()],
+            },
+            true,
+        );
+        assert_eq!(test.world.rigid_bodies(), vec![(kept.get(), None)]);
+        assert!(test.world.areas().is_empty());
+        assert!(test.world.shapes().is_empty());
+        assert!(test.world.joints().is_empty());
+
+        // Dropping the handles of the destroyed objects is harmless.
+        drop((body, area, shape, joint));
+        test.step();
+        assert_eq!(test.world.rigid_bodies(), vec![(kept.get(), None)]);
+    }
+}
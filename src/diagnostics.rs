@@ -0,0 +1,98 @@
+use std::{
+    collections::HashMap,
+    panic::Location,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use amethyst_physics::PtReal;
+use log::error;
+
+use crate::{error::PhysicsError, WorldNpServer};
+
+/// The minimum time between two logs of the same error.
+const LOG_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Counts the errors of the servers, and logs each kind of error at most once per
+/// `LOG_INTERVAL`, so a call that fails each frame doesn't flood the log.
+#[derive(Debug, Default)]
+pub(crate) struct ErrorDiagnostics {
+    errors: Mutex<HashMap<PhysicsError, ErrorCounter>>,
+}
+
+#[derive(Debug)]
+struct ErrorCounter {
+    count: u64,
+    /// `None` until the error is logged the first time.
+    last_log: Option<Instant>,
+    /// The errors not logged since `last_log`.
+    suppressed: u64,
+}
+
+impl ErrorDiagnostics {
+    /// Counts the error, and logs it with the location of the caller unless it was logged
+    /// recently.
+    #[track_caller]
+    pub fn report(&self, error: PhysicsError) {
+        let now = Instant::now();
+        let suppressed = {
+            let mut errors = self.errors.lock().unwrap();
+            let counter = errors.entry(error).or_insert_with(|| ErrorCounter {
+                count: 0,
+                last_log: None,
+                suppressed: 0,
+            });
+            counter.count += 1;
+            if let Some(last_log) = counter.last_log {
+                if now.duration_since(last_log) < LOG_INTERVAL {
+                    counter.suppressed += 1;
+                    return;
+                }
+            }
+            counter.last_log = Some(now);
+            std::mem::replace(&mut counter.suppressed, 0)
+        };
+
+        let location = Location::caller();
+        if suppressed == 0 {
+            error!("[{}::{}] {}", location.file(), location.line(), error);
+        } else {
+            error!(
+                "[{}::{}] {}; {} more times since the last log.",
+                location.file(),
+                location.line(),
+                error,
+                suppressed
+            );
+        }
+    }
+
+    pub fn counters(&self) -> HashMap<PhysicsError, u64> {
+        self.errors
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(error, counter)| (*error, counter.count))
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.errors.lock().unwrap().clear();
+    }
+}
+
+// Diagnostics
+impl<N: PtReal> WorldNpServer<N> {
+    /// Returns how many times each error happened, since the storages were created or the
+    /// counters were reset.
+    ///
+    /// The servers log each kind of error at most once per second, so this tells how often the
+    /// calls are failing without reading the log.
+    pub fn error_counters(&self) -> HashMap<PhysicsError, u64> {
+        self.storages.diagnostics.counters()
+    }
+
+    pub fn reset_error_counters(&self) {
+        self.storages.diagnostics.clear();
+    }
+}
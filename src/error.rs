@@ -0,0 +1,174 @@
+use std::{fmt, panic::Location};
+
+use amethyst_physics::PtReal;
+use log::error;
+
+use crate::servers_storage::ServersStorage;
+
+/// The errors returned by the fallible functions of the servers, the ones prefixed by `try_`, and
+/// propagated by the internal helpers.
+///
+/// The functions of the `amethyst_physics` traits can't return an error, so they log it and
+/// return a default value instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PhysicsError {
+    /// The tag doesn't refer to any body or area, or it was dropped.
+    BodyNotFound,
+    /// The tag refers to a body of another type; e.g. an area tag passed to the rigid body server.
+    WrongBodyType,
+    /// The tag doesn't refer to any shape, or it was dropped.
+    ShapeMissing,
+    /// The tag doesn't refer to any joint, or it was dropped.
+    JointNotFound,
+    /// The joint already constrains the body.
+    BodyAlreadyInJoint,
+    /// The joint already constrains two bodies.
+    JointFull,
+    /// The shape is not of the expected type; e.g. a box passed to `resize_capsule`.
+    WrongShapeType,
+    /// The shape is used by more bodies, so it can't be changed for just one of them.
+    ShapeShared,
+    /// The body is using its LOD shape.
+    LodActive,
+    /// The body is not a dynamic rigid body.
+    NotDynamic,
+    /// The body is not a static rigid body.
+    NotStatic,
+    /// The soft body, or the `TriMesh` shape, doesn't have the vertex.
+    VertexNotFound,
+    /// The compound shape doesn't have the child.
+    ChildNotFound,
+    /// The child is the only one of the compound shape, that can't be empty.
+    LastChild,
+    /// The world already contains some bodies or areas.
+    WorldNotEmpty,
+    /// No collision group is registered with the name.
+    LayerNotFound,
+    /// The collision group index is greater than the ones supported by NPhysics.
+    GroupOutOfRange,
+    /// The prefab refers to a name that it doesn't define.
+    PrefabNameNotFound,
+    /// The bytes are not a shape asset, or it was encoded by another version of the format.
+    InvalidShapeAsset,
+    /// The world contains soft bodies or force generators, that the snapshots don't include.
+    NotSnapshotable,
+}
+
+pub type PhysicsResult<T> = Result<T, PhysicsError>;
+
+impl fmt::Display for PhysicsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PhysicsError::BodyNotFound => write!(f, "Body not found"),
+            PhysicsError::WrongBodyType => write!(f, "The body is not of the expected type"),
+            PhysicsError::ShapeMissing => write!(f, "Shape not found"),
+            PhysicsError::JointNotFound => write!(f, "Joint not found"),
+            PhysicsError::BodyAlreadyInJoint => write!(f, "The joint already constrains the body"),
+            PhysicsError::JointFull => write!(f, "The joint already constrains two bodies"),
+            PhysicsError::WrongShapeType => write!(f, "The shape is not of the expected type"),
+            PhysicsError::ShapeShared => write!(f, "The shape is used by more bodies"),
+            PhysicsError::LodActive => write!(f, "The body is using its LOD shape"),
+            PhysicsError::NotDynamic => write!(f, "The body is not dynamic"),
+            PhysicsError::NotStatic => write!(f, "The body is not static"),
+            PhysicsError::VertexNotFound => write!(f, "Vertex not found"),
+            PhysicsError::ChildNotFound => write!(f, "Compound child not found"),
+            PhysicsError::LastChild => write!(f, "The child is the only one of the compound"),
+            PhysicsError::WorldNotEmpty => write!(f, "The world is not empty"),
+            PhysicsError::LayerNotFound => write!(f, "Collision layer not found"),
+            PhysicsError::GroupOutOfRange => write!(f, "The collision group is out of range"),
+            PhysicsError::PrefabNameNotFound => write!(f, "Prefab name not found"),
+            PhysicsError::InvalidShapeAsset => write!(f, "Invalid shape asset"),
+            PhysicsError::NotSnapshotable => {
+                write!(f, "The world contains soft bodies or force generators")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PhysicsError {}
+
+/// Logs the error of the functions that can't return it, like the `fail!` macro does.
+pub(crate) trait LogError<T> {
+    /// Returns the value, or logs the error together with the location of the caller.
+    fn or_log(self) -> Option<T>;
+
+    /// Like `or_log`, but the error is counted, and its log is rate limited, by the diagnostics
+    /// of the storages.
+    fn or_report<N: PtReal>(self, storages: &ServersStorage<N>) -> Option<T>;
+}
+
+impl<T> LogError<T> for PhysicsResult<T> {
+    #[track_caller]
+    fn or_log(self) -> Option<T> {
+        match self {
+            Ok(value) => Some(value),
+            Err(e) => {
+                let location = Location::caller();
+                error!("[{}::{}] {}", location.file(), location.line(), e);
+                None
+            }
+        }
+    }
+
+    #[track_caller]
+    fn or_report<N: PtReal>(self, storages: &ServersStorage<N>) -> Option<T> {
+        match self {
+            Ok(value) => Some(value),
+            Err(e) => {
+                storages.diagnostics.report(e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_physics::servers::{
+        RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc, ShapePhysicsServerTrait,
+    };
+
+    use super::PhysicsError;
+    use crate::test_world::TestWorld;
+
+    #[test]
+    fn helpers_errors_returned_or_counted() {
+        let test = TestWorld::<f32>::new();
+
+        let capsule = test.shapes.create(&ShapeDesc::Capsule {
+            half_height: 0.5,
+            radius: 0.5,
+        });
+        let a = test.bodies.create(&RigidBodyDesc::default());
+        let b = test.bodies.create(&RigidBodyDesc::default());
+        test.bodies.set_shape(a.get(), Some(capsule.get()));
+        test.bodies.set_shape(b.get(), Some(capsule.get()));
+        let (destroyed_body, destroyed_shape) = {
+            let body = test.bodies.create(&RigidBodyDesc::default());
+            let shape = test.shapes.create(&ShapeDesc::Sphere { radius: 0.5 });
+            (body.get(), shape.get())
+        };
+        test.step();
+
+        assert_eq!(
+            test.bodies.try_set_entity(destroyed_body, None),
+            Err(PhysicsError::BodyNotFound)
+        );
+        assert_eq!(
+            test.bodies
+                .try_set_lod_shape(a.get(), Some(destroyed_shape)),
+            Err(PhysicsError::ShapeMissing)
+        );
+        assert_eq!(
+            test.world.try_resize_capsule(a.get(), 1.0),
+            Err(PhysicsError::ShapeShared)
+        );
+
+        // The functions that can't return the error count it.
+        assert!(!test.world.resize_capsule(a.get(), 1.0));
+        assert_eq!(
+            test.world.error_counters().get(&PhysicsError::ShapeShared),
+            Some(&1)
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use amethyst_core::ecs::Entity;
+use amethyst_physics::{
+    objects::*,
+    servers::{ContactEvent, OverlapEvent},
+    PtReal,
+};
+
+use crate::{
+    body::{BodyData, ContactState},
+    body_storage::BodyStorage,
+    conversors::*,
+    joint_storage::JointStorage,
+    query::QueryHit,
+    storage::StoreKey,
+    WorldNpServer,
+};
+
+/// An event that happened during the last step.
+#[derive(Debug, Clone)]
+pub enum PhysicsEvent<N: PtReal> {
+    /// A body entered or exited an area.
+    Overlap {
+        area: PhysicsAreaTag,
+        event: OverlapEvent,
+    },
+    /// A body started, kept or stopped to touch another body.
+    ///
+    /// Reported only for the bodies that have `contacts_to_report` greater than 0.
+    Contact {
+        body: PhysicsRigidBodyTag,
+        event: ContactEvent<N>,
+        state: ContactState,
+        other: QueryHit,
+    },
+    /// A rigid body fell asleep.
+    Sleep(PhysicsRigidBodyTag),
+    /// A rigid body woke up.
+    WakeUp(PhysicsRigidBodyTag),
+    /// A joint broke.
+    JointBroken {
+        joint: PhysicsJointTag,
+        body_0: Option<PhysicsRigidBodyTag>,
+        body_1: Option<PhysicsRigidBodyTag>,
+    },
+    /// A joint stopped to constrain a body, since the body was dropped by the garbage collector.
+    ///
+    /// The `other_body` is the body still in the joint, if any.
+    JointDetached {
+        joint: PhysicsJointTag,
+        removed_body: PhysicsRigidBodyTag,
+        other_body: Option<PhysicsRigidBodyTag>,
+    },
+    /// A dynamic rigid body escaped the `WorldBounds`.
+    ///
+    /// When `destroyed` is `true` the body is dropped before the next step, otherwise it's
+    /// disabled.
+    OutOfBounds {
+        body: PhysicsRigidBodyTag,
+        entity: Option<Entity>,
+        destroyed: bool,
+    },
+    /// A rigid body was dropped by the garbage collector.
+    ///
+    /// The `entity` is the one the body was associated with, so its components can be cleaned up.
+    RigidBodyRemoved {
+        body: PhysicsRigidBodyTag,
+        entity: Option<Entity>,
+    },
+    /// An area was dropped by the garbage collector.
+    AreaRemoved {
+        area: PhysicsAreaTag,
+        entity: Option<Entity>,
+    },
+    /// A shape was dropped by the garbage collector.
+    ShapeRemoved(PhysicsShapeTag),
+    /// A joint was dropped by the garbage collector.
+    JointRemoved(PhysicsJointTag),
+}
+
+// Events
+impl<N: PtReal> WorldNpServer<N> {
+    /// Returns all the events of the last step, removing them.
+    ///
+    /// The events are cleared at the beginning of each step, so it must be called after each step.
+    pub fn drain_events(&self) -> Vec<PhysicsEvent<N>> {
+        std::mem::replace(&mut *self.events.write().unwrap(), Vec::new())
+    }
+
+    pub(crate) fn push_events<I: IntoIterator<Item = PhysicsEvent<N>>>(&self, events: I) {
+        self.events.write().unwrap().extend(events);
+    }
+
+    pub(crate) fn clear_events(&self) {
+        self.events.write().unwrap().clear();
+    }
+
+    /// Collects the contacts, the overlaps, the sleep changes and the broken joints.
+    pub(crate) fn collect_events(
+        &self,
+        bodies: &BodyStorage<N>,
+        joints: &JointStorage<N, StoreKey>,
+    ) {
+        let mut events = self.events.write().unwrap();
+
+        for (key, _) in bodies.iter() {
+            let mut body = bodies.get_body(key).unwrap();
+            let sleeping = !body.np_body.is_active();
+            match &body.body_data {
+                BodyData::Rigid { contacts, .. } => {
+                    let tag = store_key_to_rigid_tag(key);
+                    events.extend(contacts.iter().map(|c| PhysicsEvent::Contact {
+                        body: tag,
+                        event: c.event,
+                        state: c.state,
+                        other: c.other,
+                    }));
+                    if sleeping != body.sleeping {
+                        events.push(if sleeping {
+                            PhysicsEvent::Sleep(tag)
+                        } else {
+                            PhysicsEvent::WakeUp(tag)
+                        });
+                    }
+                }
+                BodyData::Area(overlaps) => {
+                    let tag = store_key_to_area_tag(key);
+                    events.extend(overlaps.iter().map(|e| PhysicsEvent::Overlap {
+                        area: tag,
+                        event: e.event.clone(),
+                    }));
+                }
+                BodyData::Soft { .. } => {}
+            }
+            body.sleeping = sleeping;
+        }
+
+        for (key, _) in joints.iter() {
+            let mut joint = joints.get_joint(key).unwrap();
+            let broken = joint.np_joint.as_ref().map_or(false, |j| j.is_broken());
+            if broken && !joint.broken {
+                events.push(PhysicsEvent::JointBroken {
+                    joint: store_key_to_joint_tag(key),
+                    body_0: joint.body_0.map(|(key, _)| store_key_to_rigid_tag(key)),
+                    body_1: joint.body_1.map(|(key, _)| store_key_to_rigid_tag(key)),
+                });
+            }
+            joint.broken = broken;
+        }
+    }
+}
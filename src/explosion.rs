@@ -0,0 +1,201 @@
+use amethyst_core::math::{Point3, Vector3};
+use amethyst_physics::PtReal;
+use ncollide3d::{bounding_volume::AABB, pipeline::object::CollisionGroups, query::Ray};
+use nphysics3d::{
+    math::{Force, ForceType},
+    world::GeometricalWorld,
+};
+
+use crate::{
+    collider_storage::ColliderStorage, query::QueryHit, replay::ReplayCall, storage::StoreKey,
+    WorldNpServer,
+};
+
+/// How the explosion impulse decreases with the distance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExplosionFalloff {
+    /// The same impulse is applied within the radius.
+    Constant,
+    /// The impulse decreases linearly, reaching 0 at the radius.
+    Linear,
+    /// The impulse decreases quadratically, reaching 0 at the radius.
+    Quadratic,
+}
+
+/// Describes an explosion.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExplosionDesc<N: PtReal> {
+    pub center: Point3<N>,
+    pub radius: N,
+    /// The impulse applied at the center.
+    pub strength: N,
+    pub falloff: ExplosionFalloff,
+    /// When `true`, the bodies hidden behind other bodies are not affected.
+    pub occlusion: bool,
+}
+
+// Explosion
+impl<N: PtReal> WorldNpServer<N> {
+    /// Applies an outward impulse to all the dynamic rigid bodies within the explosion radius.
+    ///
+    /// Returns the affected bodies; none when the radius is not positive.
+    /// The running async step is waited, so the impulses are applied after it.
+    pub fn explode(&self, desc: &ExplosionDesc<N>) -> Vec<QueryHit> {
+        self.wait_step_done();
+        if desc.radius <= N::from(0.0) {
+            return Vec::new();
+        }
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::Explode(desc.clone()));
+        let gw = self.geometrical_world.read().unwrap();
+        let colliders = self.storages.colliders_r();
+        let bodies = self.storages.bodies_r();
+
+        let groups = CollisionGroups::new();
+        let half_extents = Vector3::repeat(desc.radius);
+        let aabb = AABB::new(desc.center - half_extents, desc.center + half_extents);
+
+        let mut affected = Vec::new();
+        for (_, collider) in gw.interferences_with_aabb(&*colliders, &aabb, &groups) {
+            if collider.is_sensor() {
+                continue;
+            }
+
+            let body_key = collider.body();
+            let mut body = if let Some(body) = bodies.get_body(body_key) {
+                body
+            } else {
+                continue;
+            };
+            if !body.np_body.is_dynamic() {
+                continue;
+            }
+
+            let offset = if let Some(transform) = body.body_transform() {
+                transform.translation.vector - desc.center.coords
+            } else {
+                continue;
+            };
+            let distance = offset.norm();
+            if distance > desc.radius {
+                continue;
+            }
+
+            if desc.occlusion && is_occluded(&gw, &colliders, desc.center, offset, body_key) {
+                continue;
+            }
+
+            let ratio = N::from(1.0) - distance / desc.radius;
+            let magnitude = desc.strength
+                * match desc.falloff {
+                    ExplosionFalloff::Constant => N::from(1.0),
+                    ExplosionFalloff::Linear => ratio,
+                    ExplosionFalloff::Quadratic => ratio * ratio,
+                };
+            let direction = offset
+                .try_normalize(N::default_epsilon())
+                .unwrap_or_else(Vector3::y);
+
+            body.np_body.apply_force(
+                0,
+                &Force::linear(direction * magnitude),
+                ForceType::Impulse,
+                true,
+            );
+            affected.extend(QueryHit::from_collider(collider));
+        }
+        affected
+    }
+}
+
+/// Returns `true` if the first collider hit, going from the center to the body, belongs to another
+/// body.
+fn is_occluded<N: PtReal>(
+    gw: &GeometricalWorld<N, StoreKey, StoreKey>,
+    colliders: &ColliderStorage<N, StoreKey>,
+    center: Point3<N>,
+    offset: Vector3<N>,
+    body_key: StoreKey,
+) -> bool {
+    let ray = Ray::new(center, offset);
+    let groups = CollisionGroups::new();
+
+    let mut nearest: Option<(N, StoreKey)> = None;
+    for (_, collider, intersection) in gw.interferences_with_ray(colliders, &ray, &groups) {
+        if collider.is_sensor() {
+            continue;
+        }
+        if nearest.map_or(true, |(toi, _)| intersection.toi < toi) {
+            nearest = Some((intersection.toi, collider.body()));
+        }
+    }
+
+    nearest.map_or(false, |(_, key)| key != body_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Point3, Vector3};
+    use amethyst_physics::servers::{
+        RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc, WorldPhysicsServerTrait,
+    };
+
+    use super::{ExplosionDesc, ExplosionFalloff};
+    use crate::{test_world::TestWorld, PhysicsObject};
+
+    #[test]
+    fn explosion_pushes_the_bodies_in_radius() {
+        let test = TestWorld::<f32>::new();
+        test.world.set_gravity(&Vector3::zeros());
+
+        let sphere = ShapeDesc::Sphere { radius: 0.5 };
+        let desc = RigidBodyDesc::default();
+        let near = test.create_body(&desc, &sphere, &Isometry3::translation(2.0, 0.0, 0.0));
+        let behind = test.create_body(&desc, &sphere, &Isometry3::translation(4.0, 0.0, 0.0));
+        let far = test.create_body(&desc, &sphere, &Isometry3::translation(20.0, 0.0, 0.0));
+        test.step();
+
+        let mut explosion = ExplosionDesc {
+            center: Point3::origin(),
+            radius: 10.0,
+            strength: 10.0,
+            falloff: ExplosionFalloff::Linear,
+            occlusion: true,
+        };
+        let affected: Vec<_> = test
+            .world
+            .explode(&explosion)
+            .iter()
+            .map(|hit| hit.object)
+            .collect();
+        assert_eq!(affected, vec![PhysicsObject::RigidBody(near.get())]);
+
+        explosion.occlusion = false;
+        assert_eq!(test.world.explode(&explosion).len(), 2);
+        let near_velocity = test.bodies.linear_velocity(near.get());
+        let behind_velocity = test.bodies.linear_velocity(behind.get());
+        assert!(near_velocity.x > behind_velocity.x);
+        assert!(behind_velocity.x > 0.0);
+        assert_eq!(test.bodies.linear_velocity(far.get()), Vector3::zeros());
+    }
+
+    #[test]
+    fn explosion_without_radius_affects_nothing() {
+        let test = TestWorld::<f32>::new();
+        test.world.set_gravity(&Vector3::zeros());
+        let sphere = ShapeDesc::Sphere { radius: 0.5 };
+        let body = test.create_body(&RigidBodyDesc::default(), &sphere, &Isometry3::identity());
+        test.step();
+
+        let explosion = ExplosionDesc {
+            center: Point3::origin(),
+            radius: 0.0,
+            strength: 10.0,
+            falloff: ExplosionFalloff::Linear,
+            occlusion: false,
+        };
+        assert!(test.world.explode(&explosion).is_empty());
+        assert_eq!(test.bodies.linear_velocity(body.get()), Vector3::zeros());
+    }
+}
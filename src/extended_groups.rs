@@ -0,0 +1,78 @@
+use amethyst_physics::PtReal;
+use ncollide3d::pipeline::broad_phase::BroadPhasePairFilter;
+use nphysics3d::{object::ColliderSet, world::BroadPhasePairFilterSets};
+use serde::{Deserialize, Serialize};
+
+use crate::{storage::StoreKey, utils::UserData};
+
+/// Collision masks of 64 layers, for the projects that need more layers than the 30 collision
+/// groups supported by NPhysics; e.g. a mask per team.
+///
+/// They are checked in the broad phase, after the collision groups: two colliders interact only
+/// when each one belongs to a layer the other collides with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ExtendedGroups {
+    /// The mask of the layers the body belongs to.
+    pub belong_to: u64,
+    /// The mask of the layers the body collides with.
+    pub collide_with: u64,
+}
+
+impl ExtendedGroups {
+    /// Belongs to all the layers and collides with all the layers, so nothing is filtered.
+    pub const ALL: ExtendedGroups = ExtendedGroups {
+        belong_to: u64::MAX,
+        collide_with: u64::MAX,
+    };
+
+    /// Creates the masks from the indices of the layers, from 0 to 63.
+    pub fn new(belong_to: &[u8], collide_with: &[u8]) -> Self {
+        ExtendedGroups {
+            belong_to: to_mask(belong_to),
+            collide_with: to_mask(collide_with),
+        }
+    }
+
+    /// Returns `true` if the bodies with these masks can interact.
+    pub fn interacts_with(&self, other: &ExtendedGroups) -> bool {
+        self.belong_to & other.collide_with != 0 && other.belong_to & self.collide_with != 0
+    }
+}
+
+impl Default for ExtendedGroups {
+    fn default() -> Self {
+        ExtendedGroups::ALL
+    }
+}
+
+fn to_mask(layers: &[u8]) -> u64 {
+    layers
+        .iter()
+        .filter(|layer| **layer < 64)
+        .fold(0, |mask, layer| mask | 1 << layer)
+}
+
+/// Discards the pairs of colliders that the `ExtendedGroups`, stored in the collider `UserData`,
+/// don't allow to interact.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ExtendedGroupsFilter;
+
+impl<'a, N: PtReal> BroadPhasePairFilter<N, BroadPhasePairFilterSets<'a, N, StoreKey, StoreKey>>
+    for ExtendedGroupsFilter
+{
+    fn is_pair_valid(
+        &self,
+        h1: StoreKey,
+        h2: StoreKey,
+        set: &BroadPhasePairFilterSets<'a, N, StoreKey, StoreKey>,
+    ) -> bool {
+        let groups = |handle| {
+            set.colliders()
+                .get(handle)
+                .and_then(|collider| collider.user_data())
+                .and_then(|user_data| user_data.downcast_ref::<UserData>())
+                .map_or(ExtendedGroups::ALL, |user_data| user_data.extended_groups())
+        };
+        groups(h1).interacts_with(&groups(h2))
+    }
+}
@@ -1,28 +1,109 @@
 use amethyst_physics::PtReal;
 use nphysics3d::{
-    force_generator::ForceGenerator as NpForceGenerator,
-    object::{ BodyHandle as NpBodyHandle},
+    force_generator::ForceGenerator as NpForceGenerator, object::BodyHandle as NpBodyHandle,
 };
 
 use crate::storage::StoreKey;
 
+/// Opaque tag of a force generator, created by the `ForceGeneratorNpServer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ForceGeneratorTag(pub(crate) StoreKey);
+
+/// How long a force generator lives before being destroyed automatically.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ForceGeneratorLifetime<N: PtReal> {
+    /// Seconds of simulated time.
+    Duration(N),
+    /// Number of steps.
+    Steps(usize),
+}
+
+impl<N: PtReal> ForceGeneratorLifetime<N> {
+    /// Consumes a step, and returns `true` if the lifetime is expired.
+    fn consume(&mut self, delta_time: N) -> bool {
+        match self {
+            ForceGeneratorLifetime::Duration(remaining) => {
+                *remaining -= delta_time;
+                *remaining <= N::from(0.0)
+            }
+            ForceGeneratorLifetime::Steps(remaining) => {
+                *remaining = remaining.saturating_sub(1);
+                *remaining == 0
+            }
+        }
+    }
+}
+
 #[allow(missing_debug_implementations)]
 pub struct ForceGenerator<N: PtReal, Handle: NpBodyHandle> {
     pub self_key: Option<StoreKey>,
     pub np_force_generator: Box<dyn NpForceGenerator<N, Handle>>,
-    pub world_key: StoreKey,
+    /// The remaining lifetime, `None` to never expire.
+    pub lifetime: Option<ForceGeneratorLifetime<N>>,
+    /// When `false` the force generator is not applied, and its lifetime is not consumed.
+    pub enabled: bool,
+    /// The bodies explicitly targeted by this force generator; empty for the global ones, like
+    /// the wind.
+    pub targets: Vec<StoreKey>,
 }
 
 impl<N: PtReal, Handle: NpBodyHandle> ForceGenerator<N, Handle> {
-    #[allow(dead_code)]
     pub(crate) fn new(
         np_force_generator: Box<dyn NpForceGenerator<N, Handle>>,
-        world_key: StoreKey,
+        targets: Vec<StoreKey>,
     ) -> Self {
         ForceGenerator {
             self_key: None,
             np_force_generator,
-            world_key,
+            lifetime: None,
+            enabled: true,
+            targets,
+        }
+    }
+
+    /// Consumes a step of the lifetime, and returns `true` if the force generator is expired.
+    pub(crate) fn consume_lifetime(&mut self, delta_time: N) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        if let Some(lifetime) = &mut self.lifetime {
+            lifetime.consume(delta_time)
+        } else {
+            false
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ForceGeneratorLifetime;
+    use crate::{test_world::TestWorld, ForceGeneratorNpServer, WindDesc};
+
+    #[test]
+    fn expired_force_generator_destroyed() {
+        let test = TestWorld::<f32>::new();
+        let server = ForceGeneratorNpServer::new(test.storages.clone());
+
+        let by_steps = server.create_wind(&WindDesc::default());
+        server.set_lifetime(by_steps, Some(ForceGeneratorLifetime::Steps(3)));
+        let by_time = server.create_wind(&WindDesc::default());
+        server.set_lifetime(
+            by_time,
+            Some(ForceGeneratorLifetime::Duration(test.time_step() * 4.5)),
+        );
+        let forever = server.create_wind(&WindDesc::default());
+
+        test.step_n(2);
+        assert_eq!(
+            server.lifetime(by_steps),
+            Some(ForceGeneratorLifetime::Steps(1))
+        );
+        test.step();
+        assert!(!server.exists(by_steps));
+        assert!(server.exists(by_time));
+
+        test.step_n(2);
+        assert!(!server.exists(by_time));
+        assert!(server.exists(forever));
+    }
+}
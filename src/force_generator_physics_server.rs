@@ -0,0 +1,208 @@
+use amethyst_physics::{objects::*, PtReal};
+use log::error;
+
+use crate::{
+    conversors::*,
+    custom_force_generator::{BodyForceGenerator, CustomForceGenerator},
+    force_generator::{ForceGenerator, ForceGeneratorLifetime, ForceGeneratorTag},
+    servers_storage::ServersStorages,
+    storage::StoreKey,
+    wind_force_generator::{WindDesc, WindForceGenerator},
+};
+
+/// The server that manages the force generators.
+///
+/// The force generators are applied by the solver during each step.
+/// Unlike the other objects, the force generators are not owned by a `PhysicsHandle`, so they must
+/// be explicitly destroyed.
+#[allow(missing_debug_implementations)]
+pub struct ForceGeneratorNpServer<N: PtReal> {
+    storages: ServersStorages<N>,
+}
+
+impl<N: PtReal> ForceGeneratorNpServer<N> {
+    pub fn new(storages: ServersStorages<N>) -> Self {
+        ForceGeneratorNpServer { storages }
+    }
+
+    /// Creates a wind.
+    pub fn create_wind(&self, desc: &WindDesc<N>) -> ForceGeneratorTag {
+        self.insert(ForceGenerator::new(
+            Box::new(WindForceGenerator::new(desc.clone())),
+            Vec::new(),
+        ))
+    }
+
+    /// Creates a force generator that calls the passed `BodyForceGenerator`, or closure, for each
+    /// of the passed bodies during each step.
+    pub fn create_custom<G>(
+        &self,
+        bodies: &[PhysicsRigidBodyTag],
+        generator: G,
+    ) -> ForceGeneratorTag
+    where
+        G: BodyForceGenerator<N> + 'static,
+    {
+        let bodies: Vec<StoreKey> = bodies.iter().map(|b| rigid_tag_to_store_key(*b)).collect();
+        self.insert(ForceGenerator::new(
+            Box::new(CustomForceGenerator::new(
+                bodies.clone(),
+                Box::new(generator),
+            )),
+            bodies,
+        ))
+    }
+
+    /// Set after how long the force generator is automatically destroyed, counting from the next
+    /// step; `None` to never destroy it.
+    pub fn set_lifetime(
+        &self,
+        tag: ForceGeneratorTag,
+        lifetime: Option<ForceGeneratorLifetime<N>>,
+    ) {
+        let force_generators = self.storages.force_generator_r();
+        if let Some(mut force_generator) = force_generators.get_force_generator(tag.0) {
+            force_generator.lifetime = lifetime;
+        } else {
+            error!("Force generator not found");
+        }
+    }
+
+    /// Returns the remaining lifetime of the force generator.
+    pub fn lifetime(&self, tag: ForceGeneratorTag) -> Option<ForceGeneratorLifetime<N>> {
+        self.storages
+            .force_generator_r()
+            .get_force_generator(tag.0)
+            .and_then(|force_generator| force_generator.lifetime)
+    }
+
+    /// Enable or disable the force generator, without losing its configuration.
+    pub fn set_enabled(&self, tag: ForceGeneratorTag, enabled: bool) {
+        let force_generators = self.storages.force_generator_r();
+        if let Some(mut force_generator) = force_generators.get_force_generator(tag.0) {
+            force_generator.enabled = enabled;
+        } else {
+            error!("Force generator not found");
+        }
+    }
+
+    pub fn is_enabled(&self, tag: ForceGeneratorTag) -> bool {
+        self.storages
+            .force_generator_r()
+            .get_force_generator(tag.0)
+            .map_or(false, |force_generator| force_generator.enabled)
+    }
+
+    /// Returns the force generators that are explicitly targeting the body.
+    ///
+    /// The global force generators, like the wind, are not returned.
+    pub fn force_generators_of(&self, body_tag: PhysicsRigidBodyTag) -> Vec<ForceGeneratorTag> {
+        self.storages
+            .force_generator_r()
+            .targeting(rigid_tag_to_store_key(body_tag))
+            .into_iter()
+            .map(ForceGeneratorTag)
+            .collect()
+    }
+
+    /// Removes the body from all the force generators targeting it.
+    ///
+    /// The force generators that remain without targets are destroyed.
+    /// This is done automatically when the body is destroyed.
+    pub fn clear_force_generators(&self, body_tag: PhysicsRigidBodyTag) {
+        self.storages
+            .force_generator_w()
+            .remove_target(rigid_tag_to_store_key(body_tag));
+    }
+
+    /// Destroys the force generator.
+    pub fn destroy(&self, tag: ForceGeneratorTag) {
+        self.storages.force_generator_w().drop(tag.0);
+    }
+
+    /// Returns `true` if the force generator exists.
+    pub fn exists(&self, tag: ForceGeneratorTag) -> bool {
+        self.storages
+            .force_generator_r()
+            .get_force_generator(tag.0)
+            .is_some()
+    }
+
+    pub(crate) fn insert(&self, force_generator: ForceGenerator<N, StoreKey>) -> ForceGeneratorTag {
+        let mut force_generators = self.storages.force_generator_w();
+        let key = force_generators.insert(force_generator);
+        force_generators.get_force_generator(key).unwrap().self_key = Some(key);
+        ForceGeneratorTag(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Vector3};
+    use amethyst_physics::{
+        objects::PhysicsRigidBodyTag,
+        servers::{RigidBodyDesc, ShapeDesc, WorldPhysicsServerTrait},
+    };
+    use nphysics3d::object::Body as NpBody;
+
+    use super::ForceGeneratorNpServer;
+    use crate::{test_world::TestWorld, ForceGeneratorLifetime, WindDesc};
+
+    #[test]
+    fn disabled_force_generator_not_applied() {
+        let test = TestWorld::<f32>::new();
+        test.world.set_gravity(&Vector3::zeros());
+        let server = ForceGeneratorNpServer::new(test.storages.clone());
+
+        let body = test.create_body(
+            &RigidBodyDesc::default(),
+            &ShapeDesc::Sphere { radius: 0.5 },
+            &Isometry3::identity(),
+        );
+        let wind = server.create_wind(&WindDesc::default());
+        server.set_lifetime(wind, Some(ForceGeneratorLifetime::Steps(10)));
+        server.set_enabled(wind, false);
+        assert!(!server.is_enabled(wind));
+
+        test.step_n(5);
+        assert_eq!(test.position(body.get()).x, 0.0);
+        assert_eq!(
+            server.lifetime(wind),
+            Some(ForceGeneratorLifetime::Steps(10))
+        );
+
+        server.set_enabled(wind, true);
+        test.step_n(5);
+        assert!(test.position(body.get()).x > 0.0);
+        assert_eq!(
+            server.lifetime(wind),
+            Some(ForceGeneratorLifetime::Steps(5))
+        );
+    }
+
+    #[test]
+    fn force_generators_of_the_body_cleared() {
+        let test = TestWorld::<f32>::new();
+        let server = ForceGeneratorNpServer::new(test.storages.clone());
+
+        let sphere = ShapeDesc::Sphere { radius: 0.5 };
+        let desc = RigidBodyDesc::default();
+        let a = test.create_body(&desc, &sphere, &Isometry3::identity());
+        let b = test.create_body(&desc, &sphere, &Isometry3::translation(5.0, 0.0, 0.0));
+
+        let no_force = |_: PhysicsRigidBodyTag, _: &mut dyn NpBody<f32>, _: f32| {};
+        let only_a = server.create_custom(&[a.get()], no_force);
+        let both = server.create_custom(&[a.get(), b.get()], no_force);
+        let wind = server.create_wind(&WindDesc::default());
+
+        assert_eq!(server.force_generators_of(a.get()), vec![only_a, both]);
+        assert_eq!(server.force_generators_of(b.get()), vec![both]);
+
+        server.clear_force_generators(a.get());
+        assert!(server.force_generators_of(a.get()).is_empty());
+        assert!(!server.exists(only_a));
+        assert!(server.exists(both));
+        assert!(server.exists(wind));
+        assert_eq!(server.force_generators_of(b.get()), vec![both]);
+    }
+}
@@ -7,7 +7,9 @@ use nphysics3d::{
 };
 
 use crate::{
+    custom_force_generator::CustomForceGenerator,
     force_generator::ForceGenerator,
+    statistics::StorageUsage,
     storage::{Storage, StorageGuard, StoreKey},
 };
 
@@ -22,6 +24,14 @@ impl<N: PtReal, Handle: NpBodyHandle> ForceGeneratorStorage<N, Handle> {
             storage: Storage::new(5, 5),
         }
     }
+
+    /// Create a storage that can hold `capacity` objects before growing, and then grows by the
+    /// same amount.
+    pub fn with_capacity(capacity: usize) -> Self {
+        ForceGeneratorStorage {
+            storage: Storage::new(capacity, capacity.max(1)),
+        }
+    }
 }
 
 impl<N: PtReal, Handle: NpBodyHandle> Default for ForceGeneratorStorage<N, Handle> {
@@ -39,6 +49,39 @@ impl<N: PtReal, Handle: NpBodyHandle> ForceGeneratorStorage<N, Handle> {
         self.storage.remove(key);
     }
 
+    pub fn clear(&mut self) {
+        self.storage.clear();
+    }
+
+    /// Releases the memory of the empty storage; see `Storage::shrink_to`.
+    pub fn shrink_to(&mut self, capacity: usize) {
+        self.storage.shrink_to(capacity);
+    }
+
+    pub fn usage(&self) -> StorageUsage {
+        self.storage.usage()
+    }
+
+    /// Consumes a step of the force generators lifetime, and drops the expired ones.
+    pub fn update_lifetimes(&mut self, delta_time: N) {
+        let expired: Vec<StoreKey> = self
+            .storage
+            .iter()
+            .filter_map(|(key, _)| {
+                let mut force_generator = self.storage.get(key).unwrap();
+                if force_generator.consume_lifetime(delta_time) {
+                    Some(key)
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        for key in expired {
+            self.storage.remove(key);
+        }
+    }
+
     /// Returns a `Mutex` guarded force generator that can be used safely to get or set data.
     pub fn get_force_generator(
         &self,
@@ -48,6 +91,42 @@ impl<N: PtReal, Handle: NpBodyHandle> ForceGeneratorStorage<N, Handle> {
     }
 }
 
+// Targets
+impl<N: PtReal> ForceGeneratorStorage<N, StoreKey> {
+    /// Returns the force generators that are explicitly targeting the body.
+    pub fn targeting(&self, body: StoreKey) -> Vec<StoreKey> {
+        self.storage
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| self.storage.get(*key).unwrap().targets.contains(&body))
+            .collect()
+    }
+
+    /// Removes the body from the targets of all the force generators.
+    ///
+    /// The force generators that remain without targets are dropped.
+    pub fn remove_target(&mut self, body: StoreKey) {
+        let mut to_drop = Vec::new();
+        for key in self.targeting(body) {
+            let mut force_generator = self.storage.get(key).unwrap();
+            force_generator.targets.retain(|&b| b != body);
+            if let Some(custom) = force_generator
+                .np_force_generator
+                .downcast_mut::<CustomForceGenerator<N>>()
+            {
+                custom.remove_body(body);
+            }
+            if force_generator.targets.is_empty() {
+                to_drop.push(key);
+            }
+        }
+
+        for key in to_drop {
+            self.storage.remove(key);
+        }
+    }
+}
+
 impl<N: PtReal, Handle: NpBodyHandle + 'static> NpForceGeneratorSet<N, Handle>
     for ForceGeneratorStorage<N, Handle>
 {
@@ -70,17 +149,28 @@ impl<N: PtReal, Handle: NpBodyHandle + 'static> NpForceGeneratorSet<N, Handle>
         self.storage.has(handle)
     }
 
+    // The disabled force generators are skipped, so NPhysics doesn't apply them.
     fn foreach(&self, mut f: impl FnMut(Self::Handle, &Self::ForceGenerator)) {
         for (i, c) in self.storage.iter() {
             // Safe because NPhysics use this in single thread.
-            unsafe { f(i, (*c.0.get()).np_force_generator.as_ref()) }
+            unsafe {
+                let force_generator = &*c.0.get();
+                if force_generator.enabled {
+                    f(i, force_generator.np_force_generator.as_ref())
+                }
+            }
         }
     }
 
     fn foreach_mut(&mut self, mut f: impl FnMut(Self::Handle, &mut Self::ForceGenerator)) {
         for (i, c) in self.storage.iter_mut() {
             // Safe because NPhysics use this in single thread.
-            unsafe { f(i, (*c.0.get()).np_force_generator.as_mut()) }
+            unsafe {
+                let force_generator = &mut *c.0.get();
+                if force_generator.enabled {
+                    f(i, force_generator.np_force_generator.as_mut())
+                }
+            }
         }
     }
 }
@@ -0,0 +1,89 @@
+use amethyst_core::ecs::Entity;
+use amethyst_physics::{objects::*, PtReal};
+
+use crate::{body::BodyData, conversors::*, WorldNpServer};
+
+// Introspection
+impl<N: PtReal> WorldNpServer<N> {
+    /// Returns all the live rigid bodies, with their entities.
+    pub fn rigid_bodies(&self) -> Vec<(PhysicsRigidBodyTag, Option<Entity>)> {
+        let bodies = self.storages.bodies_r();
+        bodies
+            .iter()
+            .filter_map(|(key, _)| {
+                let body = bodies.get_body(key)?;
+                match body.body_data {
+                    BodyData::Rigid { .. } => Some((store_key_to_rigid_tag(key), body.entity)),
+                    BodyData::Area(..) | BodyData::Soft { .. } => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns all the live areas, with their entities.
+    pub fn areas(&self) -> Vec<(PhysicsAreaTag, Option<Entity>)> {
+        let bodies = self.storages.bodies_r();
+        bodies
+            .iter()
+            .filter_map(|(key, _)| {
+                let body = bodies.get_body(key)?;
+                match body.body_data {
+                    BodyData::Area(..) => Some((store_key_to_area_tag(key), body.entity)),
+                    BodyData::Rigid { .. } | BodyData::Soft { .. } => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns all the live shapes, including the ones that are waiting to be dropped because still
+    /// in use.
+    pub fn shapes(&self) -> Vec<PhysicsShapeTag> {
+        self.storages
+            .shapes_r()
+            .iter()
+            .map(|(key, _)| store_key_to_shape_tag(key))
+            .collect()
+    }
+
+    /// Returns all the live joints.
+    pub fn joints(&self) -> Vec<PhysicsJointTag> {
+        self.storages
+            .joints_r()
+            .iter()
+            .map(|(key, _)| store_key_to_joint_tag(key))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_physics::servers::{
+        AreaDesc, AreaPhysicsServerTrait, JointDesc, JointPhysicsServerTrait, JointPosition,
+        RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc, ShapePhysicsServerTrait,
+    };
+
+    use crate::test_world::TestWorld;
+
+    #[test]
+    fn all_objects_enumerated() {
+        let test = TestWorld::<f32>::new();
+
+        let shape = test.shapes.create(&ShapeDesc::Sphere { radius: 1.0 });
+        let body = test.bodies.create(&RigidBodyDesc::default());
+        test.bodies.set_shape(body.get(), Some(shape.get()));
+        let area = test.areas.create(&AreaDesc::default());
+        let joint = test.joints.create(&JointDesc::Fixed, JointPosition::Middle);
+
+        assert_eq!(test.world.rigid_bodies(), vec![(body.get(), None)]);
+        assert_eq!(test.world.areas(), vec![(area.get(), None)]);
+        assert_eq!(test.world.shapes(), vec![shape.get()]);
+        assert_eq!(test.world.joints(), vec![joint.get()]);
+
+        drop(body);
+        drop(area);
+        test.step();
+        assert!(test.world.rigid_bodies().is_empty());
+        assert!(test.world.areas().is_empty());
+        assert_eq!(test.world.shapes(), vec![shape.get()]);
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use amethyst_physics::{objects::*, PtReal};
+
+use crate::{
+    body_storage::BodyStorage,
+    conversors::*,
+    error::{LogError, PhysicsError, PhysicsResult},
+    storage::StoreKey,
+    WorldNpServer,
+};
+
+// Islands
+impl<N: PtReal> WorldNpServer<N> {
+    /// Returns the other dynamic rigid bodies that are in the same island of the passed one.
+    ///
+    /// Two bodies are in the same island when they are touching, or are constrained by a joint,
+    /// directly or through other dynamic bodies.
+    /// The islands are computed using the contacts of the last step.
+    pub fn island(&self, body_tag: PhysicsRigidBodyTag) -> Vec<PhysicsRigidBodyTag> {
+        self.try_island(body_tag)
+            .or_report(&self.storages)
+            .unwrap_or_default()
+    }
+
+    /// Like `island`, but returns an error when the body is not a dynamic rigid body.
+    pub fn try_island(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+    ) -> PhysicsResult<Vec<PhysicsRigidBodyTag>> {
+        let body_key = rigid_tag_to_store_key(body_tag);
+
+        let bodies = self.storages.bodies_r();
+        let colliders = self.storages.colliders_r();
+        let joints = self.storages.joints_r();
+        let gw = self.geometrical_world.read().unwrap();
+
+        if bodies.get_body(body_key).is_none() {
+            return Err(PhysicsError::BodyNotFound);
+        }
+        if !is_dynamic(&bodies, body_key) {
+            return Err(PhysicsError::NotDynamic);
+        }
+
+        let mut links: HashMap<StoreKey, Vec<StoreKey>> = HashMap::new();
+        let mut link = |a: StoreKey, b: StoreKey| {
+            links.entry(a).or_insert_with(Vec::new).push(b);
+            links.entry(b).or_insert_with(Vec::new).push(a);
+        };
+
+        for (_, collider_1, _, collider_2, _, _) in gw.contact_pairs(&*colliders, true) {
+            link(collider_1.body(), collider_2.body());
+        }
+        for (key, _) in joints.iter() {
+            let joint = joints.get_joint(key).unwrap();
+            if let (Some(_), Some(body_0), Some(body_1)) =
+                (&joint.np_joint, joint.body_0, joint.body_1)
+            {
+                link(body_0.0, body_1.0);
+            }
+        }
+
+        // The static and kinematic bodies don't propagate the island.
+        let mut visited = HashSet::new();
+        let mut to_visit = VecDeque::new();
+        visited.insert(body_key);
+        to_visit.push_back(body_key);
+        while let Some(key) = to_visit.pop_front() {
+            if let Some(linked) = links.get(&key) {
+                for other in linked {
+                    if !visited.contains(other) && is_dynamic(&bodies, *other) {
+                        visited.insert(*other);
+                        to_visit.push_back(*other);
+                    }
+                }
+            }
+        }
+
+        visited.remove(&body_key);
+        Ok(visited.into_iter().map(store_key_to_rigid_tag).collect())
+    }
+}
+
+fn is_dynamic<N: PtReal>(bodies: &BodyStorage<N>, key: StoreKey) -> bool {
+    bodies
+        .get_body(key)
+        .map(|body| body.rigid_body().is_some() && body.np_body.is_dynamic())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::Isometry3;
+    use amethyst_physics::servers::{
+        JointDesc, JointPhysicsServerTrait, JointPosition, RigidBodyDesc, ShapeDesc,
+    };
+
+    use crate::{test_world::TestWorld, PhysicsError};
+
+    #[test]
+    fn island_propagated_by_the_dynamic_bodies() {
+        let test = TestWorld::<f32>::new();
+
+        let ground = test.create_ground();
+        let sphere = ShapeDesc::Sphere { radius: 0.5 };
+        let desc = RigidBodyDesc::default();
+        let body_a = test.create_body(&desc, &sphere, &Isometry3::translation(-3.0, 0.6, 0.0));
+        let body_b = test.create_body(&desc, &sphere, &Isometry3::translation(3.0, 0.6, 0.0));
+        let body_c = test.create_body(&desc, &sphere, &Isometry3::translation(-3.0, 2.0, 0.0));
+        let joint = test.joints.create(&JointDesc::Fixed, JointPosition::Middle);
+        test.joints.insert_rigid_body(joint.get(), body_a.get());
+        test.joints.insert_rigid_body(joint.get(), body_c.get());
+        test.step_n(10);
+
+        // A and B touch the ground, but the static bodies don't propagate the island.
+        assert_eq!(test.world.island(body_a.get()), vec![body_c.get()]);
+        assert!(test.world.island(body_b.get()).is_empty());
+        assert_eq!(
+            test.world.try_island(ground.get()),
+            Err(PhysicsError::NotDynamic)
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use amethyst_core::math::{Isometry3, Vector3};
 use amethyst_physics::{
     servers::{JointDesc, JointPosition},
     PtReal,
@@ -11,9 +12,18 @@ pub struct Joint<N: PtReal, Handle: NpBodyHandle> {
     pub self_key: Option<StoreKey>,
     pub joint_desc: JointDesc,
     pub initial_position: JointPosition<N>,
+    /// The anchors in the local space of `body_0` and `body_1`; when set, the `initial_position`
+    /// is not used.
+    pub local_anchors: Option<[Isometry3<N>; 2]>,
+    /// The sliding axis and the rotation axis, in the space of the anchors, of the suspension
+    /// joints created with `JointNpServer::create_suspension`; when set, the `joint_desc` is not
+    /// used.
+    pub suspension_axes: Option<[Vector3<N>; 2]>,
     pub np_joint: Option<Box<dyn NpJointConstraint<N, Handle>>>,
     pub body_0: Option<(StoreKey, usize)>, // Body key, Part id
     pub body_1: Option<(StoreKey, usize)>, // Body key, Part id
+    /// `true` when the broken state was already notified.
+    pub broken: bool,
 }
 
 impl<N: PtReal, Handle: NpBodyHandle> Joint<N, Handle> {
@@ -22,9 +32,12 @@ impl<N: PtReal, Handle: NpBodyHandle> Joint<N, Handle> {
             self_key: None,
             joint_desc,
             initial_position,
+            local_anchors: None,
+            suspension_axes: None,
             np_joint: None,
             body_0: None,
             body_1: None,
+            broken: false,
         }
     }
 }
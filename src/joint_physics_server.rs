@@ -1,4 +1,4 @@
-use amethyst_core::math::{convert, Isometry3, Translation3};
+use amethyst_core::math::{convert, Isometry3, Translation3, Unit, Vector3};
 use amethyst_physics::{
     objects::*,
     servers::{JointDesc, JointPhysicsServerTrait, JointPosition},
@@ -6,17 +6,25 @@ use amethyst_physics::{
 };
 use log::error;
 use nphysics3d::{
-    joint::FixedConstraint as NpFixedConstraint, object::BodyPartHandle as NpBodyPartHandle,
+    joint::{
+        FixedConstraint as NpFixedConstraint, JointConstraint as NpJointConstraint,
+        PinSlotConstraint as NpPinSlotConstraint,
+    },
+    object::BodyPartHandle as NpBodyPartHandle,
 };
 
 use crate::{
+    body::BodyData,
     conversors::*,
+    error::{LogError, PhysicsError, PhysicsResult},
     joint::Joint,
+    replay::ReplayCall,
     servers_storage::{BodiesStorageRead, JointsStorageWrite, ServersStorages},
     storage::StoreKey,
     RBodyNpServer,
 };
 
+#[allow(missing_debug_implementations)]
 pub struct JointNpServer<N: PtReal> {
     storages: ServersStorages<N>,
 }
@@ -37,24 +45,80 @@ impl<N: PtReal> JointNpServer<N> {
     ) {
         let j_key = joint_tag_to_store_key(joint_tag);
 
-        // Active the constraint bodies
+        // Active the constraint bodies, and release them
         if let Some(joint) = joints.get_joint(j_key) {
-            if joint.body_0.is_some() {
-                RBodyNpServer::active_body(joint.body_0.unwrap().0, bodies);
+            if let Some((body_key, _)) = joint.body_0 {
+                RBodyNpServer::active_body(body_key, bodies);
+                Self::unregister_joint(body_key, j_key, bodies);
             }
-            if joint.body_1.is_some() {
-                RBodyNpServer::active_body(joint.body_1.unwrap().0, bodies);
+            if let Some((body_key, _)) = joint.body_1 {
+                RBodyNpServer::active_body(body_key, bodies);
+                Self::unregister_joint(body_key, j_key, bodies);
             }
         }
 
         joints.drop_joint(j_key);
     }
 
+    /// Removes the body from all the joints that are constraining it.
+    ///
+    /// This must be called before dropping a body, so no joint refers to it anymore.
+    ///
+    /// Returns the joints the body was detached from, with the other body of each joint.
+    pub fn detach_body(
+        body_key: StoreKey,
+        joints: &mut JointsStorageWrite<'_, N>,
+        bodies: &BodiesStorageRead<'_, N>,
+    ) -> Vec<(StoreKey, Option<StoreKey>)> {
+        let body_joints = if let Some(mut body) = bodies.get_body(body_key) {
+            std::mem::replace(&mut body.joints, Vec::new())
+        } else {
+            return Vec::new();
+        };
+
+        let mut detached = Vec::with_capacity(body_joints.len());
+        for joint_key in body_joints {
+            if let Some(mut joint) = joints.get_joint(joint_key) {
+                let other = if joint.body_0.map(|v| v.0) == Some(body_key) {
+                    joint.body_0 = None;
+                    joint.body_1
+                } else if joint.body_1.map(|v| v.0) == Some(body_key) {
+                    joint.body_1 = None;
+                    joint.body_0
+                } else {
+                    continue;
+                };
+                if let Some((other_key, _)) = other {
+                    RBodyNpServer::active_body(other_key, bodies);
+                }
+                detached.push((joint_key, other.map(|(other_key, _)| other_key)));
+            }
+            Self::update_internal_joint(joint_key, joints, bodies).or_log();
+        }
+        detached
+    }
+
+    fn register_joint(body_key: StoreKey, joint_key: StoreKey, bodies: &BodiesStorageRead<'_, N>) {
+        if let Some(mut body) = bodies.get_body(body_key) {
+            body.joints.push(joint_key);
+        }
+    }
+
+    fn unregister_joint(
+        body_key: StoreKey,
+        joint_key: StoreKey,
+        bodies: &BodiesStorageRead<'_, N>,
+    ) {
+        if let Some(mut body) = bodies.get_body(body_key) {
+            body.joints.retain(|&j| j != joint_key);
+        }
+    }
+
     pub fn update_internal_joint(
         joint_key: StoreKey,
         joints: &mut JointsStorageWrite<'_, N>,
         bodies: &BodiesStorageRead<'_, N>,
-    ) {
+    ) -> PhysicsResult<()> {
         let mut notify_added = false;
         let mut notify_removed = false;
         {
@@ -69,44 +133,68 @@ impl<N: PtReal> JointNpServer<N> {
                     }
                 } else if joint.body_0.is_some() && joint.body_1.is_some() {
                     // -- Create the joint --
-                    let body_0 = bodies.get_body(joint.body_0.unwrap().0);
-                    let body_1 = bodies.get_body(joint.body_1.unwrap().0);
-                    fail_cond!(body_0.is_none() || body_1.is_none());
-
-                    let body_0 = body_0.unwrap();
-                    let body_1 = body_1.unwrap();
-
-                    let body_0_trsf = body_0.body_transform();
-                    let body_1_trsf = body_1.body_transform();
-
-                    let joint_initial_isometry = match joint.initial_position {
-                        JointPosition::Exact(pos) => pos,
-                        JointPosition::Middle => {
-                            let v = body_0_trsf
-                                .translation
-                                .vector
-                                .lerp(&body_1_trsf.translation.vector, convert(0.5));
-                            let r = body_0_trsf
-                                .rotation
-                                .slerp(&body_1_trsf.rotation, convert(0.5));
-                            Isometry3::from_parts(Translation3::from(v), r)
-                        }
+                    let [anchor_0, anchor_1] = if let Some(anchors) = joint.local_anchors {
+                        anchors
+                    } else {
+                        let body_0 = bodies
+                            .get_body(joint.body_0.unwrap().0)
+                            .ok_or(PhysicsError::BodyNotFound)?;
+                        let body_1 = bodies
+                            .get_body(joint.body_1.unwrap().0)
+                            .ok_or(PhysicsError::BodyNotFound)?;
+
+                        let body_0_trsf =
+                            body_0.body_transform().ok_or(PhysicsError::WrongBodyType)?;
+                        let body_1_trsf =
+                            body_1.body_transform().ok_or(PhysicsError::WrongBodyType)?;
+
+                        let joint_initial_isometry = match joint.initial_position {
+                            JointPosition::Exact(pos) => pos,
+                            JointPosition::Middle => {
+                                let v = body_0_trsf
+                                    .translation
+                                    .vector
+                                    .lerp(&body_1_trsf.translation.vector, convert(0.5));
+                                let r = body_0_trsf
+                                    .rotation
+                                    .slerp(&body_1_trsf.rotation, convert(0.5));
+                                Isometry3::from_parts(Translation3::from(v), r)
+                            }
+                        };
+
+                        [
+                            body_0_trsf.inverse() * joint_initial_isometry,
+                            body_1_trsf.inverse() * joint_initial_isometry,
+                        ]
                     };
 
-                    let anchor_0: Isometry3<N> = body_0_trsf.inverse() * joint_initial_isometry;
-                    let anchor_1: Isometry3<N> = body_1_trsf.inverse() * joint_initial_isometry;
-
-                    match joint.joint_desc {
-                        JointDesc::Fixed => {
-                            let np_joint = NpFixedConstraint::new(
-                                joint.body_0.map(|v| NpBodyPartHandle(v.0, v.1)).unwrap(),
-                                joint.body_1.map(|v| NpBodyPartHandle(v.0, v.1)).unwrap(),
-                                anchor_0.translation.vector.into(),
-                                anchor_0.rotation,
-                                anchor_1.translation.vector.into(),
-                                anchor_1.rotation,
-                            );
-                            joint.np_joint = Some(Box::new(np_joint));
+                    let part_0 = joint.body_0.map(|v| NpBodyPartHandle(v.0, v.1)).unwrap();
+                    let part_1 = joint.body_1.map(|v| NpBodyPartHandle(v.0, v.1)).unwrap();
+                    if let Some([slide_axis, axle]) = joint.suspension_axes {
+                        // Slides along one axis and rotates around the other, like a wheel.
+                        let np_joint = NpPinSlotConstraint::new(
+                            part_0,
+                            part_1,
+                            anchor_0.translation.vector.into(),
+                            Unit::new_normalize(anchor_0.rotation * slide_axis),
+                            Unit::new_normalize(anchor_0.rotation * axle),
+                            anchor_1.translation.vector.into(),
+                            Unit::new_normalize(anchor_1.rotation * axle),
+                        );
+                        joint.np_joint = Some(Box::new(np_joint));
+                    } else {
+                        match joint.joint_desc {
+                            JointDesc::Fixed => {
+                                let np_joint = NpFixedConstraint::new(
+                                    part_0,
+                                    part_1,
+                                    anchor_0.translation.vector.into(),
+                                    anchor_0.rotation,
+                                    anchor_1.translation.vector.into(),
+                                    anchor_1.rotation,
+                                );
+                                joint.np_joint = Some(Box::new(np_joint));
+                            }
                         }
                     }
                     notify_added = true;
@@ -118,46 +206,198 @@ impl<N: PtReal> JointNpServer<N> {
         } else if notify_removed {
             joints.notify_joint_removed(joint_key);
         }
+        Ok(())
     }
 }
 
-impl<N: PtReal> JointPhysicsServerTrait<N> for JointNpServer<N> {
-    fn create(
+// Local anchors
+impl<N: PtReal> JointNpServer<N> {
+    /// Creates a joint with an anchor for each body, in the local space of the body; useful for
+    /// the authored mechanisms, where the anchors can't be derived from a single world position.
+    ///
+    /// The first inserted body uses `anchor_0`, the second `anchor_1`.
+    pub fn create_with_anchors(
         &self,
         desc: &JointDesc,
-        initial_position: JointPosition<N>,
+        anchor_0: &Isometry3<N>,
+        anchor_1: &Isometry3<N>,
     ) -> PhysicsHandle<PhysicsJointTag> {
+        let mut joint = Joint::new(*desc, JointPosition::Middle);
+        joint.local_anchors = Some([*anchor_0, *anchor_1]);
+
         let mut joints = self.storages.joints_w();
-        let key = joints.insert(Joint::new(*desc, initial_position));
+        let key = joints.insert(joint);
         joints.get_joint(key).unwrap().self_key = Some(key);
-        PhysicsHandle::new(store_key_to_joint_tag(key), self.storages.gc.clone())
+        let joint_tag = store_key_to_joint_tag(key);
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::CreateJointWithAnchors(joint_tag, *desc, *anchor_0, *anchor_1));
+        PhysicsHandle::new(joint_tag, self.storages.gc())
     }
 
-    fn insert_rigid_body(&self, joint_tag: PhysicsJointTag, body_tag: PhysicsRigidBodyTag) {
-        let joint_key = joint_tag_to_store_key(joint_tag);
+    /// Returns the anchors set with `create_with_anchors`, if any.
+    pub fn local_anchors(&self, joint_tag: PhysicsJointTag) -> Option<[Isometry3<N>; 2]> {
+        self.storages
+            .joints_r()
+            .get_joint(joint_tag_to_store_key(joint_tag))
+            .and_then(|joint| joint.local_anchors)
+    }
+}
+
+// Suspensions
+impl<N: PtReal> JointNpServer<N> {
+    /// Creates a suspension joint: the second body slides along `slide_axis` and rotates around
+    /// `axle`, while the other movements are locked; e.g. a wheel attached to a chassis.
+    ///
+    /// The axes are in the space of the anchors, that are set like `create_with_anchors` does.
+    /// The sliding is not limited, nor pushed back; see `WorldNpServer::create_wheel` for a
+    /// suspension with a spring.
+    pub fn create_suspension(
+        &self,
+        anchor_0: &Isometry3<N>,
+        anchor_1: &Isometry3<N>,
+        slide_axis: &Vector3<N>,
+        axle: &Vector3<N>,
+    ) -> PhysicsHandle<PhysicsJointTag> {
+        let mut joint = Joint::new(JointDesc::Fixed, JointPosition::Middle);
+        joint.local_anchors = Some([*anchor_0, *anchor_1]);
+        joint.suspension_axes = Some([*slide_axis, *axle]);
+
         let mut joints = self.storages.joints_w();
+        let key = joints.insert(joint);
+        joints.get_joint(key).unwrap().self_key = Some(key);
+        let joint_tag = store_key_to_joint_tag(key);
+        self.storages.replay_recorder.record(|| {
+            ReplayCall::CreateSuspension(joint_tag, *anchor_0, *anchor_1, *slide_axis, *axle)
+        });
+        PhysicsHandle::new(joint_tag, self.storages.gc())
+    }
+}
 
-        {
-            let joint = joints.get_joint(joint_key);
-            if let Some(mut joint) = joint {
-                if joint.body_0.is_none() {
-                    joint.body_0 = Some((rigid_tag_to_store_key(body_tag), 0));
-                } else if joint.body_1.is_none() {
-                    joint.body_1 = Some((rigid_tag_to_store_key(body_tag), 0));
-                } else {
-                    error!("This joint is already joining two other bodies, and you can't add more. Remove one of them if you want to constraint this new joint.");
-                    return;
+// Raw access
+impl<N: PtReal> JointNpServer<N> {
+    /// Runs `f` with the NPhysics constraint of the joint, to reach the features not wrapped by
+    /// the server.
+    ///
+    /// Returns `None` when the constraint is not created yet, because the joint doesn't
+    /// constrain two bodies. This is an advanced API: the constraint is rebuilt, losing the
+    /// changes, each time a body is inserted or removed.
+    pub fn with_np_constraint<R>(
+        &self,
+        joint_tag: PhysicsJointTag,
+        f: impl FnOnce(&mut dyn NpJointConstraint<N, StoreKey>) -> R,
+    ) -> PhysicsResult<Option<R>> {
+        let joint_key = self.checked_key(joint_tag);
+        let joints = self.storages.joints_r();
+        let mut joint = joints
+            .get_joint(joint_key)
+            .ok_or(PhysicsError::JointNotFound)?;
+        Ok(joint.np_joint.as_mut().map(|np_joint| f(&mut **np_joint)))
+    }
+}
+
+// Stale tags
+impl<N: PtReal> JointNpServer<N> {
+    /// Returns `true` if the tag refers to an existing joint.
+    pub fn is_valid(&self, joint_tag: PhysicsJointTag) -> bool {
+        self.storages
+            .joints_r()
+            .get_joint(joint_tag_to_store_key(joint_tag))
+            .is_some()
+    }
+
+    /// Converts the tag to the storage key, reporting it when it's stale and the report is on.
+    fn checked_key(&self, joint_tag: PhysicsJointTag) -> StoreKey {
+        #[cfg(feature = "validation")]
+        self.storages.validate();
+
+        if self.storages.report_stale_tags() && !self.is_valid(joint_tag) {
+            self.storages.report_stale_tag("joint");
+        }
+        joint_tag_to_store_key(joint_tag)
+    }
+}
+
+// Fallible functions
+impl<N: PtReal> JointNpServer<N> {
+    /// Like `insert_rigid_body`, but returns an error when the body can't be inserted.
+    ///
+    /// The body is rejected when it doesn't exist, when it's not a rigid body, when the joint
+    /// already constrains it, or when the joint already constrains two bodies.
+    pub fn try_insert_rigid_body(
+        &self,
+        joint_tag: PhysicsJointTag,
+        body_tag: PhysicsRigidBodyTag,
+    ) -> PhysicsResult<()> {
+        let joint_key = self.checked_key(joint_tag);
+        let body_key = rigid_tag_to_store_key(body_tag);
+        let mut joints = self.storages.joints_w();
+        let bodies = self.storages.bodies_r();
+
+        match bodies.get_body(body_key) {
+            Some(body) => {
+                if !matches!(body.body_data, BodyData::Rigid { .. }) {
+                    return Err(PhysicsError::WrongBodyType);
                 }
+            }
+            None => return Err(PhysicsError::BodyNotFound),
+        }
+
+        {
+            let mut joint = joints
+                .get_joint(joint_key)
+                .ok_or(PhysicsError::JointNotFound)?;
+            let constrains =
+                |slot: Option<(StoreKey, usize)>| slot.map_or(false, |b| b.0 == body_key);
+            if constrains(joint.body_0) || constrains(joint.body_1) {
+                return Err(PhysicsError::BodyAlreadyInJoint);
+            }
+
+            if joint.body_0.is_none() {
+                joint.body_0 = Some((body_key, 0));
+            } else if joint.body_1.is_none() {
+                joint.body_1 = Some((body_key, 0));
             } else {
-                error!("Joint tag not found!");
+                return Err(PhysicsError::JointFull);
             }
+            Self::register_joint(body_key, joint_key, &bodies);
         }
 
-        Self::update_internal_joint(joint_key, &mut joints, &self.storages.bodies_r());
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::InsertJointBody(joint_tag, body_tag));
+
+        Self::update_internal_joint(joint_key, &mut joints, &bodies)
+    }
+}
+
+impl<N: PtReal> JointPhysicsServerTrait<N> for JointNpServer<N> {
+    fn create(
+        &self,
+        desc: &JointDesc,
+        initial_position: JointPosition<N>,
+    ) -> PhysicsHandle<PhysicsJointTag> {
+        let mut joints = self.storages.joints_w();
+        let key = joints.insert(Joint::new(*desc, initial_position));
+        joints.get_joint(key).unwrap().self_key = Some(key);
+        let joint_tag = store_key_to_joint_tag(key);
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::CreateJoint(joint_tag, *desc, initial_position));
+        PhysicsHandle::new(joint_tag, self.storages.gc())
+    }
+
+    fn insert_rigid_body(&self, joint_tag: PhysicsJointTag, body_tag: PhysicsRigidBodyTag) {
+        if let Err(e) = self.try_insert_rigid_body(joint_tag, body_tag) {
+            error!("The body can't be inserted in the joint: {}", e);
+        }
     }
 
     fn remove_rigid_body(&self, joint_tag: PhysicsJointTag, body_tag: PhysicsRigidBodyTag) {
-        let joint_key = joint_tag_to_store_key(joint_tag);
+        let joint_key = self.checked_key(joint_tag);
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::RemoveJointBody(joint_tag, body_tag));
         let mut joints = self.storages.joints_w();
         let bodies = self.storages.bodies_r();
 
@@ -171,16 +411,13 @@ impl<N: PtReal> JointPhysicsServerTrait<N> for JointNpServer<N> {
                     RBodyNpServer::active_body(joint.body_1.unwrap().0, &bodies);
                 }
 
-                if let Some(true) = joint
-                    .body_0
-                    .map(|v| v.0 == rigid_tag_to_store_key(body_tag))
-                {
+                let body_key = rigid_tag_to_store_key(body_tag);
+                if let Some(true) = joint.body_0.map(|v| v.0 == body_key) {
                     joint.body_0 = None;
-                } else if let Some(true) = joint
-                    .body_1
-                    .map(|v| v.0 == rigid_tag_to_store_key(body_tag))
-                {
+                    Self::unregister_joint(body_key, joint_key, &bodies);
+                } else if let Some(true) = joint.body_1.map(|v| v.0 == body_key) {
                     joint.body_1 = None;
+                    Self::unregister_joint(body_key, joint_key, &bodies);
                 } else {
                     error!("The body was not found in this joint");
                 }
@@ -189,6 +426,120 @@ impl<N: PtReal> JointPhysicsServerTrait<N> for JointNpServer<N> {
             }
         }
 
-        Self::update_internal_joint(joint_key, &mut joints, &bodies);
+        Self::update_internal_joint(joint_key, &mut joints, &bodies).or_report(&self.storages);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::Isometry3;
+    use amethyst_physics::servers::{
+        JointDesc, JointPhysicsServerTrait, JointPosition, RBodyPhysicsServerTrait, RigidBodyDesc,
+    };
+
+    use crate::{conversors::joint_tag_to_store_key, test_world::TestWorld, PhysicsError};
+
+    #[test]
+    fn joint_rejects_invalid_bodies() {
+        let test = TestWorld::<f32>::new();
+
+        let body_a = test.bodies.create(&RigidBodyDesc::default());
+        let body_b = test.bodies.create(&RigidBodyDesc::default());
+        let body_c = test.bodies.create(&RigidBodyDesc::default());
+        let joint = test.joints.create(&JointDesc::Fixed, JointPosition::Middle);
+
+        assert_eq!(
+            test.joints.try_insert_rigid_body(joint.get(), body_a.get()),
+            Ok(())
+        );
+        assert_eq!(
+            test.joints.try_insert_rigid_body(joint.get(), body_a.get()),
+            Err(PhysicsError::BodyAlreadyInJoint)
+        );
+        assert_eq!(
+            test.joints.try_insert_rigid_body(joint.get(), body_b.get()),
+            Ok(())
+        );
+        assert_eq!(
+            test.joints.try_insert_rigid_body(joint.get(), body_c.get()),
+            Err(PhysicsError::JointFull)
+        );
+    }
+
+    #[test]
+    fn joint_created_with_local_anchors() {
+        let test = TestWorld::<f32>::new();
+
+        let body_a = test.bodies.create(&RigidBodyDesc::default());
+        let body_b = test.bodies.create(&RigidBodyDesc::default());
+        let anchor_0 = Isometry3::translation(0.0, 1.0, 0.0);
+        let anchor_1 = Isometry3::translation(0.0, -1.0, 0.0);
+        let joint = test
+            .joints
+            .create_with_anchors(&JointDesc::Fixed, &anchor_0, &anchor_1);
+        test.joints.insert_rigid_body(joint.get(), body_a.get());
+        test.joints.insert_rigid_body(joint.get(), body_b.get());
+        test.step();
+
+        assert_eq!(
+            test.joints.local_anchors(joint.get()),
+            Some([anchor_0, anchor_1])
+        );
+        let joints = test.storages.joints_r();
+        let joint = joints
+            .get_joint(joint_tag_to_store_key(joint.get()))
+            .unwrap();
+        assert!(joint.np_joint.is_some());
+    }
+
+    #[test]
+    fn jointed_bodies_teleported_together() {
+        let test = TestWorld::<f32>::new();
+
+        let body_a = test.bodies.create(&RigidBodyDesc::default());
+        let body_b = test.bodies.create(&RigidBodyDesc::default());
+        let body_c = test.bodies.create(&RigidBodyDesc::default());
+        test.bodies
+            .set_transform(body_b.get(), &Isometry3::translation(1.0, 0.0, 0.0));
+        test.bodies
+            .set_transform(body_c.get(), &Isometry3::translation(5.0, 0.0, 0.0));
+        let joint = test.joints.create(&JointDesc::Fixed, JointPosition::Middle);
+        test.joints.insert_rigid_body(joint.get(), body_a.get());
+        test.joints.insert_rigid_body(joint.get(), body_b.get());
+
+        test.bodies
+            .set_transform_with_joints(body_a.get(), &Isometry3::translation(0.0, 10.0, 0.0))
+            .unwrap();
+
+        assert_eq!(
+            test.bodies.transform(body_b.get()),
+            Isometry3::translation(1.0, 10.0, 0.0)
+        );
+        assert_eq!(
+            test.bodies.transform(body_c.get()),
+            Isometry3::translation(5.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn raw_access_to_nphysics_constraint() {
+        let test = TestWorld::<f32>::new();
+
+        let joint = test.joints.create(&JointDesc::Fixed, JointPosition::Middle);
+        assert_eq!(
+            test.joints.with_np_constraint(joint.get(), |_| ()),
+            Ok(None)
+        );
+
+        let body_a = test.bodies.create(&RigidBodyDesc::default());
+        let body_b = test.bodies.create(&RigidBodyDesc::default());
+        test.joints.insert_rigid_body(joint.get(), body_a.get());
+        test.joints.insert_rigid_body(joint.get(), body_b.get());
+        test.step();
+        assert_eq!(
+            test.joints
+                .with_np_constraint(joint.get(), |constraint| constraint.is_broken()),
+            Ok(Some(false))
+        );
     }
 }
@@ -6,7 +6,8 @@ use nphysics3d::{
 
 use crate::{
     joint::Joint,
-    storage::{Storage, StorageGuard, StoreKey},
+    statistics::StorageUsage,
+    storage::{Iter, Storage, StorageGuard, StoreKey},
 };
 
 #[allow(missing_debug_implementations)]
@@ -27,6 +28,16 @@ impl<N: PtReal, Handle: NpBodyHandle> JointStorage<N, Handle> {
             removed: Vec::new(),
         }
     }
+
+    /// Create a storage that can hold `capacity` objects before growing, and then grows by the
+    /// same amount.
+    pub fn with_capacity(capacity: usize) -> Self {
+        JointStorage {
+            storage: Storage::new(capacity, capacity.max(1)),
+            inserted: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
 }
 
 impl<N: PtReal, Handle: NpBodyHandle> Default for JointStorage<N, Handle> {
@@ -85,10 +96,40 @@ impl<N: PtReal, Handle: NpBodyHandle> JointStorage<N, Handle> {
         }
     }
 
+    pub fn len(&self) -> usize {
+        self.storage.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.storage.is_empty()
+    }
+
+    pub fn usage(&self) -> StorageUsage {
+        self.storage.usage()
+    }
+
+    /// Drop all the joints, without notifying their insertion or removal.
+    ///
+    /// This must be used only when the `MechanicalWorld` is rebuilt.
+    pub fn clear(&mut self) {
+        self.storage.clear();
+        self.inserted.clear();
+        self.removed.clear();
+    }
+
+    /// Releases the memory of the empty storage; see `Storage::shrink_to`.
+    pub fn shrink_to(&mut self, capacity: usize) {
+        self.storage.shrink_to(capacity);
+    }
+
     /// Returns a `Mutex` guarded joint that can be used safely to get or set data.
     pub fn get_joint(&self, key: StoreKey) -> Option<StorageGuard<'_, Joint<N, Handle>>> {
         self.storage.get(key)
     }
+
+    pub fn iter(&self) -> Iter<'_, Joint<N, Handle>> {
+        self.storage.iter()
+    }
 }
 
 impl<N: PtReal, Handle: NpBodyHandle + 'static> NpJointConstraintSet<N, Handle>
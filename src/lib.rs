@@ -9,6 +9,11 @@
 //!
 //! Follow the `amethyst_physics` instructions know more.
 //!
+//! ## Extended API
+//! The servers expose some functionalities that are not part of the `amethyst_physics` traits.
+//! To use them, create the servers by using the same `ServersStorage` and keep a clone of the
+//! `WorldNpServer` before moving it inside the `PhysicsWorld`.
+//!
 
 // ## Naming
 // Since NPhysics doesn't use any prefix to identify its structures this implementation take care
@@ -23,11 +28,47 @@
     clippy::all
 )]
 
-use area_physics_server::AreaNpServer;
-use joint_physics_server::JointNpServer;
-use rigid_body_physics_server::RBodyNpServer;
-use shape_physics_server::ShapeNpServer;
-use world_physics_server::WorldNpServer;
+pub use area_physics_server::AreaNpServer;
+pub use body::{ContactDetail, ContactState, OverlapDetail};
+pub use collision_layers::CollisionLayers;
+pub use commands::{CommandQueue, PhysicsCommand};
+pub use contact_events::{ContactEventsSystem, ContactPair, ContactStatusEvent};
+pub use custom_force_generator::BodyForceGenerator;
+pub use debug_geometry::{DebugLine, DebugMesh};
+pub use debug_stats::{PhysicsDebugStats, PhysicsDebugStatsSystem};
+pub use destroy_batch::DestroyBatch;
+pub use error::{PhysicsError, PhysicsResult};
+pub use events::PhysicsEvent;
+pub use explosion::{ExplosionDesc, ExplosionFalloff};
+pub use extended_groups::ExtendedGroups;
+pub use force_generator::{ForceGeneratorLifetime, ForceGeneratorTag};
+pub use force_generator_physics_server::ForceGeneratorNpServer;
+pub use joint_physics_server::JointNpServer;
+pub use lod::LodSettings;
+pub use query::{PhysicsObject, QueryHit};
+pub use replay::{Replay, ReplayCall, Replayer};
+pub use replication::{ReplicationRecord, ReplicationTracker};
+pub use rigid_body_physics_server::RBodyNpServer;
+pub use shape_asset::{decode_shape_asset, encode_shape_asset};
+pub use shape_physics_server::ShapeNpServer;
+pub use shape_scale::{scale_shape_desc, ScaledShape, ShapeScaleSyncSystem};
+pub use single_precision::{
+    isometry_from_f32, isometry_to_f32, real_from_f32, real_to_f32, vector_from_f32, vector_to_f32,
+    SinglePrecisionSyncSystem,
+};
+pub use soft_body::{SoftBodyDesc, SoftBodyKind, SoftBodyShape, SoftBodyTag};
+pub use soft_body_physics_server::SoftBodyNpServer;
+pub use stable_id::StableId;
+pub use static_bake::BakedStaticBody;
+pub use statistics::{PhysicsStatistics, ServersStorageUsage, StepTimings, StorageUsage};
+pub use step_accumulator::StepAccumulator;
+pub use transforms_snapshot::TransformsSnapshot;
+pub use utils::ColliderTag;
+pub use wheel::{Wheel, WheelDesc};
+pub use wind_force_generator::WindDesc;
+pub use world_bounds::{OutOfBoundsAction, WorldBounds};
+pub use world_config::{FrictionModel, WorldConfig};
+pub use world_physics_server::{CcdSettings, NarrowPhaseDispatchers, WorldNpServer};
 
 use amethyst_physics::{servers::PhysicsWorld, PtReal};
 
@@ -55,20 +96,70 @@ where
 
 #[macro_use]
 mod conditional_macros;
+mod activation_regions;
 mod area_physics_server;
+mod async_step;
 mod body;
+mod body_info;
 mod body_storage;
+mod capsule_resize;
+mod checksum;
 mod collider_storage;
+mod collision_layers;
+mod commands;
+mod compaction;
+mod contact_events;
 mod conversors;
+mod custom_force_generator;
+mod debug_geometry;
+mod debug_stats;
+mod depenetration;
+mod destroy_batch;
+mod diagnostics;
+mod error;
+mod events;
+mod explosion;
+mod extended_groups;
 mod force_generator;
+mod force_generator_physics_server;
 mod force_generator_storage;
+mod introspection;
+mod islands;
 mod joint;
 mod joint_physics_server;
 mod joint_storage;
+mod lod;
+mod platforms;
+pub mod prefab;
+mod query;
+mod replay;
+mod replication;
 mod rigid_body_physics_server;
+mod rollback;
 pub mod servers_storage;
 mod shape;
+mod shape_asset;
 mod shape_physics_server;
+mod shape_scale;
+mod single_precision;
+mod soft_body;
+mod soft_body_physics_server;
+mod stable_id;
+mod static_bake;
+mod static_contacts;
+mod static_moves;
+mod statistics;
+mod step_accumulator;
 mod storage;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_world;
+mod transforms_snapshot;
 mod utils;
+#[cfg(feature = "validation")]
+mod validation;
+mod wheel;
+mod wind_force_generator;
+mod world_bounds;
+mod world_config;
 mod world_physics_server;
+pub mod world_snapshot;
@@ -0,0 +1,125 @@
+use amethyst_core::math::Point3;
+use amethyst_physics::PtReal;
+
+use crate::{
+    body_storage::BodyStorage, servers_storage::CollidersStorageWrite, RBodyNpServer, WorldNpServer,
+};
+
+/// The settings used to swap the body shapes with their LOD shapes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LodSettings<N: PtReal> {
+    /// The points, usually the players or the cameras, around which the bodies use their shape.
+    ///
+    /// When empty (default) the LOD shapes are never used.
+    pub focus_points: Vec<Point3<N>>,
+    /// The bodies farther than this distance from all the focus points use their LOD shape.
+    pub distance: N,
+}
+
+impl<N: PtReal> Default for LodSettings<N> {
+    fn default() -> Self {
+        LodSettings {
+            focus_points: Vec::new(),
+            distance: N::from(50.0),
+        }
+    }
+}
+
+// LOD
+impl<N: PtReal> WorldNpServer<N> {
+    /// Set the LOD settings, the LOD shapes are set using `RBodyNpServer::set_lod_shape`.
+    ///
+    /// The shapes are swapped during the next `step`.
+    pub fn set_lod_settings(&self, settings: LodSettings<N>) {
+        *self.lod_settings.write().unwrap() = settings;
+    }
+
+    pub fn lod_settings(&self) -> LodSettings<N> {
+        self.lod_settings.read().unwrap().clone()
+    }
+
+    /// Swaps the shape of the bodies that crossed the LOD distance.
+    pub(crate) fn apply_lod(
+        &self,
+        bodies: &BodyStorage<N>,
+        colliders: &mut CollidersStorageWrite<'_, N>,
+    ) {
+        let settings = self.lod_settings.read().unwrap();
+        let shapes = self.storages.shapes_r();
+        let distance_squared = settings.distance * settings.distance;
+
+        for (key, _) in bodies.iter() {
+            let mut body = bodies.get_body(key).unwrap();
+            if body.lod_shape_key.is_none() || body.shape_key.is_none() {
+                continue;
+            }
+
+            let position = if let Some(transform) = body.body_transform() {
+                transform.translation.vector
+            } else {
+                continue;
+            };
+            let use_lod_shape = !settings.focus_points.is_empty()
+                && settings
+                    .focus_points
+                    .iter()
+                    .all(|p| (p.coords - position).norm_squared() > distance_squared);
+
+            if use_lod_shape != body.lod_active {
+                RBodyNpServer::install_lod_collider(&mut *body, use_lod_shape, &shapes, colliders);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Point3, Vector3};
+    use amethyst_physics::servers::{BodyMode, RigidBodyDesc, ShapeDesc, ShapePhysicsServerTrait};
+    use ncollide3d::shape::{Ball, Cuboid};
+
+    use super::LodSettings;
+    use crate::test_world::TestWorld;
+
+    #[test]
+    fn lod_shape_used_far_from_the_focus_points() {
+        let test = TestWorld::<f32>::new();
+
+        let mut desc = RigidBodyDesc::default();
+        desc.mode = BodyMode::Static;
+        let body = test.create_body(
+            &desc,
+            &ShapeDesc::Sphere { radius: 1.0 },
+            &Isometry3::identity(),
+        );
+        let lod_shape = test.shapes.create(&ShapeDesc::Cube {
+            half_extents: Vector3::new(1.0, 1.0, 1.0),
+        });
+        test.bodies.set_lod_shape(body.get(), Some(lod_shape.get()));
+        let is_shape = |ball: bool| {
+            test.bodies
+                .with_np_collider(body.get(), |collider| {
+                    if ball {
+                        collider.shape().as_shape::<Ball<f32>>().is_some()
+                    } else {
+                        collider.shape().as_shape::<Cuboid<f32>>().is_some()
+                    }
+                })
+                .unwrap()
+        };
+
+        test.world.set_lod_settings(LodSettings {
+            focus_points: vec![Point3::new(100.0, 0.0, 0.0)],
+            distance: 50.0,
+        });
+        test.step();
+        assert!(is_shape(false));
+
+        test.world.set_lod_settings(LodSettings {
+            focus_points: vec![Point3::new(10.0, 0.0, 0.0)],
+            distance: 50.0,
+        });
+        test.step();
+        assert!(is_shape(true));
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use amethyst_core::math::{Isometry3, Translation3, UnitQuaternion, Vector3};
+use amethyst_physics::PtReal;
+use ncollide3d::{bounding_volume::BoundingVolume, query};
+use nphysics3d::{object::BodyStatus as NpBodyStatus, world::GeometricalWorld};
+
+use crate::{
+    body_storage::BodyStorage, collider_storage::ColliderStorage, storage::StoreKey, WorldNpServer,
+};
+
+/// The minimum cosine, between the contact normal and the up direction, to consider a body
+/// standing on the platform.
+const STANDING_COS: f64 = 0.7;
+
+// Platforms
+impl<N: PtReal> WorldNpServer<N> {
+    /// Moves the not dynamic bodies standing on a kinematic platform by the platform displacement
+    /// of this step.
+    pub(crate) fn apply_platforms_carrying(
+        gw: &GeometricalWorld<N, StoreKey, StoreKey>,
+        bodies: &BodyStorage<N>,
+        colliders: &ColliderStorage<N, StoreKey>,
+        gravity: &Vector3<N>,
+        delta_time: N,
+    ) {
+        let up = (-gravity)
+            .try_normalize(N::default_epsilon())
+            .unwrap_or_else(Vector3::y);
+        let is_platform = |key: StoreKey| {
+            bodies.get_body(key).map_or(false, |body| {
+                body.rigid_body().is_some() && body.np_body.status() == NpBodyStatus::Kinematic
+            })
+        };
+
+        // NPhysics doesn't compute the contacts between two not dynamic bodies, so they are
+        // queried here, against the platform position at the start of the step.
+        // Carried body -> Platform
+        let mut carried: HashMap<StoreKey, StoreKey> = HashMap::new();
+        for (body_key, _) in bodies.iter() {
+            let body = bodies.get_body(body_key).unwrap();
+            if !body.carried_by_platforms || body.np_body.is_dynamic() {
+                continue;
+            }
+            let collider = if let Some(collider) = body
+                .collider_key
+                .and_then(|key| colliders.get_collider(key))
+            {
+                collider
+            } else {
+                continue;
+            };
+            drop(body);
+
+            let prediction = collider.margin();
+            let aabb = collider
+                .shape()
+                .aabb(collider.position())
+                .loosened(prediction);
+            for (_, other) in
+                gw.interferences_with_aabb(colliders, &aabb, collider.collision_groups())
+            {
+                let platform_key = other.body();
+                if platform_key == body_key || other.is_sensor() || !is_platform(platform_key) {
+                    continue;
+                }
+                let old_position = Self::platform_displacement(bodies, platform_key, delta_time)
+                    .inverse()
+                    * other.position();
+                if let Some(contact) = query::contact(
+                    collider.position(),
+                    collider.shape(),
+                    &old_position,
+                    other.shape(),
+                    prediction,
+                ) {
+                    // The normal goes from the carried body to the platform.
+                    if (-contact.normal.into_inner()).dot(&up) > N::from(STANDING_COS) {
+                        carried.insert(body_key, platform_key);
+                    }
+                }
+            }
+        }
+
+        for (body_key, platform_key) in carried {
+            let displacement = Self::platform_displacement(bodies, platform_key, delta_time);
+
+            let mut body = bodies.get_body(body_key).unwrap();
+            let transform: Isometry3<N> = if let Some(transform) = body.body_transform() {
+                displacement * *transform
+            } else {
+                continue;
+            };
+            let _ = body.set_body_transform(&transform);
+        }
+    }
+
+    /// The movement of the kinematic platform during this step, from its velocity.
+    fn platform_displacement(
+        bodies: &BodyStorage<N>,
+        platform_key: StoreKey,
+        delta_time: N,
+    ) -> Isometry3<N> {
+        let platform = bodies.get_body(platform_key).unwrap();
+        let platform_body = platform.rigid_body().unwrap();
+        let velocity = platform_body.velocity();
+        let linear = velocity.linear * delta_time;
+        let rotation = UnitQuaternion::new(velocity.angular * delta_time);
+
+        // The platform position is the one at the end of the step.
+        let center = platform_body.position().translation.vector;
+        let old_center = center - linear;
+        Translation3::from(center) * rotation * Translation3::from(-old_center)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Vector3};
+    use amethyst_physics::servers::{BodyMode, RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc};
+
+    use crate::test_world::TestWorld;
+
+    #[test]
+    fn kinematic_body_carried_by_the_platform() {
+        let test = TestWorld::<f32>::new();
+
+        let mut kinematic = RigidBodyDesc::default();
+        kinematic.mode = BodyMode::Kinematic;
+        let platform = test.create_body(
+            &kinematic,
+            &ShapeDesc::Cube {
+                half_extents: Vector3::new(3.0, 0.25, 3.0),
+            },
+            &Isometry3::identity(),
+        );
+        let sphere = ShapeDesc::Sphere { radius: 0.5 };
+        let carried = test.create_body(
+            &kinematic,
+            &sphere,
+            &Isometry3::translation(-1.0, 0.75, 0.0),
+        );
+        let left = test.create_body(&kinematic, &sphere, &Isometry3::translation(1.0, 0.75, 0.0));
+        test.bodies.set_carried_by_platforms(carried.get(), true);
+        assert!(test.bodies.is_carried_by_platforms(carried.get()));
+
+        test.bodies
+            .set_linear_velocity(platform.get(), &Vector3::new(1.0, 0.0, 0.0));
+        test.step_n(60);
+
+        test.assert_position(platform.get(), &Vector3::new(1.0, 0.0, 0.0), 0.01);
+        test.assert_position(carried.get(), &Vector3::new(0.0, 0.75, 0.0), 0.01);
+        test.assert_position(left.get(), &Vector3::new(1.0, 0.75, 0.0), 0.001);
+    }
+}
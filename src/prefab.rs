@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+
+use amethyst_core::math::Isometry3;
+use amethyst_physics::{
+    objects::*,
+    servers::{
+        AreaDesc, AreaPhysicsServerTrait, JointDesc, JointPhysicsServerTrait, JointPosition,
+        RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc, ShapePhysicsServerTrait,
+    },
+    PtReal,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{PhysicsError, PhysicsResult},
+    world_snapshot::{BodyModeSnapshot, JointDescSnapshot, ShapeDescSnapshot},
+    AreaNpServer, JointNpServer, RBodyNpServer, ShapeNpServer, WorldNpServer,
+};
+
+/// A physics setup described by data, so it doesn't need to be hardcoded in the systems.
+///
+/// The objects have a name, used by the other objects to refer to them and by the
+/// `PrefabHandles` to return them. It can be deserialized using any `serde` format, or from RON
+/// with `from_ron` when the `ron` feature is enabled; then it's instantiated with
+/// `WorldNpServer::instantiate_prefab`.
+///
+/// ```ron
+/// (
+///     shapes: [(name: "box", desc: Cube(half_extents: (0.5, 0.5, 0.5)))],
+///     bodies: [(name: "crate", shape: Some("box"))],
+/// )
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhysicsPrefab<N: PtReal> {
+    #[serde(default)]
+    pub shapes: Vec<ShapePrefab<N>>,
+    #[serde(default)]
+    pub bodies: Vec<BodyPrefab<N>>,
+    #[serde(default)]
+    pub areas: Vec<AreaPrefab<N>>,
+    #[serde(default)]
+    pub joints: Vec<JointPrefab<N>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShapePrefab<N: PtReal> {
+    pub name: String,
+    pub desc: ShapeDescSnapshot<N>,
+}
+
+/// The omitted fields take the value of the `RigidBodyDesc::default`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BodyPrefab<N: PtReal> {
+    pub name: String,
+    #[serde(default)]
+    pub mode: Option<BodyModeSnapshot>,
+    #[serde(default)]
+    pub mass: Option<N>,
+    #[serde(default)]
+    pub friction: Option<N>,
+    #[serde(default)]
+    pub bounciness: Option<N>,
+    #[serde(default)]
+    pub belong_to: Vec<u8>,
+    #[serde(default)]
+    pub collide_with: Vec<u8>,
+    /// The transform relative to the one passed to `instantiate_prefab`.
+    #[serde(default = "Isometry3::identity")]
+    pub transform: Isometry3<N>,
+    /// The name of the shape.
+    #[serde(default)]
+    pub shape: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AreaPrefab<N: PtReal> {
+    pub name: String,
+    #[serde(default)]
+    pub belong_to: Vec<u8>,
+    #[serde(default)]
+    pub collide_with: Vec<u8>,
+    /// The transform relative to the one passed to `instantiate_prefab`.
+    #[serde(default = "Isometry3::identity")]
+    pub transform: Isometry3<N>,
+    /// The name of the shape.
+    #[serde(default)]
+    pub shape: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JointPrefab<N: PtReal> {
+    pub name: String,
+    pub desc: JointDescSnapshot,
+    /// The names of the two constrained bodies.
+    pub bodies: (String, String),
+    /// The position of the joint relative to the one passed to `instantiate_prefab`; `None` to
+    /// position it in the middle of the two bodies.
+    #[serde(default)]
+    pub position: Option<Isometry3<N>>,
+}
+
+#[cfg(feature = "ron")]
+impl<N: PtReal> PhysicsPrefab<N> {
+    /// Parses the prefab from its RON description.
+    pub fn from_ron(source: &str) -> Result<Self, ron::de::Error> {
+        ron::de::from_str(source)
+    }
+}
+
+/// The handles of the objects created by `WorldNpServer::instantiate_prefab`, by name.
+///
+/// Dropping the handles drops the objects; so make sure to store them.
+#[allow(missing_debug_implementations)]
+pub struct PrefabHandles {
+    pub shapes: HashMap<String, PhysicsHandle<PhysicsShapeTag>>,
+    pub bodies: HashMap<String, PhysicsHandle<PhysicsRigidBodyTag>>,
+    pub areas: HashMap<String, PhysicsHandle<PhysicsAreaTag>>,
+    pub joints: HashMap<String, PhysicsHandle<PhysicsJointTag>>,
+}
+
+fn groups_from_prefab(groups: &[u8]) -> Vec<CollisionGroup> {
+    groups.iter().map(|g| CollisionGroup::new(*g)).collect()
+}
+
+// Prefab
+impl<N: PtReal> WorldNpServer<N> {
+    /// Creates the objects of the `prefab`, placed relative to `transform`.
+    ///
+    /// Fails with `PrefabNameNotFound`, without creating anything, when an object refers to a
+    /// name not defined by the prefab.
+    pub fn instantiate_prefab(
+        &self,
+        prefab: &PhysicsPrefab<N>,
+        transform: &Isometry3<N>,
+    ) -> PhysicsResult<PrefabHandles> {
+        let has_shape = |name: &Option<String>| {
+            name.as_ref()
+                .map_or(true, |name| prefab.shapes.iter().any(|s| s.name == *name))
+        };
+        let has_body = |name: &String| prefab.bodies.iter().any(|b| b.name == *name);
+        if !prefab.bodies.iter().all(|b| has_shape(&b.shape))
+            || !prefab.areas.iter().all(|a| has_shape(&a.shape))
+            || !prefab
+                .joints
+                .iter()
+                .all(|j| has_body(&j.bodies.0) && has_body(&j.bodies.1))
+        {
+            return Err(PhysicsError::PrefabNameNotFound);
+        }
+
+        let shape_server = ShapeNpServer::new(self.storages.clone());
+        let body_server = RBodyNpServer::new(self.storages.clone());
+        let area_server = AreaNpServer::new(self.storages.clone());
+        let joint_server = JointNpServer::new(self.storages.clone());
+
+        let mut handles = PrefabHandles {
+            shapes: HashMap::with_capacity(prefab.shapes.len()),
+            bodies: HashMap::with_capacity(prefab.bodies.len()),
+            areas: HashMap::with_capacity(prefab.areas.len()),
+            joints: HashMap::with_capacity(prefab.joints.len()),
+        };
+
+        for s in prefab.shapes.iter() {
+            let handle = shape_server.create(&ShapeDesc::from(&s.desc));
+            handles.shapes.insert(s.name.clone(), handle);
+        }
+
+        let find_shape = |handles: &PrefabHandles, name: &Option<String>| {
+            name.as_ref()
+                .and_then(|name| handles.shapes.get(name))
+                .map(PhysicsHandle::get)
+        };
+
+        for b in prefab.bodies.iter() {
+            let mut desc = RigidBodyDesc::default();
+            if let Some(mode) = b.mode {
+                desc.mode = mode.into();
+            }
+            desc.mass = b.mass.unwrap_or(desc.mass);
+            desc.friction = b.friction.unwrap_or(desc.friction);
+            desc.bounciness = b.bounciness.unwrap_or(desc.bounciness);
+            desc.belong_to = groups_from_prefab(&b.belong_to);
+            desc.collide_with = groups_from_prefab(&b.collide_with);
+
+            let handle = body_server.create(&desc);
+            body_server.set_transform(handle.get(), &(transform * b.transform));
+            body_server.set_shape(handle.get(), find_shape(&handles, &b.shape));
+            handles.bodies.insert(b.name.clone(), handle);
+        }
+
+        for a in prefab.areas.iter() {
+            let mut desc = AreaDesc::default();
+            desc.belong_to = groups_from_prefab(&a.belong_to);
+            desc.collide_with = groups_from_prefab(&a.collide_with);
+
+            let handle = area_server.create(&desc);
+            area_server.set_transform(handle.get(), &(transform * a.transform));
+            area_server.set_shape(handle.get(), find_shape(&handles, &a.shape));
+            handles.areas.insert(a.name.clone(), handle);
+        }
+
+        for j in prefab.joints.iter() {
+            let desc = match j.desc {
+                JointDescSnapshot::Fixed => JointDesc::Fixed,
+            };
+            let position = match j.position {
+                Some(pos) => JointPosition::Exact(transform * pos),
+                None => JointPosition::Middle,
+            };
+
+            let handle = joint_server.create(&desc, position);
+            joint_server.insert_rigid_body(handle.get(), handles.bodies[&j.bodies.0].get());
+            joint_server.insert_rigid_body(handle.get(), handles.bodies[&j.bodies.1].get());
+            handles.joints.insert(j.name.clone(), handle);
+        }
+
+        Ok(handles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::Isometry3;
+    use amethyst_physics::servers::RBodyPhysicsServerTrait;
+
+    use super::{BodyPrefab, JointPrefab, PhysicsPrefab, ShapePrefab};
+    use crate::{
+        error::PhysicsError,
+        test_world::TestWorld,
+        world_snapshot::{JointDescSnapshot, ShapeDescSnapshot},
+    };
+
+    #[test]
+    fn prefab_instantiated_by_name() {
+        let test = TestWorld::<f32>::new();
+
+        let body = |name: &str, x: f32| BodyPrefab {
+            name: name.to_owned(),
+            mode: None,
+            mass: None,
+            friction: None,
+            bounciness: None,
+            belong_to: Vec::new(),
+            collide_with: Vec::new(),
+            transform: Isometry3::translation(x, 0.0, 0.0),
+            shape: Some("ball".to_owned()),
+        };
+        let mut prefab = PhysicsPrefab {
+            shapes: vec![ShapePrefab {
+                name: "ball".to_owned(),
+                desc: ShapeDescSnapshot::Sphere { radius: 0.5 },
+            }],
+            bodies: vec![body("left", -1.0), body("right", 1.0)],
+            areas: Vec::new(),
+            joints: vec![JointPrefab {
+                name: "weld".to_owned(),
+                desc: JointDescSnapshot::Fixed,
+                bodies: ("left".to_owned(), "right".to_owned()),
+                position: None,
+            }],
+        };
+
+        let handles = test
+            .world
+            .instantiate_prefab(&prefab, &Isometry3::translation(0.0, 5.0, 0.0))
+            .unwrap();
+        assert_eq!(handles.bodies.len(), 2);
+        assert_eq!(
+            test.bodies.transform(handles.bodies["right"].get()),
+            Isometry3::translation(1.0, 5.0, 0.0)
+        );
+        assert_eq!(
+            test.bodies.shape(handles.bodies["left"].get()),
+            Some(handles.shapes["ball"].get())
+        );
+        assert!(handles.joints.contains_key("weld"));
+
+        prefab.joints[0].bodies.1 = "missing".to_owned();
+        assert!(matches!(
+            test.world
+                .instantiate_prefab(&prefab, &Isometry3::identity()),
+            Err(PhysicsError::PrefabNameNotFound)
+        ));
+    }
+}
@@ -0,0 +1,52 @@
+use amethyst_core::ecs::Entity;
+use amethyst_physics::{objects::*, PtReal};
+use nphysics3d::object::Collider as NpCollider;
+
+use crate::{
+    conversors::*,
+    soft_body::SoftBodyTag,
+    storage::StoreKey,
+    utils::{ColliderTag, ObjectType, UserData},
+};
+
+/// The object a collider belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhysicsObject {
+    RigidBody(PhysicsRigidBodyTag),
+    Area(PhysicsAreaTag),
+    SoftBody(SoftBodyTag),
+}
+
+/// The object hit by a query, or the other object of an event, resolved from the collider.
+///
+/// It carries the entity, so it doesn't need to be looked up from the tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryHit {
+    pub object: PhysicsObject,
+    pub entity: Option<Entity>,
+    /// The tag of the collider, set with `RBodyNpServer::set_collider_tag`.
+    pub collider_tag: Option<ColliderTag>,
+}
+
+impl QueryHit {
+    pub(crate) fn from_user_data(user_data: &UserData) -> Self {
+        let key = user_data.store_key();
+        QueryHit {
+            object: match user_data.object_type() {
+                ObjectType::RigidBody => PhysicsObject::RigidBody(store_key_to_rigid_tag(key)),
+                ObjectType::Area => PhysicsObject::Area(store_key_to_area_tag(key)),
+                ObjectType::SoftBody => PhysicsObject::SoftBody(SoftBodyTag(key)),
+            },
+            entity: user_data.entity(),
+            collider_tag: user_data.collider_tag(),
+        }
+    }
+
+    /// Returns `None` if the collider doesn't have the `UserData`.
+    pub(crate) fn from_collider<N: PtReal>(collider: &NpCollider<N, StoreKey>) -> Option<Self> {
+        collider
+            .user_data()
+            .and_then(|user_data| user_data.downcast_ref::<UserData>())
+            .map(QueryHit::from_user_data)
+    }
+}
@@ -0,0 +1,564 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+
+use amethyst_core::math::{Isometry3, Point3, Vector3};
+use amethyst_physics::{
+    objects::*,
+    servers::{
+        AreaDesc, AreaPhysicsServerTrait, JointDesc, JointPhysicsServerTrait, JointPosition,
+        RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc, ShapePhysicsServerTrait,
+        WorldPhysicsServerTrait,
+    },
+    PtReal,
+};
+
+use crate::{
+    commands::{apply_command, PhysicsCommand},
+    error::{LogError, PhysicsError, PhysicsResult},
+    AreaNpServer, CcdSettings, ExplosionDesc, JointNpServer, RBodyNpServer, ShapeNpServer,
+    WheelDesc, WorldNpServer,
+};
+
+/// A server call recorded in a `Replay`.
+///
+/// The tags are the ones of the recorded world; the `Replayer` maps them to the new objects.
+#[derive(Clone)]
+#[allow(missing_debug_implementations)]
+pub enum ReplayCall<N: PtReal> {
+    CreateShape(PhysicsShapeTag, ShapeDesc<N>),
+    UpdateShape(PhysicsShapeTag, ShapeDesc<N>),
+    /// The vertices of a `TriMesh` moved by `ShapeNpServer::update_trimesh_vertices`, each with
+    /// its index; only the moved ones are recorded, rather than the whole mesh.
+    UpdateTriMeshVertices(PhysicsShapeTag, Vec<(usize, Point3<N>)>),
+    /// The shape was removed by the garbage collector.
+    DropShape(PhysicsShapeTag),
+    CreateBody(PhysicsRigidBodyTag, RigidBodyDesc<N>),
+    /// The body was removed by the garbage collector.
+    DropBody(PhysicsRigidBodyTag),
+    SetShape(PhysicsRigidBodyTag, Option<PhysicsShapeTag>),
+    /// A call that changes the state of a body, like `set_transform` or `apply_force`.
+    Body(PhysicsCommand<N>),
+    /// A capsule resized by `WorldNpServer::resize_capsule`; only the successful ones are recorded.
+    ResizeCapsule(PhysicsRigidBodyTag, N),
+    /// The spring of a wheel created by `WorldNpServer::create_wheel`, between the chassis and the
+    /// wheel body; the other objects of the wheel are recorded by their own calls.
+    CreateWheelSpring(PhysicsRigidBodyTag, PhysicsRigidBodyTag, WheelDesc<N>),
+    Explode(ExplosionDesc<N>),
+    /// An area created with its `belong_to` and `collide_with` groups.
+    CreateArea(PhysicsAreaTag, Vec<CollisionGroup>, Vec<CollisionGroup>),
+    /// The area was removed by the garbage collector.
+    DropArea(PhysicsAreaTag),
+    SetAreaShape(PhysicsAreaTag, Option<PhysicsShapeTag>),
+    SetAreaTransform(PhysicsAreaTag, Isometry3<N>),
+    CreateJoint(PhysicsJointTag, JointDesc, JointPosition<N>),
+    /// A joint created by `JointNpServer::create_with_anchors`.
+    CreateJointWithAnchors(PhysicsJointTag, JointDesc, Isometry3<N>, Isometry3<N>),
+    /// A joint created by `JointNpServer::create_suspension`, with its anchors, the slide axis and
+    /// the axle.
+    CreateSuspension(
+        PhysicsJointTag,
+        Isometry3<N>,
+        Isometry3<N>,
+        Vector3<N>,
+        Vector3<N>,
+    ),
+    InsertJointBody(PhysicsJointTag, PhysicsRigidBodyTag),
+    RemoveJointBody(PhysicsJointTag, PhysicsRigidBodyTag),
+    /// The joint was removed by the garbage collector.
+    DropJoint(PhysicsJointTag),
+    SetGravity(Vector3<N>),
+    SetTimeStep(N),
+    SetCcdSettings(CcdSettings),
+    SetPaused(bool),
+}
+
+/// The server calls recorded between `WorldNpServer::start_replay_recording` and
+/// `WorldNpServer::stop_replay_recording`.
+#[derive(Clone, Default)]
+#[allow(missing_debug_implementations)]
+pub struct Replay<N: PtReal> {
+    /// Each call with the index of the step that follows it.
+    pub calls: Vec<(u64, ReplayCall<N>)>,
+    /// The delta time of each recorded step, so the steps of `step_with_delta` and of
+    /// `step_accumulated` are replayed as they were.
+    pub steps: Vec<N>,
+}
+
+/// Records the calls, when the recording is on.
+#[allow(missing_debug_implementations)]
+pub(crate) struct ReplayRecorder<N: PtReal> {
+    /// Checked before taking the lock, so the servers don't wait each other when not recording.
+    recording: AtomicBool,
+    replay: Mutex<Replay<N>>,
+}
+
+impl<N: PtReal> ReplayRecorder<N> {
+    pub fn new() -> Self {
+        ReplayRecorder {
+            recording: AtomicBool::new(false),
+            replay: Mutex::new(Replay::default()),
+        }
+    }
+
+    /// Records the call; it's created only when the recording is on.
+    pub fn record(&self, call: impl FnOnce() -> ReplayCall<N>) {
+        if self.recording.load(Ordering::Relaxed) {
+            let mut replay = self.replay.lock().unwrap();
+            let step = replay.steps.len() as u64;
+            replay.calls.push((step, call()));
+        }
+    }
+
+    /// Records a step; the delta time is read only when the recording is on.
+    pub fn record_step(&self, delta_time: impl FnOnce() -> N) {
+        if self.recording.load(Ordering::Relaxed) {
+            self.replay.lock().unwrap().steps.push(delta_time());
+        }
+    }
+}
+
+// Replay
+impl<N: PtReal> WorldNpServer<N> {
+    /// Starts to record the calls that create, change and drop the shapes, the rigid bodies, the
+    /// areas and the joints, together with the steps and their delta time, so they can be fed back
+    /// to a new world with a `Replayer`; e.g. to reproduce a bug, or to write a regression test of
+    /// a physics behavior.
+    ///
+    /// The calls that change the simulation are recorded: the ones that create and move the
+    /// objects, also in batch, the forces and the impulses, the commands of the `CommandQueue`,
+    /// the explosions, the wheels, and the gravity, the time step, the CCD settings and the pause
+    /// of the world. The calls that change only the queries or the events, like the collision
+    /// groups set after the creation, are not recorded.
+    /// Start it when the world is still empty, since the objects created before are not known by
+    /// the replay.
+    pub fn start_replay_recording(&self) {
+        let recorder = &self.storages.replay_recorder;
+        *recorder.replay.lock().unwrap() = Replay::default();
+        recorder.recording.store(true, Ordering::Relaxed);
+    }
+
+    /// Stops the recording, returning the recorded calls.
+    pub fn stop_replay_recording(&self) -> Replay<N> {
+        let recorder = &self.storages.replay_recorder;
+        recorder.recording.store(false, Ordering::Relaxed);
+        std::mem::take(&mut *recorder.replay.lock().unwrap())
+    }
+
+    pub fn is_recording_replay(&self) -> bool {
+        self.storages
+            .replay_recorder
+            .recording
+            .load(Ordering::Relaxed)
+    }
+}
+
+/// Feeds a `Replay` back to a world, a step at a time.
+///
+/// The world must have the same configuration of the recorded one, like the time step and the
+/// gravity, to reproduce the same simulation.
+#[allow(missing_debug_implementations)]
+pub struct Replayer<N: PtReal> {
+    world: WorldNpServer<N>,
+    replay: Replay<N>,
+    next_call: usize,
+    step: u64,
+    shapes: Vec<(PhysicsShapeTag, PhysicsHandle<PhysicsShapeTag>)>,
+    bodies: Vec<(PhysicsRigidBodyTag, PhysicsHandle<PhysicsRigidBodyTag>)>,
+    areas: Vec<(PhysicsAreaTag, PhysicsHandle<PhysicsAreaTag>)>,
+    joints: Vec<(PhysicsJointTag, PhysicsHandle<PhysicsJointTag>)>,
+}
+
+impl<N: PtReal> Replayer<N> {
+    /// Fails with `WorldNotEmpty` if the world already contains some bodies.
+    pub fn new(world: &WorldNpServer<N>, replay: Replay<N>) -> PhysicsResult<Self> {
+        if !world.storages.bodies_r().is_empty() {
+            return Err(PhysicsError::WorldNotEmpty);
+        }
+        Ok(Replayer {
+            world: world.clone(),
+            replay,
+            next_call: 0,
+            step: 0,
+            shapes: Vec::new(),
+            bodies: Vec::new(),
+            areas: Vec::new(),
+            joints: Vec::new(),
+        })
+    }
+
+    /// Applies the calls recorded before the next step, and steps the world with the recorded
+    /// delta time.
+    ///
+    /// Returns `false`, without doing anything, when all the steps are replayed.
+    /// Fails with `BodyNotFound`, `ShapeMissing` or `JointNotFound` when a call refers to an
+    /// object that the replay never created, like one created before the recording started; the
+    /// calls before it are already applied, and the world is not stepped.
+    pub fn step(&mut self) -> PhysicsResult<bool> {
+        if self.is_finished() {
+            return Ok(false);
+        }
+
+        while let Some((step, _)) = self.replay.calls.get(self.next_call) {
+            if *step != self.step {
+                break;
+            }
+            let call = self.replay.calls[self.next_call].1.clone();
+            self.next_call += 1;
+            self.apply(&call)?;
+        }
+
+        self.world
+            .step_with_delta(self.replay.steps[self.step as usize]);
+        self.step += 1;
+        Ok(true)
+    }
+
+    /// Replays all the remaining steps.
+    pub fn run(&mut self) -> PhysicsResult<()> {
+        while self.step()? {}
+        Ok(())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.step >= self.replay.steps.len() as u64
+    }
+
+    /// Returns the number of replayed steps.
+    pub fn replayed_steps(&self) -> u64 {
+        self.step
+    }
+
+    /// Returns the body created for the body of the recorded world.
+    pub fn body(&self, recorded: PhysicsRigidBodyTag) -> Option<PhysicsRigidBodyTag> {
+        self.bodies
+            .iter()
+            .find(|(tag, _)| *tag == recorded)
+            .map(|(_, handle)| handle.get())
+    }
+
+    /// Returns the shape created for the shape of the recorded world.
+    pub fn shape(&self, recorded: PhysicsShapeTag) -> Option<PhysicsShapeTag> {
+        self.shapes
+            .iter()
+            .find(|(tag, _)| *tag == recorded)
+            .map(|(_, handle)| handle.get())
+    }
+
+    /// Returns the area created for the area of the recorded world.
+    pub fn area(&self, recorded: PhysicsAreaTag) -> Option<PhysicsAreaTag> {
+        self.areas
+            .iter()
+            .find(|(tag, _)| *tag == recorded)
+            .map(|(_, handle)| handle.get())
+    }
+
+    /// Returns the joint created for the joint of the recorded world.
+    pub fn joint(&self, recorded: PhysicsJointTag) -> Option<PhysicsJointTag> {
+        self.joints
+            .iter()
+            .find(|(tag, _)| *tag == recorded)
+            .map(|(_, handle)| handle.get())
+    }
+
+    fn replayed_body(&self, recorded: PhysicsRigidBodyTag) -> PhysicsResult<PhysicsRigidBodyTag> {
+        self.body(recorded).ok_or(PhysicsError::BodyNotFound)
+    }
+
+    fn replayed_shape(&self, recorded: PhysicsShapeTag) -> PhysicsResult<PhysicsShapeTag> {
+        self.shape(recorded).ok_or(PhysicsError::ShapeMissing)
+    }
+
+    fn replayed_area(&self, recorded: PhysicsAreaTag) -> PhysicsResult<PhysicsAreaTag> {
+        self.area(recorded).ok_or(PhysicsError::BodyNotFound)
+    }
+
+    fn replayed_joint(&self, recorded: PhysicsJointTag) -> PhysicsResult<PhysicsJointTag> {
+        self.joint(recorded).ok_or(PhysicsError::JointNotFound)
+    }
+
+    /// Removes the handle of the recorded object, so the replayed one is dropped like it was.
+    fn drop_handle<T: PartialEq, H>(handles: &mut Vec<(T, H)>, recorded: T) -> bool {
+        let len = handles.len();
+        handles.retain(|(tag, _)| *tag != recorded);
+        handles.len() != len
+    }
+
+    fn apply(&mut self, call: &ReplayCall<N>) -> PhysicsResult<()> {
+        let storages = self.world.storages.clone();
+        let shape_server = ShapeNpServer::new(storages.clone());
+        let body_server = RBodyNpServer::new(storages.clone());
+        let area_server = AreaNpServer::new(storages.clone());
+        let joint_server = JointNpServer::new(storages);
+
+        match call {
+            ReplayCall::CreateShape(tag, desc) => {
+                let handle = shape_server.create(desc);
+                self.shapes.push((*tag, handle));
+            }
+            ReplayCall::UpdateShape(tag, desc) => {
+                shape_server.update(self.replayed_shape(*tag)?, desc);
+            }
+            ReplayCall::UpdateTriMeshVertices(tag, vertices) => {
+                // The same vertices were moved, without errors, when recorded.
+                shape_server
+                    .update_trimesh_vertices(self.replayed_shape(*tag)?, vertices)
+                    .or_log();
+            }
+            ReplayCall::DropShape(tag) => {
+                if !Self::drop_handle(&mut self.shapes, *tag) {
+                    return Err(PhysicsError::ShapeMissing);
+                }
+            }
+            ReplayCall::CreateBody(tag, desc) => {
+                let handle = body_server.create(desc);
+                self.bodies.push((*tag, handle));
+            }
+            ReplayCall::DropBody(tag) => {
+                if !Self::drop_handle(&mut self.bodies, *tag) {
+                    return Err(PhysicsError::BodyNotFound);
+                }
+            }
+            ReplayCall::SetShape(tag, shape_tag) => {
+                let shape_tag = match shape_tag {
+                    Some(shape_tag) => Some(self.replayed_shape(*shape_tag)?),
+                    None => None,
+                };
+                body_server.set_shape(self.replayed_body(*tag)?, shape_tag);
+            }
+            ReplayCall::Body(command) => {
+                apply_command(&body_server, self.map_command(command)?);
+            }
+            ReplayCall::ResizeCapsule(tag, half_height) => {
+                self.world
+                    .try_resize_capsule(self.replayed_body(*tag)?, *half_height)?;
+            }
+            ReplayCall::CreateWheelSpring(chassis, wheel, desc) => {
+                self.world.create_wheel_spring(
+                    self.replayed_body(*chassis)?,
+                    self.replayed_body(*wheel)?,
+                    desc,
+                );
+            }
+            ReplayCall::Explode(desc) => {
+                self.world.explode(desc);
+            }
+            ReplayCall::CreateArea(tag, belong_to, collide_with) => {
+                let handle = area_server.create(&AreaDesc {
+                    belong_to: belong_to.clone(),
+                    collide_with: collide_with.clone(),
+                });
+                self.areas.push((*tag, handle));
+            }
+            ReplayCall::DropArea(tag) => {
+                if !Self::drop_handle(&mut self.areas, *tag) {
+                    return Err(PhysicsError::BodyNotFound);
+                }
+            }
+            ReplayCall::SetAreaShape(tag, shape_tag) => {
+                let shape_tag = match shape_tag {
+                    Some(shape_tag) => Some(self.replayed_shape(*shape_tag)?),
+                    None => None,
+                };
+                area_server.set_shape(self.replayed_area(*tag)?, shape_tag);
+            }
+            ReplayCall::SetAreaTransform(tag, transform) => {
+                area_server.set_transform(self.replayed_area(*tag)?, transform);
+            }
+            ReplayCall::CreateJoint(tag, desc, position) => {
+                let handle = joint_server.create(desc, *position);
+                self.joints.push((*tag, handle));
+            }
+            ReplayCall::CreateJointWithAnchors(tag, desc, anchor_0, anchor_1) => {
+                let handle = joint_server.create_with_anchors(desc, anchor_0, anchor_1);
+                self.joints.push((*tag, handle));
+            }
+            ReplayCall::CreateSuspension(tag, anchor_0, anchor_1, slide_axis, axle) => {
+                let handle = joint_server.create_suspension(anchor_0, anchor_1, slide_axis, axle);
+                self.joints.push((*tag, handle));
+            }
+            ReplayCall::InsertJointBody(tag, body_tag) => {
+                joint_server.try_insert_rigid_body(
+                    self.replayed_joint(*tag)?,
+                    self.replayed_body(*body_tag)?,
+                )?;
+            }
+            ReplayCall::RemoveJointBody(tag, body_tag) => {
+                joint_server
+                    .remove_rigid_body(self.replayed_joint(*tag)?, self.replayed_body(*body_tag)?);
+            }
+            ReplayCall::DropJoint(tag) => {
+                if !Self::drop_handle(&mut self.joints, *tag) {
+                    return Err(PhysicsError::JointNotFound);
+                }
+            }
+            ReplayCall::SetGravity(gravity) => self.world.set_gravity(gravity),
+            ReplayCall::SetTimeStep(time_step) => self.world.set_time_step(*time_step),
+            ReplayCall::SetCcdSettings(settings) => self.world.set_ccd_settings(*settings),
+            ReplayCall::SetPaused(paused) => self.world.set_paused(*paused),
+        }
+        Ok(())
+    }
+
+    /// Returns the command with the tag of the replayed body.
+    fn map_command(&self, command: &PhysicsCommand<N>) -> PhysicsResult<PhysicsCommand<N>> {
+        let mut command = command.clone();
+        let tag = match &mut command {
+            PhysicsCommand::SetTransform(tag, _)
+            | PhysicsCommand::SetMode(tag, _)
+            | PhysicsCommand::SetLinearVelocity(tag, _)
+            | PhysicsCommand::SetAngularVelocity(tag, _)
+            | PhysicsCommand::ClearForces(tag)
+            | PhysicsCommand::ApplyForce(tag, _)
+            | PhysicsCommand::ApplyTorque(tag, _)
+            | PhysicsCommand::ApplyForceAtPosition(tag, _, _)
+            | PhysicsCommand::ApplyImpulse(tag, _)
+            | PhysicsCommand::ApplyAngularImpulse(tag, _)
+            | PhysicsCommand::ApplyImpulseAtPosition(tag, _, _) => tag,
+        };
+        *tag = self.replayed_body(*tag)?;
+        Ok(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Point3, Vector3};
+    use amethyst_physics::servers::{
+        AreaDesc, AreaPhysicsServerTrait, JointDesc, JointPhysicsServerTrait, JointPosition,
+        RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc, ShapePhysicsServerTrait,
+        WorldPhysicsServerTrait,
+    };
+
+    use super::{ReplayCall, Replayer};
+    use crate::{error::PhysicsError, test_world::TestWorld};
+
+    #[test]
+    fn replay_reproduces_the_joints_and_the_step_deltas() {
+        let test = TestWorld::<f32>::new();
+
+        test.world.start_replay_recording();
+        test.world.set_gravity(&Vector3::new(0.0, -3.0, 0.0));
+        let shape = test.shapes.create(&ShapeDesc::Sphere { radius: 0.5 });
+        let bodies = test.bodies.create_many_with_shape(&[
+            (
+                RigidBodyDesc::default(),
+                shape.get(),
+                Isometry3::translation(0.0, 0.0, 0.0),
+            ),
+            (
+                RigidBodyDesc::default(),
+                shape.get(),
+                Isometry3::translation(2.0, 0.0, 0.0),
+            ),
+        ]);
+        let joint = test.joints.create(&JointDesc::Fixed, JointPosition::Middle);
+        test.joints.insert_rigid_body(joint.get(), bodies[0].get());
+        test.joints.insert_rigid_body(joint.get(), bodies[1].get());
+        let area = test.areas.create(&AreaDesc::default());
+        test.areas.set_shape(area.get(), Some(shape.get()));
+        test.areas
+            .set_transform(area.get(), &Isometry3::translation(0.0, -1.0, 0.0));
+        for i in 0..5 {
+            test.bodies
+                .apply_impulse(bodies[1].get(), &Vector3::new(0.0, 1.0, 0.0));
+            test.world.step_with_delta(0.01 * (i + 1) as f32);
+        }
+        let replay = test.world.stop_replay_recording();
+        assert_eq!(replay.steps, vec![0.01, 0.02, 0.03, 0.04, 0.05]);
+
+        let replay_test = TestWorld::<f32>::new();
+        let mut replayer = Replayer::new(&replay_test.world, replay).unwrap();
+        replayer.run().unwrap();
+
+        assert_eq!(replay_test.world.gravity(), Vector3::new(0.0, -3.0, 0.0));
+        assert!(replayer.joint(joint.get()).is_some());
+        let replayed_area = replayer.area(area.get()).unwrap();
+        assert_eq!(
+            replay_test.areas.transform(replayed_area),
+            test.areas.transform(area.get())
+        );
+        for body in bodies.iter() {
+            let replayed_body = replayer.body(body.get()).unwrap();
+            assert_eq!(
+                replay_test.bodies.transform(replayed_body),
+                test.bodies.transform(body.get())
+            );
+        }
+    }
+
+    #[test]
+    fn replay_fails_on_the_unknown_tags() {
+        let test = TestWorld::<f32>::new();
+        let body = test.bodies.create(&RigidBodyDesc::default());
+
+        test.world.start_replay_recording();
+        test.bodies
+            .apply_impulse(body.get(), &Vector3::new(1.0, 0.0, 0.0));
+        test.step();
+        let replay = test.world.stop_replay_recording();
+
+        let replay_test = TestWorld::<f32>::new();
+        let mut replayer = Replayer::new(&replay_test.world, replay).unwrap();
+        assert_eq!(replayer.step(), Err(PhysicsError::BodyNotFound));
+        assert_eq!(replayer.replayed_steps(), 0);
+    }
+
+    #[test]
+    fn replay_reproduces_the_simulation() {
+        let test = TestWorld::<f32>::new();
+
+        test.world.start_replay_recording();
+        let shape = test.shapes.create(&ShapeDesc::Sphere { radius: 0.5 });
+        let body = test.bodies.create(&RigidBodyDesc::default());
+        test.bodies.set_shape(body.get(), Some(shape.get()));
+        for _ in 0..5 {
+            test.bodies
+                .apply_impulse(body.get(), &Vector3::new(1.0, 0.0, 0.0));
+            test.step();
+        }
+        let replay = test.world.stop_replay_recording();
+        assert_eq!(replay.steps.len(), 5);
+
+        let replay_test = TestWorld::<f32>::new();
+        let mut replayer = Replayer::new(&replay_test.world, replay).unwrap();
+        replayer.run().unwrap();
+        assert!(replayer.is_finished());
+
+        let replayed_body = replayer.body(body.get()).unwrap();
+        assert_eq!(
+            replay_test.bodies.transform(replayed_body),
+            test.bodies.transform(body.get())
+        );
+    }
+
+    #[test]
+    fn replay_records_the_moved_vertices() {
+        let test = TestWorld::<f32>::new();
+
+        let mesh = test.shapes.create(&ShapeDesc::TriMesh {
+            points: vec![
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+                Point3::new(0.0, 0.0, 1.0),
+            ],
+            indices: vec![Point3::new(0, 1, 2)],
+        });
+        test.world.start_replay_recording();
+        let moved = (1, Point3::new(2.0, 0.5, 0.0));
+        test.shapes
+            .update_trimesh_vertices(mesh.get(), &[moved])
+            .unwrap();
+        let replay = test.world.stop_replay_recording();
+
+        assert_eq!(replay.calls.len(), 1);
+        match &replay.calls[0].1 {
+            ReplayCall::UpdateTriMeshVertices(tag, vertices) => {
+                assert_eq!(*tag, mesh.get());
+                assert_eq!(vertices, &vec![moved]);
+            }
+            _ => panic!("The moved vertices are not recorded"),
+        }
+    }
+}
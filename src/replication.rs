@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use amethyst_core::{
+    ecs::Entity,
+    math::{Isometry3, Translation3, UnitQuaternion, Vector3},
+};
+use amethyst_physics::{objects::*, PtReal};
+use nphysics3d::math::Velocity;
+
+use crate::{body::BodyData, conversors::*, storage::StoreKey, WorldNpServer};
+
+/// The state of a rigid body to send to the other peers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReplicationRecord<N: PtReal> {
+    /// The tag of the body on the peer that collected the record; the receiver must replace it
+    /// with the tag of its own body, e.g. using the `entity`, before applying the record.
+    pub body: PhysicsRigidBodyTag,
+    pub entity: Option<Entity>,
+    pub position: Vector3<N>,
+    pub rotation: UnitQuaternion<N>,
+    pub linear_velocity: Vector3<N>,
+    pub angular_velocity: Vector3<N>,
+    pub sleeping: bool,
+}
+
+/// Remembers the state of the bodies sent to a peer, so only the changed ones are sent again.
+///
+/// Use a tracker for each peer, since each one may have received a different state.
+#[derive(Debug, Clone, Default)]
+pub struct ReplicationTracker<N: PtReal> {
+    sent: HashMap<StoreKey, ReplicationRecord<N>>,
+}
+
+impl<N: PtReal> ReplicationTracker<N> {
+    pub fn new() -> Self {
+        ReplicationTracker {
+            sent: HashMap::new(),
+        }
+    }
+
+    /// Returns the records of the rigid bodies that changed since the previous call, or all of
+    /// them the first time.
+    pub fn collect(&mut self, world: &WorldNpServer<N>) -> Vec<ReplicationRecord<N>> {
+        let bodies = world.storages.bodies_r();
+        self.sent.retain(|key, _| bodies.get_body(*key).is_some());
+
+        let mut records = Vec::new();
+        for (key, _) in bodies.iter() {
+            let body = bodies.get_body(key).unwrap();
+            if !matches!(body.body_data, BodyData::Rigid { .. }) {
+                continue;
+            }
+            let rb = body.rigid_body().unwrap();
+            let record = ReplicationRecord {
+                body: store_key_to_rigid_tag(key),
+                entity: body.entity,
+                position: rb.position().translation.vector,
+                rotation: rb.position().rotation,
+                linear_velocity: rb.velocity().linear,
+                angular_velocity: rb.velocity().angular,
+                sleeping: !body.np_body.is_active(),
+            };
+            if self.sent.get(&key) != Some(&record) {
+                self.sent.insert(key, record);
+                records.push(record);
+            }
+        }
+        records
+    }
+
+    /// Forgets the sent state, so the next `collect` returns all the bodies; e.g. when the peer
+    /// lost some records.
+    pub fn reset(&mut self) {
+        self.sent.clear();
+    }
+}
+
+// Replication
+impl<N: PtReal> WorldNpServer<N> {
+    /// Applies the records received from another peer; the records of the bodies that don't
+    /// exist are skipped.
+    pub fn apply_replication(&self, records: &[ReplicationRecord<N>]) {
+        let bodies = self.storages.bodies_r();
+        for record in records {
+            let body = bodies.get_body(rigid_tag_to_store_key(record.body));
+            if let Some(mut body) = body {
+                if !matches!(body.body_data, BodyData::Rigid { .. }) {
+                    continue;
+                }
+                let rb = body.rigid_body_mut().unwrap();
+                rb.set_position(Isometry3::from_parts(
+                    Translation3::from(record.position),
+                    record.rotation,
+                ));
+                rb.set_velocity(Velocity::new(
+                    record.linear_velocity,
+                    record.angular_velocity,
+                ));
+                if record.sleeping {
+                    body.np_body.deactivate();
+                } else {
+                    body.np_body.activate();
+                }
+                self.storages.track_static_move(&body);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::Isometry3;
+    use amethyst_physics::servers::{RBodyPhysicsServerTrait, RigidBodyDesc};
+
+    use super::ReplicationTracker;
+    use crate::test_world::TestWorld;
+
+    #[test]
+    fn replication_of_changed_bodies() {
+        let server = TestWorld::<f32>::new();
+        let client = TestWorld::<f32>::new();
+
+        let body = server.bodies.create(&RigidBodyDesc::default());
+        let replica = client.bodies.create(&RigidBodyDesc::default());
+
+        let mut tracker = ReplicationTracker::new();
+        assert_eq!(tracker.collect(&server.world).len(), 1);
+        assert!(tracker.collect(&server.world).is_empty());
+
+        let transform = Isometry3::translation(0.0, 2.0, 0.0);
+        server.bodies.set_transform(body.get(), &transform);
+        let mut records = tracker.collect(&server.world);
+        assert_eq!(records.len(), 1);
+
+        records[0].body = replica.get();
+        client.world.apply_replication(&records);
+        assert_eq!(client.bodies.transform(replica.get()), transform);
+    }
+}
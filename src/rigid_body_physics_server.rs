@@ -2,23 +2,31 @@ use amethyst_core::ecs::Entity;
 use amethyst_core::math::{one, zero, Isometry3, Point, Vector3};
 use amethyst_physics::{objects::*, servers::*, PtReal};
 use log::error;
+use ncollide3d::shape::ShapeHandle as NcShapeHandle;
 use nphysics3d::{
     math::{Force, ForceType},
     object::{
         BodyPartHandle as NpBodyPartHandle, Collider as NpCollider, ColliderDesc as NpColliderDesc,
-        RigidBodyDesc as NpRigidBodyDesc,
+        RigidBody as NpRigidBody, RigidBodyDesc as NpRigidBodyDesc,
     },
+    volumetric::Volumetric,
 };
 
 use crate::{
-    body::{Body, BodyData},
+    body::{Body, BodyData, ContactDetail},
+    collider_storage::ColliderStorage,
+    commands::PhysicsCommand,
     conversors::*,
+    error::{LogError, PhysicsError, PhysicsResult},
+    extended_groups::ExtendedGroups,
+    replay::ReplayCall,
     servers_storage::*,
     shape::RigidShape,
     storage::StoreKey,
-    utils::*,
+    utils::ColliderTag,
 };
 
+#[allow(missing_debug_implementations)]
 pub struct RBodyNpServer<N: PtReal> {
     storages: ServersStorages<N>,
 }
@@ -41,6 +49,11 @@ impl<N: PtReal> RBodyNpServer<N> {
         let body_key = rigid_tag_to_store_key(body_tag);
         if let Some(mut body) = bodies_storage.get_body(body_key) {
             Self::remove_shape(&mut *body, shapes_storage, colliders_storage);
+            if let Some(lod_shape_key) = body.lod_shape_key.take() {
+                if let Some(mut shape) = shapes_storage.get(lod_shape_key) {
+                    shape.unregister_lod_body(body_key);
+                }
+            }
         }
         bodies_storage.drop_body(body_key);
     }
@@ -58,6 +71,68 @@ impl<N: PtReal> RBodyNpServer<N> {
         // Collider registration
         shape.register_body(body.self_key.unwrap());
         body.shape_key = shape.self_key;
+        body.lod_active = false;
+    }
+
+    /// Replace the collider of the body with one that uses the LOD shape, when `use_lod_shape` is
+    /// `true`, or the body shape otherwise.
+    ///
+    /// The shapes registration is not changed.
+    pub fn install_lod_collider(
+        body: &mut Body<N>,
+        use_lod_shape: bool,
+        shapes: &ShapesStorageRead<'_, N>,
+        colliders: &mut CollidersStorageWrite<'_, N>,
+    ) {
+        let shape_key = if use_lod_shape {
+            body.lod_shape_key
+        } else {
+            body.shape_key
+        };
+
+        Self::drop_collider(body, colliders);
+        if let Some(shape) = shape_key.and_then(|key| shapes.get(key)) {
+            let collider_desc = Self::create_collider_desc(body, &shape);
+            Self::install_collider(body, &collider_desc, colliders);
+        }
+        body.lod_active = use_lod_shape;
+    }
+
+    /// Replace the shape of the body collider in place, so the collider keeps its broad phase
+    /// proxy and its contacts.
+    ///
+    /// The mass properties that the collider added to the body are moved to the new shape.
+    /// The two shapes must be of the same kind.
+    pub fn update_collider_shape(
+        body: &mut Body<N>,
+        old_shape_handle: &NcShapeHandle<N>,
+        shape: &RigidShape<N>,
+        colliders: &ColliderStorage<N, StoreKey>,
+        wake_up: bool,
+    ) {
+        if let Some(mut collider) = body
+            .collider_key
+            .and_then(|key| colliders.get_collider(key))
+        {
+            let density = collider.density();
+            if density != zero() {
+                let new_shape_handle = shape.shape_handle();
+                body.np_body.add_local_inertia_and_com(
+                    0,
+                    old_shape_handle.center_of_mass(),
+                    -old_shape_handle.inertia(density),
+                );
+                body.np_body.add_local_inertia_and_com(
+                    0,
+                    new_shape_handle.center_of_mass(),
+                    new_shape_handle.inertia(density),
+                );
+            }
+            collider.set_shape(shape.shape_handle().clone());
+        }
+        if wake_up {
+            body.activate();
+        }
     }
 
     /// Remove shape.
@@ -75,6 +150,7 @@ impl<N: PtReal> RBodyNpServer<N> {
             }
             body.shape_key = None;
         }
+        body.lod_active = false;
         Self::drop_collider(body, colliders);
     }
 
@@ -86,7 +162,7 @@ impl<N: PtReal> RBodyNpServer<N> {
     ) {
         let mut collider = collider_desc.build(NpBodyPartHandle(body.self_key.unwrap(), 0));
 
-        RBodyNpServer::update_user_data(&mut collider, body);
+        body.set_user_data_to(&mut collider);
 
         let key = colliders.insert_collider(collider);
         body.collider_key = Some(key);
@@ -100,14 +176,6 @@ impl<N: PtReal> RBodyNpServer<N> {
         }
     }
 
-    pub fn update_user_data(collider: &mut NpCollider<N, StoreKey>, body: &Body<N>) {
-        collider.set_user_data(Some(Box::new(UserData::new(
-            ObjectType::RigidBody,
-            body.self_key.unwrap(),
-            body.entity,
-        ))));
-    }
-
     pub fn create_collider_desc(body: &Body<N>, shape: &RigidShape<N>) -> NpColliderDesc<N> {
         let mut collider_desc = NpColliderDesc::new(shape.shape_handle().clone())
             .collision_groups(body.np_collision_groups)
@@ -126,7 +194,6 @@ impl<N: PtReal> RBodyNpServer<N> {
         }
     }
 
-    #[allow(clippy::collapsible_if)]
     pub fn update_contacts_watcher(
         body: &mut Body<N>,
         contacts_storage: &mut WatchContactsWrite<'_>,
@@ -135,17 +202,11 @@ impl<N: PtReal> RBodyNpServer<N> {
             contacts_to_report, ..
         } = body.body_data
         {
-            let i = contacts_storage.binary_search(&body.self_key.unwrap());
-            if let Result::Ok(i) = i {
-                if contacts_to_report == 0 {
-                    // Removes the entry on the contacts storage.
-                    contacts_storage.remove(i);
-                }
+            let key = body.self_key.unwrap();
+            if contacts_to_report == 0 {
+                contacts_storage.remove(&key);
             } else {
-                if contacts_to_report != 0 {
-                    // Creates new entry on the contacts storage.
-                    contacts_storage.push(body.self_key.unwrap());
-                }
+                contacts_storage.insert(key);
             }
         }
     }
@@ -164,13 +225,335 @@ impl<N: crate::PtReal> RBodyNpServer<N> {
     }
 }
 
-impl<N> RBodyPhysicsServerTrait<N> for RBodyNpServer<N>
-where
-    N: PtReal,
-{
-    fn create(&self, body_desc: &RigidBodyDesc<N>) -> PhysicsHandle<PhysicsRigidBodyTag> {
+// LOD
+impl<N: PtReal> RBodyNpServer<N> {
+    /// Set the cheap shape that the body uses when it's far from all the LOD focus points.
+    ///
+    /// The focus points are set using `WorldNpServer::set_lod_settings`.
+    pub fn set_lod_shape(&self, body_tag: PhysicsRigidBodyTag, shape_tag: Option<PhysicsShapeTag>) {
+        self.try_set_lod_shape(body_tag, shape_tag)
+            .or_report(&self.storages);
+    }
+
+    pub fn lod_shape(&self, body_tag: PhysicsRigidBodyTag) -> Option<PhysicsShapeTag> {
+        let body_key = rigid_tag_to_store_key(body_tag);
+        let bodies = self.storages.bodies_r();
+
+        bodies
+            .get_body(body_key)
+            .and_then(|body| body.lod_shape_key.map(store_key_to_shape_tag))
+    }
+}
+
+// Depenetration
+impl<N: PtReal> RBodyNpServer<N> {
+    /// Set the maximum distance the body is moved, each step, to get out of the geometry it's
+    /// overlapping; `None` (default) to disable the recovery.
+    ///
+    /// The solver already separates the dynamic bodies, but slowly; this moves them out of a wall
+    /// they are spawned inside of in a few steps. The kinematic bodies are moved only out of the
+    /// dynamic ones, since NPhysics doesn't compute their contacts with the static bodies.
+    pub fn set_depenetration(&self, body_tag: PhysicsRigidBodyTag, max_distance: Option<N>) {
+        self.with_rigid_body(body_tag, |body| body.depenetration = max_distance)
+            .or_report(&self.storages);
+    }
+
+    pub fn depenetration(&self, body_tag: PhysicsRigidBodyTag) -> Option<N> {
+        self.storages
+            .bodies_r()
+            .get_body(rigid_tag_to_store_key(body_tag))
+            .and_then(|body| body.depenetration)
+    }
+}
+
+// Platforms
+impl<N: PtReal> RBodyNpServer<N> {
+    /// Set if the body follows the movement of the kinematic platform it's standing on.
+    ///
+    /// This is meant for the kinematic bodies, since the dynamic ones are already carried by the
+    /// friction, so it has no effect on them.
+    pub fn set_carried_by_platforms(&self, body_tag: PhysicsRigidBodyTag, carried: bool) {
+        self.with_rigid_body(body_tag, |body| body.carried_by_platforms = carried)
+            .or_report(&self.storages);
+    }
+
+    pub fn is_carried_by_platforms(&self, body_tag: PhysicsRigidBodyTag) -> bool {
+        self.storages
+            .bodies_r()
+            .get_body(rigid_tag_to_store_key(body_tag))
+            .map_or(false, |body| body.carried_by_platforms)
+    }
+}
+
+// Static contacts
+impl<N: PtReal> RBodyNpServer<N> {
+    /// Set if the kinematic body reports the contacts with the static and the kinematic bodies,
+    /// that NPhysics doesn't compute; e.g. a kinematic character that needs to detect the walls.
+    ///
+    /// The contacts are reported like the others, so `contacts_to_report` must be greater than 0;
+    /// they are not published by the `ContactEventsSystem`.
+    pub fn set_report_static_contacts(&self, body_tag: PhysicsRigidBodyTag, report: bool) {
+        self.with_rigid_body(body_tag, |body| body.report_static_contacts = report)
+            .or_report(&self.storages);
+    }
+
+    pub fn reports_static_contacts(&self, body_tag: PhysicsRigidBodyTag) -> bool {
+        self.storages
+            .bodies_r()
+            .get_body(rigid_tag_to_store_key(body_tag))
+            .map_or(false, |body| body.report_static_contacts)
+    }
+}
+
+// Extended groups
+impl<N: PtReal> RBodyNpServer<N> {
+    /// Set the masks checked after the collision groups, for more than the 30 groups supported by
+    /// NPhysics.
+    pub fn set_extended_groups(&self, body_tag: PhysicsRigidBodyTag, groups: ExtendedGroups) {
+        self.with_rigid_body(body_tag, |body| {
+            body.extended_groups = groups;
+            body.sync_collider_user_data(&self.storages.colliders_r());
+            // Setting the collision groups again makes the broad phase filter the pairs again.
+            self.update_collider_collision_groups(body);
+        })
+        .or_report(&self.storages);
+    }
+
+    pub fn extended_groups(&self, body_tag: PhysicsRigidBodyTag) -> ExtendedGroups {
+        self.storages
+            .bodies_r()
+            .get_body(rigid_tag_to_store_key(body_tag))
+            .map_or(ExtendedGroups::ALL, |body| body.extended_groups)
+    }
+}
+
+// Collider tag
+impl<N: PtReal> RBodyNpServer<N> {
+    /// Set the tag of the body collider, returned with the contacts of the other bodies and with
+    /// the overlap events; e.g. to know which hitbox was hit.
+    pub fn set_collider_tag(&self, body_tag: PhysicsRigidBodyTag, tag: Option<ColliderTag>) {
+        self.with_rigid_body(body_tag, |body| {
+            body.collider_tag = tag;
+            body.sync_collider_user_data(&self.storages.colliders_r());
+        })
+        .or_report(&self.storages);
+    }
+
+    pub fn collider_tag(&self, body_tag: PhysicsRigidBodyTag) -> Option<ColliderTag> {
+        self.storages
+            .bodies_r()
+            .get_body(rigid_tag_to_store_key(body_tag))
+            .and_then(|body| body.collider_tag)
+    }
+}
+
+// Jointed teleport
+impl<N: PtReal> RBodyNpServer<N> {
+    /// Like `set_transform`, but moves by the same offset all the bodies connected to this one
+    /// through the joints, so the joints keep their anchors and don't snap the bodies back.
+    ///
+    /// The connected bodies are moved even when static; the velocities are not changed.
+    pub fn set_transform_with_joints(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+        transf: &Isometry3<N>,
+    ) -> PhysicsResult<()> {
+        let body_key = self.checked_key(body_tag);
+        let joints = self.storages.joints_r();
+        let bodies = self.storages.bodies_r();
+
+        let offset = {
+            let body = bodies
+                .get_body(body_key)
+                .ok_or(PhysicsError::BodyNotFound)?;
+            let current = body.body_transform().ok_or(PhysicsError::WrongBodyType)?;
+            transf * current.inverse()
+        };
+
+        // Collects the bodies reachable through the joints.
+        let mut group = vec![body_key];
+        let mut i = 0;
+        while i < group.len() {
+            let body_joints = bodies
+                .get_body(group[i])
+                .map(|body| body.joints.clone())
+                .unwrap_or_default();
+            for joint_key in body_joints {
+                if let Some(joint) = joints.get_joint(joint_key) {
+                    for (other_key, _) in joint.body_0.iter().chain(joint.body_1.iter()) {
+                        if !group.contains(other_key) {
+                            group.push(*other_key);
+                        }
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        for key in group {
+            if let Some(mut body) = bodies.get_body(key) {
+                if let Some(current) = body.body_transform().copied() {
+                    body.set_body_transform(&(offset * current))?;
+                    self.storages.track_static_move(&body);
+                    bodies.mark_moved(key, &mut *body);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Raw access
+impl<N: PtReal> RBodyNpServer<N> {
+    /// Runs `f` with the NPhysics rigid body, to reach the features not wrapped by the server.
+    ///
+    /// This is an advanced API: the server is not aware of the changes done through it, so its
+    /// state may go out of sync (e.g. changing the body status doesn't update the body mode
+    /// tracked by the server). Prefer the server functions when available.
+    pub fn with_np_rigid_body<R>(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+        f: impl FnOnce(&mut NpRigidBody<N>) -> R,
+    ) -> PhysicsResult<R> {
+        self.checked_key(body_tag);
+        self.with_rigid_body(body_tag, |body| {
+            body.rigid_body_mut()
+                .map(f)
+                .ok_or(PhysicsError::WrongBodyType)
+        })?
+    }
+
+    /// Runs `f` with the NPhysics collider of the body.
+    ///
+    /// Fails with `ShapeMissing` when the body has no shape. Like `with_np_rigid_body`, the
+    /// server is not aware of the changes; e.g. the collision groups and the user data are
+    /// overwritten the next time the server sets them.
+    pub fn with_np_collider<R>(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+        f: impl FnOnce(&mut NpCollider<N, StoreKey>) -> R,
+    ) -> PhysicsResult<R> {
+        self.checked_key(body_tag);
+        self.with_rigid_body(body_tag, |body| {
+            let collider_key = body.collider_key.ok_or(PhysicsError::ShapeMissing)?;
+            let colliders = self.storages.colliders_r();
+            let mut collider = colliders
+                .get_collider(collider_key)
+                .ok_or(PhysicsError::ShapeMissing)?;
+            Ok(f(&mut *collider))
+        })?
+    }
+}
+
+// Batch
+impl<N: PtReal> RBodyNpServer<N> {
+    /// Set the transforms of many bodies, taking the bodies storage lock only once.
+    pub fn set_transforms(&self, transforms: &[(PhysicsRigidBodyTag, Isometry3<N>)]) {
+        let bodies = self.storages.bodies_r();
+        for (body_tag, transf) in transforms {
+            self.storages
+                .replay_recorder
+                .record(|| ReplayCall::Body(PhysicsCommand::SetTransform(*body_tag, *transf)));
+            let body_key = rigid_tag_to_store_key(*body_tag);
+            if let Some(mut body) = bodies.get_body(body_key) {
+                if body
+                    .set_body_transform(transf)
+                    .or_report(&self.storages)
+                    .is_some()
+                {
+                    self.storages.track_static_move(&body);
+                    bodies.mark_moved(body_key, &mut *body);
+                }
+            }
+        }
+    }
+
+    /// Creates many bodies, taking the bodies storage lock only once.
+    ///
+    /// The handles are returned in the same order of the descriptors.
+    pub fn create_many(
+        &self,
+        body_descs: &[RigidBodyDesc<N>],
+    ) -> Vec<PhysicsHandle<PhysicsRigidBodyTag>> {
         let mut bodies_storage = self.storages.bodies_w();
+        let mut watch_contacts = self.storages.watch_contacts_w();
+        body_descs
+            .iter()
+            .map(|body_desc| {
+                let handle = self.create_body(body_desc, &mut bodies_storage, &mut watch_contacts);
+                self.storages
+                    .replay_recorder
+                    .record(|| ReplayCall::CreateBody(handle.get(), body_desc.clone()));
+                handle
+            })
+            .collect()
+    }
+
+    /// Creates many bodies with their shape and their transform, taking the storages locks only
+    /// once and reserving the memory for all of them upfront; useful to spawn a burst of bodies,
+    /// like the debris of an explosion or a streamed level chunk.
+    ///
+    /// The colliders are registered in the broad phase during the next step, or right away with
+    /// `WorldNpServer::register_inserted_colliders`.
+    ///
+    /// The handles are returned in the same order of the descriptors.
+    pub fn create_many_with_shape(
+        &self,
+        spawns: &[(RigidBodyDesc<N>, PhysicsShapeTag, Isometry3<N>)],
+    ) -> Vec<PhysicsHandle<PhysicsRigidBodyTag>> {
+        let mut bodies_storage = self.storages.bodies_w();
+        let mut colliders = self.storages.colliders_w();
+        let shapes = self.storages.shapes_r();
+        let mut watch_contacts = self.storages.watch_contacts_w();
+
+        bodies_storage.reserve(spawns.len());
+        colliders.reserve(spawns.len());
+
+        spawns
+            .iter()
+            .map(|(body_desc, shape_tag, transf)| {
+                let handle = self.create_body(body_desc, &mut bodies_storage, &mut watch_contacts);
+                let body_tag = handle.get();
+                self.storages.replay_recorder.record(|| {
+                    ReplayCall::CreateBody(body_tag, body_desc.clone())
+                });
+                self.storages.replay_recorder.record(|| {
+                    ReplayCall::Body(PhysicsCommand::SetTransform(body_tag, *transf))
+                });
+                self.storages
+                    .replay_recorder
+                    .record(|| ReplayCall::SetShape(body_tag, Some(*shape_tag)));
+
+                let mut body = bodies_storage
+                    .get_body(rigid_tag_to_store_key(handle.get()))
+                    .unwrap();
+                // The transform is set first, so the collider is created at its final position.
+                body.set_body_transform(transf).or_report(&self.storages);
+
+                if let Some(mut shape) = shapes.get(shape_tag_to_store_key(*shape_tag)) {
+                    let collider_desc = RBodyNpServer::create_collider_desc(&body, &shape);
+                    RBodyNpServer::install_shape(
+                        &mut *body,
+                        &mut *shape,
+                        &collider_desc,
+                        &mut colliders,
+                    );
+                    self.storages.sync_body_info(&*body);
+                } else {
+                    error!("During the rigid body creation, was not possible to find the shape to assign");
+                }
 
+                handle
+            })
+            .collect()
+    }
+
+    fn create_body(
+        &self,
+        body_desc: &RigidBodyDesc<N>,
+        bodies_storage: &mut BodiesStorageWrite<'_, N>,
+        watch_contacts: &mut WatchContactsWrite<'_>,
+    ) -> PhysicsHandle<PhysicsRigidBodyTag> {
         // Create Rigid body
         let np_rigid_body = NpRigidBodyDesc::new()
             .set_status(body_mode_conversor::to_physics(body_desc.mode))
@@ -205,46 +588,69 @@ where
                 body_desc.lock_rotation_y,
                 body_desc.lock_rotation_z,
             ));
-        Self::update_contacts_watcher(&mut *body, &mut self.storages.watch_contacts_w());
+        Self::update_contacts_watcher(&mut *body, watch_contacts);
+        self.storages.sync_body_info(&*body);
 
-        PhysicsHandle::new(store_key_to_rigid_tag(b_key), self.storages.gc.clone())
+        PhysicsHandle::new(store_key_to_rigid_tag(b_key), self.storages.gc())
     }
+}
 
-    fn set_entity(&self, body_tag: PhysicsRigidBodyTag, entity: Option<Entity>) {
+// Contacts
+impl<N: PtReal> RBodyNpServer<N> {
+    /// Calls `f` for each contact of the last step, without copying them like `contact_events`
+    /// does.
+    ///
+    /// The body is locked while `f` runs, so `f` must not call the servers on the same body.
+    pub fn for_each_contact<F>(&self, body_tag: PhysicsRigidBodyTag, mut f: F)
+    where
+        F: FnMut(&ContactEvent<N>),
+    {
         let body_key = rigid_tag_to_store_key(body_tag);
         let bodies = self.storages.bodies_r();
 
-        let body = bodies.get_body(body_key);
-        if let Some(mut body) = body {
-            fail_cond!(!matches!(body.body_data, BodyData::Rigid{..}));
-            body.entity = entity;
-
-            if let Some(collider_key) = body.collider_key {
-                let colliders = self.storages.colliders_r();
-                let collider = colliders.get_collider(collider_key);
-                if let Some(mut collider) = collider {
-                    RBodyNpServer::update_user_data(&mut *collider, &*body);
-                } else {
-                    error!("A body is assigned to a collider, but the collider doesn't exist!")
+        if let Some(body) = bodies.get_body(body_key) {
+            if let BodyData::Rigid { contacts, .. } = &body.body_data {
+                for contact in contacts.iter().filter(|c| c.is_touching()) {
+                    f(&contact.event);
                 }
             }
         }
     }
 
-    fn entity(&self, body_tag: PhysicsRigidBodyTag) -> Option<Entity> {
-        let body_key = rigid_tag_to_store_key(body_tag);
+    /// Returns the contacts of the last step, like `contact_events`, with their state; the
+    /// contacts that stopped during the last step are included.
+    pub fn contact_details(&self, body_tag: PhysicsRigidBodyTag) -> Vec<ContactDetail<N>> {
+        let body_key = self.checked_key(body_tag);
         let bodies = self.storages.bodies_r();
 
-        let body = bodies.get_body(body_key);
-        if let Some(body) = body {
-            body.entity
-        } else {
-            None
+        if let Some(body) = bodies.get_body(body_key) {
+            if let BodyData::Rigid { contacts, .. } = &body.body_data {
+                return contacts.to_vec();
+            }
         }
+        Vec::new()
     }
+}
 
-    fn set_shape(&self, body_tag: PhysicsRigidBodyTag, shape_tag: Option<PhysicsShapeTag>) {
-        let body_key = rigid_tag_to_store_key(body_tag);
+// Shape swap
+impl<N: PtReal> RBodyNpServer<N> {
+    /// Like `set_shape`, but a sleeping body is not woken up when the new shape is of the same
+    /// kind of the old one, so a resting stack doesn't pop while its shapes are swapped.
+    pub fn set_shape_keep_sleeping(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+        shape_tag: Option<PhysicsShapeTag>,
+    ) {
+        self.swap_shape(body_tag, shape_tag, false);
+    }
+
+    fn swap_shape(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+        shape_tag: Option<PhysicsShapeTag>,
+        wake_up: bool,
+    ) {
+        let body_key = self.checked_key(body_tag);
         let bodies = self.storages.bodies_r();
 
         let shape_key = shape_tag.map(shape_tag_to_store_key);
@@ -258,6 +664,31 @@ where
             let mut colliders = self.storages.colliders_w();
             let shapes = self.storages.shapes_r();
 
+            // With a shape of the same kind, the collider is updated in place, so it keeps its
+            // contacts and the solver warm start.
+            if let (Some(old_key), Some(new_key)) = (body.shape_key, shape_key) {
+                if body.collider_key.is_some() && !body.lod_active {
+                    if let (Some(mut old_shape), Some(mut new_shape)) =
+                        (shapes.get(old_key), shapes.get(new_key))
+                    {
+                        if old_shape.kind() == new_shape.kind() {
+                            old_shape.unregister_body(body_key);
+                            new_shape.register_body(body_key);
+                            body.shape_key = Some(new_key);
+                            RBodyNpServer::update_collider_shape(
+                                &mut *body,
+                                old_shape.shape_handle(),
+                                &*new_shape,
+                                &colliders,
+                                wake_up,
+                            );
+                            self.storages.sync_body_info(&*body);
+                            return;
+                        }
+                    }
+                }
+            }
+
             // Remove the old shape
             if let Some(_b_shape_key) = body.shape_key {
                 RBodyNpServer::remove_shape(&mut *body, &shapes, &mut colliders);
@@ -280,47 +711,357 @@ where
                     error!("During the rigid body creation, was not possible to find the shape to assign");
                 }
             }
+            // The new collider has not any contact yet, so the body must not sleep on it.
+            body.activate();
+            self.storages.sync_body_info(&*body);
         } else {
             error!("Body not found");
         }
     }
+}
 
-    fn shape(&self, body_tag: PhysicsRigidBodyTag) -> Option<PhysicsShapeTag> {
+// Stale tags
+impl<N: PtReal> RBodyNpServer<N> {
+    /// Returns `true` if the tag refers to an existing rigid body.
+    pub fn is_valid(&self, body_tag: PhysicsRigidBodyTag) -> bool {
+        self.storages
+            .bodies_info()
+            .contains_key(rigid_tag_to_store_key(body_tag))
+    }
+
+    /// Converts the tag to the storage key, reporting it when it's stale and the report is on.
+    fn checked_key(&self, body_tag: PhysicsRigidBodyTag) -> StoreKey {
+        #[cfg(feature = "validation")]
+        self.storages.validate();
+
+        if self.storages.report_stale_tags() && !self.is_valid(body_tag) {
+            self.storages.report_stale_tag("rigid body");
+        }
+        rigid_tag_to_store_key(body_tag)
+    }
+}
+
+// Fallible functions
+impl<N: PtReal> RBodyNpServer<N> {
+    /// Calls `f` with the rigid body of the tag, or returns the reason why it can't.
+    fn with_rigid_body<R>(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+        f: impl FnOnce(&mut Body<N>) -> R,
+    ) -> PhysicsResult<R> {
+        let bodies = self.storages.bodies_r();
+        let mut body = bodies
+            .get_body(rigid_tag_to_store_key(body_tag))
+            .ok_or(PhysicsError::BodyNotFound)?;
+        if !matches!(body.body_data, BodyData::Rigid { .. }) {
+            return Err(PhysicsError::WrongBodyType);
+        }
+        Ok(f(&mut *body))
+    }
+
+    /// Like `set_entity`, but returns an error when the body doesn't exist.
+    pub fn try_set_entity(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+        entity: Option<Entity>,
+    ) -> PhysicsResult<()> {
+        self.with_rigid_body(body_tag, |body| {
+            body.entity = entity;
+            self.storages.sync_body_info(&*body);
+            body.sync_collider_user_data(&self.storages.colliders_r());
+        })
+    }
+
+    /// Like `set_lod_shape`, but returns an error when the body or the shape doesn't exist.
+    pub fn try_set_lod_shape(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+        shape_tag: Option<PhysicsShapeTag>,
+    ) -> PhysicsResult<()> {
+        let body_key = rigid_tag_to_store_key(body_tag);
+        self.with_rigid_body(body_tag, |body| {
+            let mut colliders = self.storages.colliders_w();
+            let shapes = self.storages.shapes_r();
+
+            let shape_key = shape_tag.map(shape_tag_to_store_key);
+            if let Some(shape_key) = shape_key {
+                if !shapes.has(shape_key) {
+                    return Err(PhysicsError::ShapeMissing);
+                }
+            }
+
+            // Restore the body shape
+            if body.lod_active {
+                Self::install_lod_collider(body, false, &shapes, &mut colliders);
+            }
+
+            if let Some(lod_shape_key) = body.lod_shape_key.take() {
+                if let Some(mut shape) = shapes.get(lod_shape_key) {
+                    shape.unregister_lod_body(body_key);
+                }
+            }
+
+            if let Some(shape_key) = shape_key {
+                shapes.get(shape_key).unwrap().register_lod_body(body_key);
+                body.lod_shape_key = Some(shape_key);
+            }
+            Ok(())
+        })?
+    }
+
+    /// Like `set_shape`, but returns an error when the body or the shape doesn't exist.
+    pub fn try_set_shape(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+        shape_tag: Option<PhysicsShapeTag>,
+    ) -> PhysicsResult<()> {
+        self.with_rigid_body(body_tag, |_| ())?;
+        if let Some(shape_tag) = shape_tag {
+            if !self
+                .storages
+                .shapes_r()
+                .has(shape_tag_to_store_key(shape_tag))
+            {
+                return Err(PhysicsError::ShapeMissing);
+            }
+        }
+        self.swap_shape(body_tag, shape_tag, true);
+        Ok(())
+    }
+
+    /// Like `shape`, but returns an error when the body doesn't exist.
+    pub fn try_shape(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+    ) -> PhysicsResult<Option<PhysicsShapeTag>> {
+        self.with_rigid_body(body_tag, |body| body.shape_key.map(store_key_to_shape_tag))
+    }
+
+    /// Like `set_transform`, but returns an error when the body doesn't exist.
+    pub fn try_set_transform(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+        transf: &Isometry3<N>,
+    ) -> PhysicsResult<()> {
         let body_key = rigid_tag_to_store_key(body_tag);
         let bodies = self.storages.bodies_r();
+        let mut body = bodies
+            .get_body(body_key)
+            .ok_or(PhysicsError::BodyNotFound)?;
+        if !matches!(body.body_data, BodyData::Rigid { .. }) {
+            return Err(PhysicsError::WrongBodyType);
+        }
+        body.set_body_transform(transf)?;
+        self.storages.track_static_move(&body);
+        bodies.mark_moved(body_key, &mut *body);
+        Ok(())
+    }
 
-        let body = bodies.get_body(body_key);
-        if let Some(body) = body {
-            body.shape_key.map(store_key_to_shape_tag)
-        } else {
-            None
+    /// Like `transform`, but returns an error when the body doesn't exist.
+    pub fn try_transform(&self, body_tag: PhysicsRigidBodyTag) -> PhysicsResult<Isometry3<N>> {
+        self.with_rigid_body(body_tag, |body| {
+            body.body_transform()
+                .copied()
+                .ok_or(PhysicsError::WrongBodyType)
+        })?
+    }
+
+    /// Like `set_mode`, but returns an error when the body doesn't exist.
+    pub fn try_set_mode(&self, body_tag: PhysicsRigidBodyTag, mode: BodyMode) -> PhysicsResult<()> {
+        self.with_rigid_body(body_tag, |body| {
+            body.np_body
+                .set_status(body_mode_conversor::to_physics(mode))
+        })
+    }
+
+    /// Like `mode`, but returns an error when the body doesn't exist.
+    pub fn try_mode(&self, body_tag: PhysicsRigidBodyTag) -> PhysicsResult<BodyMode> {
+        self.with_rigid_body(body_tag, |body| {
+            body_mode_conversor::from_physics(body.np_body.status())
+        })
+    }
+
+    /// Like `set_linear_velocity`, but returns an error when the body doesn't exist.
+    pub fn try_set_linear_velocity(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+        velocity: &Vector3<N>,
+    ) -> PhysicsResult<()> {
+        self.with_rigid_body(body_tag, |body| {
+            body.rigid_body_mut()
+                .map(|rb_body| rb_body.set_linear_velocity(*velocity))
+                .ok_or(PhysicsError::WrongBodyType)
+        })?
+    }
+
+    /// Like `linear_velocity`, but returns an error when the body doesn't exist.
+    pub fn try_linear_velocity(&self, body_tag: PhysicsRigidBodyTag) -> PhysicsResult<Vector3<N>> {
+        self.with_rigid_body(body_tag, |body| {
+            body.rigid_body()
+                .map(|rb_body| rb_body.velocity().linear)
+                .ok_or(PhysicsError::WrongBodyType)
+        })?
+    }
+
+    /// Like `set_angular_velocity`, but returns an error when the body doesn't exist.
+    pub fn try_set_angular_velocity(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+        velocity: &Vector3<N>,
+    ) -> PhysicsResult<()> {
+        self.with_rigid_body(body_tag, |body| {
+            body.rigid_body_mut()
+                .map(|rb_body| rb_body.set_angular_velocity(*velocity))
+                .ok_or(PhysicsError::WrongBodyType)
+        })?
+    }
+
+    /// Like `angular_velocity`, but returns an error when the body doesn't exist.
+    pub fn try_angular_velocity(&self, body_tag: PhysicsRigidBodyTag) -> PhysicsResult<Vector3<N>> {
+        self.with_rigid_body(body_tag, |body| {
+            body.rigid_body()
+                .map(|rb_body| rb_body.velocity().angular)
+                .ok_or(PhysicsError::WrongBodyType)
+        })?
+    }
+
+    /// Like `apply_impulse`, but returns an error when the body doesn't exist.
+    pub fn try_apply_impulse(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+        impulse: &Vector3<N>,
+    ) -> PhysicsResult<()> {
+        self.with_rigid_body(body_tag, |body| {
+            body.np_body
+                .apply_force(0, &Force::linear(*impulse), ForceType::Impulse, true)
+        })
+    }
+
+    /// Like `contact_events`, but returns an error when the body doesn't exist.
+    pub fn try_contact_events(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+        out_contacts: &mut Vec<ContactEvent<N>>,
+    ) -> PhysicsResult<()> {
+        self.with_rigid_body(body_tag, |body| {
+            out_contacts.clear();
+            if let BodyData::Rigid { contacts, .. } = &body.body_data {
+                out_contacts.extend(contacts.iter().filter(|c| c.is_touching()).map(|c| c.event));
+            }
+        })
+    }
+}
+
+// Collision layers
+impl<N: PtReal> RBodyNpServer<N> {
+    /// Like `set_belong_to`, but the groups are resolved by name.
+    pub fn set_belong_to_layers(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+        names: &[&str],
+    ) -> PhysicsResult<()> {
+        let groups = self.storages.collision_layers_r().groups(names)?;
+        self.set_belong_to(body_tag, groups);
+        Ok(())
+    }
+
+    /// Like `set_collide_with`, but the groups are resolved by name.
+    pub fn set_collide_with_layers(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+        names: &[&str],
+    ) -> PhysicsResult<()> {
+        let groups = self.storages.collision_layers_r().groups(names)?;
+        self.set_collide_with(body_tag, groups);
+        Ok(())
+    }
+}
+
+impl<N> RBodyPhysicsServerTrait<N> for RBodyNpServer<N>
+where
+    N: PtReal,
+{
+    fn create(&self, body_desc: &RigidBodyDesc<N>) -> PhysicsHandle<PhysicsRigidBodyTag> {
+        let mut bodies_storage = self.storages.bodies_w();
+        let mut watch_contacts = self.storages.watch_contacts_w();
+        let handle = self.create_body(body_desc, &mut bodies_storage, &mut watch_contacts);
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::CreateBody(handle.get(), body_desc.clone()));
+        handle
+    }
+
+    fn set_entity(&self, body_tag: PhysicsRigidBodyTag, entity: Option<Entity>) {
+        self.checked_key(body_tag);
+        match self.try_set_entity(body_tag, entity) {
+            // Like the other setters, a missing body is reported only as a stale tag.
+            Err(PhysicsError::BodyNotFound) => {}
+            result => {
+                result.or_report(&self.storages);
+            }
         }
     }
 
+    fn entity(&self, body_tag: PhysicsRigidBodyTag) -> Option<Entity> {
+        let body_key = self.checked_key(body_tag);
+
+        self.storages
+            .bodies_info()
+            .get(body_key, |info| info.entity)
+            .flatten()
+    }
+
+    fn set_shape(&self, body_tag: PhysicsRigidBodyTag, shape_tag: Option<PhysicsShapeTag>) {
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::SetShape(body_tag, shape_tag));
+        self.swap_shape(body_tag, shape_tag, true);
+    }
+
+    fn shape(&self, body_tag: PhysicsRigidBodyTag) -> Option<PhysicsShapeTag> {
+        let body_key = self.checked_key(body_tag);
+
+        self.storages
+            .bodies_info()
+            .get(body_key, |info| info.shape_key)
+            .flatten()
+            .map(store_key_to_shape_tag)
+    }
+
     fn set_transform(&self, body_tag: PhysicsRigidBodyTag, transf: &Isometry3<N>) {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::Body(PhysicsCommand::SetTransform(body_tag, *transf)));
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
         if let Some(mut body) = body {
-            body.set_body_transform(transf);
+            if body
+                .set_body_transform(transf)
+                .or_report(&self.storages)
+                .is_some()
+            {
+                self.storages.track_static_move(&body);
+                bodies.mark_moved(body_key, &mut *body);
+            }
         }
     }
 
     fn transform(&self, body_tag: PhysicsRigidBodyTag) -> Isometry3<N> {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
-        if let Some(body) = body {
-            *body.body_transform()
-        } else {
-            Isometry3::identity()
-        }
+        body.and_then(|body| body.body_transform().copied())
+            .unwrap_or_else(Isometry3::identity)
     }
 
     fn set_mode(&self, body_tag: PhysicsRigidBodyTag, mode: BodyMode) {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::Body(PhysicsCommand::SetMode(body_tag, mode)));
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
@@ -331,14 +1072,14 @@ where
     }
 
     fn mode(&self, body_tag: PhysicsRigidBodyTag) -> BodyMode {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
         if let Some(body) = body {
             body_mode_conversor::from_physics(body.np_body.status())
         } else {
-            error!("Rigid Body not foud");
+            self.storages.diagnostics.report(PhysicsError::BodyNotFound);
             BodyMode::Disabled
         }
     }
@@ -347,71 +1088,80 @@ where
         unimplemented!("Make sure to have a sharable material instead");
     }
 
-    fn friction(&self, _body_tag: PhysicsRigidBodyTag) -> N {
-        unimplemented!();
+    fn friction(&self, body_tag: PhysicsRigidBodyTag) -> N {
+        let body_key = self.checked_key(body_tag);
+        let bodies = self.storages.bodies_r();
+
+        let body = bodies.get_body(body_key);
+        if let Some(body) = body {
+            body.friction
+        } else {
+            self.storages.diagnostics.report(PhysicsError::BodyNotFound);
+            zero()
+        }
     }
 
     fn set_bounciness(&self, _body_tag: PhysicsRigidBodyTag, _bounciness: N) {
         unimplemented!("Make sure to have a sharable material instead");
     }
 
-    fn bounciness(&self, _body_tag: PhysicsRigidBodyTag) -> N {
-        unimplemented!();
+    fn bounciness(&self, body_tag: PhysicsRigidBodyTag) -> N {
+        let body_key = self.checked_key(body_tag);
+        let bodies = self.storages.bodies_r();
+
+        let body = bodies.get_body(body_key);
+        if let Some(body) = body {
+            body.bounciness
+        } else {
+            self.storages.diagnostics.report(PhysicsError::BodyNotFound);
+            zero()
+        }
     }
 
     fn set_belong_to(&self, body_tag: PhysicsRigidBodyTag, groups: Vec<CollisionGroup>) {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
         if let Some(mut body) = body {
-            let (_, collide_with) =
-                collision_group_conversor::from_nphysics(&body.np_collision_groups);
-            body.np_collision_groups =
-                collision_group_conversor::to_nphysics(&groups, &collide_with);
+            body.set_belong_to(groups);
             self.update_collider_collision_groups(&body);
+            self.storages.sync_body_info(&*body);
         }
     }
 
     fn belong_to(&self, body_tag: PhysicsRigidBodyTag) -> Vec<CollisionGroup> {
-        let body_key = rigid_tag_to_store_key(body_tag);
-        let bodies = self.storages.bodies_r();
+        let body_key = self.checked_key(body_tag);
 
-        let body = bodies.get_body(body_key);
-        if let Some(body) = body {
-            collision_group_conversor::from_nphysics(&body.np_collision_groups).0
-        } else {
-            Vec::new()
-        }
+        self.storages
+            .bodies_info()
+            .get(body_key, |info| info.belong_to.clone())
+            .unwrap_or_default()
     }
 
     fn set_collide_with(&self, body_tag: PhysicsRigidBodyTag, groups: Vec<CollisionGroup>) {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
         if let Some(mut body) = body {
-            let (belong_to, _) =
-                collision_group_conversor::from_nphysics(&body.np_collision_groups);
-            body.np_collision_groups = collision_group_conversor::to_nphysics(&belong_to, &groups);
+            body.set_collide_with(groups);
             self.update_collider_collision_groups(&body);
+            self.storages.sync_body_info(&*body);
         }
     }
 
     fn collide_with(&self, body_tag: PhysicsRigidBodyTag) -> Vec<CollisionGroup> {
-        let body_key = rigid_tag_to_store_key(body_tag);
-        let bodies = self.storages.bodies_r();
+        let body_key = self.checked_key(body_tag);
 
-        let body = bodies.get_body(body_key);
-        if let Some(body) = body {
-            collision_group_conversor::from_nphysics(&body.np_collision_groups).1
-        } else {
-            Vec::new()
-        }
+        self.storages
+            .bodies_info()
+            .get(body_key, |info| info.collide_with.clone())
+            .unwrap_or_default()
     }
 
     fn set_lock_translation(&self, body_tag: PhysicsRigidBodyTag, axis: Vector3<bool>) {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
@@ -423,7 +1173,7 @@ where
     }
 
     fn lock_translation(&self, body_tag: PhysicsRigidBodyTag) -> Vector3<bool> {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
@@ -436,7 +1186,7 @@ where
     }
 
     fn set_lock_rotation(&self, body_tag: PhysicsRigidBodyTag, axis: Vector3<bool>) {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
@@ -448,7 +1198,7 @@ where
     }
 
     fn lock_rotation(&self, body_tag: PhysicsRigidBodyTag) -> Vector3<bool> {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
@@ -461,7 +1211,10 @@ where
     }
 
     fn clear_forces(&self, body_tag: PhysicsRigidBodyTag) {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::Body(PhysicsCommand::ClearForces(body_tag)));
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
@@ -471,7 +1224,10 @@ where
     }
 
     fn apply_force(&self, body_tag: PhysicsRigidBodyTag, force: &Vector3<N>) {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::Body(PhysicsCommand::ApplyForce(body_tag, *force)));
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
@@ -482,7 +1238,10 @@ where
     }
 
     fn apply_torque(&self, body_tag: PhysicsRigidBodyTag, force: &Vector3<N>) {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::Body(PhysicsCommand::ApplyTorque(body_tag, *force)));
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
@@ -498,7 +1257,12 @@ where
         force: &Vector3<N>,
         position: &Vector3<N>,
     ) {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
+        self.storages.replay_recorder.record(|| {
+            ReplayCall::Body(PhysicsCommand::ApplyForceAtPosition(
+                body_tag, *force, *position,
+            ))
+        });
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
@@ -514,7 +1278,10 @@ where
     }
 
     fn apply_impulse(&self, body_tag: PhysicsRigidBodyTag, impulse: &Vector3<N>) {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::Body(PhysicsCommand::ApplyImpulse(body_tag, *impulse)));
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
@@ -525,7 +1292,10 @@ where
     }
 
     fn apply_angular_impulse(&self, body_tag: PhysicsRigidBodyTag, impulse: &Vector3<N>) {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::Body(PhysicsCommand::ApplyAngularImpulse(body_tag, *impulse)));
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
@@ -541,7 +1311,12 @@ where
         impulse: &Vector3<N>,
         position: &Vector3<N>,
     ) {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
+        self.storages.replay_recorder.record(|| {
+            ReplayCall::Body(PhysicsCommand::ApplyImpulseAtPosition(
+                body_tag, *impulse, *position,
+            ))
+        });
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
@@ -557,7 +1332,10 @@ where
     }
 
     fn set_linear_velocity(&self, body_tag: PhysicsRigidBodyTag, velocity: &Vector3<N>) {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::Body(PhysicsCommand::SetLinearVelocity(body_tag, *velocity)));
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
@@ -565,13 +1343,15 @@ where
             if let Some(rb_body) = body.rigid_body_mut() {
                 rb_body.set_linear_velocity(*velocity);
             } else {
-                error!("The tag is not associated to any RigidBody");
+                self.storages
+                    .diagnostics
+                    .report(PhysicsError::WrongBodyType);
             }
         }
     }
 
     fn linear_velocity(&self, body_tag: PhysicsRigidBodyTag) -> Vector3<N> {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
@@ -579,14 +1359,19 @@ where
             if let Some(rb_body) = body.rigid_body() {
                 return rb_body.velocity().linear;
             } else {
-                error!("The tag is not associated to any RigidBody");
+                self.storages
+                    .diagnostics
+                    .report(PhysicsError::WrongBodyType);
             }
         }
         Vector3::zeros()
     }
 
     fn set_angular_velocity(&self, body_tag: PhysicsRigidBodyTag, velocity: &Vector3<N>) {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::Body(PhysicsCommand::SetAngularVelocity(body_tag, *velocity)));
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
@@ -594,13 +1379,15 @@ where
             if let Some(rb_body) = body.rigid_body_mut() {
                 rb_body.set_angular_velocity(*velocity);
             } else {
-                error!("The tag is not associated to any RigidBody");
+                self.storages
+                    .diagnostics
+                    .report(PhysicsError::WrongBodyType);
             }
         }
     }
 
     fn angular_velocity(&self, body_tag: PhysicsRigidBodyTag) -> Vector3<N> {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
@@ -608,7 +1395,9 @@ where
             if let Some(rb_body) = body.rigid_body() {
                 return rb_body.velocity().angular;
             } else {
-                error!("The tag is not associated to any RigidBody");
+                self.storages
+                    .diagnostics
+                    .report(PhysicsError::WrongBodyType);
             }
         }
         Vector3::zeros()
@@ -619,7 +1408,7 @@ where
         body_tag: PhysicsRigidBodyTag,
         position: &Vector3<N>,
     ) -> Vector3<N> {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
         let bodies = self.storages.bodies_r();
 
         let body = bodies.get_body(body_key);
@@ -627,14 +1416,16 @@ where
             if let Some(rb_body) = body.rigid_body() {
                 return rb_body.velocity().shift(&position).linear;
             } else {
-                error!("The tag is not associated to any RigidBody");
+                self.storages
+                    .diagnostics
+                    .report(PhysicsError::WrongBodyType);
             }
         }
         Vector3::zeros()
     }
 
     fn set_contacts_to_report(&self, body_tag: PhysicsRigidBodyTag, count: usize) {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
         let bodies = self.storages.bodies_r();
 
         let b = bodies.get_body(body_key);
@@ -650,7 +1441,7 @@ where
     }
 
     fn contacts_to_report(&self, body_tag: PhysicsRigidBodyTag) -> usize {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
         let bodies = self.storages.bodies_r();
 
         let b = bodies.get_body(body_key);
@@ -673,16 +1464,246 @@ where
         body_tag: PhysicsRigidBodyTag,
         out_contacts: &mut Vec<ContactEvent<N>>,
     ) {
-        let body_key = rigid_tag_to_store_key(body_tag);
+        let body_key = self.checked_key(body_tag);
         let bodies = self.storages.bodies_r();
 
         if let Some(body) = bodies.get_body(body_key) {
             if let BodyData::Rigid { contacts, .. } = &body.body_data {
-                out_contacts.resize_with(contacts.len(), ContactEvent::default);
-                out_contacts.copy_from_slice(contacts.as_slice());
+                out_contacts.clear();
+                out_contacts.extend(contacts.iter().filter(|c| c.is_touching()).map(|c| c.event));
                 return;
             }
         }
         out_contacts.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Vector3};
+    use amethyst_physics::{
+        objects::CollisionGroup,
+        servers::{
+            AreaDesc, AreaPhysicsServerTrait, BodyMode, RBodyPhysicsServerTrait, RigidBodyDesc,
+            ShapeDesc, ShapePhysicsServerTrait,
+        },
+    };
+
+    use crate::{conversors::*, error::PhysicsError, test_world::TestWorld};
+
+    #[test]
+    fn transforms_set_in_batch() {
+        let test = TestWorld::<f32>::new();
+        let a = test.bodies.create(&RigidBodyDesc::default());
+        let b = test.bodies.create(&RigidBodyDesc::default());
+        let untouched = test.bodies.create(&RigidBodyDesc::default());
+
+        test.bodies.set_transforms(&[
+            (a.get(), Isometry3::translation(1.0, 0.0, 0.0)),
+            (b.get(), Isometry3::translation(0.0, 2.0, 0.0)),
+        ]);
+
+        test.assert_position(a.get(), &Vector3::new(1.0, 0.0, 0.0), 0.0);
+        test.assert_position(b.get(), &Vector3::new(0.0, 2.0, 0.0), 0.0);
+        test.assert_position(untouched.get(), &Vector3::zeros(), 0.0);
+    }
+
+    #[test]
+    fn bodies_created_in_batch_in_order() {
+        let test = TestWorld::<f32>::new();
+        let descs: Vec<_> = [0.1, 0.2, 0.3]
+            .iter()
+            .map(|friction| {
+                let mut desc = RigidBodyDesc::default();
+                desc.friction = *friction;
+                desc
+            })
+            .collect();
+
+        let bodies = test.bodies.create_many(&descs);
+        let frictions: Vec<_> = bodies
+            .iter()
+            .map(|body| test.bodies.friction(body.get()))
+            .collect();
+        assert_eq!(frictions, vec![0.1, 0.2, 0.3]);
+    }
+
+    #[test]
+    fn contacts_read_without_copying() {
+        let test = TestWorld::<f32>::new();
+        let ground = test.create_ground();
+        let mut desc = RigidBodyDesc::default();
+        desc.contacts_to_report = 2;
+        let sphere = test.create_sphere_on_ground(&desc);
+        test.step_n(30);
+
+        let mut others = Vec::new();
+        test.bodies
+            .for_each_contact(sphere.get(), |contact| others.push(contact.other_body));
+        assert_eq!(others, vec![ground.get()]);
+
+        let mut events = Vec::new();
+        test.bodies.contact_events(sphere.get(), &mut events);
+        assert_eq!(events.len(), others.len());
+    }
+
+    #[test]
+    fn cached_collision_groups_applied() {
+        let test = TestWorld::<f32>::new();
+        let _ground = test.create_ground();
+        let sphere = test.create_sphere_on_ground(&RigidBodyDesc::default());
+
+        let (one, two) = (CollisionGroup::new(1), CollisionGroup::new(2));
+        test.bodies.set_belong_to(sphere.get(), vec![two, one, two]);
+        test.bodies.set_collide_with(sphere.get(), vec![two]);
+        assert_eq!(test.bodies.belong_to(sphere.get()), vec![one, two]);
+        assert_eq!(test.bodies.collide_with(sphere.get()), vec![two]);
+
+        // The ground is only in the first group, so the sphere falls through it.
+        test.step_n(60);
+        test.assert_fallen_below(sphere.get(), -1.0);
+    }
+
+    #[test]
+    fn similar_shape_swapped_while_sleeping() {
+        let test = TestWorld::<f32>::new();
+        let _ground = test.create_ground();
+        let sphere = test.create_sphere_on_ground(&RigidBodyDesc::default());
+        test.step_n(300);
+        test.assert_sleeping(sphere.get());
+
+        let bigger = test.shapes.create(&ShapeDesc::Sphere { radius: 0.55 });
+        test.bodies
+            .set_shape_keep_sleeping(sphere.get(), Some(bigger.get()));
+        assert_eq!(test.bodies.shape(sphere.get()), Some(bigger.get()));
+        test.step();
+        test.assert_sleeping(sphere.get());
+
+        // A shape of another kind replaces the collider, and wakes the body up.
+        let cube = test.shapes.create(&ShapeDesc::Cube {
+            half_extents: Vector3::repeat(0.5),
+        });
+        test.bodies
+            .set_shape_keep_sleeping(sphere.get(), Some(cube.get()));
+        assert_eq!(test.bodies.shape(sphere.get()), Some(cube.get()));
+        test.assert_awake(sphere.get());
+    }
+
+    #[test]
+    fn contacts_beyond_the_inline_capacity_reported() {
+        let test = TestWorld::<f32>::new();
+        let _ground = test.create_ground();
+
+        // Four walls touch the sphere, that also touches the ground.
+        let mut wall_desc = RigidBodyDesc::default();
+        wall_desc.mode = BodyMode::Static;
+        let wall_shape = ShapeDesc::Cube {
+            half_extents: Vector3::repeat(0.5),
+        };
+        let _walls: Vec<_> = [(1.0, 0.0), (-1.0, 0.0), (0.0, 1.0), (0.0, -1.0)]
+            .iter()
+            .map(|(x, z)| {
+                test.create_body(
+                    &wall_desc,
+                    &wall_shape,
+                    &Isometry3::translation(*x, 0.5, *z),
+                )
+            })
+            .collect();
+
+        let mut desc = RigidBodyDesc::default();
+        desc.contacts_to_report = 10;
+        let sphere = test.create_body(
+            &desc,
+            &ShapeDesc::Sphere { radius: 0.5 },
+            &Isometry3::translation(0.0, 0.5, 0.0),
+        );
+        test.step();
+
+        let mut contacts = Vec::new();
+        test.bodies.contact_events(sphere.get(), &mut contacts);
+        assert_eq!(contacts.len(), 5);
+    }
+
+    #[test]
+    fn contacts_watchers_tracked_once() {
+        let test = TestWorld::<f32>::new();
+        let watched = || test.storages.watch_contacts_r().len();
+
+        let mut desc = RigidBodyDesc::default();
+        desc.contacts_to_report = 1;
+        let a = test.bodies.create(&desc);
+        let b = test.bodies.create(&desc);
+        assert_eq!(watched(), 2);
+
+        test.bodies.set_contacts_to_report(a.get(), 3);
+        assert_eq!(watched(), 2);
+        test.bodies.set_contacts_to_report(a.get(), 0);
+        assert_eq!(watched(), 1);
+
+        drop(b);
+        test.step();
+        assert_eq!(watched(), 0);
+    }
+
+    #[test]
+    fn burst_spawned_with_the_shapes() {
+        let test = TestWorld::<f32>::new();
+        let shape = test.shapes.create(&ShapeDesc::Sphere { radius: 0.5 });
+
+        let spawns: Vec<_> = (0..20)
+            .map(|i| {
+                (
+                    RigidBodyDesc::default(),
+                    shape.get(),
+                    Isometry3::translation(i as f32 * 2.0, 5.0, 0.0),
+                )
+            })
+            .collect();
+        let bodies = test.bodies.create_many_with_shape(&spawns);
+        assert_eq!(test.storages.colliders_r().pending_insertions(), 20);
+
+        test.world.register_inserted_colliders();
+        assert_eq!(test.storages.colliders_r().pending_insertions(), 0);
+        for (i, body) in bodies.iter().enumerate() {
+            assert_eq!(test.bodies.shape(body.get()), Some(shape.get()));
+            test.assert_position(body.get(), &Vector3::new(i as f32 * 2.0, 5.0, 0.0), 0.0);
+        }
+    }
+
+    #[test]
+    fn fallible_functions_return_the_errors() {
+        let test = TestWorld::<f32>::new();
+        let body = test.bodies.create(&RigidBodyDesc::default());
+        let area = test.areas.create(&AreaDesc::default());
+        let area_as_body = store_key_to_rigid_tag(area_tag_to_store_key(area.get()));
+        let (destroyed_body, destroyed_shape) = {
+            let body = test.bodies.create(&RigidBodyDesc::default());
+            let shape = test.shapes.create(&ShapeDesc::Sphere { radius: 0.5 });
+            (body.get(), shape.get())
+        };
+        test.step();
+
+        assert_eq!(
+            test.bodies.try_transform(destroyed_body),
+            Err(PhysicsError::BodyNotFound)
+        );
+        assert_eq!(
+            test.bodies.try_mode(area_as_body),
+            Err(PhysicsError::WrongBodyType)
+        );
+        assert_eq!(
+            test.bodies.try_set_shape(body.get(), Some(destroyed_shape)),
+            Err(PhysicsError::ShapeMissing)
+        );
+        assert_eq!(
+            test.bodies
+                .try_set_linear_velocity(body.get(), &Vector3::new(1.0, 0.0, 0.0)),
+            Ok(())
+        );
+        assert_eq!(
+            test.bodies.try_linear_velocity(body.get()),
+            Ok(Vector3::new(1.0, 0.0, 0.0))
+        );
+    }
+}
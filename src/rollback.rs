@@ -0,0 +1,104 @@
+use std::collections::VecDeque;
+
+use amethyst_core::math::Isometry3;
+use amethyst_physics::PtReal;
+use nphysics3d::math::Velocity;
+
+use crate::storage::StoreKey;
+
+/// The state of a body, recorded after each step.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BodyState<N: PtReal> {
+    pub key: StoreKey,
+    pub position: Isometry3<N>,
+    pub velocity: Velocity<N>,
+    pub sleeping: bool,
+}
+
+/// Ring buffer that keeps the last `capacity` frames.
+///
+/// The most recent frame is the one at index 0.
+#[derive(Debug)]
+pub(crate) struct RollbackBuffer<F> {
+    frames: VecDeque<F>,
+    capacity: usize,
+}
+
+impl<F> RollbackBuffer<F> {
+    pub fn new(capacity: usize) -> Self {
+        RollbackBuffer {
+            frames: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Set the number of frames to keep; the oldest exceeding frames are dropped.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.frames.truncate(capacity);
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn push(&mut self, frame: F) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.frames.len() == self.capacity {
+            self.frames.pop_back();
+        }
+        self.frames.push_front(frame);
+    }
+
+    /// Drops the `n` most recent frames and returns the frame that becomes the most recent one.
+    ///
+    /// Returns `None`, without dropping anything, if there are not enough frames.
+    pub fn rewind(&mut self, n: usize) -> Option<&F> {
+        if n >= self.frames.len() {
+            return None;
+        }
+        self.frames.drain(..n);
+        self.frames.front()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+impl<F> Default for RollbackBuffer<F> {
+    fn default() -> Self {
+        RollbackBuffer::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RollbackBuffer;
+
+    #[test]
+    fn keeps_last_frames() {
+        let mut buffer = RollbackBuffer::new(3);
+        for i in 0..5 {
+            buffer.push(i);
+        }
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.rewind(0), Some(&4));
+        assert_eq!(buffer.rewind(2), Some(&2));
+        assert_eq!(buffer.len(), 1);
+        assert_eq!(buffer.rewind(1), None);
+    }
+
+    #[test]
+    fn disabled_when_empty_capacity() {
+        let mut buffer = RollbackBuffer::new(0);
+        buffer.push(1);
+        assert_eq!(buffer.len(), 0);
+    }
+}
@@ -1,13 +1,29 @@
-use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::{
+    backtrace::Backtrace,
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+    thread::{self, ThreadId},
+};
 
 use amethyst_physics::{objects::*, PtReal};
+use log::error;
 
 use crate::{
+    body::Body,
+    body_info::{BodiesInfo, BodyInfo},
     body_storage::BodyStorage,
     collider_storage::ColliderStorage,
+    collision_layers::CollisionLayers,
+    commands::CommandQueue,
+    diagnostics::ErrorDiagnostics,
     force_generator_storage::ForceGeneratorStorage,
     joint_storage::JointStorage,
+    replay::ReplayRecorder,
     shape::RigidShape,
+    statistics::ServersStorageUsage,
     storage::{Storage, StoreKey},
 };
 
@@ -25,8 +41,30 @@ pub type ForceGeneratorsStorageRead<'a, N> =
     RwLockReadGuard<'a, ForceGeneratorStorage<N, StoreKey>>;
 pub type ShapesStorageWrite<'a, N> = RwLockWriteGuard<'a, Storage<Box<RigidShape<N>>>>;
 pub type ShapesStorageRead<'a, N> = RwLockReadGuard<'a, Storage<Box<RigidShape<N>>>>;
-pub type WatchContactsWrite<'a> = RwLockWriteGuard<'a, Vec<StoreKey>>;
-pub type WatchContactsRead<'a> = RwLockReadGuard<'a, Vec<StoreKey>>;
+pub type WatchContactsWrite<'a> = RwLockWriteGuard<'a, HashSet<StoreKey>>;
+pub type WatchContactsRead<'a> = RwLockReadGuard<'a, HashSet<StoreKey>>;
+
+/// The expected number of objects of each storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageCapacities {
+    pub bodies: usize,
+    pub colliders: usize,
+    pub shapes: usize,
+    pub joints: usize,
+    pub force_generators: usize,
+}
+
+impl Default for StorageCapacities {
+    fn default() -> Self {
+        StorageCapacities {
+            bodies: 50,
+            colliders: 50,
+            shapes: 50,
+            joints: 15,
+            force_generators: 5,
+        }
+    }
+}
 
 /// This struct is responsible to hold all the storages
 ///
@@ -39,74 +77,129 @@ pub type WatchContactsRead<'a> = RwLockReadGuard<'a, Vec<StoreKey>>;
 /// The problem is that taking a mutable World using the `RwLock::write()` function make all others
 /// threads to wait the unlock of the world.
 ///
-/// Since each call like apply_force, or set_velocity, or set_friction need a mutable body that
-/// can be taken only if the world is mutable.
-/// Again to take the World mutable I have to use `RwLock::write()` that synchronize the execution.
+/// To avoid it, each object of the `Storage` has its own `Mutex`, so the calls like apply_force,
+/// or set_velocity, or set_friction take the `RwLock::read()` and lock only the object they
+/// change; the calls on different objects don't wait each other.
+/// The `RwLock::write()` is taken only to insert or remove the objects, and during the step,
+/// since NPhysics needs the full storages.
 ///
-/// A solution to this problem would be support add multithreading support on NPhysics
+/// To also avoid waiting the step, the systems that run in parallel can push their calls to the `CommandQueue`,
+/// that doesn't take any storage lock, and are applied at the beginning of the next step.
 #[allow(missing_debug_implementations)]
 pub struct ServersStorage<N: PtReal> {
-    pub(crate) gc: Arc<RwLock<PhysicsGarbageCollector>>,
+    /// Replaced when the storages are rebuilt, so the handles of the old objects don't drop the new
+    /// ones.
+    gc: Arc<RwLock<PhysicsGarbageCollector>>,
     bodies: RwLock<BodyStorage<N>>,
     colliders: RwLock<ColliderStorage<N, StoreKey>>,
     joints: RwLock<JointStorage<N, StoreKey>>,
     force_generators: RwLock<ForceGeneratorStorage<N, StoreKey>>,
     shapes: RwLock<Storage<Box<RigidShape<N>>>>,
-    watch_contacts: RwLock<Vec<StoreKey>>,
+    /// The rigid bodies that report the contacts.
+    watch_contacts: RwLock<HashSet<StoreKey>>,
+    /// The areas and the not dynamic bodies moved since the last step.
+    static_moves: RwLock<HashSet<StoreKey>>,
+    /// The rarely changed fields of the rigid bodies, readable while the step is running.
+    bodies_info: BodiesInfo,
+    /// The soft bodies to drop, since the `PhysicsGarbageCollector` only knows the
+    /// `amethyst_physics` objects.
+    soft_bodies_gc: RwLock<Vec<StoreKey>>,
+    /// When `true`, the servers log the use of the tags of dropped objects.
+    report_stale_tags: AtomicBool,
+    /// The names of the collision groups.
+    collision_layers: RwLock<CollisionLayers>,
+    pub(crate) command_queue: CommandQueue<N>,
+    pub(crate) diagnostics: ErrorDiagnostics,
+    pub(crate) replay_recorder: ReplayRecorder<N>,
+    /// The thread of the step started with `WorldNpServer::step_async`, until
+    /// `WorldNpServer::wait_step_done` is called.
+    async_step_thread: Mutex<Option<ThreadId>>,
 }
 
 impl<N: PtReal> ServersStorage<N> {
     pub fn new() -> ServersStorages<N> {
+        Self::with_capacities(StorageCapacities::default())
+    }
+
+    /// Create the storages reserving the memory for the expected number of objects.
+    ///
+    /// When a storage is full, it grows by the same amount.
+    pub fn with_capacities(capacities: StorageCapacities) -> ServersStorages<N> {
         Arc::new(ServersStorage {
             gc: Arc::new(RwLock::new(PhysicsGarbageCollector::default())),
-            bodies: RwLock::new(BodyStorage::default()),
-            colliders: RwLock::new(ColliderStorage::default()),
-            joints: RwLock::new(JointStorage::default()),
-            force_generators: RwLock::new(ForceGeneratorStorage::default()),
-            shapes: RwLock::new(Storage::new(50, 50)),
-            watch_contacts: RwLock::new(Vec::new()),
+            bodies: RwLock::new(BodyStorage::with_capacity(capacities.bodies)),
+            colliders: RwLock::new(ColliderStorage::with_capacity(capacities.colliders)),
+            joints: RwLock::new(JointStorage::with_capacity(capacities.joints)),
+            force_generators: RwLock::new(ForceGeneratorStorage::with_capacity(
+                capacities.force_generators,
+            )),
+            shapes: RwLock::new(Storage::new(capacities.shapes, capacities.shapes.max(1))),
+            watch_contacts: RwLock::new(HashSet::new()),
+            static_moves: RwLock::new(HashSet::new()),
+            bodies_info: BodiesInfo::new(),
+            soft_bodies_gc: RwLock::new(Vec::new()),
+            report_stale_tags: AtomicBool::new(false),
+            collision_layers: RwLock::new(CollisionLayers::default()),
+            command_queue: CommandQueue::new(),
+            diagnostics: ErrorDiagnostics::default(),
+            replay_recorder: ReplayRecorder::new(),
+            async_step_thread: Mutex::new(None),
         })
     }
 }
 
 impl<N: PtReal> ServersStorage<N> {
+    pub fn gc(&self) -> Arc<RwLock<PhysicsGarbageCollector>> {
+        self.gc.clone()
+    }
+
     pub fn bodies_w(&self) -> BodiesStorageWrite<'_, N> {
+        self.debug_assert_not_async_step();
         self.bodies.write().unwrap()
     }
 
     pub fn bodies_r(&self) -> BodiesStorageRead<'_, N> {
+        self.debug_assert_not_async_step();
         self.bodies.read().unwrap()
     }
 
     pub fn colliders_w(&self) -> CollidersStorageWrite<'_, N> {
+        self.debug_assert_not_async_step();
         self.colliders.write().unwrap()
     }
 
     pub fn colliders_r(&self) -> CollidersStorageRead<'_, N> {
+        self.debug_assert_not_async_step();
         self.colliders.read().unwrap()
     }
 
     pub fn joints_w(&self) -> JointsStorageWrite<'_, N> {
+        self.debug_assert_not_async_step();
         self.joints.write().unwrap()
     }
 
     pub fn joints_r(&self) -> JointsStorageRead<'_, N> {
+        self.debug_assert_not_async_step();
         self.joints.read().unwrap()
     }
 
     pub fn force_generator_w(&self) -> ForceGeneratorsStorageWrite<'_, N> {
+        self.debug_assert_not_async_step();
         self.force_generators.write().unwrap()
     }
 
     pub fn force_generator_r(&self) -> ForceGeneratorsStorageRead<'_, N> {
+        self.debug_assert_not_async_step();
         self.force_generators.read().unwrap()
     }
 
     pub fn shapes_w(&self) -> ShapesStorageWrite<'_, N> {
+        self.debug_assert_not_async_step();
         self.shapes.write().unwrap()
     }
 
     pub fn shapes_r(&self) -> ShapesStorageRead<'_, N> {
+        self.debug_assert_not_async_step();
         self.shapes.read().unwrap()
     }
 
@@ -117,4 +210,174 @@ impl<N: PtReal> ServersStorage<N> {
     pub fn watch_contacts_r(&self) -> WatchContactsRead<'_> {
         self.watch_contacts.read().unwrap()
     }
+
+    pub fn static_moves_w(&self) -> RwLockWriteGuard<'_, HashSet<StoreKey>> {
+        self.static_moves.write().unwrap()
+    }
+
+    /// Remembers that the body was moved, when it's an area or a static or kinematic body, so the
+    /// next step wakes up the bodies around it.
+    pub fn track_static_move(&self, body: &Body<N>) {
+        if let Some(key) = body.self_key {
+            if !body.np_body.is_dynamic() {
+                self.static_moves_w().insert(key);
+            }
+        }
+    }
+
+    pub fn bodies_info(&self) -> &BodiesInfo {
+        &self.bodies_info
+    }
+
+    /// Copies the rarely changed fields of the rigid body to the side table.
+    ///
+    /// Must be called each time the `entity`, the `shape_key` or the collision groups change.
+    pub fn sync_body_info(&self, body: &Body<N>) {
+        if let Some(key) = body.self_key {
+            self.bodies_info.insert(key, BodyInfo::new(body));
+        }
+    }
+
+    /// When `true`, each time a server receives the tag of a dropped object, an error is logged
+    /// with the backtrace of the call site.
+    ///
+    /// Using a tag after the object is dropped does nothing, so this is useful to find where the
+    /// game keeps the tags of the dropped objects. Capturing the backtrace is slow, so this is
+    /// meant only for debugging.
+    pub fn set_report_stale_tags(&self, report: bool) {
+        self.report_stale_tags.store(report, Ordering::Relaxed);
+    }
+
+    pub fn report_stale_tags(&self) -> bool {
+        self.report_stale_tags.load(Ordering::Relaxed)
+    }
+
+    /// Logs the use of a tag of a dropped object, with the backtrace of the call site.
+    pub fn report_stale_tag(&self, object_type: &str) {
+        error!(
+            "The {} tag refers to an object that doesn't exist; it was dropped or the tag is of another world.\n{}",
+            object_type,
+            Backtrace::force_capture()
+        );
+    }
+
+    pub(crate) fn set_async_step_thread(&self, thread: Option<ThreadId>) {
+        *self.async_step_thread.lock().unwrap() = thread;
+    }
+
+    /// Panics, in the debug builds, when the storages are used by another thread while the step
+    /// started with `WorldNpServer::step_async` is running.
+    ///
+    /// The step takes the storages in an unpredictable moment, so these changes would be applied
+    /// randomly before or after it.
+    fn debug_assert_not_async_step(&self) {
+        debug_assert!(
+            self.async_step_thread
+                .lock()
+                .unwrap()
+                .map_or(true, |step_thread| step_thread == thread::current().id()),
+            "The servers can't be used during the async step; call `wait_step_done` first."
+        );
+    }
+
+    pub fn soft_bodies_gc_w(&self) -> RwLockWriteGuard<'_, Vec<StoreKey>> {
+        self.soft_bodies_gc.write().unwrap()
+    }
+
+    pub fn soft_bodies_gc_r(&self) -> RwLockReadGuard<'_, Vec<StoreKey>> {
+        self.soft_bodies_gc.read().unwrap()
+    }
+
+    pub fn collision_layers_w(&self) -> RwLockWriteGuard<'_, CollisionLayers> {
+        self.collision_layers.write().unwrap()
+    }
+
+    pub fn collision_layers_r(&self) -> RwLockReadGuard<'_, CollisionLayers> {
+        self.collision_layers.read().unwrap()
+    }
+
+    /// Releases the memory of the storages, that must be empty, keeping the passed capacities.
+    ///
+    /// The keys of the new objects never match the ones of the dropped objects, so the stale tags
+    /// still refer to nothing.
+    pub(crate) fn shrink_to(&self, capacities: StorageCapacities) {
+        self.bodies_w().shrink_to(capacities.bodies);
+        self.colliders_w().shrink_to(capacities.colliders);
+        self.joints_w().shrink_to(capacities.joints);
+        self.force_generator_w()
+            .shrink_to(capacities.force_generators);
+        self.shapes_w().shrink_to(capacities.shapes);
+    }
+
+    /// Returns the number of stored objects and the capacity of each storage.
+    ///
+    /// Useful to monitor the memory growth of long running servers.
+    pub fn usage(&self) -> ServersStorageUsage {
+        ServersStorageUsage {
+            bodies: self.bodies_r().usage(),
+            colliders: self.colliders_r().usage(),
+            shapes: self.shapes_r().usage(),
+            joints: self.joints_r().usage(),
+            force_generators: self.force_generator_r().usage(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::Isometry3;
+    use amethyst_physics::servers::{
+        AreaDesc, AreaPhysicsServerTrait, JointDesc, JointPhysicsServerTrait, JointPosition,
+        RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc, ShapePhysicsServerTrait,
+    };
+
+    use super::{ServersStorage, StorageCapacities};
+    use crate::test_world::TestWorld;
+
+    #[test]
+    fn storages_created_with_the_capacities() {
+        let storages = ServersStorage::<f32>::with_capacities(StorageCapacities {
+            bodies: 100,
+            colliders: 80,
+            shapes: 10,
+            joints: 5,
+            force_generators: 1,
+        });
+
+        let usage = storages.usage();
+        assert_eq!(usage.bodies.capacity, 100);
+        assert_eq!(usage.colliders.capacity, 80);
+        assert_eq!(usage.shapes.capacity, 10);
+        assert_eq!(usage.joints.capacity, 5);
+        assert_eq!(usage.force_generators.capacity, 1);
+        assert_eq!(usage.bodies.len, 0);
+    }
+
+    #[test]
+    fn stale_tags_detected() {
+        let test = TestWorld::<f32>::new();
+        test.storages.set_report_stale_tags(true);
+        assert!(test.storages.report_stale_tags());
+
+        let body = test.bodies.create(&RigidBodyDesc::default());
+        let area = test.areas.create(&AreaDesc::default());
+        let shape = test.shapes.create(&ShapeDesc::Sphere { radius: 0.5 });
+        let joint = test.joints.create(&JointDesc::Fixed, JointPosition::Middle);
+        let tags = (body.get(), area.get(), shape.get(), joint.get());
+        assert!(test.bodies.is_valid(tags.0));
+        assert!(test.areas.is_valid(tags.1));
+        assert!(test.shapes.is_valid(tags.2));
+        assert!(test.joints.is_valid(tags.3));
+
+        drop((body, area, shape, joint));
+        test.step();
+        assert!(!test.bodies.is_valid(tags.0));
+        assert!(!test.areas.is_valid(tags.1));
+        assert!(!test.shapes.is_valid(tags.2));
+        assert!(!test.joints.is_valid(tags.3));
+
+        // The stale tags are only reported, the calls don't panic.
+        test.bodies.set_transform(tags.0, &Isometry3::identity());
+        assert!(test.areas.overlap_events(tags.1).is_empty());
+    }
 }
@@ -1,18 +1,31 @@
-use amethyst_core::math::{convert, Unit, Vector3};
+use std::{
+    borrow::Cow,
+    mem::{self, Discriminant},
+};
+
+use amethyst_core::math::{convert, Isometry3, Point3, Unit, Vector3};
 use amethyst_physics::{servers::ShapeDesc, PtReal};
 use ncollide3d::shape::{
     Ball as NcBall, Capsule as NcCapsule, Compound as NcCompound, ConvexHull as NcConvexHull,
     Cuboid as NcCuboid, Plane as NcPlane, ShapeHandle as NcShapeHandle, TriMesh as NcTriMesh,
 };
 
-use crate::storage::StoreKey;
+use crate::{
+    error::{PhysicsError, PhysicsResult},
+    storage::StoreKey,
+};
 
 #[allow(missing_debug_implementations)]
 pub struct RigidShape<N: PtReal> {
     pub self_key: Option<StoreKey>,
-    shape_desc: ShapeDesc<N>,
+    /// `None` for the `TriMesh` shapes, since their points are already stored by the shape
+    /// handle; the `Convex` shapes keep the passed points, since the hull drops the inner ones.
+    shape_desc: Option<ShapeDesc<N>>,
+    kind: Discriminant<ShapeDesc<N>>,
     shape_handle: NcShapeHandle<N>,
     bodies: Vec<StoreKey>,
+    /// The bodies that use this shape as LOD shape.
+    lod_bodies: Vec<StoreKey>,
     /// This is used to know if the shape will be soon dropped since no one own it anymore.
     ///
     /// When the shape is no more owned but still in use by a rigid body or an area is safer not delete it.
@@ -25,18 +38,56 @@ impl<N: PtReal> RigidShape<N> {
     pub fn new(shape_desc: &ShapeDesc<N>) -> Self {
         RigidShape {
             self_key: None,
-            shape_desc: shape_desc.clone(), // Consider to not save this in order to save memory when Convex and TriMeshes are used.
+            shape_desc: RigidShape::stored_desc(shape_desc),
+            kind: mem::discriminant(shape_desc),
             shape_handle: RigidShape::generate_handle(shape_desc),
             bodies: Vec::new(),
+            lod_bodies: Vec::new(),
+            marked_for_drop: false,
+        }
+    }
+
+    /// Like `new`, but the points of the `TriMesh` are moved to the shape handle rather than
+    /// copied.
+    pub fn new_owned(shape_desc: ShapeDesc<N>) -> Self {
+        RigidShape {
+            self_key: None,
+            shape_desc: RigidShape::stored_desc(&shape_desc),
+            kind: mem::discriminant(&shape_desc),
+            shape_handle: RigidShape::generate_owned_handle(shape_desc),
+            bodies: Vec::new(),
+            lod_bodies: Vec::new(),
             marked_for_drop: false,
         }
     }
 
     pub fn update(&mut self, shape_desc: &ShapeDesc<N>) {
-        self.shape_desc = shape_desc.clone();
+        self.shape_desc = RigidShape::stored_desc(shape_desc);
+        self.kind = mem::discriminant(shape_desc);
         self.shape_handle = RigidShape::generate_handle(shape_desc);
     }
 
+    /// Returns the description of the shape.
+    ///
+    /// The points of the `TriMesh` shapes are read back from the shape handle.
+    pub fn shape_desc(&self) -> Cow<'_, ShapeDesc<N>> {
+        if let Some(shape_desc) = &self.shape_desc {
+            Cow::Borrowed(shape_desc)
+        } else if let Some(mesh) = self.shape_handle.as_shape::<NcTriMesh<N>>() {
+            Cow::Owned(ShapeDesc::TriMesh {
+                points: mesh.points().to_vec(),
+                indices: mesh.faces().iter().map(|face| face.indices).collect(),
+            })
+        } else {
+            unreachable!("Only the `TriMesh` shapes don't store the description.");
+        }
+    }
+
+    /// Returns the kind of the shape, like `Sphere` or `TriMesh`.
+    pub fn kind(&self) -> Discriminant<ShapeDesc<N>> {
+        self.kind
+    }
+
     pub fn shape_handle(&self) -> &NcShapeHandle<N> {
         &self.shape_handle
     }
@@ -53,15 +104,110 @@ impl<N: PtReal> RigidShape<N> {
         &self.bodies
     }
 
-    pub fn is_concave(&self) -> bool {
-        match &self.shape_desc {
-            ShapeDesc::TriMesh { .. } => true,
-            _ => false,
+    pub fn register_lod_body(&mut self, body: StoreKey) {
+        self.lod_bodies.push(body);
+    }
+
+    pub fn unregister_lod_body(&mut self, body: StoreKey) {
+        self.lod_bodies.retain(|&b| b != body);
+    }
+
+    pub fn lod_bodies(&self) -> &Vec<StoreKey> {
+        &self.lod_bodies
+    }
+
+    /// Moves the vertices of the `TriMesh` shape, keeping its faces.
+    ///
+    /// The `TriMesh` of ncollide can't refit its BVT, so the whole mesh, with its BVT, is
+    /// rebuilt; only the changed vertices are copied from the passed slice.
+    pub fn set_trimesh_vertices(&mut self, vertices: &[(usize, Point3<N>)]) -> PhysicsResult<()> {
+        let mesh = self
+            .shape_handle
+            .as_shape::<NcTriMesh<N>>()
+            .ok_or(PhysicsError::WrongShapeType)?;
+        let mut points = mesh.points().to_vec();
+        if vertices.iter().any(|(i, _)| *i >= points.len()) {
+            return Err(PhysicsError::VertexNotFound);
+        }
+        for (i, point) in vertices {
+            points[*i] = *point;
+        }
+        let indices = mesh.faces().iter().map(|face| face.indices).collect();
+        self.shape_handle = NcShapeHandle::new(NcTriMesh::new(points, indices, None));
+        Ok(())
+    }
+
+    pub fn add_compound_child(
+        &mut self,
+        transform: &Isometry3<N>,
+        shape_desc: &ShapeDesc<N>,
+    ) -> PhysicsResult<usize> {
+        let (shapes, mut children) = self.compound_parts()?;
+        shapes.push((*transform, shape_desc.clone()));
+        children.push((*transform, RigidShape::generate_handle(shape_desc)));
+        let index = shapes.len() - 1;
+        self.shape_handle = NcShapeHandle::new(NcCompound::new(children));
+        Ok(index)
+    }
+
+    pub fn remove_compound_child(
+        &mut self,
+        index: usize,
+    ) -> PhysicsResult<(Isometry3<N>, ShapeDesc<N>)> {
+        let (shapes, mut children) = self.compound_parts()?;
+        if index >= shapes.len() {
+            return Err(PhysicsError::ChildNotFound);
+        }
+        if shapes.len() == 1 {
+            return Err(PhysicsError::LastChild);
+        }
+        children.remove(index);
+        let child = shapes.remove(index);
+        self.shape_handle = NcShapeHandle::new(NcCompound::new(children));
+        Ok(child)
+    }
+
+    /// Returns the children descriptions and a copy of the children shapes of the `Compound`,
+    /// so the shapes of the unchanged children are not created again.
+    #[allow(clippy::type_complexity)]
+    fn compound_parts(
+        &mut self,
+    ) -> PhysicsResult<(
+        &mut Vec<(Isometry3<N>, ShapeDesc<N>)>,
+        Vec<(Isometry3<N>, NcShapeHandle<N>)>,
+    )> {
+        let children = self
+            .shape_handle
+            .as_shape::<NcCompound<N>>()
+            .map(|compound| compound.shapes().to_vec());
+        match (&mut self.shape_desc, children) {
+            (Some(ShapeDesc::Compound { shapes }), Some(children)) => Ok((shapes, children)),
+            _ => Err(PhysicsError::WrongShapeType),
         }
     }
+
+    pub fn is_concave(&self) -> bool {
+        self.shape_handle.as_shape::<NcTriMesh<N>>().is_some()
+    }
 }
 
 impl<N: PtReal> RigidShape<N> {
+    fn stored_desc(shape_desc: &ShapeDesc<N>) -> Option<ShapeDesc<N>> {
+        match shape_desc {
+            ShapeDesc::TriMesh { .. } => None,
+            _ => Some(shape_desc.clone()),
+        }
+    }
+
+    fn generate_owned_handle(shape_desc: ShapeDesc<N>) -> NcShapeHandle<N> {
+        match shape_desc {
+            ShapeDesc::TriMesh { points, indices } => {
+                NcShapeHandle::new(NcTriMesh::new(points, indices, None))
+            }
+            shape_desc => RigidShape::generate_handle(&shape_desc),
+        }
+    }
+
     fn generate_handle(shape_desc: &ShapeDesc<N>) -> NcShapeHandle<N> {
         match shape_desc {
             ShapeDesc::Sphere { radius } => NcShapeHandle::new(NcBall::new(*radius)),
@@ -0,0 +1,225 @@
+use amethyst_core::math::{
+    convert, try_convert, Isometry3, Point3, Quaternion, Translation3, UnitQuaternion, Vector3,
+};
+use amethyst_physics::{servers::ShapeDesc, PtReal};
+
+use crate::error::{PhysicsError, PhysicsResult};
+
+/// The first bytes of a shape asset.
+const MAGIC: &[u8; 4] = b"NPSA";
+/// The version of the format, increased when it changes.
+const VERSION: u8 = 1;
+
+const SPHERE: u8 = 0;
+const CUBE: u8 = 1;
+const CAPSULE: u8 = 2;
+const CYLINDER: u8 = 3;
+const PLANE: u8 = 4;
+const CONVEX: u8 = 5;
+const TRI_MESH: u8 = 6;
+const COMPOUND: u8 = 7;
+
+/// Encodes the shape description in the binary shape asset format.
+///
+/// The format is compact and fast to load, compared to a text format like RON, so it fits the
+/// big `TriMesh` and `Convex` shapes: the reals are stored as little endian `f32` and the indices
+/// as little endian `u32`, so a mesh is read back with a single precision.
+pub fn encode_shape_asset<N: PtReal>(shape_desc: &ShapeDesc<N>) -> Vec<u8> {
+    let mut writer = Writer(Vec::new());
+    writer.0.extend_from_slice(MAGIC);
+    writer.0.push(VERSION);
+    writer.shape(shape_desc);
+    writer.0
+}
+
+/// Decodes a shape description encoded by `encode_shape_asset`.
+///
+/// Fails with `InvalidShapeAsset` when the bytes are not a shape asset of this version.
+pub fn decode_shape_asset<N: PtReal>(bytes: &[u8]) -> PhysicsResult<ShapeDesc<N>> {
+    let mut reader = Reader(bytes);
+    if reader.take(MAGIC.len())? != MAGIC || reader.u8()? != VERSION {
+        return Err(PhysicsError::InvalidShapeAsset);
+    }
+    let shape_desc = reader.shape()?;
+    if !reader.0.is_empty() {
+        return Err(PhysicsError::InvalidShapeAsset);
+    }
+    Ok(shape_desc)
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u32(&mut self, v: usize) {
+        self.0.extend_from_slice(&(v as u32).to_le_bytes());
+    }
+
+    fn real<N: PtReal>(&mut self, v: N) {
+        let v = try_convert::<N, f64>(v).unwrap_or_default() as f32;
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn reals<N: PtReal>(&mut self, v: &[N]) {
+        v.iter().for_each(|v| self.real(*v));
+    }
+
+    fn isometry<N: PtReal>(&mut self, iso: &Isometry3<N>) {
+        let q = iso.rotation.quaternion();
+        self.reals(iso.translation.vector.as_slice());
+        self.reals(&[q.i, q.j, q.k, q.w]);
+    }
+
+    fn shape<N: PtReal>(&mut self, shape_desc: &ShapeDesc<N>) {
+        match shape_desc {
+            ShapeDesc::Sphere { radius } => {
+                self.0.push(SPHERE);
+                self.real(*radius);
+            }
+            ShapeDesc::Cube { half_extents } => {
+                self.0.push(CUBE);
+                self.reals(half_extents.as_slice());
+            }
+            ShapeDesc::Capsule {
+                half_height,
+                radius,
+            } => {
+                self.0.push(CAPSULE);
+                self.reals(&[*half_height, *radius]);
+            }
+            ShapeDesc::Cylinder {
+                half_height,
+                radius,
+            } => {
+                self.0.push(CYLINDER);
+                self.reals(&[*half_height, *radius]);
+            }
+            ShapeDesc::Plane => self.0.push(PLANE),
+            ShapeDesc::Convex { points } => {
+                self.0.push(CONVEX);
+                self.u32(points.len());
+                points.iter().for_each(|p| self.reals(p.coords.as_slice()));
+            }
+            ShapeDesc::TriMesh { points, indices } => {
+                self.0.push(TRI_MESH);
+                self.u32(points.len());
+                points.iter().for_each(|p| self.reals(p.coords.as_slice()));
+                self.u32(indices.len());
+                indices
+                    .iter()
+                    .flat_map(|i| i.coords.iter())
+                    .for_each(|i| self.u32(*i));
+            }
+            ShapeDesc::Compound { shapes } => {
+                self.0.push(COMPOUND);
+                self.u32(shapes.len());
+                for (iso, shape) in shapes {
+                    self.isometry(iso);
+                    self.shape(shape);
+                }
+            }
+        }
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> PhysicsResult<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(PhysicsError::InvalidShapeAsset);
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> PhysicsResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> PhysicsResult<usize> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes) as usize)
+    }
+
+    /// Reads the length of a list of items of at least `item_size` bytes, failing early if the
+    /// asset is too short, so a corrupted length doesn't allocate a huge vector.
+    fn list_len(&mut self, item_size: usize) -> PhysicsResult<usize> {
+        let len = self.u32()?;
+        if self.0.len() / item_size < len {
+            return Err(PhysicsError::InvalidShapeAsset);
+        }
+        Ok(len)
+    }
+
+    fn real<N: PtReal>(&mut self) -> PhysicsResult<N> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(convert(f64::from(f32::from_le_bytes(bytes))))
+    }
+
+    fn vector<N: PtReal>(&mut self) -> PhysicsResult<Vector3<N>> {
+        Ok(Vector3::new(self.real()?, self.real()?, self.real()?))
+    }
+
+    fn points<N: PtReal>(&mut self) -> PhysicsResult<Vec<Point3<N>>> {
+        let len = self.list_len(12)?;
+        (0..len).map(|_| self.vector().map(Point3::from)).collect()
+    }
+
+    fn isometry<N: PtReal>(&mut self) -> PhysicsResult<Isometry3<N>> {
+        let translation = Translation3::from(self.vector()?);
+        let (i, j, k, w) = (self.real()?, self.real()?, self.real()?, self.real()?);
+        let rotation = UnitQuaternion::new_normalize(Quaternion::new(w, i, j, k));
+        Ok(Isometry3::from_parts(translation, rotation))
+    }
+
+    fn shape<N: PtReal>(&mut self) -> PhysicsResult<ShapeDesc<N>> {
+        Ok(match self.u8()? {
+            SPHERE => ShapeDesc::Sphere {
+                radius: self.real()?,
+            },
+            CUBE => ShapeDesc::Cube {
+                half_extents: self.vector()?,
+            },
+            CAPSULE => ShapeDesc::Capsule {
+                half_height: self.real()?,
+                radius: self.real()?,
+            },
+            CYLINDER => ShapeDesc::Cylinder {
+                half_height: self.real()?,
+                radius: self.real()?,
+            },
+            PLANE => ShapeDesc::Plane,
+            CONVEX => ShapeDesc::Convex {
+                points: self.points()?,
+            },
+            TRI_MESH => {
+                let points = self.points()?;
+                let len = self.list_len(12)?;
+                let indices = (0..len)
+                    .map(|_| -> PhysicsResult<_> {
+                        Ok(Point3::new(self.u32()?, self.u32()?, self.u32()?))
+                    })
+                    .collect::<PhysicsResult<Vec<_>>>()?;
+                if indices
+                    .iter()
+                    .flat_map(|i| i.iter())
+                    .any(|i| *i >= points.len())
+                {
+                    return Err(PhysicsError::InvalidShapeAsset);
+                }
+                ShapeDesc::TriMesh { points, indices }
+            }
+            COMPOUND => {
+                let len = self.list_len(29)?;
+                let shapes = (0..len)
+                    .map(|_| -> PhysicsResult<_> { Ok((self.isometry()?, self.shape()?)) })
+                    .collect::<PhysicsResult<Vec<_>>>()?;
+                ShapeDesc::Compound { shapes }
+            }
+            _ => return Err(PhysicsError::InvalidShapeAsset),
+        })
+    }
+}
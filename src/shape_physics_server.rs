@@ -1,3 +1,4 @@
+use amethyst_core::math::{Isometry3, Point3, Vector3};
 use amethyst_physics::{
     objects::*,
     servers::{ShapeDesc, ShapePhysicsServerTrait},
@@ -6,11 +7,20 @@ use amethyst_physics::{
 use log::error;
 
 use crate::{
-    area_physics_server::AreaNpServer, body::BodyData, conversors::*,
-    rigid_body_physics_server::RBodyNpServer, servers_storage::*, shape::RigidShape,
+    area_physics_server::AreaNpServer,
+    body::BodyData,
+    conversors::*,
+    error::{PhysicsError, PhysicsResult},
+    replay::ReplayCall,
+    rigid_body_physics_server::RBodyNpServer,
+    servers_storage::*,
+    shape::RigidShape,
+    shape_asset::{decode_shape_asset, encode_shape_asset},
+    shape_scale::scale_shape_desc,
     storage::StoreKey,
 };
 
+#[allow(missing_debug_implementations)]
 pub struct ShapeNpServer<N: PtReal> {
     storages: ServersStorages<N>,
 }
@@ -33,7 +43,7 @@ impl<N: PtReal> ShapeNpServer<N> {
             if let Some(mut shape) = shapes_storage.get(shape_key) {
                 if !shape.marked_for_drop {
                     shape.marked_for_drop = true;
-                    fail!("A shape is marked for drop while still in use. Consider to store the PhysicsHandle<PhysicsShapeTag> to not waste resources.", false);
+                    error!("A shape is marked for drop while still in use. Consider to store the PhysicsHandle<PhysicsShapeTag> to not waste resources.");
                 }
             }
             false
@@ -51,43 +61,270 @@ impl<N: PtReal> ShapeNpServer<N> {
         shapes_storage: &mut ShapesStorageWrite<'_, N>,
     ) -> bool {
         if let Some(shape) = shapes_storage.get(shape_key) {
-            if shape.bodies().is_empty() {
-                return true;
-            }
+            !shape.bodies().is_empty() || !shape.lod_bodies().is_empty()
+        } else {
+            false
         }
-        false
     }
-}
-
-impl<N: PtReal> ShapePhysicsServerTrait<N> for ShapeNpServer<N> {
-    fn create(&self, shape_desc: &ShapeDesc<N>) -> PhysicsHandle<PhysicsShapeTag> {
-        let shape = Box::new(RigidShape::new(shape_desc));
 
+    fn insert_shape(&self, shape: RigidShape<N>) -> PhysicsHandle<PhysicsShapeTag> {
         let mut shapes_storage = self.storages.shapes_w();
-        let shape_key = shapes_storage.insert(shape);
+        let shape_key = shapes_storage.insert(Box::new(shape));
 
         let mut shape = shapes_storage.get(shape_key).unwrap();
         shape.self_key = Some(shape_key);
 
-        PhysicsHandle::new(store_key_to_shape_tag(shape_key), self.storages.gc.clone())
+        PhysicsHandle::new(store_key_to_shape_tag(shape_key), self.storages.gc())
+    }
+}
+
+// Owned creation
+impl<N: PtReal> ShapeNpServer<N> {
+    /// Creates a shape taking the ownership of the description.
+    ///
+    /// The `TriMesh` points and indices are moved into the shape, rather than copied like
+    /// `create` does; useful to create big meshes.
+    pub fn create_owned(&self, shape_desc: ShapeDesc<N>) -> PhysicsHandle<PhysicsShapeTag> {
+        let handle = self.insert_shape(RigidShape::new_owned(shape_desc));
+        self.storages.replay_recorder.record(|| {
+            let shapes = self.storages.shapes_r();
+            let shape = shapes.get(shape_tag_to_store_key(handle.get())).unwrap();
+            ReplayCall::CreateShape(handle.get(), shape.shape_desc().into_owned())
+        });
+        handle
+    }
+}
+
+// Shape assets
+impl<N: PtReal> ShapeNpServer<N> {
+    /// Creates a shape from the bytes of a shape asset, see `encode_shape_asset`.
+    ///
+    /// The description is moved into the shape like `create_owned` does.
+    pub fn create_from_asset(&self, bytes: &[u8]) -> PhysicsResult<PhysicsHandle<PhysicsShapeTag>> {
+        Ok(self.create_owned(decode_shape_asset(bytes)?))
+    }
+
+    /// Encodes the shape as a shape asset; useful to bake the meshes at build time.
+    pub fn shape_asset(&self, shape_tag: PhysicsShapeTag) -> PhysicsResult<Vec<u8>> {
+        let shapes = self.storages.shapes_r();
+        let shape = shapes
+            .get(shape_tag_to_store_key(shape_tag))
+            .ok_or(PhysicsError::ShapeMissing)?;
+        Ok(encode_shape_asset(&shape.shape_desc()))
+    }
+}
+
+// Scaled shapes
+impl<N: PtReal> ShapeNpServer<N> {
+    /// Creates a copy of the shape scaled by `scale`; see `scale_shape_desc`.
+    pub fn create_scaled(
+        &self,
+        shape_tag: PhysicsShapeTag,
+        scale: &Vector3<N>,
+    ) -> PhysicsResult<PhysicsHandle<PhysicsShapeTag>> {
+        let shape_desc = {
+            let shapes = self.storages.shapes_r();
+            let shape = shapes
+                .get(shape_tag_to_store_key(shape_tag))
+                .ok_or(PhysicsError::ShapeMissing)?;
+            scale_shape_desc(&shape.shape_desc(), scale)
+        };
+        Ok(self.create_owned(shape_desc))
+    }
+}
+
+// TriMesh deformation
+impl<N: PtReal> ShapeNpServer<N> {
+    /// Moves some vertices of the `TriMesh` shape, each passed with its index; e.g. to follow a
+    /// destructible or an animated mesh.
+    ///
+    /// The colliders that use the shape are updated in place, like `update` does when the kind of
+    /// the shape doesn't change, so they keep their broad phase proxies and contacts.
+    /// The BVT of the mesh is not refitted but rebuilt, since the one of ncollide can't be
+    /// changed; so the cost grows with the size of the mesh, not with the moved vertices.
+    /// Fails with `WrongShapeType` if the shape is not a `TriMesh`, and with `VertexNotFound` if an
+    /// index is out of range; in this case nothing is changed.
+    pub fn update_trimesh_vertices(
+        &self,
+        shape_tag: PhysicsShapeTag,
+        vertices: &[(usize, Point3<N>)],
+    ) -> PhysicsResult<()> {
+        self.change_in_place(
+            shape_tag,
+            |shape| shape.set_trimesh_vertices(vertices),
+            |_| ReplayCall::UpdateTriMeshVertices(shape_tag, vertices.to_vec()),
+        )
+    }
+}
+
+// Compound children
+impl<N: PtReal> ShapeNpServer<N> {
+    /// Adds a child to the `Compound` shape, returning its index.
+    ///
+    /// The shapes of the other children are not created again, and the colliders that use the
+    /// compound are updated in place.
+    /// Fails with `WrongShapeType` if the shape is not a `Compound`.
+    pub fn add_compound_child(
+        &self,
+        shape_tag: PhysicsShapeTag,
+        transform: &Isometry3<N>,
+        shape_desc: &ShapeDesc<N>,
+    ) -> PhysicsResult<usize> {
+        self.change_in_place(
+            shape_tag,
+            |shape| shape.add_compound_child(transform, shape_desc),
+            |shape| ReplayCall::UpdateShape(shape_tag, shape.shape_desc().into_owned()),
+        )
+    }
+
+    /// Removes the child of the `Compound` shape, returning it; the index of the following
+    /// children decreases by one.
+    ///
+    /// Fails with `WrongShapeType` if the shape is not a `Compound`, with `ChildNotFound` if the
+    /// index is out of range, and with `LastChild` if it's the only child, since a compound can't
+    /// be empty.
+    pub fn remove_compound_child(
+        &self,
+        shape_tag: PhysicsShapeTag,
+        index: usize,
+    ) -> PhysicsResult<(Isometry3<N>, ShapeDesc<N>)> {
+        self.change_in_place(
+            shape_tag,
+            |shape| shape.remove_compound_child(index),
+            |shape| ReplayCall::UpdateShape(shape_tag, shape.shape_desc().into_owned()),
+        )
+    }
+}
+
+// In place changes
+impl<N: PtReal> ShapeNpServer<N> {
+    /// Changes the shape without changing its kind, and updates in place the colliders that use
+    /// it, like `update` does, so they keep their broad phase proxies and contacts.
+    ///
+    /// `replay_call` creates the recorded call, only when the replay recording is on; it can
+    /// record just the change, rather than the whole description of the changed shape.
+    fn change_in_place<R>(
+        &self,
+        shape_tag: PhysicsShapeTag,
+        change: impl FnOnce(&mut RigidShape<N>) -> PhysicsResult<R>,
+        replay_call: impl FnOnce(&RigidShape<N>) -> ReplayCall<N>,
+    ) -> PhysicsResult<R> {
+        let shape_key = self.checked_key(shape_tag);
+        let bodies = self.storages.bodies_r();
+        let colliders = self.storages.colliders_w();
+        let shapes = self.storages.shapes_r();
+
+        let mut shape = shapes.get(shape_key).ok_or(PhysicsError::ShapeMissing)?;
+        let old_shape_handle = shape.shape_handle().clone();
+        let result = change(&mut *shape)?;
+        self.storages
+            .replay_recorder
+            .record(|| replay_call(&*shape));
+
+        for body_key in shape.bodies() {
+            if let Some(mut body) = bodies.get_body(*body_key) {
+                if !body.lod_active {
+                    RBodyNpServer::update_collider_shape(
+                        &mut *body,
+                        &old_shape_handle,
+                        &*shape,
+                        &colliders,
+                        true,
+                    );
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+// Stale tags
+impl<N: PtReal> ShapeNpServer<N> {
+    /// Returns `true` if the tag refers to an existing shape.
+    pub fn is_valid(&self, shape_tag: PhysicsShapeTag) -> bool {
+        self.storages
+            .shapes_r()
+            .has(shape_tag_to_store_key(shape_tag))
+    }
+
+    /// Converts the tag to the storage key, reporting it when it's stale and the report is on.
+    fn checked_key(&self, shape_tag: PhysicsShapeTag) -> StoreKey {
+        #[cfg(feature = "validation")]
+        self.storages.validate();
+
+        if self.storages.report_stale_tags() && !self.is_valid(shape_tag) {
+            self.storages.report_stale_tag("shape");
+        }
+        shape_tag_to_store_key(shape_tag)
+    }
+}
+
+// Fallible functions
+impl<N: PtReal> ShapeNpServer<N> {
+    /// Like `update`, but returns an error when the shape doesn't exist.
+    pub fn try_update(
+        &self,
+        shape_tag: PhysicsShapeTag,
+        shape_desc: &ShapeDesc<N>,
+    ) -> PhysicsResult<()> {
+        if !self
+            .storages
+            .shapes_r()
+            .has(shape_tag_to_store_key(shape_tag))
+        {
+            return Err(PhysicsError::ShapeMissing);
+        }
+        self.update(shape_tag, shape_desc);
+        Ok(())
+    }
+}
+
+impl<N: PtReal> ShapePhysicsServerTrait<N> for ShapeNpServer<N> {
+    fn create(&self, shape_desc: &ShapeDesc<N>) -> PhysicsHandle<PhysicsShapeTag> {
+        let handle = self.insert_shape(RigidShape::new(shape_desc));
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::CreateShape(handle.get(), shape_desc.clone()));
+        handle
     }
 
     fn update(&self, shape_tag: PhysicsShapeTag, shape_desc: &ShapeDesc<N>) {
+        let shape_key = self.checked_key(shape_tag);
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::UpdateShape(shape_tag, shape_desc.clone()));
         let bodies = self.storages.bodies_r();
         let mut colliders = self.storages.colliders_w();
         let shapes = self.storages.shapes_r();
 
-        let shape_key = shape_tag_to_store_key(shape_tag);
         let shape = shapes.get(shape_key);
         if let Some(mut shape) = shape {
+            let old_shape_handle = shape.shape_handle().clone();
+            let old_kind = shape.kind();
             shape.update(shape_desc);
 
+            // When the kind of the shape doesn't change, the colliders are updated in place, so
+            // they keep their broad phase proxies and contacts.
+            let in_place = old_kind == shape.kind();
+
             let b_keys = shape.bodies();
             for body_key in b_keys {
                 let body = bodies.get_body(*body_key);
                 if let Some(mut body) = body {
+                    if in_place && body.collider_key.is_some() && !body.lod_active {
+                        RBodyNpServer::update_collider_shape(
+                            &mut *body,
+                            &old_shape_handle,
+                            &*shape,
+                            &colliders,
+                            true,
+                        );
+                        continue;
+                    }
+
                     match &body.body_data {
                         BodyData::Rigid { .. } => {
+                            body.lod_active = false;
                             RBodyNpServer::drop_collider(&mut *body, &mut colliders);
                             let collider_desc = RBodyNpServer::create_collider_desc(&body, &*shape);
                             RBodyNpServer::install_collider(
@@ -105,6 +342,7 @@ impl<N: PtReal> ShapePhysicsServerTrait<N> for ShapeNpServer<N> {
                                 &mut colliders,
                             );
                         }
+                        BodyData::Soft { .. } => {}
                     }
                 }
             }
@@ -113,3 +351,266 @@ impl<N: PtReal> ShapePhysicsServerTrait<N> for ShapeNpServer<N> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Point3, Vector3};
+    use amethyst_physics::servers::{
+        BodyMode, RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc, ShapePhysicsServerTrait,
+    };
+
+    use crate::{
+        conversors::shape_tag_to_store_key, decode_shape_asset, encode_shape_asset,
+        test_world::TestWorld, world_snapshot::ShapeDescSnapshot, ContactState, PhysicsError,
+    };
+
+    #[test]
+    fn shape_asset_round_trip() {
+        let test = TestWorld::<f32>::new();
+
+        let desc = ShapeDesc::Compound {
+            shapes: vec![
+                (
+                    Isometry3::translation(0.0, 1.0, 0.0),
+                    ShapeDesc::Sphere { radius: 0.5 },
+                ),
+                (
+                    Isometry3::identity(),
+                    ShapeDesc::TriMesh {
+                        points: vec![
+                            Point3::new(0.0, 0.0, 0.0),
+                            Point3::new(1.0, 0.0, 0.0),
+                            Point3::new(0.0, 0.0, 1.0),
+                        ],
+                        indices: vec![Point3::new(0, 1, 2)],
+                    },
+                ),
+            ],
+        };
+        let bytes = encode_shape_asset(&desc);
+        assert_eq!(
+            decode_shape_asset::<f32>(&bytes).map(|d| ShapeDescSnapshot::from(&d)),
+            Ok(ShapeDescSnapshot::from(&desc))
+        );
+
+        let shape = test.shapes.create_from_asset(&bytes).unwrap();
+        assert_eq!(test.shapes.shape_asset(shape.get()), Ok(bytes.clone()));
+        assert!(test
+            .shapes
+            .create_from_asset(&bytes[..bytes.len() - 1])
+            .is_err());
+    }
+
+    #[test]
+    fn shape_scaled_on_each_axis() {
+        let test = TestWorld::<f32>::new();
+
+        let shape = test.shapes.create(&ShapeDesc::Cube {
+            half_extents: Vector3::new(1.0, 1.0, 1.0),
+        });
+        let scaled = test
+            .shapes
+            .create_scaled(shape.get(), &Vector3::new(2.0, -3.0, 1.0))
+            .unwrap();
+
+        let shapes = test.storages.shapes_r();
+        let scaled = shapes.get(shape_tag_to_store_key(scaled.get())).unwrap();
+        assert_eq!(
+            ShapeDescSnapshot::from(&*scaled.shape_desc()),
+            ShapeDescSnapshot::Cube {
+                half_extents: Vector3::new(2.0, 3.0, 1.0)
+            }
+        );
+    }
+
+    #[test]
+    fn trimesh_vertices_updated() {
+        let test = TestWorld::<f32>::new();
+
+        let mesh = test.shapes.create(&ShapeDesc::TriMesh {
+            points: vec![
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+                Point3::new(0.0, 0.0, 1.0),
+            ],
+            indices: vec![Point3::new(0, 1, 2)],
+        });
+        let mut desc = RigidBodyDesc::default();
+        desc.mode = BodyMode::Static;
+        let body = test.bodies.create(&desc);
+        test.bodies.set_shape(body.get(), Some(mesh.get()));
+
+        assert_eq!(
+            test.shapes
+                .update_trimesh_vertices(mesh.get(), &[(3, Point3::origin())]),
+            Err(PhysicsError::VertexNotFound)
+        );
+        test.shapes
+            .update_trimesh_vertices(mesh.get(), &[(1, Point3::new(2.0, 0.5, 0.0))])
+            .unwrap();
+
+        let shapes = test.storages.shapes_r();
+        match &*shapes
+            .get(shape_tag_to_store_key(mesh.get()))
+            .unwrap()
+            .shape_desc()
+        {
+            ShapeDesc::TriMesh { points, indices } => {
+                assert_eq!(points[1], Point3::new(2.0, 0.5, 0.0));
+                assert_eq!(indices, &vec![Point3::new(0, 1, 2)]);
+            }
+            _ => panic!("The shape is not a mesh"),
+        }
+    }
+
+    #[test]
+    fn mesh_contacts_persist_after_vertices_update() {
+        let test = TestWorld::<f32>::new();
+
+        let mesh = test.shapes.create(&ShapeDesc::TriMesh {
+            points: vec![
+                Point3::new(-10.0, 0.0, -10.0),
+                Point3::new(10.0, 0.0, -10.0),
+                Point3::new(10.0, 0.0, 10.0),
+                Point3::new(-10.0, 0.0, 10.0),
+            ],
+            indices: vec![Point3::new(0, 2, 1), Point3::new(0, 3, 2)],
+        });
+        let mut ground_desc = RigidBodyDesc::default();
+        ground_desc.mode = BodyMode::Static;
+        let ground = test.bodies.create(&ground_desc);
+        test.bodies.set_shape(ground.get(), Some(mesh.get()));
+
+        let mut sphere_desc = RigidBodyDesc::default();
+        sphere_desc.contacts_to_report = 1;
+        let sphere = test.create_sphere_on_ground(&sphere_desc);
+
+        let states = || {
+            test.bodies
+                .contact_details(sphere.get())
+                .iter()
+                .map(|c| c.state)
+                .collect::<Vec<_>>()
+        };
+        for _ in 0..30 {
+            test.step();
+            if !states().is_empty() {
+                break;
+            }
+        }
+        assert_eq!(states(), vec![ContactState::Started]);
+
+        // Moves a corner far from the sphere, the surface below it doesn't change.
+        test.shapes
+            .update_trimesh_vertices(mesh.get(), &[(0, Point3::new(-12.0, 0.0, -12.0))])
+            .unwrap();
+        test.step();
+        assert_eq!(states(), vec![ContactState::Persisted]);
+    }
+
+    #[test]
+    fn convex_points_kept() {
+        let test = TestWorld::<f32>::new();
+
+        // The last point is inside the hull.
+        let desc = ShapeDesc::Convex {
+            points: vec![
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0),
+                Point3::new(0.0, 0.0, 1.0),
+                Point3::new(0.1, 0.1, 0.1),
+            ],
+        };
+        let convex = test.shapes.create(&desc);
+
+        let shapes = test.storages.shapes_r();
+        let shape = shapes.get(shape_tag_to_store_key(convex.get())).unwrap();
+        assert_eq!(
+            ShapeDescSnapshot::from(&*shape.shape_desc()),
+            ShapeDescSnapshot::from(&desc)
+        );
+    }
+
+    #[test]
+    fn compound_children_added_and_removed() {
+        let test = TestWorld::<f32>::new();
+
+        let sphere = ShapeDesc::Sphere { radius: 1.0 };
+        let compound = test.shapes.create(&ShapeDesc::Compound {
+            shapes: vec![(Isometry3::identity(), sphere.clone())],
+        });
+        let body = test.bodies.create(&RigidBodyDesc::default());
+        test.bodies.set_shape(body.get(), Some(compound.get()));
+
+        let transform = Isometry3::translation(0.0, 2.0, 0.0);
+        let cube = ShapeDesc::Cube {
+            half_extents: Vector3::new(1.0, 1.0, 1.0),
+        };
+        assert_eq!(
+            test.shapes
+                .add_compound_child(compound.get(), &transform, &cube),
+            Ok(1)
+        );
+        assert_eq!(
+            test.shapes.remove_compound_child(compound.get(), 2).err(),
+            Some(PhysicsError::ChildNotFound)
+        );
+
+        let (_, removed) = test
+            .shapes
+            .remove_compound_child(compound.get(), 0)
+            .unwrap();
+        assert_eq!(
+            ShapeDescSnapshot::from(&removed),
+            ShapeDescSnapshot::from(&sphere)
+        );
+        assert_eq!(
+            test.shapes.remove_compound_child(compound.get(), 0).err(),
+            Some(PhysicsError::LastChild)
+        );
+
+        let shapes = test.storages.shapes_r();
+        let shape = shapes.get(shape_tag_to_store_key(compound.get())).unwrap();
+        assert_eq!(
+            ShapeDescSnapshot::from(&*shape.shape_desc()),
+            ShapeDescSnapshot::from(&ShapeDesc::Compound {
+                shapes: vec![(transform, cube)],
+            })
+        );
+    }
+
+    #[test]
+    fn updated_shape_keeps_the_contacts() {
+        let test = TestWorld::<f32>::new();
+        let _ground = test.create_ground();
+
+        let shape = test.shapes.create(&ShapeDesc::Sphere { radius: 0.5 });
+        let mut sphere_desc = RigidBodyDesc::default();
+        sphere_desc.contacts_to_report = 1;
+        let sphere = test.bodies.create(&sphere_desc);
+        test.bodies.set_shape(sphere.get(), Some(shape.get()));
+        test.bodies
+            .set_transform(sphere.get(), &Isometry3::translation(0.0, 0.5, 0.0));
+        test.step_n(10);
+
+        let mass = || {
+            test.bodies
+                .with_np_rigid_body(sphere.get(), |rb| rb.augmented_mass().linear)
+                .unwrap()
+        };
+        let old_mass = mass();
+
+        test.shapes
+            .update(shape.get(), &ShapeDesc::Sphere { radius: 0.6 });
+        test.step();
+        let states: Vec<_> = test
+            .bodies
+            .contact_details(sphere.get())
+            .iter()
+            .map(|c| c.state)
+            .collect();
+        assert_eq!(states, vec![ContactState::Persisted]);
+        assert!(mass() > old_mass);
+    }
+}
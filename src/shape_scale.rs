@@ -0,0 +1,146 @@
+use amethyst_core::{
+    ecs::{Component, DenseVecStorage, Join, ReadStorage, System, WriteStorage},
+    math::{convert, Isometry3, Point3, Translation3, Vector3},
+    transform::Transform,
+};
+use amethyst_physics::{
+    objects::*,
+    servers::{RBodyPhysicsServerTrait, ShapeDesc},
+    PtReal,
+};
+
+use crate::{RBodyNpServer, ShapeNpServer, WorldNpServer};
+
+/// Returns the description of the shape scaled by `scale`, along its local axes.
+///
+/// The shapes that can't be scaled on each axis are approximated: the `Sphere` takes the biggest
+/// scale, and the radius of the `Capsule` and the `Cylinder` takes the biggest of the `x` and `z`
+/// scales. The children of a `Compound` are scaled along the axes of the compound, so they are
+/// approximated when rotated by an angle that is not a multiple of 90 degrees.
+pub fn scale_shape_desc<N: PtReal>(shape_desc: &ShapeDesc<N>, scale: &Vector3<N>) -> ShapeDesc<N> {
+    let scale = scale.map(|v| v.abs());
+    match shape_desc {
+        ShapeDesc::Sphere { radius } => ShapeDesc::Sphere {
+            radius: *radius * scale.x.max(scale.y).max(scale.z),
+        },
+        ShapeDesc::Cube { half_extents } => ShapeDesc::Cube {
+            half_extents: half_extents.component_mul(&scale),
+        },
+        ShapeDesc::Capsule {
+            half_height,
+            radius,
+        } => ShapeDesc::Capsule {
+            half_height: *half_height * scale.y,
+            radius: *radius * scale.x.max(scale.z),
+        },
+        ShapeDesc::Cylinder {
+            half_height,
+            radius,
+        } => ShapeDesc::Cylinder {
+            half_height: *half_height * scale.y,
+            radius: *radius * scale.x.max(scale.z),
+        },
+        ShapeDesc::Plane => ShapeDesc::Plane,
+        ShapeDesc::Convex { points } => ShapeDesc::Convex {
+            points: points
+                .iter()
+                .map(|p| Point3::from(p.coords.component_mul(&scale)))
+                .collect(),
+        },
+        ShapeDesc::TriMesh { points, indices } => ShapeDesc::TriMesh {
+            points: points
+                .iter()
+                .map(|p| Point3::from(p.coords.component_mul(&scale)))
+                .collect(),
+            indices: indices.clone(),
+        },
+        ShapeDesc::Compound { shapes } => ShapeDesc::Compound {
+            shapes: shapes
+                .iter()
+                .map(|(iso, shape)| {
+                    let translation = iso.translation.vector.component_mul(&scale);
+                    let child_scale = iso.rotation.inverse() * scale;
+                    (
+                        Isometry3::from_parts(Translation3::from(translation), iso.rotation),
+                        scale_shape_desc(shape, &child_scale),
+                    )
+                })
+                .collect(),
+        },
+    }
+}
+
+/// The shape of the entity rigid body, that the `ShapeScaleSyncSystem` scales by the scale of
+/// the entity `Transform`.
+#[allow(missing_debug_implementations)]
+pub struct ScaledShape<N: PtReal> {
+    /// The shape with the unit scale.
+    pub shape: PhysicsHandle<PhysicsShapeTag>,
+    scale: Option<Vector3<N>>,
+    scaled: Option<PhysicsHandle<PhysicsShapeTag>>,
+}
+
+impl<N: PtReal> ScaledShape<N> {
+    pub fn new(shape: PhysicsHandle<PhysicsShapeTag>) -> Self {
+        ScaledShape {
+            shape,
+            scale: None,
+            scaled: None,
+        }
+    }
+}
+
+impl<N: PtReal> Component for ScaledShape<N> {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// This system sets to the rigid bodies of the entities with the `ScaledShape` component the
+/// shape scaled by the scale of their `Transform`.
+///
+/// The scaled shape is created again only when the scale changes.
+/// Create it using `WorldNpServer::shape_scale_sync_system`, and add it before the physics
+/// systems.
+#[allow(missing_debug_implementations)]
+pub struct ShapeScaleSyncSystem<N: PtReal> {
+    body_server: RBodyNpServer<N>,
+    shape_server: ShapeNpServer<N>,
+}
+
+impl<'a, N: PtReal> System<'a> for ShapeScaleSyncSystem<N> {
+    type SystemData = (
+        ReadStorage<'a, Transform>,
+        ReadStorage<'a, PhysicsHandle<PhysicsRigidBodyTag>>,
+        WriteStorage<'a, ScaledShape<N>>,
+    );
+
+    fn run(&mut self, (transforms, bodies, mut scaled_shapes): Self::SystemData) {
+        for (transform, body, scaled_shape) in (&transforms, &bodies, &mut scaled_shapes).join() {
+            let scale: Vector3<N> = transform.scale().map(|v| convert(f64::from(v)));
+            if scaled_shape.scale == Some(scale) {
+                continue;
+            }
+
+            let scaled = self
+                .shape_server
+                .create_scaled(scaled_shape.shape.get(), &scale);
+            if let Ok(scaled) = scaled {
+                self.body_server.set_shape(body.get(), Some(scaled.get()));
+                scaled_shape.scale = Some(scale);
+                // The previous scaled shape is dropped, now that the body doesn't use it.
+                scaled_shape.scaled = Some(scaled);
+            }
+        }
+    }
+}
+
+// Shape scale
+impl<N: PtReal> WorldNpServer<N> {
+    /// Returns the system that applies the scale of the entities `Transform` to the shape of
+    /// their rigid body.
+    pub fn shape_scale_sync_system(&self) -> ShapeScaleSyncSystem<N> {
+        ShapeScaleSyncSystem {
+            body_server: RBodyNpServer::new(self.storages.clone()),
+            shape_server: ShapeNpServer::new(self.storages.clone()),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use amethyst_core::{
+    ecs::{System, WriteStorage},
+    math::{convert, try_convert, Isometry3, Quaternion, Translation3, UnitQuaternion, Vector3},
+    transform::Transform,
+};
+use amethyst_physics::{objects::*, servers::RBodyPhysicsServerTrait, PtReal};
+
+use crate::{RBodyNpServer, TransformsSnapshot, WorldNpServer};
+
+// The amethyst `Transform` is single precision, so these functions convert to and from it when
+// the world runs with another precision, like `f64` for the precision critical simulations.
+// When the world runs with `f32` they don't change anything.
+
+pub fn real_to_f32<N: PtReal>(v: N) -> f32 {
+    try_convert::<N, f64>(v).unwrap_or_default() as f32
+}
+
+pub fn real_from_f32<N: PtReal>(v: f32) -> N {
+    convert(f64::from(v))
+}
+
+pub fn vector_to_f32<N: PtReal>(v: &Vector3<N>) -> Vector3<f32> {
+    v.map(real_to_f32)
+}
+
+pub fn vector_from_f32<N: PtReal>(v: &Vector3<f32>) -> Vector3<N> {
+    v.map(real_from_f32)
+}
+
+pub fn isometry_to_f32<N: PtReal>(iso: &Isometry3<N>) -> Isometry3<f32> {
+    let rotation = iso.rotation.quaternion().coords.map(real_to_f32);
+    Isometry3::from_parts(
+        Translation3::from(vector_to_f32(&iso.translation.vector)),
+        // Normalized again, since the conversion may lose the precision of the unit quaternion.
+        UnitQuaternion::new_normalize(Quaternion::from(rotation)),
+    )
+}
+
+pub fn isometry_from_f32<N: PtReal>(iso: &Isometry3<f32>) -> Isometry3<N> {
+    let rotation = iso.rotation.quaternion().coords.map(real_from_f32);
+    Isometry3::from_parts(
+        Translation3::from(vector_from_f32(&iso.translation.vector)),
+        UnitQuaternion::new_normalize(Quaternion::from(rotation)),
+    )
+}
+
+impl<N: PtReal> TransformsSnapshot<N> {
+    /// Returns the transform of the rigid body, converted to `f32`.
+    pub fn get_f32(&self, body_tag: PhysicsRigidBodyTag) -> Option<Isometry3<f32>> {
+        self.get(body_tag).map(isometry_to_f32)
+    }
+}
+
+/// This system writes the transforms of the rigid bodies that moved to the `Transform` of their
+/// entity, converted to `f32`.
+///
+/// It drains the moved bodies like `WorldNpServer::sync_moved_bodies`, so each moved body is read
+/// only by one of them; don't use both.
+/// Create it using `WorldNpServer::single_precision_sync_system`, and add it after the physics
+/// systems.
+#[allow(missing_debug_implementations)]
+pub struct SinglePrecisionSyncSystem<N: PtReal> {
+    world_server: WorldNpServer<N>,
+}
+
+impl<'a, N: PtReal> System<'a> for SinglePrecisionSyncSystem<N> {
+    type SystemData = WriteStorage<'a, Transform>;
+
+    fn run(&mut self, mut transforms: Self::SystemData) {
+        self.world_server.drain_moved_bodies(|_, body| {
+            let transform = body.entity.and_then(|entity| transforms.get_mut(entity));
+            if let (Some(transform), Some(body_transform)) = (transform, body.body_transform()) {
+                transform.set_isometry(isometry_to_f32(body_transform));
+            }
+        });
+    }
+}
+
+// Single precision
+impl<N: PtReal> WorldNpServer<N> {
+    /// Like `sync_moved_bodies`, but the transforms are converted to `f32`.
+    ///
+    /// It drains the same queue of `sync_moved_bodies`, so don't use both.
+    pub fn sync_moved_bodies_f32<F>(&self, mut f: F)
+    where
+        F: FnMut(PhysicsRigidBodyTag, &Isometry3<f32>),
+    {
+        self.sync_moved_bodies(|body_tag, transform| f(body_tag, &isometry_to_f32(transform)));
+    }
+
+    /// Returns the transform of the rigid body, converted to `f32`.
+    pub fn rigid_body_transform_f32(&self, body_tag: PhysicsRigidBodyTag) -> Isometry3<f32> {
+        let body_server = RBodyNpServer::new(self.storages.clone());
+        isometry_to_f32(&body_server.transform(body_tag))
+    }
+
+    /// Sets the transform of the rigid body, from an `f32` one; e.g. the `Transform` of its
+    /// entity.
+    pub fn set_rigid_body_transform_f32(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+        transform: &Isometry3<f32>,
+    ) {
+        let body_server = RBodyNpServer::new(self.storages.clone());
+        body_server.set_transform(body_tag, &isometry_from_f32(transform));
+    }
+
+    /// Returns the linear velocity of the rigid body, converted to `f32`.
+    pub fn rigid_body_linear_velocity_f32(&self, body_tag: PhysicsRigidBodyTag) -> Vector3<f32> {
+        let body_server = RBodyNpServer::new(self.storages.clone());
+        vector_to_f32(&body_server.linear_velocity(body_tag))
+    }
+
+    /// Returns the system that writes the transforms of the moved rigid bodies to the `Transform`
+    /// of their entities.
+    pub fn single_precision_sync_system(&self) -> SinglePrecisionSyncSystem<N> {
+        SinglePrecisionSyncSystem {
+            world_server: self.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Vector3};
+    use amethyst_physics::servers::{RBodyPhysicsServerTrait, RigidBodyDesc};
+
+    use crate::test_world::TestWorld;
+
+    #[test]
+    fn double_precision_world_synced_in_single_precision() {
+        let test = TestWorld::<f64>::new();
+
+        let body = test.bodies.create(&RigidBodyDesc::default());
+        let transform = Isometry3::new(Vector3::new(1.5f32, -2.0, 3.25), Vector3::y() * 0.5);
+        test.world
+            .set_rigid_body_transform_f32(body.get(), &transform);
+
+        assert_eq!(
+            test.bodies.transform(body.get()).translation.vector,
+            Vector3::new(1.5f64, -2.0, 3.25)
+        );
+        let synced_back = test.world.rigid_body_transform_f32(body.get());
+        assert!((synced_back.translation.vector - transform.translation.vector).norm() < 1.0e-6);
+
+        test.step();
+        let mut synced = Vec::new();
+        test.world
+            .sync_moved_bodies_f32(|tag, transform| synced.push((tag, *transform)));
+        assert_eq!(synced.len(), 1);
+        assert_eq!(synced[0].0, body.get());
+        assert_eq!(
+            Some(synced[0].1),
+            test.world.transforms_snapshot().get_f32(body.get())
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use amethyst_core::math::{DVector, Isometry3, Point3, Vector2};
+use amethyst_physics::{objects::CollisionGroup, PtReal};
+use nphysics3d::object::{
+    Body as NpBody, MassConstraintSystem as NpMassConstraintSystem,
+    MassSpringSystem as NpMassSpringSystem,
+};
+
+use crate::storage::StoreKey;
+
+/// Opaque tag of a soft body, created by the `SoftBodyNpServer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SoftBodyTag(pub(crate) StoreKey);
+
+/// The surface of the soft body.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SoftBodyShape<N: PtReal> {
+    /// A rectangle, on the local XZ plane, subdivided in a grid of vertices.
+    ///
+    /// The vertices are ordered by row, starting from the corner at `-extents / 2`.
+    Grid {
+        position: Isometry3<N>,
+        extents: Vector2<N>,
+        /// Number of cells along X and Z; the grid has `(x + 1) * (z + 1)` vertices.
+        subdivisions: (usize, usize),
+    },
+    /// A triangle mesh, in world space.
+    Mesh {
+        points: Vec<Point3<N>>,
+        indices: Vec<[usize; 3]>,
+    },
+}
+
+impl<N: PtReal> SoftBodyShape<N> {
+    /// Returns the vertices and the triangles of the surface.
+    pub(crate) fn to_mesh(&self) -> (Vec<Point3<N>>, Vec<[usize; 3]>) {
+        match self {
+            SoftBodyShape::Grid {
+                position,
+                extents,
+                subdivisions: (cells_x, cells_z),
+            } => {
+                let (cells_x, cells_z) = ((*cells_x).max(1), (*cells_z).max(1));
+                let row = cells_x + 1;
+
+                let mut points = Vec::with_capacity(row * (cells_z + 1));
+                for z in 0..=cells_z {
+                    for x in 0..=cells_x {
+                        let local = Point3::new(
+                            extents.x
+                                * (N::from(x as f64) / N::from(cells_x as f64) - N::from(0.5)),
+                            N::from(0.0),
+                            extents.y
+                                * (N::from(z as f64) / N::from(cells_z as f64) - N::from(0.5)),
+                        );
+                        points.push(position * local);
+                    }
+                }
+
+                let mut indices = Vec::with_capacity(cells_x * cells_z * 2);
+                for z in 0..cells_z {
+                    for x in 0..cells_x {
+                        let i = z * row + x;
+                        indices.push([i, i + row, i + 1]);
+                        indices.push([i + 1, i + row, i + row + 1]);
+                    }
+                }
+
+                (points, indices)
+            }
+            SoftBodyShape::Mesh { points, indices } => (points.clone(), indices.clone()),
+        }
+    }
+}
+
+/// How the vertices of the soft body are connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoftBodyKind {
+    /// The vertices are connected by springs; good for cloths.
+    MassSpring,
+    /// The vertices are connected by constraints, that are stiffer than the springs.
+    ///
+    /// The `stiffness` and the `damping_ratio` are not used.
+    MassConstraint,
+}
+
+/// Describes a soft body, like a cloth.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoftBodyDesc<N: PtReal> {
+    pub kind: SoftBodyKind,
+    pub shape: SoftBodyShape<N>,
+    /// The mass of the whole body.
+    pub mass: N,
+    /// The stiffness of the springs between the vertices.
+    pub stiffness: N,
+    pub damping_ratio: N,
+    /// The vertices that don't move, like the corners of a flag attached to its pole.
+    pub pinned: Vec<usize>,
+    /// The thickness of the surface; a margin avoids that the thin surface passes through the
+    /// level geometry, like the `TriMesh` shapes.
+    pub margin: N,
+    pub belong_to: Vec<CollisionGroup>,
+    pub collide_with: Vec<CollisionGroup>,
+}
+
+/// A vertex of the soft body that follows a rigid body.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SoftBodyAttachment<N: PtReal> {
+    pub vertex: usize,
+    pub body: StoreKey,
+    /// The vertex position in the rigid body space.
+    pub anchor: Point3<N>,
+}
+
+/// Returns the positions of the soft body vertices, as a flat vector.
+pub(crate) fn positions<N: PtReal>(np_body: &dyn NpBody<N>) -> Option<&DVector<N>> {
+    if let Some(np_body) = np_body.downcast_ref::<NpMassSpringSystem<N>>() {
+        Some(np_body.positions())
+    } else if let Some(np_body) = np_body.downcast_ref::<NpMassConstraintSystem<N>>() {
+        Some(np_body.positions())
+    } else {
+        None
+    }
+}
+
+/// Returns the position of the vertex, if it exists.
+pub(crate) fn node_position<N: PtReal>(np_body: &dyn NpBody<N>, node: usize) -> Option<Point3<N>> {
+    positions(np_body)
+        .filter(|p| node < p.len() / 3)
+        .map(|p| Point3::new(p[node * 3], p[node * 3 + 1], p[node * 3 + 2]))
+}
+
+pub(crate) fn set_node_kinematic<N: PtReal>(
+    np_body: &mut dyn NpBody<N>,
+    node: usize,
+    is_kinematic: bool,
+) {
+    if let Some(np_body) = np_body.downcast_mut::<NpMassSpringSystem<N>>() {
+        np_body.set_node_kinematic(node, is_kinematic);
+    } else if let Some(np_body) = np_body.downcast_mut::<NpMassConstraintSystem<N>>() {
+        np_body.set_node_kinematic(node, is_kinematic);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_mesh() {
+        let shape = SoftBodyShape::Grid {
+            position: Isometry3::identity(),
+            extents: Vector2::new(2.0f32, 4.0),
+            subdivisions: (2, 1),
+        };
+        let (points, indices) = shape.to_mesh();
+
+        assert_eq!(points.len(), 6);
+        assert_eq!(indices.len(), 4);
+        assert_eq!(points[0], Point3::new(-1.0, 0.0, -2.0));
+        assert_eq!(points[5], Point3::new(1.0, 0.0, 2.0));
+        assert!(indices.iter().flatten().all(|i| *i < points.len()));
+    }
+}
@@ -0,0 +1,421 @@
+use amethyst_core::math::Point3;
+use amethyst_physics::{objects::*, PtReal};
+use log::error;
+use ncollide3d::shape::TriMesh as NcTriMesh;
+use nphysics3d::object::{
+    Body as NpBody, MassConstraintSystemDesc as NpMassConstraintSystemDesc,
+    MassSpringSystemDesc as NpMassSpringSystemDesc,
+};
+
+use crate::{
+    body::{Body, BodyData},
+    body_storage::BodyStorage,
+    conversors::*,
+    error::{LogError, PhysicsError, PhysicsResult},
+    servers_storage::{BodiesStorageWrite, CollidersStorageWrite, ServersStorages},
+    soft_body::{
+        node_position, positions, set_node_kinematic, SoftBodyAttachment, SoftBodyDesc,
+        SoftBodyKind, SoftBodyTag,
+    },
+    storage::StoreKey,
+    WorldNpServer,
+};
+
+/// The server that manages the soft bodies, like the cloths.
+///
+/// The soft bodies are simulated as a set of vertices connected by springs or constraints.
+/// Unlike the other objects, the soft bodies are not owned by a `PhysicsHandle`, so they must be
+/// explicitly destroyed.
+/// The soft bodies are not part of the `WorldSnapshot`.
+#[allow(missing_debug_implementations)]
+pub struct SoftBodyNpServer<N: PtReal> {
+    storages: ServersStorages<N>,
+}
+
+impl<N: PtReal> SoftBodyNpServer<N> {
+    pub fn new(storages: ServersStorages<N>) -> Self {
+        SoftBodyNpServer { storages }
+    }
+
+    /// Creates a soft body.
+    pub fn create(&self, desc: &SoftBodyDesc<N>) -> SoftBodyTag {
+        let (points, indices) = desc.shape.to_mesh();
+        let np_indices = indices
+            .iter()
+            .map(|t| Point3::new(t[0], t[1], t[2]))
+            .collect();
+        let mesh = NcTriMesh::new(points, np_indices, None);
+
+        let (np_body, collider_desc) = match desc.kind {
+            SoftBodyKind::MassSpring => {
+                let mut np_body = NpMassSpringSystemDesc::from_trimesh(&mesh)
+                    .mass(desc.mass)
+                    .stiffness(desc.stiffness)
+                    .damping_ratio(desc.damping_ratio)
+                    .build();
+                for node in desc.pinned.iter() {
+                    np_body.set_node_kinematic(*node, true);
+                }
+                let collider_desc = np_body.boundary_collider_desc();
+                (Box::new(np_body) as Box<dyn NpBody<N>>, collider_desc)
+            }
+            SoftBodyKind::MassConstraint => {
+                let mut np_body = NpMassConstraintSystemDesc::from_trimesh(&mesh)
+                    .mass(desc.mass)
+                    .build();
+                for node in desc.pinned.iter() {
+                    np_body.set_node_kinematic(*node, true);
+                }
+                let collider_desc = np_body.boundary_collider_desc();
+                (Box::new(np_body) as Box<dyn NpBody<N>>, collider_desc)
+            }
+        };
+
+        let np_collision_groups =
+            collision_group_conversor::to_nphysics(&desc.belong_to, &desc.collide_with);
+
+        let mut bodies = self.storages.bodies_w();
+        let mut colliders = self.storages.colliders_w();
+
+        let key = bodies.insert_body(Body::new_soft_body(np_body, indices, np_collision_groups));
+        let mut body = bodies.get_body(key).unwrap();
+        body.self_key = Some(key);
+
+        let mut collider = collider_desc
+            .margin(desc.margin)
+            .collision_groups(np_collision_groups)
+            .build(key);
+        body.set_user_data_to(&mut collider);
+        body.collider_key = Some(colliders.insert_collider(collider));
+
+        SoftBodyTag(key)
+    }
+
+    /// Destroys the soft body.
+    ///
+    /// Like the other objects, the soft body and its collider are dropped by the garbage
+    /// collector, before the next step.
+    pub fn destroy(&self, tag: SoftBodyTag) {
+        self.storages.soft_bodies_gc_w().push(tag.0);
+    }
+
+    /// Drops the soft body and its collider.
+    pub fn drop_soft_body(
+        key: StoreKey,
+        bodies: &mut BodiesStorageWrite<'_, N>,
+        colliders: &mut CollidersStorageWrite<'_, N>,
+    ) -> PhysicsResult<()> {
+        if let Some(body) = bodies.get_body(key) {
+            if !matches!(body.body_data, BodyData::Soft { .. }) {
+                return Err(PhysicsError::WrongBodyType);
+            }
+            if let Some(collider_key) = body.collider_key {
+                colliders.drop_collider(collider_key);
+            }
+        } else {
+            return Ok(());
+        }
+        bodies.drop_body(key);
+        Ok(())
+    }
+
+    /// Returns `true` if the soft body exists.
+    pub fn exists(&self, tag: SoftBodyTag) -> bool {
+        self.storages
+            .bodies_r()
+            .get_body(tag.0)
+            .map_or(false, |body| {
+                matches!(body.body_data, BodyData::Soft { .. })
+            })
+    }
+
+    /// Pin or release the vertex; a pinned vertex doesn't move.
+    pub fn set_pinned(&self, tag: SoftBodyTag, vertex: usize, pinned: bool) {
+        let bodies = self.storages.bodies_r();
+        bodies
+            .get_body(tag.0)
+            .ok_or(PhysicsError::BodyNotFound)
+            .and_then(|mut body| {
+                node_position(&*body.np_body, vertex).ok_or(PhysicsError::VertexNotFound)?;
+                set_node_kinematic(&mut *body.np_body, vertex, pinned);
+                body.activate();
+                Ok(())
+            })
+            .or_report(&self.storages);
+    }
+
+    /// Returns the positions of the vertices, in world space.
+    ///
+    /// The vertices are in the same order of the `SoftBodyShape` used to create the body, so they
+    /// can be used to update the mesh of the renderer.
+    pub fn vertices(&self, tag: SoftBodyTag) -> Vec<Point3<N>> {
+        let bodies = self.storages.bodies_r();
+        if let Some(body) = bodies.get_body(tag.0) {
+            if let Some(positions) = positions(&*body.np_body) {
+                return positions
+                    .as_slice()
+                    .chunks(3)
+                    .map(|p| Point3::new(p[0], p[1], p[2]))
+                    .collect();
+            }
+        }
+        error!("Soft body not found");
+        Vec::new()
+    }
+
+    /// Returns the triangles of the soft body surface.
+    pub fn indices(&self, tag: SoftBodyTag) -> Vec<[usize; 3]> {
+        let bodies = self.storages.bodies_r();
+        if let Some(body) = bodies.get_body(tag.0) {
+            if let BodyData::Soft { indices, .. } = &body.body_data {
+                return indices.clone();
+            }
+        }
+        error!("Soft body not found");
+        Vec::new()
+    }
+}
+
+// Attachments
+impl<N: PtReal> SoftBodyNpServer<N> {
+    /// Attaches the vertex to the rigid body, keeping their current relative position.
+    ///
+    /// The vertex follows the rigid body, like a flag hanging from its pole; the rigid body doesn't
+    /// feel the weight of the soft body.
+    /// The attachment is removed when the rigid body is destroyed.
+    pub fn attach(&self, tag: SoftBodyTag, vertex: usize, body_tag: PhysicsRigidBodyTag) {
+        self.try_attach(tag, vertex, body_tag)
+            .or_report(&self.storages);
+    }
+
+    /// Like `attach`, but returns an error when the vertex or the rigid body doesn't exist.
+    pub fn try_attach(
+        &self,
+        tag: SoftBodyTag,
+        vertex: usize,
+        body_tag: PhysicsRigidBodyTag,
+    ) -> PhysicsResult<()> {
+        let body_key = rigid_tag_to_store_key(body_tag);
+        let bodies = self.storages.bodies_r();
+
+        let anchor = {
+            let mut soft_body = bodies.get_body(tag.0).ok_or(PhysicsError::BodyNotFound)?;
+            let position =
+                node_position(&*soft_body.np_body, vertex).ok_or(PhysicsError::VertexNotFound)?;
+            if body_key == tag.0 {
+                return Err(PhysicsError::WrongBodyType);
+            }
+
+            let rigid_body = bodies
+                .get_body(body_key)
+                .ok_or(PhysicsError::BodyNotFound)?;
+            if !matches!(rigid_body.body_data, BodyData::Rigid { .. }) {
+                return Err(PhysicsError::WrongBodyType);
+            }
+            let anchor = rigid_body
+                .body_transform()
+                .ok_or(PhysicsError::WrongBodyType)?
+                .inverse_transform_point(&position);
+
+            set_node_kinematic(&mut *soft_body.np_body, vertex, true);
+            anchor
+        };
+
+        let mut soft_body = bodies.get_body(tag.0).unwrap();
+        if let BodyData::Soft { attachments, .. } = &mut soft_body.body_data {
+            attachments.retain(|a| a.vertex != vertex);
+            attachments.push(SoftBodyAttachment {
+                vertex,
+                body: body_key,
+                anchor,
+            });
+        }
+        Ok(())
+    }
+
+    /// Detaches the vertex from the rigid body, and releases it.
+    pub fn detach(&self, tag: SoftBodyTag, vertex: usize) {
+        let bodies = self.storages.bodies_r();
+        if let Some(mut soft_body) = bodies.get_body(tag.0) {
+            let mut detached = false;
+            if let BodyData::Soft { attachments, .. } = &mut soft_body.body_data {
+                let len = attachments.len();
+                attachments.retain(|a| a.vertex != vertex);
+                detached = attachments.len() != len;
+            }
+            if detached {
+                set_node_kinematic(&mut *soft_body.np_body, vertex, false);
+                soft_body.activate();
+            }
+        } else {
+            error!("Soft body not found");
+        }
+    }
+}
+
+impl<N: PtReal> WorldNpServer<N> {
+    /// Moves the attached vertices of the soft bodies to their rigid bodies, and releases the ones
+    /// attached to a destroyed rigid body.
+    pub(crate) fn apply_soft_body_attachments(bodies: &BodyStorage<N>) {
+        for (key, _) in bodies.iter() {
+            let mut soft_body = bodies.get_body(key).unwrap();
+            let attachments = match &soft_body.body_data {
+                BodyData::Soft { attachments, .. } if !attachments.is_empty() => {
+                    attachments.clone()
+                }
+                _ => continue,
+            };
+
+            let mut displacement = vec![N::from(0.0); soft_body.np_body.ndofs()];
+            let mut released = Vec::new();
+            for attachment in attachments {
+                let target = if let Some(transform) = bodies
+                    .get_body(attachment.body)
+                    .and_then(|rigid_body| rigid_body.body_transform().copied())
+                {
+                    transform * attachment.anchor
+                } else {
+                    released.push(attachment.vertex);
+                    continue;
+                };
+
+                if let Some(position) = node_position(&*soft_body.np_body, attachment.vertex) {
+                    let offset = target - position;
+                    displacement[attachment.vertex * 3..attachment.vertex * 3 + 3]
+                        .copy_from_slice(offset.as_slice());
+                }
+            }
+            soft_body.np_body.apply_displacement(&displacement);
+
+            for vertex in released.iter() {
+                set_node_kinematic(&mut *soft_body.np_body, *vertex, false);
+            }
+            if let BodyData::Soft { attachments, .. } = &mut soft_body.body_data {
+                attachments.retain(|a| !released.contains(&a.vertex));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Point3, Vector2, Vector3};
+    use amethyst_physics::servers::{BodyMode, RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc};
+
+    use super::SoftBodyNpServer;
+    use crate::{
+        error::PhysicsError,
+        soft_body::{SoftBodyDesc, SoftBodyKind, SoftBodyShape},
+        test_world::TestWorld,
+    };
+
+    /// A cloth of 1x1 meter, 2 meters above the ground, with 5x5 vertices.
+    fn cloth_desc(kind: SoftBodyKind, pinned: Vec<usize>) -> SoftBodyDesc<f32> {
+        let groups = RigidBodyDesc::<f32>::default();
+        SoftBodyDesc {
+            kind,
+            shape: SoftBodyShape::Grid {
+                position: Isometry3::translation(0.0, 2.0, 0.0),
+                extents: Vector2::new(1.0, 1.0),
+                subdivisions: (4, 4),
+            },
+            mass: 1.0,
+            stiffness: 1000.0,
+            damping_ratio: 0.75,
+            pinned,
+            margin: 0.05,
+            belong_to: groups.belong_to,
+            collide_with: groups.collide_with,
+        }
+    }
+
+    #[test]
+    fn cloth_hangs_from_the_pinned_vertices() {
+        let test = TestWorld::<f32>::new();
+        let server = SoftBodyNpServer::new(test.storages.clone());
+
+        let cloth = server.create(&cloth_desc(SoftBodyKind::MassSpring, vec![0, 4]));
+        let start = server.vertices(cloth);
+        assert_eq!(start.len(), 25);
+        assert_eq!(server.indices(cloth).len(), 32);
+
+        test.step_n(30);
+        let vertices = server.vertices(cloth);
+        assert_eq!(vertices[0], start[0]);
+        assert_eq!(vertices[4], start[4]);
+        assert!(vertices[24].y < start[24].y - 0.1);
+
+        server.destroy(cloth);
+        assert!(server.exists(cloth));
+        test.step();
+        assert!(!server.exists(cloth));
+    }
+
+    #[test]
+    fn constraint_cloth_keeps_its_edges_length() {
+        let test = TestWorld::<f32>::new();
+        let server = SoftBodyNpServer::new(test.storages.clone());
+
+        let cloth = server.create(&cloth_desc(SoftBodyKind::MassConstraint, vec![0, 4]));
+        test.step_n(60);
+
+        let vertices = server.vertices(cloth);
+        assert!(vertices[24].y < 1.5);
+        // The cells are 0.25 meters wide.
+        let edge = (vertices[24] - vertices[23]).norm();
+        assert!((edge - 0.25).abs() < 0.05, "The edge is {} long", edge);
+    }
+
+    #[test]
+    fn attached_vertex_follows_the_body() {
+        let test = TestWorld::<f32>::new();
+        let server = SoftBodyNpServer::new(test.storages.clone());
+
+        let cloth = server.create(&cloth_desc(SoftBodyKind::MassSpring, Vec::new()));
+        let mut pole_desc = RigidBodyDesc::default();
+        pole_desc.mode = BodyMode::Kinematic;
+        let pole = test.create_body(
+            &pole_desc,
+            &ShapeDesc::Sphere { radius: 0.1 },
+            &Isometry3::translation(-1.0, 2.0, -0.5),
+        );
+        server.attach(cloth, 0, pole.get());
+        assert_eq!(
+            server.try_attach(cloth, 25, pole.get()),
+            Err(PhysicsError::VertexNotFound)
+        );
+
+        test.bodies
+            .set_linear_velocity(pole.get(), &Vector3::new(1.0, 0.0, 0.0));
+        test.step_n(60);
+        let vertex = server.vertices(cloth)[0];
+        assert!(
+            (vertex - Point3::new(0.5, 2.0, -0.5)).norm() < 0.02,
+            "The vertex is at {:?}",
+            vertex
+        );
+
+        // The vertex is released with the body.
+        drop(pole);
+        test.step_n(30);
+        assert!(server.vertices(cloth)[0].y < 1.9);
+    }
+
+    #[test]
+    fn cloth_lands_on_the_ground() {
+        let test = TestWorld::<f32>::new();
+        let server = SoftBodyNpServer::new(test.storages.clone());
+        let _ground = test.create_ground();
+
+        let cloth = server.create(&cloth_desc(SoftBodyKind::MassSpring, Vec::new()));
+        test.step_n(120);
+
+        for vertex in server.vertices(cloth) {
+            assert!(
+                vertex.y > -0.05 && vertex.y < 0.2,
+                "The vertex is at {:?}",
+                vertex
+            );
+        }
+    }
+}
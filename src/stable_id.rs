@@ -0,0 +1,64 @@
+use amethyst_physics::{objects::*, PtReal};
+use serde::{Deserialize, Serialize};
+
+use crate::{body::BodyData, conversors::*, WorldNpServer};
+
+/// An id of a rigid body or of an area that doesn't change when the world is saved and loaded,
+/// or compacted, unlike its tag.
+///
+/// The ids are assigned when the bodies are created and are never reused, so the game can store
+/// them in its own save files and find the bodies again with
+/// `WorldNpServer::rigid_body_by_stable_id` and `WorldNpServer::area_by_stable_id`.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub struct StableId(pub u64);
+
+// Stable ids
+impl<N: PtReal> WorldNpServer<N> {
+    /// Returns the `StableId` of the rigid body.
+    pub fn rigid_body_stable_id(&self, body_tag: PhysicsRigidBodyTag) -> Option<StableId> {
+        let bodies = self.storages.bodies_r();
+        let body = bodies.get_body(rigid_tag_to_store_key(body_tag))?;
+        if matches!(body.body_data, BodyData::Rigid { .. }) {
+            Some(body.stable_id)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the `StableId` of the area.
+    pub fn area_stable_id(&self, area_tag: PhysicsAreaTag) -> Option<StableId> {
+        let bodies = self.storages.bodies_r();
+        let body = bodies.get_body(area_tag_to_store_key(area_tag))?;
+        if matches!(body.body_data, BodyData::Area(..)) {
+            Some(body.stable_id)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the rigid body with this `StableId`, if it exists.
+    pub fn rigid_body_by_stable_id(&self, stable_id: StableId) -> Option<PhysicsRigidBodyTag> {
+        let bodies = self.storages.bodies_r();
+        let key = bodies.stable_key(stable_id)?;
+        let body = bodies.get_body(key)?;
+        if matches!(body.body_data, BodyData::Rigid { .. }) {
+            Some(store_key_to_rigid_tag(key))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the area with this `StableId`, if it exists.
+    pub fn area_by_stable_id(&self, stable_id: StableId) -> Option<PhysicsAreaTag> {
+        let bodies = self.storages.bodies_r();
+        let key = bodies.stable_key(stable_id)?;
+        let body = bodies.get_body(key)?;
+        if matches!(body.body_data, BodyData::Area(..)) {
+            Some(store_key_to_area_tag(key))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use amethyst_core::math::{Isometry3, Point3};
+use amethyst_physics::{
+    objects::*,
+    servers::{BodyMode, RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc},
+    PtReal,
+};
+use nphysics3d::object::BodyStatus as NpBodyStatus;
+
+use crate::{
+    body::BodyData,
+    conversors::*,
+    error::{PhysicsError, PhysicsResult},
+    DestroyBatch, RBodyNpServer, ShapeNpServer, WorldNpServer,
+};
+
+/// The static rigid body that replaces the bodies merged by `WorldNpServer::bake_static_bodies`.
+#[allow(missing_debug_implementations)]
+pub struct BakedStaticBody {
+    pub body: PhysicsHandle<PhysicsRigidBodyTag>,
+    pub shape: PhysicsHandle<PhysicsShapeTag>,
+}
+
+// Static bake
+impl<N: PtReal> WorldNpServer<N> {
+    /// Merges the static rigid bodies into a single one, so the broad phase has to check a single
+    /// object; it's meant to be used when a level is loaded.
+    ///
+    /// The `TriMesh` shapes are merged into a single `TriMesh`, and the other shapes become the
+    /// children of a `Compound` together with it; the shapes are placed at the position of their
+    /// body. The new body takes the friction, the bounciness and the collision groups of the
+    /// first body.
+    ///
+    /// The merged bodies are destroyed before the next step, even if their `PhysicsHandle`s are
+    /// still alive.
+    /// Fails with `BodyNotFound` if there are no bodies, with `NotStatic` if a body is not static,
+    /// and with `WrongShapeType` if a body has a `Plane` shape, since it's infinite; in this case
+    /// nothing is changed.
+    pub fn bake_static_bodies(
+        &self,
+        body_tags: &[PhysicsRigidBodyTag],
+    ) -> PhysicsResult<BakedStaticBody> {
+        if body_tags.is_empty() {
+            return Err(PhysicsError::BodyNotFound);
+        }
+
+        let mut desc = RigidBodyDesc::default();
+        desc.mode = BodyMode::Static;
+
+        let mut points: Vec<Point3<N>> = Vec::new();
+        let mut indices: Vec<Point3<usize>> = Vec::new();
+        let mut children: Vec<(Isometry3<N>, ShapeDesc<N>)> = Vec::new();
+        {
+            let bodies = self.storages.bodies_r();
+            let shapes = self.storages.shapes_r();
+            for (i, body_tag) in body_tags.iter().enumerate() {
+                let body = bodies
+                    .get_body(rigid_tag_to_store_key(*body_tag))
+                    .ok_or(PhysicsError::BodyNotFound)?;
+                if !matches!(body.body_data, BodyData::Rigid { .. }) {
+                    return Err(PhysicsError::WrongBodyType);
+                }
+                if body.np_body.status() != NpBodyStatus::Static {
+                    return Err(PhysicsError::NotStatic);
+                }
+                if i == 0 {
+                    desc.friction = body.friction;
+                    desc.bounciness = body.bounciness;
+                    desc.belong_to = body.belong_to.clone();
+                    desc.collide_with = body.collide_with.clone();
+                }
+
+                let shape = body
+                    .shape_key
+                    .and_then(|key| shapes.get(key))
+                    .ok_or(PhysicsError::ShapeMissing)?;
+                let transform = *body.body_transform().ok_or(PhysicsError::WrongBodyType)?;
+                match shape.shape_desc().into_owned() {
+                    ShapeDesc::Plane => return Err(PhysicsError::WrongShapeType),
+                    ShapeDesc::TriMesh {
+                        points: mesh_points,
+                        indices: mesh_indices,
+                    } => {
+                        let offset = points.len();
+                        points.extend(mesh_points.iter().map(|p| transform * p));
+                        indices.extend(mesh_indices.iter().map(|i| i.map(|i| i + offset)));
+                    }
+                    shape_desc => children.push((transform, shape_desc)),
+                }
+            }
+        }
+
+        let shape_desc = if children.is_empty() {
+            ShapeDesc::TriMesh { points, indices }
+        } else {
+            if !points.is_empty() {
+                children.push((
+                    Isometry3::identity(),
+                    ShapeDesc::TriMesh { points, indices },
+                ));
+            }
+            ShapeDesc::Compound { shapes: children }
+        };
+
+        let shape_server = ShapeNpServer::new(self.storages.clone());
+        let body_server = RBodyNpServer::new(self.storages.clone());
+        let shape = shape_server.create_owned(shape_desc);
+        let body = body_server.create(&desc);
+        body_server.set_shape(body.get(), Some(shape.get()));
+
+        self.destroy_batch(
+            DestroyBatch {
+                bodies: body_tags.to_vec(),
+                ..DestroyBatch::default()
+            },
+            false,
+        );
+
+        Ok(BakedStaticBody { body, shape })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Point3, Vector3};
+    use amethyst_physics::servers::{
+        BodyMode, RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc, ShapePhysicsServerTrait,
+    };
+
+    use crate::{conversors::shape_tag_to_store_key, error::PhysicsError, test_world::TestWorld};
+
+    #[test]
+    fn static_bodies_baked_in_one() {
+        let test = TestWorld::<f32>::new();
+
+        let mut desc = RigidBodyDesc::default();
+        desc.mode = BodyMode::Static;
+        let cube = test.shapes.create(&ShapeDesc::Cube {
+            half_extents: Vector3::new(1.0, 1.0, 1.0),
+        });
+        let mesh = test.shapes.create(&ShapeDesc::TriMesh {
+            points: vec![
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+                Point3::new(0.0, 0.0, 1.0),
+            ],
+            indices: vec![Point3::new(0, 1, 2)],
+        });
+        let bodies: Vec<_> = [&cube, &mesh, &mesh]
+            .iter()
+            .enumerate()
+            .map(|(i, shape)| {
+                let body = test.bodies.create(&desc);
+                test.bodies.set_shape(body.get(), Some(shape.get()));
+                test.bodies.set_transform(
+                    body.get(),
+                    &Isometry3::translation(i as f32 * 10.0, 0.0, 0.0),
+                );
+                body
+            })
+            .collect();
+        let tags: Vec<_> = bodies.iter().map(|b| b.get()).collect();
+
+        let dynamic = test.bodies.create(&RigidBodyDesc::default());
+        assert_eq!(
+            test.world
+                .bake_static_bodies(&[tags[0], dynamic.get()])
+                .err(),
+            Some(PhysicsError::NotStatic)
+        );
+
+        let baked = test.world.bake_static_bodies(&tags).unwrap();
+        test.step();
+        assert_eq!(test.world.statistics().rigid_bodies, 2);
+
+        let shapes = test.storages.shapes_r();
+        let shape = shapes
+            .get(shape_tag_to_store_key(baked.shape.get()))
+            .unwrap();
+        match &*shape.shape_desc() {
+            ShapeDesc::Compound { shapes } => {
+                assert_eq!(shapes.len(), 2);
+                match &shapes[1].1 {
+                    ShapeDesc::TriMesh { points, indices } => {
+                        assert_eq!(points.len(), 6);
+                        assert_eq!(points[3], Point3::new(20.0, 0.0, 0.0));
+                        assert_eq!(indices[1], Point3::new(3, 4, 5));
+                    }
+                    _ => panic!("The meshes are not merged"),
+                }
+            }
+            _ => panic!("The baked shape is not a compound"),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use amethyst_core::math::Vector3;
+use amethyst_physics::{servers::ContactEvent, PtReal};
+use ncollide3d::{bounding_volume::BoundingVolume, query};
+use nphysics3d::{object::BodyStatus as NpBodyStatus, world::GeometricalWorld};
+
+use crate::{
+    body::{BodyData, ContactDetail, ContactState},
+    body_storage::BodyStorage,
+    collider_storage::ColliderStorage,
+    conversors::*,
+    servers_storage::WatchContactsRead,
+    storage::StoreKey,
+    utils::{ObjectType, UserData},
+    WorldNpServer,
+};
+
+// Static contacts
+impl<N: PtReal> WorldNpServer<N> {
+    /// Adds the contacts between the kinematic bodies that report the static contacts, and the
+    /// static or kinematic bodies they touch.
+    ///
+    /// NPhysics doesn't compute the contacts between two not dynamic bodies, so they are queried
+    /// here, after `fetch_events` collected the others.
+    pub(crate) fn collect_static_contacts(
+        gw: &GeometricalWorld<N, StoreKey, StoreKey>,
+        watch_contacts: &WatchContactsRead<'_>,
+        bodies: &BodyStorage<N>,
+        colliders: &ColliderStorage<N, StoreKey>,
+    ) {
+        for body_key in watch_contacts.iter() {
+            let mut body = bodies.get_body(*body_key).unwrap();
+            if !body.report_static_contacts || body.np_body.status() != NpBodyStatus::Kinematic {
+                continue;
+            }
+            let collider = if let Some(collider) = body
+                .collider_key
+                .and_then(|key| colliders.get_collider(key))
+            {
+                collider
+            } else {
+                continue;
+            };
+
+            let prediction = collider.margin();
+            let aabb = collider
+                .shape()
+                .aabb(collider.position())
+                .loosened(prediction);
+
+            let mut found = Vec::new();
+            for (_, other) in
+                gw.interferences_with_aabb(colliders, &aabb, collider.collision_groups())
+            {
+                if other.body() == *body_key || other.is_sensor() {
+                    continue;
+                }
+                let other_ud = if let Some(ud) = other
+                    .user_data()
+                    .and_then(|ud| ud.downcast_ref::<UserData>())
+                {
+                    ud
+                } else {
+                    continue;
+                };
+                // The contacts with the dynamic bodies are already reported by NPhysics.
+                let dynamic = bodies
+                    .get_body(other.body())
+                    .map_or(true, |other| other.np_body.is_dynamic());
+                if dynamic || !matches!(other_ud.object_type(), ObjectType::RigidBody) {
+                    continue;
+                }
+
+                if let Some(contact) = query::contact(
+                    collider.position(),
+                    collider.shape(),
+                    other.position(),
+                    other.shape(),
+                    prediction,
+                ) {
+                    let event = ContactEvent {
+                        other_body: store_key_to_rigid_tag(other_ud.store_key()),
+                        other_entity: other_ud.entity(),
+                        normal: -contact.normal,
+                        location: contact.world1,
+                        impulse: Vector3::zeros(),
+                    };
+                    found.push((event, QueryHit::from_user_data(other_ud)));
+                }
+            }
+
+            if let BodyData::Rigid {
+                contacts_to_report,
+                contacts,
+            } = &mut body.body_data
+            {
+                for (event, other) in found {
+                    let touching = contacts.iter().filter(|c| c.is_touching()).count();
+                    match contacts
+                        .iter_mut()
+                        .find(|c| c.event.other_body == event.other_body)
+                    {
+                        // `fetch_events` marks it as stopped, since NPhysics doesn't report it.
+                        Some(c) if c.state == ContactState::Stopped => {
+                            *c = ContactDetail {
+                                event,
+                                state: ContactState::Persisted,
+                                other,
+                            };
+                        }
+                        Some(_) => {}
+                        None if touching < *contacts_to_report => {
+                            contacts.push(ContactDetail {
+                                event,
+                                state: ContactState::Started,
+                                other,
+                            });
+                        }
+                        None => {}
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use amethyst_physics::PtReal;
+use ncollide3d::{bounding_volume::BoundingVolume, pipeline::object::CollisionGroups};
+use nphysics3d::world::GeometricalWorld;
+
+use crate::{
+    body::BodyData, body_storage::BodyStorage, collider_storage::ColliderStorage,
+    storage::StoreKey, WorldNpServer,
+};
+
+// Static moves
+impl<N: PtReal> WorldNpServer<N> {
+    /// Wakes up the sleeping dynamic rigid bodies around the areas and the static or kinematic
+    /// bodies moved since the last step.
+    ///
+    /// The sleeping bodies are not checked against the not dynamic ones, so without this they float
+    /// when their support is moved away, and the areas miss their overlaps.
+    pub(crate) fn wake_around_static_moves(
+        &self,
+        gw: &GeometricalWorld<N, StoreKey, StoreKey>,
+        bodies: &BodyStorage<N>,
+        colliders: &ColliderStorage<N, StoreKey>,
+    ) {
+        let mut moved = self.storages.static_moves_w();
+        if moved.is_empty() {
+            return;
+        }
+
+        let groups = CollisionGroups::new();
+        for moved_key in moved.drain() {
+            // The collider is still at the old position, since it follows the body during the
+            // step; so both the old and the new positions are checked.
+            let aabb = {
+                let body = if let Some(body) = bodies.get_body(moved_key) {
+                    body
+                } else {
+                    continue;
+                };
+                let collider = if let Some(collider) = body
+                    .collider_key
+                    .and_then(|key| colliders.get_collider(key))
+                {
+                    collider
+                } else {
+                    continue;
+                };
+                let aabb = collider.shape().aabb(collider.position());
+                if let Some(transform) = body.body_transform() {
+                    aabb.merged(&collider.shape().aabb(transform))
+                } else {
+                    aabb
+                }
+            };
+
+            for (_, collider) in gw.interferences_with_aabb(colliders, &aabb, &groups) {
+                let body_key = collider.body();
+                if body_key == moved_key {
+                    continue;
+                }
+                if let Some(mut body) = bodies.get_body(body_key) {
+                    if matches!(body.body_data, BodyData::Rigid { .. })
+                        && body.np_body.is_dynamic()
+                        && !body.np_body.is_active()
+                    {
+                        body.activate();
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Vector3};
+    use amethyst_physics::servers::{BodyMode, RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc};
+
+    use crate::test_world::TestWorld;
+
+    #[test]
+    fn bodies_woken_when_their_support_moves() {
+        let test = TestWorld::<f32>::new();
+        let _ground = test.create_ground();
+
+        let mut static_desc = RigidBodyDesc::default();
+        static_desc.mode = BodyMode::Static;
+        let platform_shape = ShapeDesc::Cube {
+            half_extents: Vector3::new(1.0, 0.25, 1.0),
+        };
+        let platform = test.create_body(
+            &static_desc,
+            &platform_shape,
+            &Isometry3::translation(0.0, 2.0, 0.0),
+        );
+        let _far_platform = test.create_body(
+            &static_desc,
+            &platform_shape,
+            &Isometry3::translation(5.0, 2.0, 0.0),
+        );
+
+        let sphere = ShapeDesc::Sphere { radius: 0.5 };
+        let desc = RigidBodyDesc::default();
+        let on_platform = test.create_body(&desc, &sphere, &Isometry3::translation(0.0, 2.8, 0.0));
+        let on_far_platform =
+            test.create_body(&desc, &sphere, &Isometry3::translation(5.0, 2.8, 0.0));
+        test.step_n(300);
+        test.assert_sleeping(on_platform.get());
+        test.assert_sleeping(on_far_platform.get());
+
+        test.bodies
+            .set_transform(platform.get(), &Isometry3::translation(0.0, -5.0, 0.0));
+        test.step_n(60);
+        test.assert_fallen_below(on_platform.get(), 1.0);
+        test.assert_sleeping(on_far_platform.get());
+    }
+}
@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+/// Counters about the objects of the world.
+///
+/// The number of objects is computed when the statistics are requested, while the pairs are
+/// collected during the last step.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhysicsStatistics {
+    /// Number of rigid bodies.
+    pub rigid_bodies: usize,
+    /// Number of rigid bodies that are awake.
+    pub active_rigid_bodies: usize,
+    /// Number of areas.
+    pub areas: usize,
+    /// Number of soft bodies.
+    pub soft_bodies: usize,
+    /// Number of colliders.
+    pub colliders: usize,
+    /// Number of shapes.
+    pub shapes: usize,
+    /// Number of joints.
+    pub joints: usize,
+    /// Number of collider pairs in contact, during the last step.
+    pub contact_pairs: usize,
+    /// Number of collider pairs that intersect, during the last step.
+    pub proximity_pairs: usize,
+}
+
+/// Occupancy and capacity of a storage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StorageUsage {
+    /// Number of stored objects.
+    pub len: usize,
+    /// Number of objects that can be stored before growing the storage.
+    pub capacity: usize,
+}
+
+/// The usage of each storage of the `ServersStorage`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ServersStorageUsage {
+    /// Rigid bodies and areas.
+    pub bodies: StorageUsage,
+    pub colliders: StorageUsage,
+    pub shapes: StorageUsage,
+    pub joints: StorageUsage,
+    pub force_generators: StorageUsage,
+}
+
+/// Time spent by each phase of the last step.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StepTimings {
+    /// Time spent to drop the destroyed objects.
+    pub garbage_collection: Duration,
+    /// Time spent by the `MechanicalWorld` to step the simulation.
+    pub mechanical_step: Duration,
+    /// Time spent to collect the contact and overlap events.
+    pub fetch_events: Duration,
+    /// Total step time.
+    pub total: Duration,
+}
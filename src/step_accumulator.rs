@@ -0,0 +1,101 @@
+use amethyst_core::math::zero;
+use amethyst_physics::PtReal;
+
+/// The `StepAccumulator` is used to step the world with a fixed time step, even when the world is
+/// driven by a variable frame delta.
+///
+/// Each frame, the delta is accumulated and consumed in fixed size sub steps; the time that is not
+/// enough to perform a full sub step is kept for the next frame.
+/// The leftover is exposed as `alpha` (in the range [0, 1]) and can be used to interpolate the
+/// transforms of the last two steps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepAccumulator<N: PtReal> {
+    accumulated: N,
+    max_sub_steps: usize,
+    alpha: N,
+}
+
+impl<N: PtReal> StepAccumulator<N> {
+    /// Creates an accumulator that performs at most `max_sub_steps` per frame.
+    pub fn new(max_sub_steps: usize) -> Self {
+        StepAccumulator {
+            accumulated: zero(),
+            max_sub_steps,
+            alpha: zero(),
+        }
+    }
+
+    /// Set the maximum number of sub steps that can be performed each frame.
+    ///
+    /// When the frame delta is too big, the exceeding time is discarded to avoid the
+    /// "spiral of death".
+    pub fn set_max_sub_steps(&mut self, max_sub_steps: usize) {
+        self.max_sub_steps = max_sub_steps;
+    }
+
+    pub fn max_sub_steps(&self) -> usize {
+        self.max_sub_steps
+    }
+
+    /// Returns the leftover, not yet simulated, time as a fraction of the time step.
+    pub fn alpha(&self) -> N {
+        self.alpha
+    }
+
+    /// Discard the accumulated time.
+    pub fn reset(&mut self) {
+        self.accumulated = zero();
+        self.alpha = zero();
+    }
+
+    /// Accumulate the `frame_delta` and returns the number of sub steps, of `time_step` length,
+    /// that must be performed.
+    pub fn accumulate(&mut self, frame_delta: N, time_step: N) -> usize {
+        if time_step <= zero() {
+            return 0;
+        }
+
+        self.accumulated += frame_delta;
+
+        let mut sub_steps = 0;
+        while self.accumulated >= time_step && sub_steps < self.max_sub_steps {
+            self.accumulated -= time_step;
+            sub_steps += 1;
+        }
+
+        if self.accumulated >= time_step {
+            // Too much time to simulate, keep only the fractional part.
+            self.accumulated -= (self.accumulated / time_step).floor() * time_step;
+        }
+
+        self.alpha = self.accumulated / time_step;
+        sub_steps
+    }
+}
+
+impl<N: PtReal> Default for StepAccumulator<N> {
+    fn default() -> Self {
+        StepAccumulator::new(5)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StepAccumulator;
+
+    #[test]
+    fn accumulate_sub_steps() {
+        let mut acc = StepAccumulator::<f32>::new(5);
+        assert_eq!(acc.accumulate(0.25, 0.1), 2);
+        assert!((acc.alpha() - 0.5).abs() < 0.0001);
+        assert_eq!(acc.accumulate(0.05, 0.1), 1);
+        assert!(acc.alpha().abs() < 0.0001);
+    }
+
+    #[test]
+    fn discard_exceeding_time() {
+        let mut acc = StepAccumulator::<f32>::new(2);
+        assert_eq!(acc.accumulate(1.05, 0.1), 2);
+        assert!(acc.alpha() < 1.0);
+    }
+}
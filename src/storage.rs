@@ -3,7 +3,9 @@ use std::{
     sync::{Mutex, MutexGuard},
 };
 
-use generational_arena::{Arena, Index, Iter, IterMut};
+use generational_arena::{Arena, Index};
+
+use crate::statistics::StorageUsage;
 
 pub type StoreKey = Index;
 
@@ -19,6 +21,11 @@ pub type StoreKey = Index;
 pub struct Storage<T> {
     memory: Arena<(UnsafeCell<T>, Mutex<()>)>,
     growing_size: usize,
+    /// Added to the generation of the keys of `memory`; it's raised when the storage is shrunk, so
+    /// the keys of the new objects never match the ones of the dropped objects.
+    generation_floor: u64,
+    /// The greatest generation that a returned key can have.
+    last_generation: u64,
 }
 
 impl<T> Storage<T> {
@@ -30,9 +37,33 @@ impl<T> Storage<T> {
         Storage {
             memory: Arena::with_capacity(initial_capacity),
             growing_size,
+            generation_floor: 0,
+            last_generation: 0,
         }
     }
 
+    /// Releases the memory of the storage, that must be empty, keeping `capacity` objects.
+    ///
+    /// The generations of the new keys start after the ones of the dropped objects, so the old keys
+    /// still refer to nothing.
+    pub fn shrink_to(&mut self, capacity: usize) {
+        debug_assert!(
+            self.memory.is_empty(),
+            "Only an empty storage can be shrunk."
+        );
+        self.memory = Arena::with_capacity(capacity);
+        self.growing_size = capacity.max(1);
+        self.generation_floor = self.last_generation + 1;
+        self.last_generation = self.generation_floor;
+    }
+
+    fn to_index(&self, key: StoreKey) -> Option<Index> {
+        let (index, generation) = key.into_raw_parts();
+        generation
+            .checked_sub(self.generation_floor)
+            .map(|generation| Index::from_raw_parts(index, generation))
+    }
+
     /// Takes an object and returns an opaque id.
     /// This function takes also the ownership, so to drop an object you need to call the `remove`
     /// function with the ID of the object to delete.
@@ -42,13 +73,24 @@ impl<T> Storage<T> {
             self.memory.reserve(self.growing_size);
         }
 
-        self.memory
-            .insert((UnsafeCell::new(object), Mutex::new(())))
+        let index = self
+            .memory
+            .insert((UnsafeCell::new(object), Mutex::new(())));
+        raise_generation(index, self.generation_floor)
+    }
+
+    /// Reserve the memory to insert `additional` objects without growing the storage again.
+    pub fn reserve(&mut self, additional: usize) {
+        let free = self.memory.capacity() - self.memory.len();
+        if additional > free {
+            self.memory.reserve(additional - free);
+        }
     }
 
     /// Returns true if the store key is associated to something
     pub fn has(&self, key: StoreKey) -> bool {
-        self.memory.contains(key)
+        self.to_index(key)
+            .map_or(false, |index| self.memory.contains(index))
     }
 
     /// This is the default get function that must be used in order to obtain access to the stored object.
@@ -56,8 +98,9 @@ impl<T> Storage<T> {
     /// Since the storage is using a `Mutex` to prevent data races, only this function is enough to
     /// read or to write the stored data.
     pub fn get(&self, key: StoreKey) -> Option<StorageGuard<'_, T>> {
+        let index = self.to_index(key)?;
         unsafe {
-            self.memory.get(key).map(|v| StorageGuard {
+            self.memory.get(index).map(|v| StorageGuard {
                 data: &mut *v.0.get(),
                 _guard: v.1.lock().unwrap(),
             })
@@ -70,7 +113,8 @@ impl<T> Storage<T> {
     /// to the storage because it's fully locked by RwLock which own this storage.
     /// So the borrow checker is it able to correctly prevent data races.
     pub fn unchecked_get(&self, key: StoreKey) -> Option<&T> {
-        unsafe { self.memory.get(key).map(|v| &*v.0.get()) }
+        let index = self.to_index(key)?;
+        unsafe { self.memory.get(index).map(|v| &*v.0.get()) }
     }
 
     /// This function is safe only when it's used by *NPhysics* set storages.
@@ -79,26 +123,116 @@ impl<T> Storage<T> {
     /// to the storage because it's fully locked by RwLock which own this storage.
     /// So the borrow checker is it able to correctly prevent data races.
     pub fn unchecked_get_mut(&mut self, key: StoreKey) -> Option<&mut T> {
-        unsafe { self.memory.get(key).map(|v| &mut *v.0.get()) }
+        let index = self.to_index(key)?;
+        unsafe { self.memory.get(index).map(|v| &mut *v.0.get()) }
     }
 
     /// Remove an object and release the key for future use.
     ///
     /// Returns `Some` with the removed object, or `None` if nothing was removed.
     pub fn remove(&mut self, key: StoreKey) -> Option<T> {
-        self.memory.remove(key).map(|v| v.0.into_inner())
+        let index = self.to_index(key)?;
+        let removed = self.memory.remove(index).map(|v| v.0.into_inner());
+        if removed.is_some() {
+            // The arena increases its generation at each removal.
+            self.last_generation += 1;
+        }
+        removed
+    }
+
+    /// Returns the number of stored objects.
+    pub fn len(&self) -> usize {
+        self.memory.len()
+    }
+
+    /// Returns `true` if nothing is stored.
+    pub fn is_empty(&self) -> bool {
+        self.memory.is_empty()
+    }
+
+    /// Returns the number of stored objects and the current capacity.
+    pub fn usage(&self) -> StorageUsage {
+        StorageUsage {
+            len: self.memory.len(),
+            capacity: self.memory.capacity(),
+        }
+    }
+
+    /// Remove all the objects.
+    ///
+    /// Each object is removed singularly, so the released keys are never reused.
+    pub fn clear(&mut self) {
+        let keys: Vec<StoreKey> = self.iter().map(|(key, _)| key).collect();
+        for key in keys {
+            self.remove(key);
+        }
     }
 
     /// Returns an iterator to the data.
     // TODO consider to create a for each, similar to NPhysics set trait, instead?
-    pub fn iter(&self) -> Iter<'_, (UnsafeCell<T>, Mutex<()>)> {
-        self.memory.iter()
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            inner: self.memory.iter(),
+            generation_floor: self.generation_floor,
+        }
     }
 
     /// Returns a mutable iterator to the data.
     // TODO consider to create a for each, similar to NPhysics set trait, instead?
-    pub fn iter_mut(&mut self) -> IterMut<'_, (UnsafeCell<T>, Mutex<()>)> {
-        self.memory.iter_mut()
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            inner: self.memory.iter_mut(),
+            generation_floor: self.generation_floor,
+        }
+    }
+}
+
+fn raise_generation(index: Index, generation_floor: u64) -> StoreKey {
+    let (index, generation) = index.into_raw_parts();
+    Index::from_raw_parts(index, generation + generation_floor)
+}
+
+/// Iterates the stored objects with their keys.
+#[allow(missing_debug_implementations)]
+pub struct Iter<'a, T> {
+    inner: generational_arena::Iter<'a, (UnsafeCell<T>, Mutex<()>)>,
+    generation_floor: u64,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (StoreKey, &'a (UnsafeCell<T>, Mutex<()>));
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let generation_floor = self.generation_floor;
+        self.inner
+            .next()
+            .map(|(index, v)| (raise_generation(index, generation_floor), v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+/// Iterates mutably the stored objects with their keys.
+#[allow(missing_debug_implementations)]
+pub struct IterMut<'a, T> {
+    inner: generational_arena::IterMut<'a, (UnsafeCell<T>, Mutex<()>)>,
+    generation_floor: u64,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = (StoreKey, &'a mut (UnsafeCell<T>, Mutex<()>));
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let generation_floor = self.generation_floor;
+        self.inner
+            .next()
+            .map(|(index, v)| (raise_generation(index, generation_floor), v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
     }
 }
 
@@ -134,3 +268,62 @@ impl<T> std::ops::DerefMut for StorageGuard<'_, T> {
         self.data
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Storage;
+    use crate::statistics::StorageUsage;
+
+    #[test]
+    fn usage_grows_by_the_growing_size() {
+        let mut storage = Storage::new(2, 3);
+        assert_eq!(
+            storage.usage(),
+            StorageUsage {
+                len: 0,
+                capacity: 2
+            }
+        );
+
+        storage.insert(0);
+        storage.insert(1);
+        assert_eq!(
+            storage.usage(),
+            StorageUsage {
+                len: 2,
+                capacity: 2
+            }
+        );
+
+        let key = storage.insert(2);
+        assert_eq!(
+            storage.usage(),
+            StorageUsage {
+                len: 3,
+                capacity: 5
+            }
+        );
+
+        storage.remove(key);
+        assert_eq!(storage.usage().len, 2);
+        assert_eq!(storage.usage().capacity, 5);
+    }
+
+    #[test]
+    fn shrunk_storage_keeps_the_old_keys_invalid() {
+        let mut storage = Storage::new(10, 10);
+        let old_keys: Vec<_> = (0..10).map(|i| storage.insert(i)).collect();
+        storage.clear();
+
+        storage.shrink_to(2);
+        assert_eq!(storage.usage().capacity, 2);
+        let new_keys: Vec<_> = (0..10).map(|i| storage.insert(i + 10)).collect();
+        for key in old_keys.iter() {
+            assert!(!storage.has(*key));
+            assert!(!new_keys.contains(key));
+        }
+        let iterated: Vec<_> = storage.iter().map(|(key, _)| key).collect();
+        assert_eq!(iterated, new_keys);
+        assert_eq!(*storage.get(new_keys[3]).unwrap(), 13);
+    }
+}
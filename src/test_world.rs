@@ -0,0 +1,202 @@
+//! A standalone world to write the physics integration tests without the amethyst engine.
+//!
+//! Enable it with the `test-support` feature, usually in the `dev-dependencies`.
+//!
+//! ```ignore
+//! let world = TestWorld::<f32>::new();
+//! let ball = world.bodies.create(&RigidBodyDesc::default());
+//! world.step_n(60);
+//! world.assert_fallen_below(ball.get(), 0.0);
+//! ```
+
+use amethyst_core::math::{convert, try_convert, zero, Isometry3, Vector3};
+use amethyst_physics::{
+    objects::*,
+    servers::{
+        BodyMode, RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc, ShapePhysicsServerTrait,
+        WorldPhysicsServerTrait,
+    },
+    PtReal,
+};
+
+use crate::{
+    conversors::*,
+    servers_storage::{ServersStorage, ServersStorages},
+    AreaNpServer, JointNpServer, RBodyNpServer, ShapeNpServer, WorldNpServer,
+};
+
+/// The default time step of the `TestWorld`, in seconds.
+pub const TEST_TIME_STEP: f64 = 1.0 / 60.0;
+
+/// A world with its servers, that is stepped with a fixed time step.
+///
+/// It doesn't need the `PhysicsBundle`, so the tests don't have to create the amethyst
+/// application; the servers are public, to create and change the objects.
+#[allow(missing_debug_implementations)]
+pub struct TestWorld<N: PtReal> {
+    pub storages: ServersStorages<N>,
+    pub world: WorldNpServer<N>,
+    pub bodies: RBodyNpServer<N>,
+    pub areas: AreaNpServer<N>,
+    pub shapes: ShapeNpServer<N>,
+    pub joints: JointNpServer<N>,
+    time_step: N,
+}
+
+impl<N: PtReal> TestWorld<N> {
+    /// Creates the world with the default gravity and the `TEST_TIME_STEP`.
+    pub fn new() -> Self {
+        Self::with_time_step(convert(TEST_TIME_STEP))
+    }
+
+    pub fn with_time_step(time_step: N) -> Self {
+        let storages = ServersStorage::new();
+        let world = WorldNpServer::new(storages.clone());
+        world.set_time_step(time_step);
+        TestWorld {
+            world,
+            bodies: RBodyNpServer::new(storages.clone()),
+            areas: AreaNpServer::new(storages.clone()),
+            shapes: ShapeNpServer::new(storages.clone()),
+            joints: JointNpServer::new(storages.clone()),
+            storages,
+            time_step,
+        }
+    }
+
+    pub fn step(&self) {
+        self.world.step();
+    }
+
+    /// Steps the world `steps` times.
+    pub fn step_n(&self, steps: usize) {
+        for _ in 0..steps {
+            self.world.step();
+        }
+    }
+
+    /// Steps the world for `seconds`, rounded to the closest number of steps.
+    pub fn step_for(&self, seconds: N) {
+        let steps = try_convert::<N, f64>(seconds / self.time_step).unwrap_or(0.0);
+        self.step_n(steps.round().max(0.0) as usize);
+    }
+
+    pub fn time_step(&self) -> N {
+        self.time_step
+    }
+
+    /// Creates a body with its own shape, and places it at `transform`.
+    ///
+    /// The shape is owned by the body, so it's dropped together with it.
+    pub fn create_body(
+        &self,
+        body_desc: &RigidBodyDesc<N>,
+        shape_desc: &ShapeDesc<N>,
+        transform: &Isometry3<N>,
+    ) -> PhysicsHandle<PhysicsRigidBodyTag> {
+        let shape = self.shapes.create(shape_desc);
+        let body = self.bodies.create(body_desc);
+        self.bodies.set_shape(body.get(), Some(shape.get()));
+        self.bodies.set_transform(body.get(), transform);
+        body
+    }
+
+    /// Creates a static ground, 20 x 2 x 20, with the top face at the height `0`.
+    pub fn create_ground(&self) -> PhysicsHandle<PhysicsRigidBodyTag> {
+        let mut ground_desc = RigidBodyDesc::default();
+        ground_desc.mode = BodyMode::Static;
+        self.create_body(
+            &ground_desc,
+            &ShapeDesc::Cube {
+                half_extents: Vector3::new(convert(10.0), convert(1.0), convert(10.0)),
+            },
+            &Isometry3::translation(zero(), convert(-1.0), zero()),
+        )
+    }
+
+    /// Creates a sphere, with radius `0.5`, that touches the `create_ground` ground within a
+    /// few steps.
+    pub fn create_sphere_on_ground(
+        &self,
+        body_desc: &RigidBodyDesc<N>,
+    ) -> PhysicsHandle<PhysicsRigidBodyTag> {
+        self.create_body(
+            body_desc,
+            &ShapeDesc::Sphere {
+                radius: convert(0.5),
+            },
+            &Isometry3::translation(zero(), convert(0.6), zero()),
+        )
+    }
+
+    pub fn position(&self, body_tag: PhysicsRigidBodyTag) -> Vector3<N> {
+        self.bodies.transform(body_tag).translation.vector
+    }
+
+    /// Returns `true` when the body is sleeping.
+    pub fn is_sleeping(&self, body_tag: PhysicsRigidBodyTag) -> bool {
+        self.storages
+            .bodies_r()
+            .get_body(rigid_tag_to_store_key(body_tag))
+            .map_or(false, |body| !body.np_body.is_active())
+    }
+
+    /// Panics if the body is farther than `tolerance` from `expected`.
+    pub fn assert_position(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+        expected: &Vector3<N>,
+        tolerance: N,
+    ) {
+        let position = self.position(body_tag);
+        assert!(
+            (position - expected).norm() <= tolerance,
+            "The body is at {:?}, expected {:?} within {:?}",
+            position,
+            expected,
+            tolerance
+        );
+    }
+
+    /// Panics if the linear velocity of the body is farther than `tolerance` from `expected`.
+    pub fn assert_linear_velocity(
+        &self,
+        body_tag: PhysicsRigidBodyTag,
+        expected: &Vector3<N>,
+        tolerance: N,
+    ) {
+        let velocity = self.bodies.linear_velocity(body_tag);
+        assert!(
+            (velocity - expected).norm() <= tolerance,
+            "The body moves at {:?}, expected {:?} within {:?}",
+            velocity,
+            expected,
+            tolerance
+        );
+    }
+
+    /// Panics if the body is not below the height `y`.
+    pub fn assert_fallen_below(&self, body_tag: PhysicsRigidBodyTag, y: N) {
+        let position = self.position(body_tag);
+        assert!(
+            position.y < y,
+            "The body is at {:?}, expected below {:?}",
+            position,
+            y
+        );
+    }
+
+    pub fn assert_sleeping(&self, body_tag: PhysicsRigidBodyTag) {
+        assert!(self.is_sleeping(body_tag), "The body is awake");
+    }
+
+    pub fn assert_awake(&self, body_tag: PhysicsRigidBodyTag) {
+        assert!(!self.is_sleeping(body_tag), "The body is sleeping");
+    }
+}
+
+impl<N: PtReal> Default for TestWorld<N> {
+    fn default() -> Self {
+        TestWorld::new()
+    }
+}
@@ -0,0 +1,257 @@
+use std::{collections::HashMap, sync::Arc};
+
+use amethyst_core::math::{Isometry3, Translation3};
+use amethyst_physics::{objects::*, PtReal};
+
+use crate::{
+    body::{Body, BodyData},
+    body_storage::BodyStorage,
+    conversors::*,
+    storage::StoreKey,
+    WorldNpServer,
+};
+
+/// The transforms of the rigid bodies at the end of a step, and at the end of the previous one.
+///
+/// It's immutable, so it can be read by many threads without taking any storage lock.
+#[derive(Debug, Clone, Default)]
+pub struct TransformsSnapshot<N: PtReal> {
+    /// The current and the previous transform of each body.
+    transforms: HashMap<StoreKey, (Isometry3<N>, Isometry3<N>)>,
+}
+
+impl<N: PtReal> TransformsSnapshot<N> {
+    pub fn get(&self, body_tag: PhysicsRigidBodyTag) -> Option<&Isometry3<N>> {
+        self.transforms
+            .get(&rigid_tag_to_store_key(body_tag))
+            .map(|(current, _)| current)
+    }
+
+    /// Returns the transform that the body had at the end of the previous step; the bodies
+    /// created during the last step have the current one.
+    pub fn previous(&self, body_tag: PhysicsRigidBodyTag) -> Option<&Isometry3<N>> {
+        self.transforms
+            .get(&rigid_tag_to_store_key(body_tag))
+            .map(|(_, previous)| previous)
+    }
+
+    /// Returns the transform between the previous and the current one; pass the
+    /// `WorldNpServer::interpolation_alpha` to render the bodies smoothly in accumulator mode.
+    pub fn interpolated(&self, body_tag: PhysicsRigidBodyTag, alpha: N) -> Option<Isometry3<N>> {
+        self.transforms
+            .get(&rigid_tag_to_store_key(body_tag))
+            .map(|(current, previous)| {
+                let translation = previous.translation.vector
+                    + (current.translation.vector - previous.translation.vector) * alpha;
+                Isometry3::from_parts(
+                    Translation3::from(translation),
+                    previous.rotation.slerp(&current.rotation, alpha),
+                )
+            })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (PhysicsRigidBodyTag, &Isometry3<N>)> {
+        self.transforms
+            .iter()
+            .map(|(key, (current, _))| (store_key_to_rigid_tag(*key), current))
+    }
+
+    pub fn len(&self) -> usize {
+        self.transforms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+}
+
+// Transforms snapshot
+impl<N: PtReal> WorldNpServer<N> {
+    /// Returns the transforms of the rigid bodies published by the last step.
+    ///
+    /// Unlike `RBodyPhysicsServerTrait::transform`, reading it doesn't lock the bodies storage, so
+    /// the systems that read many transforms can run in parallel with the others.
+    pub fn transforms_snapshot(&self) -> Arc<TransformsSnapshot<N>> {
+        self.transforms_snapshot.read().unwrap().clone()
+    }
+
+    /// Publishes the new transforms, and marks the bodies that moved since the previous step.
+    ///
+    /// The published snapshot is updated in place; it's copied only when a reader still holds it.
+    pub(crate) fn publish_transforms_snapshot(&self, bodies: &BodyStorage<N>) {
+        let mut published = self.transforms_snapshot.write().unwrap();
+        let snapshot = Arc::make_mut(&mut *published);
+
+        let mut rigid_bodies = 0;
+        for (key, _) in bodies.iter() {
+            let mut body = bodies.get_body(key).unwrap();
+            if !matches!(body.body_data, BodyData::Rigid { .. }) {
+                continue;
+            }
+            let transform = match body.body_transform() {
+                Some(transform) => *transform,
+                None => continue,
+            };
+            rigid_bodies += 1;
+            let (current, previous) = snapshot
+                .transforms
+                .entry(key)
+                .or_insert((transform, transform));
+            *previous = *current;
+            *current = transform;
+            if *previous != transform {
+                bodies.mark_moved(key, &mut *body);
+            }
+        }
+
+        // Some bodies were dropped.
+        if snapshot.transforms.len() > rigid_bodies {
+            snapshot
+                .transforms
+                .retain(|key, _| bodies.get_body(*key).is_some());
+        }
+    }
+}
+
+// Moved bodies
+impl<N: PtReal> WorldNpServer<N> {
+    /// Calls `f` with the transform of each rigid body that moved since the last call, so the
+    /// sleeping bodies are not synchronized each frame.
+    ///
+    /// The bodies are marked as moved at the end of each step and when they are teleported, also
+    /// while the world is paused; the new bodies are always reported the first time.
+    ///
+    /// The moved bodies are drained from a single queue, so each one is reported only to the
+    /// first reader: use only one of this, `sync_moved_bodies_f32` and the
+    /// `SinglePrecisionSyncSystem`.
+    pub fn sync_moved_bodies<F>(&self, mut f: F)
+    where
+        F: FnMut(PhysicsRigidBodyTag, &Isometry3<N>),
+    {
+        self.drain_moved_bodies(|key, body| {
+            if let Some(transform) = body.body_transform() {
+                f(store_key_to_rigid_tag(key), transform);
+            }
+        });
+    }
+
+    /// Calls `f` with each rigid body marked as moved, clearing the mark.
+    ///
+    /// Only the queued bodies are visited, so the cost doesn't depend on the sleeping ones.
+    pub(crate) fn drain_moved_bodies<F>(&self, mut f: F)
+    where
+        F: FnMut(StoreKey, &Body<N>),
+    {
+        let bodies = self.storages.bodies_r();
+        for key in bodies.take_moved() {
+            if let Some(mut body) = bodies.get_body(key) {
+                body.moved = false;
+                if matches!(body.body_data, BodyData::Rigid { .. }) {
+                    f(key, &*body);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::Isometry3;
+    use amethyst_physics::servers::{RBodyPhysicsServerTrait, RigidBodyDesc};
+
+    use crate::test_world::TestWorld;
+
+    #[test]
+    fn only_moved_bodies_synced() {
+        let test = TestWorld::<f32>::new();
+        let ground = test.create_ground();
+        let sphere = test.create_sphere_on_ground(&RigidBodyDesc::default());
+        test.bodies
+            .set_transform(sphere.get(), &Isometry3::translation(0.0, 5.0, 0.0));
+
+        let synced = || {
+            let mut synced = Vec::new();
+            test.world.sync_moved_bodies(|tag, _| synced.push(tag));
+            synced
+        };
+
+        // The new bodies are always synced.
+        assert_eq!(synced().len(), 2);
+        assert!(synced().is_empty());
+
+        test.step();
+        assert_eq!(synced(), vec![sphere.get()]);
+
+        test.bodies
+            .set_transform(ground.get(), &Isometry3::translation(0.0, -2.0, 0.0));
+        drop(sphere);
+        test.step();
+        assert_eq!(synced(), vec![ground.get()]);
+        assert_eq!(test.world.transforms_snapshot().len(), 1);
+    }
+
+    #[test]
+    fn previous_transforms_interpolated() {
+        let test = TestWorld::<f32>::new();
+        let body = test.bodies.create(&RigidBodyDesc::default());
+
+        test.step();
+        let previous = test.position(body.get());
+        test.step();
+        let current = test.position(body.get());
+
+        let snapshot = test.world.transforms_snapshot();
+        assert_eq!(
+            snapshot.previous(body.get()).unwrap().translation.vector,
+            previous
+        );
+        assert_eq!(
+            snapshot.get(body.get()).unwrap().translation.vector,
+            current
+        );
+        let halfway = snapshot.interpolated(body.get(), 0.5).unwrap();
+        assert!((halfway.translation.vector - (previous + current) * 0.5).norm() < 1.0e-6);
+    }
+
+    #[test]
+    fn snapshot_published_after_the_step() {
+        let test = TestWorld::<f32>::new();
+        let body = test.bodies.create(&RigidBodyDesc::default());
+        assert!(test.world.transforms_snapshot().is_empty());
+
+        test.step();
+        let held = test.world.transforms_snapshot();
+        assert_eq!(held.len(), 1);
+        assert_eq!(
+            held.get(body.get()),
+            Some(&test.bodies.transform(body.get()))
+        );
+
+        // The held snapshot is not changed by the next step.
+        test.step();
+        let published = test.world.transforms_snapshot();
+        assert_eq!(
+            published.get(body.get()),
+            Some(&test.bodies.transform(body.get()))
+        );
+        assert_ne!(held.get(body.get()), published.get(body.get()));
+        assert_eq!(published.iter().next().unwrap().0, body.get());
+    }
+
+    #[test]
+    fn bodies_teleported_while_paused_synced() {
+        let test = TestWorld::<f32>::new();
+        let body = test.bodies.create(&RigidBodyDesc::default());
+        test.step();
+        test.world.sync_moved_bodies(|_, _| {});
+
+        test.world.set_paused(true);
+        test.bodies
+            .set_transform(body.get(), &Isometry3::translation(0.0, 5.0, 0.0));
+        test.step();
+
+        let mut synced = Vec::new();
+        test.world.sync_moved_bodies(|tag, _| synced.push(tag));
+        assert_eq!(synced, vec![body.get()]);
+    }
+}
@@ -1,10 +1,19 @@
-use crate::storage::StoreKey;
+use crate::{extended_groups::ExtendedGroups, storage::StoreKey};
 use amethyst_core::ecs::Entity;
 
 #[derive(Copy, Clone, Debug)]
 pub(crate) enum ObjectType {
     RigidBody,
     Area,
+    SoftBody,
+}
+
+/// A small tag attached to the collider of a body, returned with its contacts and overlaps; e.g.
+/// to know which hitbox was hit without an entity for each of them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ColliderTag {
+    Id(u32),
+    Name(&'static str),
 }
 
 #[derive(Clone, Debug)]
@@ -12,6 +21,8 @@ pub(crate) struct UserData {
     object_type: ObjectType,
     store_key: StoreKey,
     entity: Option<Entity>,
+    extended_groups: ExtendedGroups,
+    collider_tag: Option<ColliderTag>,
 }
 
 impl UserData {
@@ -19,11 +30,15 @@ impl UserData {
         object_type: ObjectType,
         store_key: StoreKey,
         entity: Option<Entity>,
+        extended_groups: ExtendedGroups,
+        collider_tag: Option<ColliderTag>,
     ) -> Self {
         UserData {
             object_type,
             store_key,
             entity,
+            extended_groups,
+            collider_tag,
         }
     }
 }
@@ -38,4 +53,10 @@ impl UserData {
     pub fn entity(&self) -> Option<Entity> {
         self.entity
     }
+    pub fn extended_groups(&self) -> ExtendedGroups {
+        self.extended_groups
+    }
+    pub fn collider_tag(&self) -> Option<ColliderTag> {
+        self.collider_tag
+    }
 }
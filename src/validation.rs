@@ -0,0 +1,252 @@
+use amethyst_physics::PtReal;
+use nphysics3d::object::ColliderSet;
+
+use crate::{body::BodyData, conversors::*, servers_storage::ServersStorage};
+
+// Validation
+impl<N: PtReal> ServersStorage<N> {
+    /// Checks that the storages are consistent, and panics with the broken invariant.
+    ///
+    /// It's called by each server call and at the beginning of each step, when the `validation`
+    /// feature is enabled. It checks all the objects, so it's meant only to catch the backend bugs
+    /// in the tests.
+    pub fn validate(&self) {
+        let bodies = self.bodies_r();
+        let colliders = self.colliders_r();
+        let shapes = self.shapes_r();
+        let joints = self.joints_r();
+
+        for (key, _) in bodies.iter() {
+            let body = bodies.get_body(key).unwrap();
+
+            if let Some(collider_key) = body.collider_key {
+                let collider = colliders.get_collider(collider_key).unwrap_or_else(|| {
+                    panic!(
+                        "The body {:?} uses the collider {:?}, that doesn't exist",
+                        key, collider_key
+                    )
+                });
+                assert_eq!(
+                    collider.body(),
+                    key,
+                    "The collider {:?} of the body {:?} belongs to another body",
+                    collider_key,
+                    key
+                );
+            }
+
+            if let Some(shape_key) = body.shape_key {
+                let shape = shapes.get(shape_key).unwrap_or_else(|| {
+                    panic!(
+                        "The body {:?} uses the shape {:?}, that doesn't exist",
+                        key, shape_key
+                    )
+                });
+                assert!(
+                    shape.bodies().contains(&key),
+                    "The shape {:?} doesn't know it's used by the body {:?}",
+                    shape_key,
+                    key
+                );
+            }
+
+            if let Some(shape_key) = body.lod_shape_key {
+                let shape = shapes.get(shape_key).unwrap_or_else(|| {
+                    panic!(
+                        "The body {:?} uses the LOD shape {:?}, that doesn't exist",
+                        key, shape_key
+                    )
+                });
+                assert!(
+                    shape.lod_bodies().contains(&key),
+                    "The LOD shape {:?} doesn't know it's used by the body {:?}",
+                    shape_key,
+                    key
+                );
+            }
+
+            for joint_key in body.joints.iter() {
+                let joint = joints.get_joint(*joint_key).unwrap_or_else(|| {
+                    panic!(
+                        "The body {:?} is constrained by the joint {:?}, that doesn't exist",
+                        key, joint_key
+                    )
+                });
+                assert!(
+                    joint.body_0.map(|b| b.0) == Some(key)
+                        || joint.body_1.map(|b| b.0) == Some(key),
+                    "The joint {:?} doesn't constrain the body {:?}, that refers to it",
+                    joint_key,
+                    key
+                );
+            }
+        }
+
+        ColliderSet::foreach(&*colliders, |collider_key, collider| {
+            let body = bodies.get_body(collider.body()).unwrap_or_else(|| {
+                panic!(
+                    "The collider {:?} belongs to the body {:?}, that doesn't exist",
+                    collider_key,
+                    collider.body()
+                )
+            });
+            assert_eq!(
+                body.collider_key,
+                Some(collider_key),
+                "The collider {:?} is not used by its body {:?}",
+                collider_key,
+                collider.body()
+            );
+        });
+
+        for (key, _) in shapes.iter() {
+            let shape = shapes.get(key).unwrap();
+            for body_key in shape.bodies() {
+                let body = bodies.get_body(*body_key).unwrap_or_else(|| {
+                    panic!(
+                        "The shape {:?} is used by the body {:?}, that doesn't exist",
+                        key, body_key
+                    )
+                });
+                assert_eq!(
+                    body.shape_key,
+                    Some(key),
+                    "The shape {:?} is not used by the body {:?}, that refers to it",
+                    key,
+                    body_key
+                );
+            }
+            for body_key in shape.lod_bodies() {
+                let body = bodies.get_body(*body_key).unwrap_or_else(|| {
+                    panic!(
+                        "The LOD shape {:?} is used by the body {:?}, that doesn't exist",
+                        key, body_key
+                    )
+                });
+                assert_eq!(
+                    body.lod_shape_key,
+                    Some(key),
+                    "The LOD shape {:?} is not used by the body {:?}, that refers to it",
+                    key,
+                    body_key
+                );
+            }
+        }
+
+        for (key, _) in joints.iter() {
+            let joint = joints.get_joint(key).unwrap();
+            for (body_key, _) in joint.body_0.iter().chain(joint.body_1.iter()) {
+                let body = bodies.get_body(*body_key).unwrap_or_else(|| {
+                    panic!(
+                        "The joint {:?} constrains the body {:?}, that doesn't exist",
+                        key, body_key
+                    )
+                });
+                assert!(
+                    body.joints.contains(&key),
+                    "The body {:?} doesn't know it's constrained by the joint {:?}",
+                    body_key,
+                    key
+                );
+            }
+        }
+
+        for key in self.watch_contacts_r().iter() {
+            let body = bodies.get_body(*key).unwrap_or_else(|| {
+                panic!("The body {:?} reports the contacts, but doesn't exist", key)
+            });
+            let contacts_to_report = match body.body_data {
+                BodyData::Rigid {
+                    contacts_to_report, ..
+                } => contacts_to_report,
+                _ => 0,
+            };
+            assert!(
+                contacts_to_report > 0,
+                "The body {:?} reports the contacts, but it's not a rigid body with contacts to report",
+                key
+            );
+        }
+
+        for key in self.bodies_info().keys() {
+            assert!(
+                bodies.get_body(key).is_some(),
+                "The info of the body {:?} is kept, but the body doesn't exist",
+                key
+            );
+        }
+
+        // The GC lists can contain the tags of the objects already dropped, but not the ones of
+        // other objects types.
+        let gc = self.gc();
+        let gc = gc.read().unwrap();
+        for tag in gc.bodies.iter() {
+            if let Some(body) = bodies.get_body(rigid_tag_to_store_key(*tag)) {
+                assert!(
+                    matches!(body.body_data, BodyData::Rigid { .. }),
+                    "The rigid body {:?} to drop is not a rigid body",
+                    tag
+                );
+            }
+        }
+        for tag in gc.areas.iter() {
+            if let Some(body) = bodies.get_body(area_tag_to_store_key(*tag)) {
+                assert!(
+                    matches!(body.body_data, BodyData::Area(_)),
+                    "The area {:?} to drop is not an area",
+                    tag
+                );
+            }
+        }
+        for key in self.soft_bodies_gc_r().iter() {
+            if let Some(body) = bodies.get_body(*key) {
+                assert!(
+                    matches!(body.body_data, BodyData::Soft { .. }),
+                    "The soft body {:?} to drop is not a soft body",
+                    key
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_physics::servers::{
+        JointDesc, JointPhysicsServerTrait, JointPosition, RBodyPhysicsServerTrait, RigidBodyDesc,
+        ShapeDesc, ShapePhysicsServerTrait,
+    };
+
+    use crate::{conversors::*, test_world::TestWorld};
+
+    #[test]
+    fn consistent_storages_validated() {
+        let test = TestWorld::<f32>::new();
+        let _ground = test.create_ground();
+        let sphere = test.create_sphere_on_ground(&RigidBodyDesc::default());
+        let other = test.bodies.create(&RigidBodyDesc::default());
+        let joint = test.joints.create(&JointDesc::Fixed, JointPosition::Middle);
+        test.joints.insert_rigid_body(joint.get(), sphere.get());
+        test.joints.insert_rigid_body(joint.get(), other.get());
+        test.step_n(10);
+
+        drop(other);
+        test.step();
+        test.storages.validate();
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't know it's used by the body")]
+    fn unregistered_shape_detected() {
+        let test = TestWorld::<f32>::new();
+        let body = test.bodies.create(&RigidBodyDesc::default());
+        let shape = test.shapes.create(&ShapeDesc::Sphere { radius: 0.5 });
+
+        test.storages
+            .bodies_r()
+            .get_body(rigid_tag_to_store_key(body.get()))
+            .unwrap()
+            .shape_key = Some(shape_tag_to_store_key(shape.get()));
+        test.storages.validate();
+    }
+}
@@ -0,0 +1,371 @@
+use amethyst_core::math::{Isometry3, Point3, Unit, Vector3};
+use amethyst_physics::{
+    objects::*,
+    servers::{RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc, ShapePhysicsServerTrait},
+    PtReal,
+};
+use nphysics3d::{
+    force_generator::ForceGenerator as NpForceGenerator,
+    math::{Force, ForceType},
+    object::BodySet as NpBodySet,
+    solver::IntegrationParameters,
+};
+
+use crate::{
+    conversors::*,
+    error::{PhysicsError, PhysicsResult},
+    force_generator::{ForceGenerator, ForceGeneratorTag},
+    replay::ReplayCall,
+    storage::StoreKey,
+    ForceGeneratorNpServer, JointNpServer, RBodyNpServer, ShapeNpServer, WorldNpServer,
+};
+
+/// Describes a wheel, attached to a chassis by a suspension.
+///
+/// The wheel is a sphere that slides along the `suspension_axis`, pushed back by a spring, and
+/// rotates around the `axle`. The axes are in the local space of the chassis, and are normalized.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WheelDesc<N: PtReal> {
+    /// The center of the wheel when the spring is at rest, in the local space of the chassis.
+    pub anchor: Vector3<N>,
+    pub radius: N,
+    pub mass: N,
+    pub friction: N,
+    /// The direction the wheel slides along, pointing from the wheel to the chassis.
+    pub suspension_axis: Vector3<N>,
+    /// The axis the wheel rotates around.
+    pub axle: Vector3<N>,
+    /// The force of the spring for each unit the suspension is compressed.
+    pub stiffness: N,
+    /// The force that opposes each unit of speed of the suspension.
+    pub damping: N,
+    /// The lowest and the highest position of the wheel along the suspension axis, relative to
+    /// the anchor; the suspension stops there, rather than being only pushed back by the spring.
+    pub min_travel: N,
+    pub max_travel: N,
+    /// The angular speed, around the axle and relative to the chassis, that the motor drives the
+    /// wheel to.
+    pub target_speed: N,
+    /// The maximum torque of the motor; 0 disables it.
+    pub max_torque: N,
+}
+
+impl<N: PtReal> Default for WheelDesc<N> {
+    fn default() -> Self {
+        WheelDesc {
+            anchor: Vector3::zeros(),
+            radius: N::from(0.5),
+            mass: N::from(1.0),
+            friction: N::from(1.0),
+            suspension_axis: Vector3::y(),
+            axle: Vector3::x(),
+            stiffness: N::from(100.0),
+            damping: N::from(10.0),
+            min_travel: N::from(-0.5),
+            max_travel: N::from(0.5),
+            target_speed: N::from(0.0),
+            max_torque: N::from(0.0),
+        }
+    }
+}
+
+/// The objects of a wheel created with `WorldNpServer::create_wheel`.
+#[allow(missing_debug_implementations)]
+pub struct Wheel {
+    /// The body of the wheel; it owns its sphere shape.
+    pub body: PhysicsHandle<PhysicsRigidBodyTag>,
+    /// The suspension joint, that attaches the wheel to the chassis.
+    pub joint: PhysicsHandle<PhysicsJointTag>,
+    /// The spring of the suspension; it's destroyed together with the wheel body.
+    pub spring: ForceGeneratorTag,
+}
+
+// Wheels
+impl<N: PtReal> WorldNpServer<N> {
+    /// Creates a wheel attached to the chassis, for the physically simulated vehicles.
+    ///
+    /// The wheel is placed at its `anchor`, so it must not touch the chassis, or the two bodies
+    /// must not collide. To drive the vehicle, set the motor of the `WheelDesc`, or apply a torque
+    /// along the axle to the wheel body.
+    /// The spring is a force generator, so like the others it's not kept by the `WorldSnapshot`.
+    ///
+    /// Fails with `BodyNotFound` if the chassis doesn't exist, and with `WrongBodyType` if it's
+    /// not a rigid body.
+    pub fn create_wheel(
+        &self,
+        chassis_tag: PhysicsRigidBodyTag,
+        desc: &WheelDesc<N>,
+    ) -> PhysicsResult<Wheel> {
+        let body_server = RBodyNpServer::new(self.storages.clone());
+        let shape_server = ShapeNpServer::new(self.storages.clone());
+        let joint_server = JointNpServer::new(self.storages.clone());
+        if !body_server.is_valid(chassis_tag) {
+            return Err(PhysicsError::BodyNotFound);
+        }
+
+        let suspension_axis = Unit::new_normalize(desc.suspension_axis);
+        let axle = Unit::new_normalize(desc.axle);
+        let anchor = Isometry3::translation(desc.anchor.x, desc.anchor.y, desc.anchor.z);
+
+        let shape = shape_server.create(&ShapeDesc::Sphere {
+            radius: desc.radius,
+        });
+        let mut body_desc = RigidBodyDesc::default();
+        body_desc.mass = desc.mass;
+        body_desc.friction = desc.friction;
+        let body = body_server.create(&body_desc);
+        body_server.set_shape(body.get(), Some(shape.get()));
+        body_server.set_transform(body.get(), &(body_server.transform(chassis_tag) * anchor));
+
+        let joint = joint_server.create_suspension(
+            &anchor,
+            &Isometry3::identity(),
+            &suspension_axis,
+            &axle,
+        );
+        joint_server.try_insert_rigid_body(joint.get(), chassis_tag)?;
+        joint_server.try_insert_rigid_body(joint.get(), body.get())?;
+
+        let spring = self.create_wheel_spring(chassis_tag, body.get(), desc);
+
+        Ok(Wheel {
+            body,
+            joint,
+            spring,
+        })
+    }
+
+    /// Creates the spring of the wheel suspension, that also drives the motor; it's destroyed
+    /// together with the wheel body.
+    pub(crate) fn create_wheel_spring(
+        &self,
+        chassis_tag: PhysicsRigidBodyTag,
+        wheel_tag: PhysicsRigidBodyTag,
+        desc: &WheelDesc<N>,
+    ) -> ForceGeneratorTag {
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::CreateWheelSpring(chassis_tag, wheel_tag, desc.clone()));
+
+        let wheel_key = rigid_tag_to_store_key(wheel_tag);
+        ForceGeneratorNpServer::new(self.storages.clone()).insert(ForceGenerator::new(
+            Box::new(SpringForceGenerator {
+                chassis: rigid_tag_to_store_key(chassis_tag),
+                wheel: wheel_key,
+                anchor: Point3::from(desc.anchor),
+                axis: Unit::new_normalize(desc.suspension_axis),
+                axle: Unit::new_normalize(desc.axle),
+                mass: desc.mass,
+                stiffness: desc.stiffness,
+                damping: desc.damping,
+                min_travel: desc.min_travel,
+                max_travel: desc.max_travel,
+                target_speed: desc.target_speed,
+                max_torque: desc.max_torque,
+            }),
+            vec![wheel_key],
+        ))
+    }
+}
+
+/// Pushes the wheel back to its anchor, along the suspension axis, and the chassis away from it;
+/// stops the wheel at the travel limits, and drives its motor.
+struct SpringForceGenerator<N: PtReal> {
+    chassis: StoreKey,
+    wheel: StoreKey,
+    /// In the local space of the chassis.
+    anchor: Point3<N>,
+    /// In the local space of the chassis.
+    axis: Unit<Vector3<N>>,
+    /// In the local space of the chassis.
+    axle: Unit<Vector3<N>>,
+    /// The mass of the wheel, to push the chassis back when the wheel is stopped.
+    mass: N,
+    stiffness: N,
+    damping: N,
+    min_travel: N,
+    max_travel: N,
+    target_speed: N,
+    max_torque: N,
+}
+
+impl<N: PtReal> NpForceGenerator<N, StoreKey> for SpringForceGenerator<N> {
+    fn apply(
+        &mut self,
+        parameters: &IntegrationParameters<N>,
+        bodies: &mut dyn NpBodySet<N, Handle = StoreKey>,
+    ) {
+        let (position, velocity) = match bodies.get(self.chassis).and_then(|b| b.part(0)) {
+            Some(part) => (part.position(), part.velocity()),
+            None => return,
+        };
+        let anchor = position * self.anchor;
+        let axis = position.rotation * self.axis.into_inner();
+        let axle = position.rotation * self.axle.into_inner();
+        let anchor_velocity = velocity.linear
+            + velocity
+                .angular
+                .cross(&(anchor.coords - position.translation.vector));
+
+        let (compression, speed, spin) = match bodies.get(self.wheel).and_then(|b| b.part(0)) {
+            Some(part) => (
+                (part.position().translation.vector - anchor.coords).dot(&axis),
+                (part.velocity().linear - anchor_velocity).dot(&axis),
+                (part.velocity().angular - velocity.angular).dot(&axle),
+            ),
+            None => return,
+        };
+
+        let force = axis * (-self.stiffness * compression - self.damping * speed);
+
+        // Beyond a travel limit, the wheel is moved back within the step, unless it's already
+        // moving back fast enough.
+        let excess = if compression > self.max_travel {
+            compression - self.max_travel
+        } else if compression < self.min_travel {
+            compression - self.min_travel
+        } else {
+            N::from(0.0)
+        };
+        let stop_speed = -excess / parameters.dt() - speed;
+        let stop = if excess * stop_speed < N::from(0.0) {
+            Some(axis * stop_speed)
+        } else {
+            None
+        };
+
+        // The motor reaches the target speed with the maximum torque, and slows down within 1
+        // unit of speed from it.
+        let torque = axle
+            * ((self.target_speed - spin) * self.max_torque)
+                .max(-self.max_torque)
+                .min(self.max_torque);
+
+        if let Some(wheel) = bodies.get_mut(self.wheel) {
+            wheel.apply_force(0, &Force::new(force, torque), ForceType::Force, true);
+            if let Some(stop) = stop {
+                wheel.apply_force(0, &Force::linear(stop), ForceType::VelocityChange, true);
+            }
+        }
+        if let Some(chassis) = bodies.get_mut(self.chassis) {
+            chassis.apply_force_at_point(0, &-force, &anchor, ForceType::Force, true);
+            chassis.apply_force(0, &Force::torque(-torque), ForceType::Force, true);
+            if let Some(stop) = stop {
+                let impulse = -stop * self.mass;
+                chassis.apply_force_at_point(0, &impulse, &anchor, ForceType::Impulse, true);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Vector3};
+    use amethyst_physics::servers::{RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc};
+
+    use super::WheelDesc;
+    use crate::test_world::TestWorld;
+
+    #[test]
+    fn wheels_roll_and_suspension_compresses() {
+        let test = TestWorld::<f32>::new();
+        let _ground = test.create_ground();
+
+        let mut chassis_desc = RigidBodyDesc::default();
+        chassis_desc.mass = 10.0;
+        let chassis = test.create_body(
+            &chassis_desc,
+            &ShapeDesc::Cube {
+                half_extents: Vector3::new(1.0, 0.25, 0.5),
+            },
+            &Isometry3::translation(0.0, 0.6, 0.0),
+        );
+
+        // The wheels are beside the chassis, so they never touch it.
+        let wheels: Vec<_> = [(0.7, 0.85), (0.7, -0.85), (-0.7, 0.85), (-0.7, -0.85)]
+            .iter()
+            .map(|(x, z)| {
+                let desc = WheelDesc {
+                    anchor: Vector3::new(*x, -0.3, *z),
+                    radius: 0.3,
+                    axle: Vector3::z(),
+                    stiffness: 200.0,
+                    damping: 20.0,
+                    ..WheelDesc::default()
+                };
+                test.world.create_wheel(chassis.get(), &desc).unwrap()
+            })
+            .collect();
+
+        test.step_n(120);
+        let chassis_y = test.position(chassis.get()).y;
+        for wheel in wheels.iter() {
+            let compression = test.position(wheel.body.get()).y - (chassis_y - 0.3);
+            assert!(
+                compression > 0.05 && compression < 0.3,
+                "The suspension is compressed by {}",
+                compression
+            );
+        }
+
+        for _ in 0..60 {
+            test.bodies
+                .apply_force(chassis.get(), &Vector3::new(60.0, 0.0, 0.0));
+            test.step();
+        }
+        assert!(test.position(chassis.get()).x > 0.5);
+        for wheel in wheels.iter() {
+            // Rolling towards +x, the wheels rotate around -z.
+            assert!(test.bodies.angular_velocity(wheel.body.get()).z < -1.0);
+        }
+    }
+
+    #[test]
+    fn motor_drives_and_travel_limits_the_suspension() {
+        let test = TestWorld::<f32>::new();
+        let _ground = test.create_ground();
+
+        let mut chassis_desc = RigidBodyDesc::default();
+        chassis_desc.mass = 10.0;
+        let chassis = test.create_body(
+            &chassis_desc,
+            &ShapeDesc::Cube {
+                half_extents: Vector3::new(1.0, 0.25, 0.5),
+            },
+            &Isometry3::translation(0.0, 0.6, 0.0),
+        );
+
+        // The springs are too soft to hold the chassis, so it rests on the travel limits.
+        let wheels: Vec<_> = [(0.7, 0.85), (0.7, -0.85), (-0.7, 0.85), (-0.7, -0.85)]
+            .iter()
+            .map(|(x, z)| {
+                let desc = WheelDesc {
+                    anchor: Vector3::new(*x, -0.3, *z),
+                    radius: 0.3,
+                    axle: Vector3::z(),
+                    stiffness: 1.0,
+                    damping: 20.0,
+                    max_travel: 0.1,
+                    target_speed: -10.0,
+                    max_torque: 5.0,
+                    ..WheelDesc::default()
+                };
+                test.world.create_wheel(chassis.get(), &desc).unwrap()
+            })
+            .collect();
+
+        test.step_n(120);
+        let chassis_y = test.position(chassis.get()).y;
+        for wheel in wheels.iter() {
+            let compression = test.position(wheel.body.get()).y - (chassis_y - 0.3);
+            assert!(
+                compression < 0.12,
+                "The suspension is compressed by {}",
+                compression
+            );
+            // Rolling towards +x, the wheels rotate around -z.
+            assert!(test.bodies.angular_velocity(wheel.body.get()).z < -1.0);
+        }
+        assert!(test.position(chassis.get()).x > 0.5);
+    }
+}
@@ -0,0 +1,158 @@
+use amethyst_core::math::{convert, try_convert, Vector3};
+use amethyst_physics::PtReal;
+use ncollide3d::bounding_volume::AABB;
+use nphysics3d::{
+    force_generator::ForceGenerator as NpForceGenerator,
+    math::{Force, ForceType},
+    object::{Body as NpBody, BodySet as NpBodySet},
+    solver::IntegrationParameters,
+};
+
+use crate::storage::StoreKey;
+
+/// Describes a wind.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindDesc<N: PtReal> {
+    /// The direction of the wind, it's normalized.
+    pub direction: Vector3<N>,
+    /// The force applied to each body.
+    pub strength: N,
+    /// The region where the wind blows, or `None` to blow everywhere.
+    pub zone: Option<AABB<N>>,
+    /// The amplitude of the gusts, as a fraction of the strength. 0 disables the gusts.
+    pub turbulence: N,
+    /// How many gusts per second, on average.
+    pub gust_frequency: N,
+}
+
+impl<N: PtReal> Default for WindDesc<N> {
+    fn default() -> Self {
+        WindDesc {
+            direction: Vector3::x(),
+            strength: N::from(1.0),
+            zone: None,
+            turbulence: N::from(0.0),
+            gust_frequency: N::from(0.5),
+        }
+    }
+}
+
+/// Applies the wind force to all the dynamic bodies in its zone.
+///
+/// The sleeping bodies are not woken up.
+pub(crate) struct WindForceGenerator<N: PtReal> {
+    desc: WindDesc<N>,
+    /// Elapsed time, used to compute the gusts.
+    time: f64,
+}
+
+impl<N: PtReal> WindForceGenerator<N> {
+    pub(crate) fn new(mut desc: WindDesc<N>) -> Self {
+        desc.direction = desc
+            .direction
+            .try_normalize(N::default_epsilon())
+            .unwrap_or_else(Vector3::zeros);
+        WindForceGenerator { desc, time: 0.0 }
+    }
+
+    /// Returns the current strength, gusts included.
+    fn strength(&self) -> N {
+        let frequency: f64 = try_convert(self.desc.gust_frequency).unwrap_or(0.0);
+        let gust: N = convert(value_noise(self.time * frequency));
+        self.desc.strength * (N::from(1.0) + self.desc.turbulence * gust)
+    }
+}
+
+impl<N: PtReal> NpForceGenerator<N, StoreKey> for WindForceGenerator<N> {
+    fn apply(
+        &mut self,
+        parameters: &IntegrationParameters<N>,
+        bodies: &mut dyn NpBodySet<N, Handle = StoreKey>,
+    ) {
+        self.time += try_convert::<N, f64>(parameters.dt()).unwrap_or(0.0);
+
+        let force = Force::linear(self.desc.direction * self.strength());
+        let zone = &self.desc.zone;
+        bodies.foreach_mut(&mut |_, body: &mut dyn NpBody<N>| {
+            if !body.is_dynamic() {
+                return;
+            }
+            if let Some(zone) = zone {
+                let position = if let Some(part) = body.part(0) {
+                    part.position().translation.vector
+                } else {
+                    return;
+                };
+                let inside =
+                    (0..3).all(|i| zone.mins()[i] <= position[i] && position[i] <= zone.maxs()[i]);
+                if !inside {
+                    return;
+                }
+            }
+            body.apply_force(0, &force, ForceType::Force, false);
+        });
+    }
+}
+
+/// Smooth 1D noise in the range [-1, 1].
+fn value_noise(x: f64) -> f64 {
+    let i = x.floor();
+    let t = x - i;
+    let t = t * t * (3.0 - 2.0 * t);
+    let a = lattice_value(i as i64);
+    let b = lattice_value(i as i64 + 1);
+    a + (b - a) * t
+}
+
+/// Deterministic pseudo random value in the range [-1, 1].
+fn lattice_value(i: i64) -> f64 {
+    let mut h = (i as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    h ^= h >> 32;
+    h = h.wrapping_mul(0xD6E8_FEB8_6659_FD93);
+    h ^= h >> 32;
+    (h as f64 / u64::max_value() as f64) * 2.0 - 1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Point3, Vector3};
+    use amethyst_physics::servers::{RigidBodyDesc, ShapeDesc, WorldPhysicsServerTrait};
+    use ncollide3d::bounding_volume::AABB;
+
+    use super::{value_noise, WindDesc};
+    use crate::{test_world::TestWorld, ForceGeneratorNpServer};
+
+    #[test]
+    fn noise_in_range_and_continuous() {
+        let mut previous = value_noise(0.0);
+        for i in 1..1000 {
+            let value = value_noise(f64::from(i) * 0.01);
+            assert!(value >= -1.0 && value <= 1.0);
+            assert!((value - previous).abs() < 0.1);
+            previous = value;
+        }
+    }
+
+    #[test]
+    fn wind_blows_in_its_zone() {
+        let test = TestWorld::<f32>::new();
+        test.world.set_gravity(&Vector3::zeros());
+
+        let sphere = ShapeDesc::Sphere { radius: 0.5 };
+        let desc = RigidBodyDesc::default();
+        let inside = test.create_body(&desc, &sphere, &Isometry3::identity());
+        let outside = test.create_body(&desc, &sphere, &Isometry3::translation(0.0, 10.0, 0.0));
+
+        ForceGeneratorNpServer::new(test.storages.clone()).create_wind(&WindDesc {
+            zone: Some(AABB::new(
+                Point3::new(-5.0, -5.0, -5.0),
+                Point3::new(5.0, 5.0, 5.0),
+            )),
+            ..WindDesc::default()
+        });
+        test.step_n(10);
+
+        assert!(test.position(inside.get()).x > 0.0);
+        assert_eq!(test.position(outside.get()).x, 0.0);
+    }
+}
@@ -0,0 +1,138 @@
+use amethyst_core::math::Point3;
+use amethyst_physics::{objects::*, PtReal};
+use ncollide3d::bounding_volume::AABB;
+use nphysics3d::object::BodyStatus as NpBodyStatus;
+
+use crate::{
+    activation_regions::contains, body::BodyData, body_storage::BodyStorage, conversors::*,
+    events::PhysicsEvent, WorldNpServer,
+};
+
+/// What happens to the rigid bodies that escape the `WorldBounds`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutOfBoundsAction {
+    /// The body is disabled, so it's no more simulated; set its mode to wake it up again.
+    Disable,
+    /// The body is destroyed before the next step, even if its `PhysicsHandle` is still alive.
+    Destroy,
+}
+
+/// The playable area of the world.
+///
+/// The dynamic rigid bodies that escape it are reported with the `PhysicsEvent::OutOfBounds`,
+/// and are disabled or destroyed, so they don't fall forever wasting the broad phase time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldBounds<N: PtReal> {
+    /// The bodies below this height are out of bounds.
+    pub min_y: Option<N>,
+    /// The bodies outside this box are out of bounds.
+    pub aabb: Option<AABB<N>>,
+    pub action: OutOfBoundsAction,
+}
+
+impl<N: PtReal> WorldBounds<N> {
+    pub fn contains(&self, point: &Point3<N>) -> bool {
+        self.min_y.map_or(true, |min_y| point.y >= min_y)
+            && self
+                .aabb
+                .as_ref()
+                .map_or(true, |aabb| contains(aabb, point))
+    }
+}
+
+// World bounds
+impl<N: PtReal> WorldNpServer<N> {
+    /// Set the playable area of the world; `None` (default) doesn't check the bodies.
+    pub fn set_world_bounds(&self, bounds: Option<WorldBounds<N>>) {
+        *self.world_bounds.write().unwrap() = bounds;
+    }
+
+    pub fn world_bounds(&self) -> Option<WorldBounds<N>> {
+        self.world_bounds.read().unwrap().clone()
+    }
+
+    /// Disables the dynamic rigid bodies out of the bounds, and reports them.
+    ///
+    /// Returns the bodies to destroy; they can't be pushed to the garbage collector here, since
+    /// it's locked before the storages.
+    pub(crate) fn apply_world_bounds(&self, bodies: &BodyStorage<N>) -> Vec<PhysicsRigidBodyTag> {
+        let bounds = self.world_bounds.read().unwrap();
+        let bounds = match bounds.as_ref() {
+            Some(bounds) => bounds,
+            None => return Vec::new(),
+        };
+
+        let mut escaped = Vec::new();
+        let mut events = Vec::new();
+        for (key, _) in bodies.iter() {
+            let mut body = bodies.get_body(key).unwrap();
+            if !matches!(body.body_data, BodyData::Rigid { .. }) || !body.np_body.is_dynamic() {
+                continue;
+            }
+            let position = match body.body_transform() {
+                Some(transform) => Point3::from(transform.translation.vector),
+                None => continue,
+            };
+            if bounds.contains(&position) {
+                continue;
+            }
+
+            // The disabled bodies are not dynamic, so each body is reported once.
+            body.np_body.set_status(NpBodyStatus::Disabled);
+            let tag = store_key_to_rigid_tag(key);
+            if bounds.action == OutOfBoundsAction::Destroy {
+                escaped.push(tag);
+            }
+            events.push(PhysicsEvent::OutOfBounds {
+                body: tag,
+                entity: body.entity,
+                destroyed: bounds.action == OutOfBoundsAction::Destroy,
+            });
+        }
+        self.push_events(events);
+        escaped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_physics::servers::{RBodyPhysicsServerTrait, RigidBodyDesc};
+
+    use super::{OutOfBoundsAction, WorldBounds};
+    use crate::{conversors::rigid_tag_to_store_key, test_world::TestWorld, PhysicsEvent};
+
+    #[test]
+    fn body_out_of_bounds_destroyed() {
+        let test = TestWorld::<f32>::new();
+
+        let body = test.bodies.create(&RigidBodyDesc::default());
+        let body_tag = body.get();
+        test.world.set_world_bounds(Some(WorldBounds {
+            min_y: Some(-0.1),
+            aabb: None,
+            action: OutOfBoundsAction::Destroy,
+        }));
+
+        let mut out_of_bounds = Vec::new();
+        for _ in 0..30 {
+            test.step();
+            out_of_bounds.extend(
+                test.world
+                    .drain_events()
+                    .into_iter()
+                    .filter_map(|e| match e {
+                        PhysicsEvent::OutOfBounds {
+                            body, destroyed, ..
+                        } => Some((body, destroyed)),
+                        _ => None,
+                    }),
+            );
+        }
+        assert_eq!(out_of_bounds, vec![(body_tag, true)]);
+        assert!(test
+            .storages
+            .bodies_r()
+            .get_body(rigid_tag_to_store_key(body_tag))
+            .is_none());
+    }
+}
@@ -0,0 +1,241 @@
+use amethyst_core::math::Vector3;
+use amethyst_physics::PtReal;
+use nphysics3d::{
+    solver::{SignoriniCoulombPyramidModel, SignoriniModel},
+    world::MechanicalWorld,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{storage::StoreKey, WorldNpServer};
+
+/// The friction approximation used by the solver.
+///
+/// NPhysics doesn't have a cone model, so the friction can only be approximated with a pyramid
+/// or disabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FrictionModel {
+    /// The friction cone is approximated with a pyramid built on two tangent directions, so the
+    /// friction is slightly stronger along the diagonals.
+    Pyramid,
+    /// No friction; useful for the top-down games that slow down the bodies with the damping, and
+    /// would notice the anisotropy of the pyramid.
+    Frictionless,
+}
+
+impl FrictionModel {
+    /// Set the contact model of the solver.
+    pub(crate) fn install<N: PtReal>(self, mw: &mut MechanicalWorld<N, StoreKey, StoreKey>) {
+        match self {
+            FrictionModel::Pyramid => mw
+                .solver
+                .set_contact_model(Box::new(SignoriniCoulombPyramidModel::new())),
+            FrictionModel::Frictionless => {
+                mw.solver.set_contact_model(Box::new(SignoriniModel::new()))
+            }
+        }
+    }
+}
+
+/// The tunable parameters of the world.
+///
+/// It can be deserialized from any `serde` format, like RON, so it's possible to tune the world
+/// while the game is running.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldConfig<N: PtReal> {
+    pub gravity: Vector3<N>,
+    pub time_step: N,
+    /// Number of iterations performed by the solver to resolve the velocity constraints.
+    pub max_velocity_iterations: usize,
+    /// Number of iterations performed by the solver to resolve the position constraints.
+    pub max_position_iterations: usize,
+    /// The impact velocity below which the restitution is ignored, so the slightly bouncy bodies
+    /// come to rest instead of bouncing forever.
+    pub restitution_velocity_threshold: N,
+    pub friction_model: FrictionModel,
+    /// The kinetic energy below which the rigid bodies fall asleep.
+    ///
+    /// It's applied to all the existing rigid bodies, `None` doesn't change them.
+    pub sleep_threshold: Option<N>,
+}
+
+impl<N: PtReal> Default for WorldConfig<N> {
+    fn default() -> Self {
+        WorldConfig {
+            gravity: Vector3::new(N::from(0.0), N::from(-9.8), N::from(0.0)),
+            time_step: N::from(1.0 / 60.0),
+            max_velocity_iterations: 8,
+            max_position_iterations: 3,
+            restitution_velocity_threshold: N::from(1.0),
+            friction_model: FrictionModel::Pyramid,
+            sleep_threshold: None,
+        }
+    }
+}
+
+// Config
+impl<N: PtReal> WorldNpServer<N> {
+    /// Set the configuration of the world, that is applied before the next step.
+    ///
+    /// Calling it more times between two steps, only the last configuration is applied.
+    pub fn set_config(&self, config: WorldConfig<N>) {
+        *self.pending_config.write().unwrap() = Some(config);
+    }
+
+    /// Returns the configuration currently in use.
+    ///
+    /// The `sleep_threshold` is the last one applied.
+    pub fn config(&self) -> WorldConfig<N> {
+        let mw = self.mechanical_world.read().unwrap();
+        WorldConfig {
+            gravity: mw.gravity,
+            time_step: mw.timestep(),
+            max_velocity_iterations: mw.integration_parameters.max_velocity_iterations,
+            max_position_iterations: mw.integration_parameters.max_position_iterations,
+            restitution_velocity_threshold: mw
+                .integration_parameters
+                .restitution_velocity_threshold,
+            friction_model: *self.friction_model.read().unwrap(),
+            sleep_threshold: *self.sleep_threshold.read().unwrap(),
+        }
+    }
+
+    /// Applies the pending configuration, if any.
+    pub(crate) fn apply_pending_config(&self) {
+        let config = if let Some(config) = self.pending_config.write().unwrap().take() {
+            config
+        } else {
+            return;
+        };
+
+        {
+            let mut mw = self.mechanical_world.write().unwrap();
+            mw.gravity = config.gravity;
+            mw.set_timestep(config.time_step);
+            mw.integration_parameters.max_velocity_iterations = config.max_velocity_iterations;
+            mw.integration_parameters.max_position_iterations = config.max_position_iterations;
+            mw.integration_parameters.restitution_velocity_threshold =
+                config.restitution_velocity_threshold;
+
+            // Replacing the contact model drops its warm start, so it's done only when it changes.
+            let mut friction_model = self.friction_model.write().unwrap();
+            if *friction_model != config.friction_model {
+                config.friction_model.install(&mut mw);
+                *friction_model = config.friction_model;
+            }
+        }
+
+        if let Some(sleep_threshold) = config.sleep_threshold {
+            let bodies = self.storages.bodies_r();
+            for (key, _) in bodies.iter() {
+                let mut body = bodies.get_body(key).unwrap();
+                if let Some(rb) = body.rigid_body_mut() {
+                    rb.set_deactivation_threshold(Some(sleep_threshold));
+                }
+            }
+            *self.sleep_threshold.write().unwrap() = Some(sleep_threshold);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Vector3};
+    use amethyst_physics::servers::{BodyMode, RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc};
+
+    use super::{FrictionModel, WorldConfig};
+    use crate::test_world::TestWorld;
+
+    #[test]
+    fn config_applied_at_the_next_step() {
+        let test = TestWorld::<f32>::new();
+        let initial = test.world.config();
+
+        test.world.set_config(WorldConfig {
+            gravity: Vector3::new(0.0, -1.0, 0.0),
+            ..WorldConfig::default()
+        });
+        let config = WorldConfig {
+            gravity: Vector3::new(0.0, -20.0, 0.0),
+            time_step: 0.01,
+            max_velocity_iterations: 12,
+            friction_model: FrictionModel::Frictionless,
+            sleep_threshold: Some(0.5),
+            ..WorldConfig::default()
+        };
+        test.world.set_config(config.clone());
+        assert_eq!(test.world.config(), initial);
+
+        test.step();
+        assert_eq!(test.world.config(), config);
+    }
+
+    #[test]
+    fn slow_impacts_not_bouncing() {
+        // Returns the highest upward velocity of a bouncy sphere dropped from 1 meter.
+        let bounce = |restitution_velocity_threshold: f32| {
+            let test = TestWorld::<f32>::new();
+            test.world.set_config(WorldConfig {
+                restitution_velocity_threshold,
+                ..WorldConfig::default()
+            });
+
+            let mut desc = RigidBodyDesc::default();
+            desc.bounciness = 1.0;
+            desc.mode = BodyMode::Static;
+            let _ground = test.create_body(
+                &desc,
+                &ShapeDesc::Cube {
+                    half_extents: Vector3::new(10.0, 1.0, 10.0),
+                },
+                &Isometry3::translation(0.0, -1.0, 0.0),
+            );
+            desc.mode = BodyMode::Dynamic;
+            let sphere = test.create_body(
+                &desc,
+                &ShapeDesc::Sphere { radius: 0.5 },
+                &Isometry3::translation(0.0, 1.5, 0.0),
+            );
+
+            (0..60)
+                .map(|_| {
+                    test.step();
+                    test.bodies.linear_velocity(sphere.get()).y
+                })
+                .fold(0.0, f32::max)
+        };
+
+        // The sphere hits the ground at about 4.4 m/s.
+        assert!(bounce(1.0) > 2.0);
+        assert!(bounce(10.0) < 0.5);
+    }
+
+    #[test]
+    fn frictionless_model_keeps_the_sliding_speed() {
+        // Returns the speed of a box, resting on the ground, one second after being pushed.
+        let slide = |friction_model: FrictionModel| {
+            let test = TestWorld::<f32>::new();
+            test.world.set_config(WorldConfig {
+                friction_model,
+                ..WorldConfig::default()
+            });
+            let _ground = test.create_ground();
+            let cube = test.create_body(
+                &RigidBodyDesc::default(),
+                &ShapeDesc::Cube {
+                    half_extents: Vector3::repeat(0.5),
+                },
+                &Isometry3::translation(0.0, 0.5, 0.0),
+            );
+            test.step();
+
+            test.bodies
+                .set_linear_velocity(cube.get(), &Vector3::new(3.0, 0.0, 0.0));
+            test.step_n(60);
+            test.bodies.linear_velocity(cube.get()).x
+        };
+
+        assert!(slide(FrictionModel::Pyramid) < 0.5);
+        assert!(slide(FrictionModel::Frictionless) > 2.9);
+    }
+}
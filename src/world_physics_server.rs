@@ -1,4 +1,9 @@
-use std::sync::RwLock;
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex, RwLock},
+    thread::JoinHandle,
+    time::Instant,
+};
 
 use amethyst_core::math::Vector3;
 use amethyst_physics::{
@@ -6,192 +11,694 @@ use amethyst_physics::{
     servers::{ContactEvent, OverlapEvent, WorldPhysicsServerTrait},
     PtReal,
 };
-use ncollide3d::query::Proximity;
+use ncollide3d::{
+    bounding_volume::AABB,
+    broad_phase::DBVTBroadPhase,
+    narrow_phase::{ContactDispatcher, NarrowPhase, ProximityDispatcher, ProximityEvent},
+    query::Proximity,
+};
 use nphysics3d::world::{GeometricalWorld, MechanicalWorld};
+use rayon::prelude::*;
 
 use crate::{
-    body::BodyData,
+    body::{BodyData, ContactDetail, ContactEvents, ContactState, OverlapDetail},
+    body_storage::BodyStorage,
+    checksum::StateHasher,
+    collider_storage::ColliderStorage,
+    contact_events::ContactStatusEvent,
     conversors::*,
+    error::{LogError, PhysicsError, PhysicsResult},
+    events::PhysicsEvent,
+    extended_groups::ExtendedGroupsFilter,
+    lod::LodSettings,
+    query::{PhysicsObject, QueryHit},
+    replay::ReplayCall,
+    rollback::{BodyState, RollbackBuffer},
     servers_storage::{
         BodiesStorageWrite, CollidersStorageWrite, ServersStorages, WatchContactsRead,
     },
+    statistics::{PhysicsStatistics, StepTimings},
+    step_accumulator::StepAccumulator,
     storage::StoreKey,
+    transforms_snapshot::TransformsSnapshot,
     utils::*,
-    AreaNpServer, JointNpServer, RBodyNpServer, ShapeNpServer,
+    world_bounds::WorldBounds,
+    world_config::{FrictionModel, WorldConfig},
+    AreaNpServer, JointNpServer, RBodyNpServer, ShapeNpServer, SoftBodyNpServer,
 };
 
+/// The continuous collision detection settings of the world.
+///
+/// The CCD is performed only for the bodies that have it enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CcdSettings {
+    /// When `false` the CCD is not performed, even for the bodies that have it enabled.
+    pub enabled: bool,
+    /// Maximum number of substeps performed to resolve the time of impacts.
+    pub max_substeps: usize,
+    /// Maximum number of position iterations performed for each substep.
+    pub max_position_iterations: usize,
+}
+
+impl Default for CcdSettings {
+    fn default() -> Self {
+        CcdSettings {
+            enabled: true,
+            max_substeps: 1,
+            max_position_iterations: 10,
+        }
+    }
+}
+
+/// The contact and the proximity dispatchers used by the narrow phase.
+pub type NarrowPhaseDispatchers<N> = (
+    Box<dyn ContactDispatcher<N>>,
+    Box<dyn ProximityDispatcher<N>>,
+);
+
+type NarrowPhaseDispatchersFactory<N> = dyn Fn() -> NarrowPhaseDispatchers<N> + Send + Sync;
+
+/// The `WorldNpServer` can be cloned; all the clones share the same world.
+///
+/// This is useful to keep a reference to the server, and so use the functionalities that are not
+/// part of the `WorldPhysicsServerTrait`, after it's moved inside the `PhysicsWorld`.
 pub struct WorldNpServer<N: PtReal> {
     pub storages: ServersStorages<N>,
-    pub geometrical_world: RwLock<GeometricalWorld<N, StoreKey, StoreKey>>,
-    pub mechanical_world: RwLock<MechanicalWorld<N, StoreKey, StoreKey>>,
+    pub geometrical_world: Arc<RwLock<GeometricalWorld<N, StoreKey, StoreKey>>>,
+    pub mechanical_world: Arc<RwLock<MechanicalWorld<N, StoreKey, StoreKey>>>,
+    step_accumulator: Arc<RwLock<StepAccumulator<N>>>,
+    rollback: Arc<RwLock<RollbackBuffer<Vec<BodyState<N>>>>>,
+    paused: Arc<RwLock<bool>>,
+    /// Contact and proximity pairs of the last step.
+    step_pairs: Arc<RwLock<(usize, usize)>>,
+    step_timings: Arc<RwLock<StepTimings>>,
+    /// Events of the last step, and of all the steps.
+    step_events: Arc<RwLock<(usize, u64)>>,
+    narrow_phase_dispatchers: Arc<RwLock<Option<Box<NarrowPhaseDispatchersFactory<N>>>>>,
+    ccd_settings: Arc<RwLock<CcdSettings>>,
+    pub(crate) events: Arc<RwLock<Vec<PhysicsEvent<N>>>>,
+    /// `None` until the `ContactEventsSystem` is created.
+    pub(crate) contact_status_events: Arc<RwLock<Option<Vec<ContactStatusEvent>>>>,
+    pub(crate) activation_regions: Arc<RwLock<Vec<AABB<N>>>>,
+    pub(crate) lod_settings: Arc<RwLock<LodSettings<N>>>,
+    pub(crate) pending_config: Arc<RwLock<Option<WorldConfig<N>>>>,
+    pub(crate) sleep_threshold: Arc<RwLock<Option<N>>>,
+    pub(crate) friction_model: Arc<RwLock<FrictionModel>>,
+    pub(crate) async_step: Arc<Mutex<Option<JoinHandle<()>>>>,
+    pub(crate) transforms_snapshot: Arc<RwLock<Arc<TransformsSnapshot<N>>>>,
+    /// The areas that got overlap events during the last step, so only them are cleared.
+    pub(crate) areas_with_events: Arc<Mutex<Vec<StoreKey>>>,
+    pub(crate) world_bounds: Arc<RwLock<Option<WorldBounds<N>>>>,
 }
 
 impl<N: PtReal> WorldNpServer<N> {
     pub fn new(storages: ServersStorages<N>) -> WorldNpServer<N> {
-        WorldNpServer {
+        let server = WorldNpServer {
             storages,
-            geometrical_world: RwLock::new(GeometricalWorld::new()),
-            mechanical_world: RwLock::new(MechanicalWorld::new(Vector3::new(
+            geometrical_world: Arc::new(RwLock::new(GeometricalWorld::new())),
+            mechanical_world: Arc::new(RwLock::new(MechanicalWorld::new(Vector3::new(
                 N::from(0.0),
                 N::from(-9.8),
                 N::from(0.0),
-            ))),
+            )))),
+            step_accumulator: Arc::new(RwLock::new(StepAccumulator::default())),
+            rollback: Arc::new(RwLock::new(RollbackBuffer::default())),
+            paused: Arc::new(RwLock::new(false)),
+            step_pairs: Arc::new(RwLock::new((0, 0))),
+            step_timings: Arc::new(RwLock::new(StepTimings::default())),
+            step_events: Arc::new(RwLock::new((0, 0))),
+            narrow_phase_dispatchers: Arc::new(RwLock::new(None)),
+            ccd_settings: Arc::new(RwLock::new(CcdSettings::default())),
+            events: Arc::new(RwLock::new(Vec::new())),
+            contact_status_events: Arc::new(RwLock::new(None)),
+            activation_regions: Arc::new(RwLock::new(Vec::new())),
+            lod_settings: Arc::new(RwLock::new(LodSettings::default())),
+            pending_config: Arc::new(RwLock::new(None)),
+            sleep_threshold: Arc::new(RwLock::new(None)),
+            friction_model: Arc::new(RwLock::new(FrictionModel::Pyramid)),
+            async_step: Arc::new(Mutex::new(None)),
+            transforms_snapshot: Arc::new(RwLock::new(Arc::new(TransformsSnapshot::default()))),
+            areas_with_events: Arc::new(Mutex::new(Vec::new())),
+            world_bounds: Arc::new(RwLock::new(None)),
+        };
+        server.set_ccd_settings(CcdSettings::default());
+        server
+    }
+}
+
+impl<N: PtReal> Clone for WorldNpServer<N> {
+    fn clone(&self) -> Self {
+        WorldNpServer {
+            storages: self.storages.clone(),
+            geometrical_world: self.geometrical_world.clone(),
+            mechanical_world: self.mechanical_world.clone(),
+            step_accumulator: self.step_accumulator.clone(),
+            rollback: self.rollback.clone(),
+            paused: self.paused.clone(),
+            step_pairs: self.step_pairs.clone(),
+            step_timings: self.step_timings.clone(),
+            step_events: self.step_events.clone(),
+            narrow_phase_dispatchers: self.narrow_phase_dispatchers.clone(),
+            ccd_settings: self.ccd_settings.clone(),
+            events: self.events.clone(),
+            contact_status_events: self.contact_status_events.clone(),
+            activation_regions: self.activation_regions.clone(),
+            lod_settings: self.lod_settings.clone(),
+            pending_config: self.pending_config.clone(),
+            sleep_threshold: self.sleep_threshold.clone(),
+            friction_model: self.friction_model.clone(),
+            async_step: self.async_step.clone(),
+            transforms_snapshot: self.transforms_snapshot.clone(),
+            areas_with_events: self.areas_with_events.clone(),
+            world_bounds: self.world_bounds.clone(),
+        }
+    }
+}
+
+// Accumulator mode
+impl<N: PtReal> WorldNpServer<N> {
+    /// Step the world using a variable frame delta.
+    ///
+    /// The frame delta is accumulated, and the world is stepped 0..N times using the fixed time step
+    /// set with `set_time_step`.
+    /// Returns the number of performed sub steps.
+    ///
+    /// The not yet simulated time is returned by `interpolation_alpha`.
+    pub fn step_accumulated(&self, frame_delta: N) -> usize {
+        if self.is_paused() {
+            // The time elapsed during the pause is not simulated.
+            self.step_accumulator.write().unwrap().reset();
+            return 0;
+        }
+
+        let time_step = self.mechanical_world.read().unwrap().timestep();
+        let sub_steps = self
+            .step_accumulator
+            .write()
+            .unwrap()
+            .accumulate(frame_delta, time_step);
+
+        for _ in 0..sub_steps {
+            self.step();
+        }
+        sub_steps
+    }
+
+    /// Set the maximum number of sub steps that `step_accumulated` can perform for each frame.
+    pub fn set_max_sub_steps(&self, max_sub_steps: usize) {
+        self.step_accumulator
+            .write()
+            .unwrap()
+            .set_max_sub_steps(max_sub_steps);
+    }
+
+    pub fn max_sub_steps(&self) -> usize {
+        self.step_accumulator.read().unwrap().max_sub_steps()
+    }
+
+    /// Returns the leftover of the last `step_accumulated` as a fraction of the time step.
+    ///
+    /// Use this value to interpolate between the previous and the current transforms, with
+    /// `TransformsSnapshot::interpolated`.
+    pub fn interpolation_alpha(&self) -> N {
+        self.step_accumulator.read().unwrap().alpha()
+    }
+}
+
+// Statistics
+impl<N: PtReal> WorldNpServer<N> {
+    /// Returns the statistics of the world.
+    pub fn statistics(&self) -> PhysicsStatistics {
+        let bodies = self.storages.bodies_r();
+
+        let mut statistics = PhysicsStatistics::default();
+        for (key, _) in bodies.iter() {
+            let body = bodies.get_body(key).unwrap();
+            match body.body_data {
+                BodyData::Rigid { .. } => {
+                    statistics.rigid_bodies += 1;
+                    if body.np_body.is_active() {
+                        statistics.active_rigid_bodies += 1;
+                    }
+                }
+                BodyData::Area(..) => {
+                    statistics.areas += 1;
+                }
+                BodyData::Soft { .. } => {
+                    statistics.soft_bodies += 1;
+                }
+            }
+        }
+
+        statistics.colliders = self.storages.colliders_r().len();
+        statistics.shapes = self.storages.shapes_r().len();
+        statistics.joints = self.storages.joints_r().len();
+
+        let step_pairs = self.step_pairs.read().unwrap();
+        statistics.contact_pairs = step_pairs.0;
+        statistics.proximity_pairs = step_pairs.1;
+
+        statistics
+    }
+
+    /// Returns the time spent by each phase of the last step.
+    pub fn step_timings(&self) -> StepTimings {
+        *self.step_timings.read().unwrap()
+    }
+
+    /// Returns the number of events of the last step, and of all the steps.
+    pub fn step_events(&self) -> (usize, u64) {
+        *self.step_events.read().unwrap()
+    }
+}
+
+// CCD
+impl<N: PtReal> WorldNpServer<N> {
+    /// Set the continuous collision detection settings of the world.
+    pub fn set_ccd_settings(&self, settings: CcdSettings) {
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::SetCcdSettings(settings));
+        *self.ccd_settings.write().unwrap() = settings;
+
+        let mut mw = self.mechanical_world.write().unwrap();
+        mw.integration_parameters.max_ccd_substeps = if settings.enabled {
+            settings.max_substeps
+        } else {
+            0
+        };
+        mw.integration_parameters.max_ccd_position_iterations = settings.max_position_iterations;
+    }
+
+    pub fn ccd_settings(&self) -> CcdSettings {
+        *self.ccd_settings.read().unwrap()
+    }
+}
+
+// Narrow phase
+impl<N: PtReal> WorldNpServer<N> {
+    /// Set the function that creates the contact and the proximity dispatchers of the narrow phase.
+    ///
+    /// Use it to support more shape pairs, or to change how the contact manifolds are generated.
+    /// The `GeometricalWorld` is rebuilt, so this must be called before creating any body or area;
+    /// otherwise `PhysicsError::WorldNotEmpty` is returned and nothing is changed.
+    pub fn set_narrow_phase_dispatchers<F>(&self, factory: F) -> PhysicsResult<()>
+    where
+        F: Fn() -> NarrowPhaseDispatchers<N> + Send + Sync + 'static,
+    {
+        if !self.storages.colliders_r().is_empty() {
+            return Err(PhysicsError::WorldNotEmpty);
+        }
+
+        *self.narrow_phase_dispatchers.write().unwrap() = Some(Box::new(factory));
+        *self.geometrical_world.write().unwrap() = self.new_geometrical_world();
+        Ok(())
+    }
+
+    /// Returns a new `GeometricalWorld` that uses the custom narrow phase dispatchers, if any.
+    pub(crate) fn new_geometrical_world(&self) -> GeometricalWorld<N, StoreKey, StoreKey> {
+        if let Some(factory) = &*self.narrow_phase_dispatchers.read().unwrap() {
+            let (contact_dispatcher, proximity_dispatcher) = factory();
+            GeometricalWorld::from_parts(
+                Box::new(DBVTBroadPhase::new(N::from(0.01))),
+                NarrowPhase::new(contact_dispatcher, proximity_dispatcher),
+            )
+        } else {
+            GeometricalWorld::new()
+        }
+    }
+}
+
+// Pause
+impl<N: PtReal> WorldNpServer<N> {
+    /// Pause or resume the world.
+    ///
+    /// When the world is paused, the `step` doesn't integrate the bodies, but still drops the
+    /// destroyed objects and registers the new ones.
+    pub fn set_paused(&self, paused: bool) {
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::SetPaused(paused));
+        *self.paused.write().unwrap() = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.read().unwrap()
+    }
+}
+
+// Rollback
+impl<N: PtReal> WorldNpServer<N> {
+    /// Set the number of steps to record in the rollback buffer.
+    ///
+    /// After each step the position, the velocity and the sleep state of all the bodies are
+    /// recorded, so it's possible to `rewind` the world.
+    /// Set it to 0 (default) to disable the recording.
+    pub fn set_rollback_capacity(&self, capacity: usize) {
+        self.rollback.write().unwrap().set_capacity(capacity);
+    }
+
+    pub fn rollback_capacity(&self) -> usize {
+        self.rollback.read().unwrap().capacity()
+    }
+
+    /// Returns the number of steps that can be rewound.
+    pub fn rollback_len(&self) -> usize {
+        self.rollback.read().unwrap().len().saturating_sub(1)
+    }
+
+    /// Restores the state of the bodies as it was `n` steps ago, dropping the newer records.
+    ///
+    /// The bodies created after that step are not touched.
+    /// Returns `false`, without changing anything, if `n` steps are not recorded.
+    ///
+    /// The running async step is waited, so it's rewound too.
+    pub fn rewind(&self, n: usize) -> bool {
+        self.wait_step_done();
+        let mut rollback = self.rollback.write().unwrap();
+        let frame = rollback.rewind(n);
+        if let Some(frame) = frame {
+            let bodies = self.storages.bodies_r();
+            for state in frame.iter() {
+                if let Some(mut body) = bodies.get_body(state.key) {
+                    if let Some(rb) = body.rigid_body_mut() {
+                        rb.set_position(state.position);
+                        rb.set_velocity(state.velocity);
+                    }
+                    if state.sleeping {
+                        body.np_body.deactivate();
+                    } else {
+                        body.np_body.activate();
+                    }
+                }
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Rewinds the world of `n` steps and re-simulates them.
+    ///
+    /// This is meant to be used after the inputs of the past steps are corrected.
+    pub fn resimulate(&self, n: usize) -> bool {
+        if !self.rewind(n) {
+            return false;
+        }
+        for _ in 0..n {
+            self.step_world(None);
+        }
+        true
+    }
+
+    /// Drops all the recorded steps.
+    pub fn clear_rollback(&self) {
+        self.rollback.write().unwrap().clear();
+    }
+
+    fn record_rollback_frame(&self, bodies: &BodyStorage<N>) {
+        let mut rollback = self.rollback.write().unwrap();
+        if rollback.capacity() == 0 {
+            return;
+        }
+
+        let mut frame = Vec::new();
+        for (key, _) in bodies.iter() {
+            let body = bodies.get_body(key).unwrap();
+            if let Some(rb) = body.rigid_body() {
+                frame.push(BodyState {
+                    key,
+                    position: *rb.position(),
+                    velocity: *rb.velocity(),
+                    sleeping: !body.np_body.is_active(),
+                });
+            }
         }
+        rollback.push(frame);
     }
 }
 
 impl<N: PtReal> WorldNpServer<N> {
-    fn garbage_collect(&self) {
-        let mut gc = self.storages.gc.write().unwrap();
+    /// Drops the destroyed objects now, without waiting the next `step`.
+    ///
+    /// Useful to release the resources while the world is not stepped.
+    pub fn flush_destroyed(&self) {
+        self.garbage_collect();
+
+        let mut mw = self.mechanical_world.write().unwrap();
+        let mut gw = self.geometrical_world.write().unwrap();
+        let mut bodies = self.storages.bodies_w();
+        let mut colliders = self.storages.colliders_w();
+        let mut joints = self.storages.joints_w();
+        mw.maintain(&mut *gw, &mut *bodies, &mut *colliders, &mut *joints);
+    }
+
+    /// Registers in the broad phase, in a single pass, all the colliders created since the last
+    /// step; otherwise they are registered at the beginning of the next step.
+    ///
+    /// Useful to move the cost of a spawn burst, like a streamed level chunk, out of the frame
+    /// that steps the world.
+    pub fn register_inserted_colliders(&self) {
+        let mut mw = self.mechanical_world.write().unwrap();
+        let mut gw = self.geometrical_world.write().unwrap();
+        let mut bodies = self.storages.bodies_w();
+        let mut colliders = self.storages.colliders_w();
+        let mut joints = self.storages.joints_w();
+
+        if colliders.pending_insertions() == 0 {
+            return;
+        }
+        mw.maintain(&mut *gw, &mut *bodies, &mut *colliders, &mut *joints);
+    }
+
+    /// Drops the objects that are no more owned by any `PhysicsHandle`.
+    ///
+    /// The objects are dropped in passes, following their dependencies:
+    /// 1. The joints, releasing the constrained bodies.
+    /// 2. The bodies, the areas and the soft bodies, detaching them from the remaining joints and
+    ///    the force generators, and releasing the colliders and the shapes.
+    /// 3. The shapes that are no more used by any body or area. A shape still in use remains in the
+    ///    garbage collector and is dropped as soon as the last body or area releases it.
+    pub(crate) fn garbage_collect(&self) {
+        let gc = self.storages.gc();
+        let mut gc = gc.write().unwrap();
+
+        // The objects destroyed by `destroy_batch` are pushed again when their handles are dropped.
+        {
+            let bodies_storage = self.storages.bodies_r();
+            let joints_storage = self.storages.joints_r();
+            let shapes_storage = self.storages.shapes_r();
+
+            retain_existing(&mut gc.joints, joint_tag_to_store_key, |key| {
+                joints_storage.get_joint(key).is_some()
+            });
+            retain_existing(&mut gc.bodies, rigid_tag_to_store_key, |key| {
+                bodies_storage.get_body(key).is_some()
+            });
+            retain_existing(&mut gc.areas, area_tag_to_store_key, |key| {
+                bodies_storage.get_body(key).is_some()
+            });
+            retain_existing(&mut gc.shapes, shape_tag_to_store_key, |key| {
+                shapes_storage.get(key).is_some()
+            });
+        }
+
+        // Remove joints
+        {
+            let mut joints_storage = self.storages.joints_w();
+            let bodies_storage = self.storages.bodies_r();
+
+            for j_tag in gc.joints.iter() {
+                self.storages
+                    .replay_recorder
+                    .record(|| ReplayCall::DropJoint(*j_tag));
+                JointNpServer::drop_joint(*j_tag, &mut joints_storage, &bodies_storage);
+            }
+            self.push_events(gc.joints.drain(..).map(PhysicsEvent::JointRemoved));
+
+            // Detach the bodies and the areas that are going to be removed.
+            let mut detached = Vec::new();
+            for rb in gc.bodies.iter() {
+                let joints = JointNpServer::detach_body(
+                    rigid_tag_to_store_key(*rb),
+                    &mut joints_storage,
+                    &bodies_storage,
+                );
+                detached.extend(joints.into_iter().map(|(joint_key, other_key)| {
+                    PhysicsEvent::JointDetached {
+                        joint: store_key_to_joint_tag(joint_key),
+                        removed_body: *rb,
+                        other_body: other_key.map(store_key_to_rigid_tag),
+                    }
+                }));
+            }
+            self.push_events(detached);
+        }
 
+        // Remove bodies and areas
         {
             let mut bodies_storage = self.storages.bodies_w();
             let mut colliders_storage = self.storages.colliders_w();
             let shapes_storage = self.storages.shapes_r();
 
-            for rb in gc.bodies.iter() {
+            let mut force_generators_storage = self.storages.force_generator_w();
+            let bodies_info = self.storages.bodies_info();
+            let mut watch_contacts = self.storages.watch_contacts_w();
+
+            let mut removed = Vec::with_capacity(gc.bodies.len() + gc.areas.len());
+            for rb in gc.bodies.drain(..) {
+                let key = rigid_tag_to_store_key(rb);
+                self.storages
+                    .replay_recorder
+                    .record(|| ReplayCall::DropBody(rb));
                 RBodyNpServer::drop_body(
-                    *rb,
+                    rb,
                     &mut bodies_storage,
                     &mut colliders_storage,
                     &shapes_storage,
                 );
+                force_generators_storage.remove_target(key);
+                watch_contacts.remove(&key);
+                let entity = bodies_info.remove(key).and_then(|info| info.entity);
+                removed.push(PhysicsEvent::RigidBodyRemoved { body: rb, entity });
             }
-            gc.bodies.clear();
 
-            for area in gc.areas.iter() {
+            for area in gc.areas.drain(..) {
+                self.storages
+                    .replay_recorder
+                    .record(|| ReplayCall::DropArea(area));
+                let entity = bodies_storage
+                    .get_body(area_tag_to_store_key(area))
+                    .and_then(|area| area.entity);
                 AreaNpServer::drop_area(
-                    *area,
+                    area,
                     &mut bodies_storage,
                     &mut colliders_storage,
                     &shapes_storage,
                 );
+                removed.push(PhysicsEvent::AreaRemoved { area, entity });
+            }
+            self.push_events(removed);
+
+            for soft_body in self.storages.soft_bodies_gc_w().drain(..) {
+                SoftBodyNpServer::drop_soft_body(
+                    soft_body,
+                    &mut bodies_storage,
+                    &mut colliders_storage,
+                )
+                .or_report(&self.storages);
             }
-            gc.areas.clear();
         }
 
-        // This happen after the bodies and the areas since they depend on this.
+        // Remove shapes
         {
             let mut shapes_storage = self.storages.shapes_w();
-            // Not all shapes can be safely removed since they could be assigned to Rigid Body and Areas.
-            // If a shape is not removed it remains in the garbage collector.
-            let mut removed_shape = Vec::<PhysicsShapeTag>::with_capacity(gc.shapes.len());
-
-            for s in gc.shapes.iter() {
+            let mut removed_shapes = Vec::new();
+            gc.shapes.retain(|s| {
                 if ShapeNpServer::drop_shape(*s, &mut shapes_storage) {
-                    removed_shape.push(*s);
+                    self.storages
+                        .replay_recorder
+                        .record(|| ReplayCall::DropShape(*s));
+                    removed_shapes.push(PhysicsEvent::ShapeRemoved(*s));
+                    false
+                } else {
+                    true
                 }
-            }
-
-            if removed_shape.is_empty() {
-                // Remove from GC only the removed shapes.
-                gc.shapes.retain(|&s| !removed_shape.contains(&s));
-            }
+            });
+            self.push_events(removed_shapes);
         }
+    }
 
-        // Remove joints
-        {
-            let mut joints_storage = self.storages.joints_w();
-            let bodies_storage = self.storages.bodies_r();
+    /// Returns the area and the overlap event it receives, if the proximity event is an area
+    /// that starts or stops to overlap a rigid body.
+    fn overlap_event(
+        e: &ProximityEvent<StoreKey>,
+        colliders: &ColliderStorage<N, StoreKey>,
+    ) -> Option<(StoreKey, OverlapDetail)> {
+        let enter = match (e.prev_status, e.new_status) {
+            (Proximity::Intersecting, Proximity::Intersecting) => return None,
+            (_, Proximity::Intersecting) => true,
+            (Proximity::Intersecting, _) => false,
+            _ => return None,
+        };
 
-            for j_tag in gc.joints.iter() {
-                JointNpServer::drop_joint(*j_tag, &mut joints_storage, &bodies_storage);
-            }
+        let collider1 = colliders.get_collider(e.collider1).unwrap();
+        let collider2 = colliders.get_collider(e.collider2).unwrap();
 
-            gc.joints.clear();
-        }
+        let body_1_ud: &UserData = collider1
+            .user_data()
+            .unwrap()
+            .downcast_ref::<UserData>()
+            .unwrap();
+        let body_2_ud: &UserData = collider2
+            .user_data()
+            .unwrap()
+            .downcast_ref::<UserData>()
+            .unwrap();
+
+        let (area_key, body_ud, collider_key, body_collider) =
+            match (body_1_ud.object_type(), body_2_ud.object_type()) {
+                // The soft bodies don't report overlaps.
+                (ObjectType::SoftBody, _) | (_, ObjectType::SoftBody) => return None,
+                (ObjectType::RigidBody, _) => {
+                    (body_2_ud.store_key(), body_1_ud, e.collider1, &collider1)
+                }
+                (ObjectType::Area, _) => {
+                    (body_1_ud.store_key(), body_2_ud, e.collider2, &collider2)
+                }
+            };
+
+        let body_tag = store_key_to_rigid_tag(body_ud.store_key());
+        let body_entity = body_ud.entity();
+        Some((
+            area_key,
+            OverlapDetail {
+                event: if enter {
+                    OverlapEvent::Enter(body_tag, body_entity)
+                } else {
+                    OverlapEvent::Exit(body_tag, body_entity)
+                },
+                collider: collider_key,
+                body_part: body_collider.body_part(0).1,
+                other: QueryHit::from_user_data(body_ud),
+            },
+        ))
     }
 
     fn fetch_events(
         g_world: &mut GeometricalWorld<N, StoreKey, StoreKey>,
         _m_world: &mut MechanicalWorld<N, StoreKey, StoreKey>, // Not yet used but will be with contact event
         watch_contacts: &WatchContactsRead<'_>,
+        areas_with_events: &mut Vec<StoreKey>,
         bodies: &mut BodiesStorageWrite<'_, N>,
         colliders: &mut CollidersStorageWrite<'_, N>,
     ) {
-        // Clear old events
-        for (_i, b) in bodies.iter_mut() {
-            unsafe {
-                if let BodyData::Area(e) = &mut (*b.0.get()).body_data {
+        // Clear old events, keeping the capacity so the vectors are not reallocated each step.
+        for area_key in areas_with_events.drain(..) {
+            if let Some(mut area) = bodies.get_body(area_key) {
+                if let BodyData::Area(e) = &mut area.body_data {
                     e.clear();
                 }
             }
         }
 
         {
-            // Fetch new events
-            let events = g_world.proximity_events();
-            for e in events {
-                if e.prev_status == e.new_status {
-                    continue;
-                }
+            // The events are classified in parallel, reading only the colliders, then they are
+            // stored in the areas.
+            let events: Vec<_> = g_world.proximity_events().iter().collect();
+            let colliders: &ColliderStorage<N, StoreKey> = colliders;
+            let overlaps: Vec<(StoreKey, OverlapDetail)> = events
+                .par_iter()
+                .filter_map(|e| Self::overlap_event(e, colliders))
+                .collect();
 
-                // 0 Enter, 1 Exit
-                let status = match e.new_status {
-                    Proximity::Intersecting => {
-                        match e.prev_status {
-                            Proximity::Intersecting => {
-                                continue;
-                            }
-                            _ => {
-                                0 // Enter
-                            }
-                        }
-                    }
-                    _ => {
-                        match e.prev_status {
-                            Proximity::Intersecting => {
-                                1 // Exit
-                            }
-                            _ => {
-                                continue;
-                            }
-                        }
-                    }
-                };
-
-                let collider1 = colliders.get_collider(e.collider1).unwrap();
-                let collider2 = colliders.get_collider(e.collider2).unwrap();
-
-                let body_1_ud: &UserData = collider1
-                    .user_data()
-                    .unwrap()
-                    .downcast_ref::<UserData>()
-                    .unwrap();
-                let body_2_ud: &UserData = collider2
-                    .user_data()
-                    .unwrap()
-                    .downcast_ref::<UserData>()
-                    .unwrap();
-
-                let (area_tag, body_key, body_entity) = match body_1_ud.object_type() {
-                    ObjectType::RigidBody => (
-                        body_2_ud.store_key(),
-                        body_1_ud.store_key(),
-                        body_1_ud.entity(),
-                    ),
-                    ObjectType::Area => (
-                        body_1_ud.store_key(),
-                        body_2_ud.store_key(),
-                        body_2_ud.entity(),
-                    ),
-                };
-
-                let mut area = bodies.get_body(area_tag).unwrap();
+            for (area_key, overlap) in overlaps {
+                let mut area = bodies.get_body(area_key).unwrap();
                 if let BodyData::Area(e) = &mut area.body_data {
-                    if status == 0 {
-                        // Enter
-                        e.push(OverlapEvent::Enter(
-                            store_key_to_rigid_tag(body_key),
-                            body_entity,
-                        ));
-                    } else {
-                        // Exit
-                        e.push(OverlapEvent::Exit(
-                            store_key_to_rigid_tag(body_key),
-                            body_entity,
-                        ));
+                    if e.is_empty() {
+                        areas_with_events.push(area_key);
                     }
+                    e.push(overlap);
                 }
             }
         }
@@ -206,14 +713,35 @@ impl<N: PtReal> WorldNpServer<N> {
                     contacts,
                 } = &mut body.body_data
                 {
-                    contacts.clear();
+                    // The contacts of the previous step, used to classify the new ones.
+                    let previous: ContactEvents<N> =
+                        contacts.drain(..).filter(|c| c.is_touching()).collect();
+                    // All the contacts of this step; the cap is applied only when they are
+                    // stored, so the `Stopped` ones are not computed from a truncated list.
+                    let mut current: ContactEvents<N> = ContactEvents::new();
                     if let Some(collider_key) = collider_key {
                         for (c_handle1, collider1, _c_handle2, collider2, _algorithm, c_manifold) in
                             g_world
                                 .contacts_with(&**colliders, collider_key, true)
-                                .unwrap()
+                                .into_iter()
+                                .flatten()
                         {
                             if let Some(contact) = c_manifold.deepest_contact() {
+                                // The contacts with the soft bodies are not reported.
+                                let other = if c_handle1 == collider_key {
+                                    collider2
+                                } else {
+                                    collider1
+                                };
+                                let other = if let Some(other) = QueryHit::from_collider(other) {
+                                    other
+                                } else {
+                                    continue;
+                                };
+                                if matches!(other.object, PhysicsObject::SoftBody(..)) {
+                                    continue;
+                                }
+
                                 let c = if c_handle1 == collider_key {
                                     let body_2_ud: &UserData = collider2
                                         .user_data()
@@ -245,14 +773,53 @@ impl<N: PtReal> WorldNpServer<N> {
                                     }
                                 };
 
-                                contacts.push(c);
-                                if contacts.len() >= *contacts_to_report {
-                                    // Cap reached
-                                    break;
-                                }
+                                let state = if previous
+                                    .iter()
+                                    .any(|p| p.event.other_body == c.other_body)
+                                {
+                                    ContactState::Persisted
+                                } else {
+                                    ContactState::Started
+                                };
+                                current.push(ContactDetail {
+                                    event: c,
+                                    state,
+                                    other,
+                                });
                             }
                         }
                     }
+
+                    // The persisted contacts are stored first, so a body that was within the
+                    // cap stays within it as long as it keeps touching.
+                    contacts.extend(
+                        current
+                            .iter()
+                            .filter(|c| c.state == ContactState::Persisted)
+                            .copied(),
+                    );
+
+                    // The stopped contacts are reported once, with their last known data.
+                    for p in previous {
+                        if !current
+                            .iter()
+                            .any(|c| c.event.other_body == p.event.other_body)
+                        {
+                            contacts.push(ContactDetail {
+                                state: ContactState::Stopped,
+                                ..p
+                            });
+                        }
+                    }
+
+                    // The previous contacts were within the cap, so only the started ones can
+                    // exceed it.
+                    contacts.extend(
+                        current
+                            .into_iter()
+                            .filter(|c| c.state == ContactState::Started),
+                    );
+                    contacts.truncate(*contacts_to_report);
                 } else {
                     panic!();
                 }
@@ -261,9 +828,51 @@ impl<N: PtReal> WorldNpServer<N> {
     }
 }
 
-impl<N: PtReal> WorldPhysicsServerTrait<N> for WorldNpServer<N> {
-    fn step(&self) {
+impl<N: PtReal> WorldNpServer<N> {
+    /// Returns a checksum of the transforms and velocities of all the bodies.
+    ///
+    /// The checksum is stable across platforms, so it can be compared, after each step, among
+    /// the peers of a lockstep multiplayer game to detect desyncs.
+    /// The running async step is waited, so the checksum is of the stepped state.
+    pub fn state_checksum(&self) -> u64 {
+        self.wait_step_done();
+        let bodies = self.storages.bodies_r();
+
+        let mut hasher = StateHasher::new();
+        for (key, _) in bodies.iter() {
+            if let Some(body) = bodies.get_body(key) {
+                hasher.write_key(key);
+                if let Some(rb) = body.rigid_body() {
+                    hasher.write_isometry(rb.position());
+                    hasher.write_vector(&rb.velocity().linear);
+                    hasher.write_vector(&rb.velocity().angular);
+                }
+            }
+        }
+        hasher.finish()
+    }
+
+    /// Step the world using the passed `delta_time`, instead of the one set with `set_time_step`.
+    ///
+    /// The time step set with `set_time_step` is not changed.
+    pub fn step_with_delta(&self, delta_time: N) {
+        self.wait_step_done();
+        self.step_world(Some(delta_time));
+    }
+
+    pub(crate) fn step_world(&self, delta_time: Option<N>) {
+        #[cfg(feature = "validation")]
+        self.storages.validate();
+
+        let step_start = Instant::now();
+        self.clear_events();
+        self.apply_pending_config();
+        self.apply_commands();
         self.garbage_collect();
+        self.storages.replay_recorder.record_step(|| {
+            delta_time.unwrap_or_else(|| self.mechanical_world.read().unwrap().timestep())
+        });
+        let garbage_collection = step_start.elapsed();
 
         let mut mw = self.mechanical_world.write().unwrap();
         let mut gw = self.geometrical_world.write().unwrap();
@@ -274,29 +883,147 @@ impl<N: PtReal> WorldPhysicsServerTrait<N> for WorldNpServer<N> {
         let mut force_generator = self.storages.force_generator_w();
         let watch_contacts = self.storages.watch_contacts_r();
 
-        mw.step(
+        if self.is_paused() {
+            mw.maintain(&mut *gw, &mut *bodies, &mut *colliders, &mut *joints);
+            // Nothing is simulated, but the dropped objects are still notified.
+            self.publish_step_stats(
+                &gw,
+                &colliders,
+                StepTimings {
+                    garbage_collection,
+                    total: step_start.elapsed(),
+                    ..StepTimings::default()
+                },
+            );
+            return;
+        }
+
+        let time_step = mw.timestep();
+        if let Some(delta_time) = delta_time {
+            mw.set_timestep(delta_time);
+        }
+
+        self.apply_activation_regions(&bodies);
+        self.wake_around_static_moves(&gw, &bodies, &colliders);
+        self.apply_lod(&bodies, &mut colliders);
+
+        let mechanical_step_start = Instant::now();
+        mw.step_with_filter(
             &mut *gw,
             &mut *bodies,
             &mut *colliders,
             &mut *joints,
             &mut *force_generator,
+            &ExtendedGroupsFilter,
         );
+        let mechanical_step = mechanical_step_start.elapsed();
 
+        Self::apply_platforms_carrying(&gw, &bodies, &colliders, &mw.gravity, mw.timestep());
+        Self::apply_soft_body_attachments(&bodies);
+
+        force_generator.update_lifetimes(mw.timestep());
+
+        if delta_time.is_some() {
+            mw.set_timestep(time_step);
+        }
+
+        let fetch_events_start = Instant::now();
         Self::fetch_events(
             &mut *gw,
             &mut *mw,
             &watch_contacts,
+            &mut self.areas_with_events.lock().unwrap(),
             &mut bodies,
             &mut colliders,
         );
+        Self::collect_static_contacts(&gw, &watch_contacts, &bodies, &colliders);
+        Self::apply_depenetration(&gw, &bodies, &colliders);
+        self.collect_events(&bodies, &joints);
+        self.collect_contact_status_events(&gw, &colliders);
+        let escaped = self.apply_world_bounds(&bodies);
+        let fetch_events = fetch_events_start.elapsed();
+
+        self.record_rollback_frame(&bodies);
+        self.publish_transforms_snapshot(&bodies);
+        self.publish_step_stats(
+            &gw,
+            &colliders,
+            StepTimings {
+                garbage_collection,
+                mechanical_step,
+                fetch_events,
+                total: step_start.elapsed(),
+            },
+        );
+
+        if !escaped.is_empty() {
+            // The garbage collector is locked before the storages, so they are released first.
+            drop((
+                watch_contacts,
+                force_generator,
+                joints,
+                colliders,
+                bodies,
+                gw,
+                mw,
+            ));
+            self.storages.gc().write().unwrap().bodies.extend(escaped);
+        }
+    }
+
+    /// Publishes the pairs, the events and the timings of the step, read by the statistics.
+    fn publish_step_stats(
+        &self,
+        gw: &GeometricalWorld<N, StoreKey, StoreKey>,
+        colliders: &ColliderStorage<N, StoreKey>,
+        timings: StepTimings,
+    ) {
+        *self.step_pairs.write().unwrap() = (
+            gw.contact_pairs(colliders, true).count(),
+            gw.proximity_pairs(colliders, true).count(),
+        );
+
+        {
+            let events = self.events.read().unwrap().len();
+            let mut step_events = self.step_events.write().unwrap();
+            *step_events = (events, step_events.1 + events as u64);
+        }
+
+        *self.step_timings.write().unwrap() = timings;
+    }
+}
+
+/// Removes the tags of the objects that don't exist anymore, and the duplicates.
+fn retain_existing<T: Copy>(
+    tags: &mut Vec<T>,
+    to_key: impl Fn(T) -> StoreKey,
+    exists: impl Fn(StoreKey) -> bool,
+) {
+    let mut seen = HashSet::new();
+    tags.retain(|tag| {
+        let key = to_key(*tag);
+        exists(key) && seen.insert(key)
+    });
+}
+
+impl<N: PtReal> WorldPhysicsServerTrait<N> for WorldNpServer<N> {
+    fn step(&self) {
+        self.wait_step_done();
+        self.step_world(None);
     }
 
     fn set_time_step(&self, delta_time: N) {
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::SetTimeStep(delta_time));
         let mut mw = self.mechanical_world.write().unwrap();
         mw.set_timestep(delta_time);
     }
 
     fn set_gravity(&self, gravity: &Vector3<N>) {
+        self.storages
+            .replay_recorder
+            .record(|| ReplayCall::SetGravity(*gravity));
         let mut mw = self.mechanical_world.write().unwrap();
         mw.gravity = *gravity;
     }
@@ -306,3 +1033,599 @@ impl<N: PtReal> WorldPhysicsServerTrait<N> for WorldNpServer<N> {
         mw.gravity
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use amethyst_core::{
+        ecs::{Builder, World, WorldExt},
+        math::{Isometry3, Vector3},
+    };
+    use amethyst_physics::servers::{
+        AreaDesc, AreaPhysicsServerTrait, BodyMode, JointDesc, JointPhysicsServerTrait,
+        JointPosition, OverlapEvent, RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc,
+        ShapePhysicsServerTrait, WorldPhysicsServerTrait,
+    };
+    use ncollide3d::{
+        narrow_phase::{
+            ContactAlgorithm, ContactDispatcher, DefaultContactDispatcher,
+            DefaultProximityDispatcher,
+        },
+        shape::Shape,
+    };
+
+    use super::CcdSettings;
+    use crate::{
+        conversors::{joint_tag_to_store_key, rigid_tag_to_store_key},
+        test_world::TestWorld,
+        ColliderTag, ContactState, ExtendedGroups, PhysicsError, PhysicsEvent, PhysicsObject,
+        QueryHit, StepTimings,
+    };
+
+    #[test]
+    fn shape_dropped_when_no_more_used() {
+        let test = TestWorld::<f32>::new();
+
+        let shape = test.shapes.create(&ShapeDesc::Sphere { radius: 1.0 });
+        let body_a = test.bodies.create(&RigidBodyDesc::default());
+        let body_b = test.bodies.create(&RigidBodyDesc::default());
+        test.bodies.set_shape(body_a.get(), Some(shape.get()));
+        test.bodies.set_shape(body_b.get(), Some(shape.get()));
+
+        drop(shape);
+        test.step();
+        assert_eq!(test.storages.shapes_r().len(), 1);
+
+        drop(body_a);
+        test.step();
+        assert_eq!(test.storages.shapes_r().len(), 1);
+        assert_eq!(test.storages.colliders_r().len(), 1);
+
+        drop(body_b);
+        test.step();
+        assert!(test.storages.shapes_r().is_empty());
+        assert!(test.storages.colliders_r().is_empty());
+        assert!(test.storages.gc().read().unwrap().shapes.is_empty());
+    }
+
+    #[test]
+    fn shape_without_bodies_dropped() {
+        let test = TestWorld::<f32>::new();
+
+        let shape = test.shapes.create(&ShapeDesc::Sphere { radius: 1.0 });
+        drop(shape);
+        test.step();
+        assert!(test.storages.shapes_r().is_empty());
+    }
+
+    #[test]
+    fn joint_released_when_body_dropped() {
+        let test = TestWorld::<f32>::new();
+
+        let body_a = test.bodies.create(&RigidBodyDesc::default());
+        let body_b = test.bodies.create(&RigidBodyDesc::default());
+        let joint = test.joints.create(&JointDesc::Fixed, JointPosition::Middle);
+        test.joints.insert_rigid_body(joint.get(), body_a.get());
+        test.joints.insert_rigid_body(joint.get(), body_b.get());
+        test.step();
+
+        let body_a_tag = body_a.get();
+        drop(body_a);
+        test.step();
+        let detached = test.world.drain_events().into_iter().find_map(|e| match e {
+            PhysicsEvent::JointDetached {
+                joint,
+                removed_body,
+                other_body,
+            } => Some((joint, removed_body, other_body)),
+            _ => None,
+        });
+        assert_eq!(
+            detached,
+            Some((joint.get(), body_a_tag, Some(body_b.get())))
+        );
+        {
+            let joints = test.storages.joints_r();
+            let joint = joints
+                .get_joint(joint_tag_to_store_key(joint.get()))
+                .unwrap();
+            assert!(joint.np_joint.is_none());
+            assert!(joint.body_0.is_none());
+        }
+
+        drop(joint);
+        test.step();
+        assert!(test.storages.joints_r().is_empty());
+        let bodies = test.storages.bodies_r();
+        let body_b = bodies
+            .get_body(rigid_tag_to_store_key(body_b.get()))
+            .unwrap();
+        assert!(body_b.joints.is_empty());
+    }
+
+    #[test]
+    fn contacts_reported() {
+        let test = TestWorld::<f32>::new();
+
+        let ground = test.create_ground();
+        test.bodies
+            .set_collider_tag(ground.get(), Some(ColliderTag::Name("ground")));
+
+        let mut sphere_desc = RigidBodyDesc::default();
+        sphere_desc.contacts_to_report = 1;
+        let sphere = test.create_sphere_on_ground(&sphere_desc);
+
+        for _ in 0..30 {
+            test.step();
+        }
+
+        let mut contacts = Vec::new();
+        test.bodies.contact_events(sphere.get(), &mut contacts);
+        assert_eq!(contacts.len(), 1);
+        assert_eq!(contacts[0].other_body, ground.get());
+        assert!(contacts[0].normal.y.abs() > 0.9);
+        assert!(contacts[0].location.y.abs() < 0.1);
+        assert_eq!(
+            test.bodies.contact_details(sphere.get())[0].other,
+            QueryHit {
+                object: PhysicsObject::RigidBody(ground.get()),
+                entity: None,
+                collider_tag: Some(ColliderTag::Name("ground")),
+            }
+        );
+    }
+
+    #[test]
+    fn removed_body_event_carries_entity() {
+        let test = TestWorld::<f32>::new();
+
+        let entity = World::new().create_entity().build();
+        let body = test.bodies.create(&RigidBodyDesc::default());
+        let body_tag = body.get();
+        test.bodies.set_entity(body_tag, Some(entity));
+        test.step();
+        test.world.drain_events();
+
+        drop(body);
+        test.step();
+        let removed = test.world.drain_events().into_iter().find_map(|e| match e {
+            PhysicsEvent::RigidBodyRemoved { body, entity } => Some((body, entity)),
+            _ => None,
+        });
+        assert_eq!(removed, Some((body_tag, Some(entity))));
+    }
+
+    #[test]
+    fn contact_states_classified() {
+        let test = TestWorld::<f32>::new();
+
+        let _ground = test.create_ground();
+
+        let mut sphere_desc = RigidBodyDesc::default();
+        sphere_desc.contacts_to_report = 1;
+        let sphere = test.create_sphere_on_ground(&sphere_desc);
+
+        let states = || {
+            test.bodies
+                .contact_details(sphere.get())
+                .iter()
+                .map(|c| c.state)
+                .collect::<Vec<_>>()
+        };
+
+        for _ in 0..30 {
+            test.step();
+            if !states().is_empty() {
+                break;
+            }
+        }
+        assert_eq!(states(), vec![ContactState::Started]);
+
+        test.step();
+        assert_eq!(states(), vec![ContactState::Persisted]);
+
+        test.bodies
+            .set_transform(sphere.get(), &Isometry3::translation(0.0, 10.0, 0.0));
+        test.step();
+        assert_eq!(states(), vec![ContactState::Stopped]);
+        let mut contacts = Vec::new();
+        test.bodies.contact_events(sphere.get(), &mut contacts);
+        assert!(contacts.is_empty());
+
+        test.step();
+        assert!(states().is_empty());
+    }
+
+    #[test]
+    fn capped_contacts_keep_their_state() {
+        let test = TestWorld::<f32>::new();
+
+        let _ground = test.create_ground();
+        let mut wall_desc = RigidBodyDesc::default();
+        wall_desc.mode = BodyMode::Static;
+        let _wall = test.create_body(
+            &wall_desc,
+            &ShapeDesc::Cube {
+                half_extents: Vector3::new(0.5, 0.5, 0.5),
+            },
+            &Isometry3::translation(1.0, 0.5, 0.0),
+        );
+
+        // The sphere touches both the ground and the wall, but only one contact is reported.
+        let mut sphere_desc = RigidBodyDesc::default();
+        sphere_desc.contacts_to_report = 1;
+        let sphere = test.create_sphere_on_ground(&sphere_desc);
+
+        let states = || {
+            test.bodies
+                .contact_details(sphere.get())
+                .iter()
+                .map(|c| c.state)
+                .collect::<Vec<_>>()
+        };
+
+        for _ in 0..30 {
+            test.step();
+            if !states().is_empty() {
+                break;
+            }
+        }
+        assert_eq!(states(), vec![ContactState::Started]);
+
+        for _ in 0..30 {
+            test.step();
+            assert_eq!(states(), vec![ContactState::Persisted]);
+        }
+    }
+
+    #[test]
+    fn kinematic_reports_static_contacts() {
+        let test = TestWorld::<f32>::new();
+
+        let wall_shape = test.shapes.create(&ShapeDesc::Cube {
+            half_extents: Vector3::new(1.0, 5.0, 5.0),
+        });
+        let mut wall_desc = RigidBodyDesc::default();
+        wall_desc.mode = BodyMode::Static;
+        let wall = test.bodies.create(&wall_desc);
+        test.bodies.set_shape(wall.get(), Some(wall_shape.get()));
+
+        let character_shape = test.shapes.create(&ShapeDesc::Sphere { radius: 0.5 });
+        let mut character_desc = RigidBodyDesc::default();
+        character_desc.mode = BodyMode::Kinematic;
+        character_desc.contacts_to_report = 1;
+        let character = test.bodies.create(&character_desc);
+        test.bodies
+            .set_shape(character.get(), Some(character_shape.get()));
+        test.bodies
+            .set_transform(character.get(), &Isometry3::translation(1.45, 0.0, 0.0));
+        test.bodies
+            .set_report_static_contacts(character.get(), true);
+
+        test.step();
+
+        let mut contacts = Vec::new();
+        test.bodies.contact_events(character.get(), &mut contacts);
+        assert_eq!(contacts.len(), 1);
+        assert_eq!(contacts[0].other_body, wall.get());
+        assert!(contacts[0].normal.x.abs() > 0.9);
+    }
+
+    #[test]
+    fn errors_counted() {
+        let test = TestWorld::<f32>::new();
+
+        let mut desc = RigidBodyDesc::default();
+        desc.mode = BodyMode::Static;
+        let body = test.bodies.create(&desc);
+        for _ in 0..3 {
+            assert!(test.world.island(body.get()).is_empty());
+        }
+        assert_eq!(
+            test.world.error_counters().get(&PhysicsError::NotDynamic),
+            Some(&3)
+        );
+
+        test.world.reset_error_counters();
+        assert!(test.world.error_counters().is_empty());
+    }
+
+    #[test]
+    fn extended_groups_filter_pairs() {
+        let test = TestWorld::<f32>::new();
+
+        let ground = test.create_ground();
+        test.bodies
+            .set_extended_groups(ground.get(), ExtendedGroups::new(&[40], &[40, 41]));
+
+        let sphere = test.create_sphere_on_ground(&RigidBodyDesc::default());
+        test.bodies
+            .set_extended_groups(sphere.get(), ExtendedGroups::new(&[41], &[50]));
+
+        for _ in 0..30 {
+            test.step();
+        }
+
+        // The ground doesn't belong to a layer the sphere collides with, so the sphere falls.
+        assert!(test.bodies.transform(sphere.get()).translation.vector.y < -1.0);
+    }
+
+    #[test]
+    fn area_proximity_margin() {
+        let test = TestWorld::<f32>::new();
+        test.world.set_gravity(&Vector3::zeros());
+
+        let sphere_shape = test.shapes.create(&ShapeDesc::Sphere { radius: 0.5 });
+        let sphere = test.bodies.create(&RigidBodyDesc::default());
+        test.bodies
+            .set_shape(sphere.get(), Some(sphere_shape.get()));
+
+        // Both areas are 0.2 far from the sphere, but only one detects it.
+        let area_shape = test.shapes.create(&ShapeDesc::Cube {
+            half_extents: Vector3::repeat(0.5),
+        });
+        let near = test.areas.create(&AreaDesc::default());
+        test.areas.set_shape(near.get(), Some(area_shape.get()));
+        test.areas
+            .set_transform(near.get(), &Isometry3::translation(1.2, 0.0, 0.0));
+        test.areas.set_proximity_margin(near.get(), 0.5);
+        let exact = test.areas.create(&AreaDesc::default());
+        test.areas.set_shape(exact.get(), Some(area_shape.get()));
+        test.areas
+            .set_transform(exact.get(), &Isometry3::translation(-1.2, 0.0, 0.0));
+        test.areas.set_proximity_margin(exact.get(), 0.0);
+
+        let mut near_events = Vec::new();
+        let mut exact_events = Vec::new();
+        for _ in 0..3 {
+            test.step();
+            near_events.extend(test.areas.overlap_events(near.get()));
+            exact_events.extend(test.areas.overlap_events(exact.get()));
+        }
+        assert_eq!(near_events.len(), 1);
+        assert!(matches!(near_events[0], OverlapEvent::Enter(..)));
+        assert!(exact_events.is_empty());
+    }
+
+    #[test]
+    fn raw_access_to_nphysics_objects() {
+        let test = TestWorld::<f32>::new();
+
+        let body = test.bodies.create(&RigidBodyDesc::default());
+        test.bodies
+            .with_np_rigid_body(body.get(), |rb| {
+                rb.set_linear_velocity(Vector3::new(0.0, 3.0, 0.0))
+            })
+            .unwrap();
+        assert_eq!(
+            test.bodies.linear_velocity(body.get()),
+            Vector3::new(0.0, 3.0, 0.0)
+        );
+        assert_eq!(
+            test.bodies.with_np_collider(body.get(), |_| ()),
+            Err(PhysicsError::ShapeMissing)
+        );
+    }
+
+    #[test]
+    fn test_world_steps_for_the_seconds() {
+        let test = TestWorld::<f32>::new();
+        let body = test.bodies.create(&RigidBodyDesc::default());
+
+        test.step_for(0.5);
+        test.assert_fallen_below(body.get(), 0.0);
+        test.assert_awake(body.get());
+    }
+
+    #[test]
+    fn paused_step_updates_the_stats() {
+        let test = TestWorld::<f32>::new();
+
+        let body = test.bodies.create(&RigidBodyDesc::default());
+        test.step();
+        test.world.set_paused(true);
+        drop(body);
+        test.step();
+
+        let stats = test.world.debug_stats();
+        assert_eq!(stats.step_events, 1);
+        assert_eq!(stats.timings.mechanical_step, Duration::default());
+        assert_eq!(stats.timings.fetch_events, Duration::default());
+
+        test.step();
+        assert_eq!(test.world.step_events().0, 0);
+    }
+
+    #[test]
+    fn step_with_delta_keeps_the_time_step() {
+        let test = TestWorld::<f32>::new();
+        test.world.set_gravity(&Vector3::zeros());
+
+        let body = test.bodies.create(&RigidBodyDesc::default());
+        test.bodies
+            .set_linear_velocity(body.get(), &Vector3::new(1.0, 0.0, 0.0));
+
+        test.world.step_with_delta(0.5);
+        test.assert_position(body.get(), &Vector3::new(0.5, 0.0, 0.0), 1.0e-4);
+
+        test.step();
+        let expected = 0.5 + test.time_step();
+        test.assert_position(body.get(), &Vector3::new(expected, 0.0, 0.0), 1.0e-4);
+    }
+
+    #[test]
+    fn statistics_count_the_objects() {
+        let test = TestWorld::<f32>::new();
+
+        let _ground = test.create_ground();
+        let sphere = test.create_sphere_on_ground(&RigidBodyDesc::default());
+        let shape = test.shapes.create(&ShapeDesc::Sphere { radius: 1.0 });
+        let area = test.areas.create(&AreaDesc::default());
+        test.areas.set_shape(area.get(), Some(shape.get()));
+        test.areas
+            .set_transform(area.get(), &Isometry3::translation(0.0, 20.0, 0.0));
+        let _joint = test.joints.create(&JointDesc::Fixed, JointPosition::Middle);
+        test.step_n(30);
+
+        let statistics = test.world.statistics();
+        assert_eq!(statistics.rigid_bodies, 2);
+        assert!(statistics.active_rigid_bodies <= 2);
+        assert_eq!(statistics.areas, 1);
+        assert_eq!(statistics.soft_bodies, 0);
+        assert_eq!(statistics.colliders, 3);
+        assert_eq!(statistics.shapes, 3);
+        assert_eq!(statistics.joints, 1);
+        assert_eq!(statistics.contact_pairs, 1);
+        assert_eq!(statistics.proximity_pairs, 0);
+
+        drop(sphere);
+        test.step();
+        assert_eq!(test.world.statistics().rigid_bodies, 1);
+    }
+
+    #[test]
+    fn step_timings_measure_the_phases() {
+        let test = TestWorld::<f32>::new();
+        assert_eq!(test.world.step_timings(), StepTimings::default());
+
+        let _ground = test.create_ground();
+        let _sphere = test.create_sphere_on_ground(&RigidBodyDesc::default());
+        test.step();
+
+        let timings = test.world.step_timings();
+        assert!(timings.total > Duration::from_secs(0));
+        assert!(
+            timings.garbage_collection + timings.mechanical_step + timings.fetch_events
+                <= timings.total
+        );
+    }
+
+    #[test]
+    fn custom_contact_dispatcher_used() {
+        struct CountingDispatcher {
+            calls: Arc<AtomicUsize>,
+            dispatcher: DefaultContactDispatcher,
+        }
+
+        impl ContactDispatcher<f32> for CountingDispatcher {
+            fn get_contact_algorithm(
+                &self,
+                a: &dyn Shape<f32>,
+                b: &dyn Shape<f32>,
+            ) -> Option<ContactAlgorithm<f32>> {
+                self.calls.fetch_add(1, Ordering::Relaxed);
+                self.dispatcher.get_contact_algorithm(a, b)
+            }
+        }
+
+        let test = TestWorld::<f32>::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let factory_calls = calls.clone();
+        test.world
+            .set_narrow_phase_dispatchers(move || {
+                (
+                    Box::new(CountingDispatcher {
+                        calls: factory_calls.clone(),
+                        dispatcher: DefaultContactDispatcher::new(),
+                    }),
+                    Box::new(DefaultProximityDispatcher::new()),
+                )
+            })
+            .unwrap();
+
+        let _ground = test.create_ground();
+        let sphere = test.create_sphere_on_ground(&RigidBodyDesc::default());
+        test.step_n(30);
+        assert!(calls.load(Ordering::Relaxed) > 0);
+        assert!(test.position(sphere.get()).y > 0.4);
+
+        assert_eq!(
+            test.world.set_narrow_phase_dispatchers(|| (
+                Box::new(DefaultContactDispatcher::new()),
+                Box::new(DefaultProximityDispatcher::new()),
+            )),
+            Err(PhysicsError::WorldNotEmpty)
+        );
+    }
+
+    #[test]
+    fn ccd_settings_applied_to_the_integration() {
+        let test = TestWorld::<f32>::new();
+
+        let settings = CcdSettings {
+            enabled: true,
+            max_substeps: 4,
+            max_position_iterations: 20,
+        };
+        test.world.set_ccd_settings(settings);
+        assert_eq!(test.world.ccd_settings(), settings);
+        {
+            let mw = test.world.mechanical_world.read().unwrap();
+            assert_eq!(mw.integration_parameters.max_ccd_substeps, 4);
+            assert_eq!(mw.integration_parameters.max_ccd_position_iterations, 20);
+        }
+
+        test.world.set_ccd_settings(CcdSettings {
+            enabled: false,
+            ..settings
+        });
+        let mw = test.world.mechanical_world.read().unwrap();
+        assert_eq!(mw.integration_parameters.max_ccd_substeps, 0);
+    }
+
+    #[test]
+    fn destroyed_objects_flushed_without_stepping() {
+        let test = TestWorld::<f32>::new();
+
+        let shape = test.shapes.create(&ShapeDesc::Sphere { radius: 1.0 });
+        let body = test.bodies.create(&RigidBodyDesc::default());
+        test.bodies.set_shape(body.get(), Some(shape.get()));
+        drop(shape);
+        drop(body);
+        assert_eq!(test.storages.usage().bodies.len, 1);
+
+        test.world.flush_destroyed();
+        assert_eq!(test.storages.usage().bodies.len, 0);
+        assert_eq!(test.storages.usage().colliders.len, 0);
+        assert_eq!(test.storages.usage().shapes.len, 0);
+    }
+
+    #[test]
+    fn events_drained_once() {
+        let test = TestWorld::<f32>::new();
+
+        let _ground = test.create_ground();
+        let mut sphere_desc = RigidBodyDesc::default();
+        sphere_desc.contacts_to_report = 1;
+        let sphere = test.create_sphere_on_ground(&sphere_desc);
+        let area_shape = test.shapes.create(&ShapeDesc::Sphere { radius: 2.0 });
+        let area = test.areas.create(&AreaDesc::default());
+        test.areas.set_shape(area.get(), Some(area_shape.get()));
+
+        let mut events = Vec::new();
+        for _ in 0..30 {
+            test.step();
+            events.extend(test.world.drain_events());
+        }
+        assert!(test.world.drain_events().is_empty());
+
+        assert!(events.iter().any(|e| match e {
+            PhysicsEvent::Contact { body, .. } => *body == sphere.get(),
+            _ => false,
+        }));
+        assert!(events.iter().any(|e| match e {
+            PhysicsEvent::Overlap {
+                area: area_tag,
+                event: OverlapEvent::Enter(body, _),
+            } => *area_tag == area.get() && *body == sphere.get(),
+            _ => false,
+        }));
+    }
+}
@@ -0,0 +1,876 @@
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex},
+};
+
+use amethyst_core::math::{Isometry3, Point3, Vector3};
+use amethyst_physics::{
+    objects::*,
+    servers::{
+        AreaDesc, AreaPhysicsServerTrait, BodyMode, JointDesc, JointPhysicsServerTrait,
+        JointPosition, RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc, ShapePhysicsServerTrait,
+    },
+    PtReal,
+};
+use nphysics3d::world::MechanicalWorld;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    body::BodyData, conversors::*, extended_groups::ExtendedGroups,
+    servers_storage::StorageCapacities, stable_id::StableId, storage::StoreKey,
+    transforms_snapshot::TransformsSnapshot, utils::ColliderTag, AreaNpServer, JointNpServer,
+    RBodyNpServer, ShapeNpServer, WorldNpServer,
+};
+
+/// The key of an object at the time of the snapshot.
+///
+/// It's stored as raw parts of the `StoreKey`, so it's possible to map the objects of the
+/// snapshot to the objects of the restored world.
+pub type SnapshotKey = (usize, u64);
+
+/// The full state of the world.
+///
+/// It can be serialized using any `serde` format and restored using `WorldNpServer::restore`.
+///
+/// The entities are not part of the snapshot; use the `RestoredHandles` to assign them again.
+///
+/// The soft bodies and the force generators, also the springs of the wheels, are not part of the
+/// snapshot either.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorldSnapshot<N: PtReal> {
+    pub gravity: Vector3<N>,
+    pub time_step: N,
+    pub shapes: Vec<ShapeSnapshot<N>>,
+    pub bodies: Vec<BodySnapshot<N>>,
+    pub areas: Vec<AreaSnapshot<N>>,
+    pub joints: Vec<JointSnapshot<N>>,
+    /// The `StableId` that the next created body takes.
+    #[serde(default)]
+    pub next_stable_id: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShapeSnapshot<N: PtReal> {
+    pub key: SnapshotKey,
+    pub desc: ShapeDescSnapshot<N>,
+}
+
+/// Serializable version of the `ShapeDesc`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ShapeDescSnapshot<N: PtReal> {
+    Sphere {
+        radius: N,
+    },
+    Cube {
+        half_extents: Vector3<N>,
+    },
+    Capsule {
+        half_height: N,
+        radius: N,
+    },
+    Cylinder {
+        half_height: N,
+        radius: N,
+    },
+    Plane,
+    Convex {
+        points: Vec<Point3<N>>,
+    },
+    TriMesh {
+        points: Vec<Point3<N>>,
+        indices: Vec<Point3<usize>>,
+    },
+    Compound {
+        shapes: Vec<(Isometry3<N>, ShapeDescSnapshot<N>)>,
+    },
+}
+
+impl<N: PtReal> From<&ShapeDesc<N>> for ShapeDescSnapshot<N> {
+    fn from(desc: &ShapeDesc<N>) -> Self {
+        match desc {
+            ShapeDesc::Sphere { radius } => ShapeDescSnapshot::Sphere { radius: *radius },
+            ShapeDesc::Cube { half_extents } => ShapeDescSnapshot::Cube {
+                half_extents: *half_extents,
+            },
+            ShapeDesc::Capsule {
+                half_height,
+                radius,
+            } => ShapeDescSnapshot::Capsule {
+                half_height: *half_height,
+                radius: *radius,
+            },
+            ShapeDesc::Cylinder {
+                half_height,
+                radius,
+            } => ShapeDescSnapshot::Cylinder {
+                half_height: *half_height,
+                radius: *radius,
+            },
+            ShapeDesc::Plane => ShapeDescSnapshot::Plane,
+            ShapeDesc::Convex { points } => ShapeDescSnapshot::Convex {
+                points: points.clone(),
+            },
+            ShapeDesc::TriMesh { points, indices } => ShapeDescSnapshot::TriMesh {
+                points: points.clone(),
+                indices: indices.clone(),
+            },
+            ShapeDesc::Compound { shapes } => ShapeDescSnapshot::Compound {
+                shapes: shapes
+                    .iter()
+                    .map(|(iso, shape)| (*iso, ShapeDescSnapshot::from(shape)))
+                    .collect(),
+            },
+        }
+    }
+}
+
+impl<N: PtReal> From<&ShapeDescSnapshot<N>> for ShapeDesc<N> {
+    fn from(desc: &ShapeDescSnapshot<N>) -> Self {
+        match desc {
+            ShapeDescSnapshot::Sphere { radius } => ShapeDesc::Sphere { radius: *radius },
+            ShapeDescSnapshot::Cube { half_extents } => ShapeDesc::Cube {
+                half_extents: *half_extents,
+            },
+            ShapeDescSnapshot::Capsule {
+                half_height,
+                radius,
+            } => ShapeDesc::Capsule {
+                half_height: *half_height,
+                radius: *radius,
+            },
+            ShapeDescSnapshot::Cylinder {
+                half_height,
+                radius,
+            } => ShapeDesc::Cylinder {
+                half_height: *half_height,
+                radius: *radius,
+            },
+            ShapeDescSnapshot::Plane => ShapeDesc::Plane,
+            ShapeDescSnapshot::Convex { points } => ShapeDesc::Convex {
+                points: points.clone(),
+            },
+            ShapeDescSnapshot::TriMesh { points, indices } => ShapeDesc::TriMesh {
+                points: points.clone(),
+                indices: indices.clone(),
+            },
+            ShapeDescSnapshot::Compound { shapes } => ShapeDesc::Compound {
+                shapes: shapes
+                    .iter()
+                    .map(|(iso, shape)| (*iso, ShapeDesc::from(shape)))
+                    .collect(),
+            },
+        }
+    }
+}
+
+/// Serializable version of the `BodyMode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BodyModeSnapshot {
+    Disabled,
+    Static,
+    Dynamic,
+    Kinematic,
+}
+
+impl From<BodyMode> for BodyModeSnapshot {
+    fn from(mode: BodyMode) -> Self {
+        match mode {
+            BodyMode::Disabled => BodyModeSnapshot::Disabled,
+            BodyMode::Static => BodyModeSnapshot::Static,
+            BodyMode::Dynamic => BodyModeSnapshot::Dynamic,
+            BodyMode::Kinematic => BodyModeSnapshot::Kinematic,
+        }
+    }
+}
+
+impl From<BodyModeSnapshot> for BodyMode {
+    fn from(mode: BodyModeSnapshot) -> Self {
+        match mode {
+            BodyModeSnapshot::Disabled => BodyMode::Disabled,
+            BodyModeSnapshot::Static => BodyMode::Static,
+            BodyModeSnapshot::Dynamic => BodyMode::Dynamic,
+            BodyModeSnapshot::Kinematic => BodyMode::Kinematic,
+        }
+    }
+}
+
+/// Serializable version of the `ColliderTag`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColliderTagSnapshot {
+    Id(u32),
+    Name(String),
+}
+
+impl From<ColliderTag> for ColliderTagSnapshot {
+    fn from(tag: ColliderTag) -> Self {
+        match tag {
+            ColliderTag::Id(id) => ColliderTagSnapshot::Id(id),
+            ColliderTag::Name(name) => ColliderTagSnapshot::Name(name.to_owned()),
+        }
+    }
+}
+
+impl From<&ColliderTagSnapshot> for ColliderTag {
+    /// The `ColliderTag` keeps a `&'static str`, so each distinct name is leaked the first time
+    /// it's restored, and then reused by all the threads.
+    fn from(tag: &ColliderTagSnapshot) -> Self {
+        static NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+        match tag {
+            ColliderTagSnapshot::Id(id) => ColliderTag::Id(*id),
+            ColliderTagSnapshot::Name(name) => {
+                let mut names = NAMES.lock().unwrap();
+                if let Some(name) = names.get(name.as_str()) {
+                    ColliderTag::Name(*name)
+                } else {
+                    let name: &'static str = Box::leak(name.clone().into_boxed_str());
+                    names.insert(name);
+                    ColliderTag::Name(name)
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BodySnapshot<N: PtReal> {
+    pub key: SnapshotKey,
+    pub mode: BodyModeSnapshot,
+    pub mass: N,
+    pub friction: N,
+    pub bounciness: N,
+    pub belong_to: Vec<u8>,
+    pub collide_with: Vec<u8>,
+    pub lock_translation: [bool; 3],
+    pub lock_rotation: [bool; 3],
+    pub contacts_to_report: usize,
+    pub transform: Isometry3<N>,
+    pub linear_velocity: Vector3<N>,
+    pub angular_velocity: Vector3<N>,
+    pub sleeping: bool,
+    pub shape: Option<SnapshotKey>,
+    #[serde(default)]
+    pub stable_id: StableId,
+    /// The shape set with `RBodyNpServer::set_lod_shape`.
+    #[serde(default)]
+    pub lod_shape: Option<SnapshotKey>,
+    /// The maximum distance set with `RBodyNpServer::set_depenetration`.
+    #[serde(default)]
+    pub depenetration: Option<N>,
+    /// Set with `RBodyNpServer::set_carried_by_platforms`.
+    #[serde(default)]
+    pub carried_by_platforms: bool,
+    /// Set with `RBodyNpServer::set_report_static_contacts`.
+    #[serde(default)]
+    pub report_static_contacts: bool,
+    #[serde(default)]
+    pub extended_groups: ExtendedGroups,
+    #[serde(default)]
+    pub collider_tag: Option<ColliderTagSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AreaSnapshot<N: PtReal> {
+    pub key: SnapshotKey,
+    pub belong_to: Vec<u8>,
+    pub collide_with: Vec<u8>,
+    pub transform: Isometry3<N>,
+    pub shape: Option<SnapshotKey>,
+    #[serde(default)]
+    pub stable_id: StableId,
+    #[serde(default)]
+    pub extended_groups: ExtendedGroups,
+    /// The margin set with `AreaNpServer::set_proximity_margin`.
+    #[serde(default)]
+    pub proximity_margin: Option<N>,
+}
+
+/// Serializable version of the `JointDesc`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JointDescSnapshot {
+    Fixed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JointSnapshot<N: PtReal> {
+    pub key: SnapshotKey,
+    pub desc: JointDescSnapshot,
+    /// `None` when the joint is positioned in the middle of the two bodies.
+    pub initial_position: Option<Isometry3<N>>,
+    /// The anchors set with `JointNpServer::create_with_anchors`.
+    #[serde(default)]
+    pub local_anchors: Option<[Isometry3<N>; 2]>,
+    /// The axes set with `JointNpServer::create_suspension`.
+    #[serde(default)]
+    pub suspension_axes: Option<[Vector3<N>; 2]>,
+    pub body_0: Option<SnapshotKey>,
+    pub body_1: Option<SnapshotKey>,
+}
+
+/// The handles of the objects created by `WorldNpServer::restore`.
+///
+/// Each handle is paired with the tag that the object had when the snapshot was taken.
+///
+/// Dropping the handles drops the restored objects; so make sure to store them.
+#[allow(missing_debug_implementations)]
+pub struct RestoredHandles {
+    pub shapes: Vec<(PhysicsShapeTag, PhysicsHandle<PhysicsShapeTag>)>,
+    pub bodies: Vec<(PhysicsRigidBodyTag, PhysicsHandle<PhysicsRigidBodyTag>)>,
+    pub areas: Vec<(PhysicsAreaTag, PhysicsHandle<PhysicsAreaTag>)>,
+    pub joints: Vec<(PhysicsJointTag, PhysicsHandle<PhysicsJointTag>)>,
+}
+
+fn to_snapshot_key(key: StoreKey) -> SnapshotKey {
+    key.into_raw_parts()
+}
+
+fn from_snapshot_key(key: SnapshotKey) -> StoreKey {
+    StoreKey::from_raw_parts(key.0, key.1)
+}
+
+fn groups_to_snapshot(groups: &[CollisionGroup]) -> Vec<u8> {
+    groups.iter().map(|g| g.get()).collect()
+}
+
+fn groups_from_snapshot(groups: &[u8]) -> Vec<CollisionGroup> {
+    groups.iter().map(|g| CollisionGroup::new(*g)).collect()
+}
+
+impl<N: PtReal> WorldNpServer<N> {
+    /// Returns a snapshot of the full state of the world.
+    ///
+    /// The garbage collector is flushed before taking the snapshot, so the objects waiting to be
+    /// dropped are not part of it. The running async step is waited, so it's part of it.
+    pub fn snapshot(&self) -> WorldSnapshot<N> {
+        self.wait_step_done();
+        self.garbage_collect();
+
+        let mw = self.mechanical_world.read().unwrap();
+        let bodies = self.storages.bodies_r();
+        let shapes = self.storages.shapes_r();
+        let joints = self.storages.joints_r();
+
+        let mut snapshot = WorldSnapshot {
+            gravity: mw.gravity,
+            time_step: mw.timestep(),
+            shapes: Vec::new(),
+            bodies: Vec::new(),
+            areas: Vec::new(),
+            joints: Vec::new(),
+            next_stable_id: bodies.next_stable_id(),
+        };
+
+        for (key, _) in shapes.iter() {
+            if let Some(shape) = shapes.get(key) {
+                snapshot.shapes.push(ShapeSnapshot {
+                    key: to_snapshot_key(key),
+                    desc: ShapeDescSnapshot::from(&*shape.shape_desc()),
+                });
+            }
+        }
+
+        for (key, _) in bodies.iter() {
+            let body = bodies.get_body(key).unwrap();
+            // The soft bodies are not part of the snapshot.
+            if matches!(body.body_data, BodyData::Soft { .. }) {
+                continue;
+            }
+            let rb = body.rigid_body().unwrap();
+
+            match &body.body_data {
+                BodyData::Rigid {
+                    contacts_to_report, ..
+                } => {
+                    let lock_translation = rb.kinematic_translations();
+                    let lock_rotation = rb.kinematic_rotations();
+                    snapshot.bodies.push(BodySnapshot {
+                        key: to_snapshot_key(key),
+                        mode: body_mode_conversor::from_physics(body.np_body.status()).into(),
+                        mass: rb.local_inertia().linear,
+                        friction: body.friction,
+                        bounciness: body.bounciness,
+                        belong_to: groups_to_snapshot(&body.belong_to),
+                        collide_with: groups_to_snapshot(&body.collide_with),
+                        lock_translation: [
+                            lock_translation.x,
+                            lock_translation.y,
+                            lock_translation.z,
+                        ],
+                        lock_rotation: [lock_rotation.x, lock_rotation.y, lock_rotation.z],
+                        contacts_to_report: *contacts_to_report,
+                        transform: *rb.position(),
+                        linear_velocity: rb.velocity().linear,
+                        angular_velocity: rb.velocity().angular,
+                        sleeping: !body.np_body.is_active(),
+                        shape: body.shape_key.map(to_snapshot_key),
+                        stable_id: body.stable_id,
+                        lod_shape: body.lod_shape_key.map(to_snapshot_key),
+                        depenetration: body.depenetration,
+                        carried_by_platforms: body.carried_by_platforms,
+                        report_static_contacts: body.report_static_contacts,
+                        extended_groups: body.extended_groups,
+                        collider_tag: body.collider_tag.map(ColliderTagSnapshot::from),
+                    });
+                }
+                BodyData::Area(..) => {
+                    snapshot.areas.push(AreaSnapshot {
+                        key: to_snapshot_key(key),
+                        belong_to: groups_to_snapshot(&body.belong_to),
+                        collide_with: groups_to_snapshot(&body.collide_with),
+                        transform: *rb.position(),
+                        shape: body.shape_key.map(to_snapshot_key),
+                        stable_id: body.stable_id,
+                        extended_groups: body.extended_groups,
+                        proximity_margin: body.proximity_margin,
+                    });
+                }
+                BodyData::Soft { .. } => unreachable!(),
+            }
+        }
+
+        for (key, _) in joints.iter() {
+            let joint = joints.get_joint(key).unwrap();
+            snapshot.joints.push(JointSnapshot {
+                key: to_snapshot_key(key),
+                desc: match joint.joint_desc {
+                    JointDesc::Fixed => JointDescSnapshot::Fixed,
+                },
+                initial_position: match joint.initial_position {
+                    JointPosition::Exact(pos) => Some(pos),
+                    JointPosition::Middle => None,
+                },
+                local_anchors: joint.local_anchors,
+                suspension_axes: joint.suspension_axes,
+                body_0: joint.body_0.map(|b| to_snapshot_key(b.0)),
+                body_1: joint.body_1.map(|b| to_snapshot_key(b.0)),
+            });
+        }
+
+        snapshot
+    }
+
+    /// Drops the current state of the world and restores the passed snapshot.
+    ///
+    /// The storages, the `GeometricalWorld` and the `MechanicalWorld` are rebuilt, so all the tags
+    /// created before this call become invalid.
+    /// Use the returned `RestoredHandles` to map the tags of the snapshot to the new objects.
+    ///
+    /// The soft bodies and the force generators are dropped and, since they are not part of the
+    /// snapshot, not restored; their tags stay invalid, so create them again if needed.
+    ///
+    /// The running async step is waited before dropping the state.
+    pub fn restore(&self, snapshot: &WorldSnapshot<N>) -> RestoredHandles {
+        self.restore_with_capacities(snapshot, None)
+    }
+
+    /// Like `restore`, but when `capacities` is some the cleared storages are shrunk to them.
+    pub(crate) fn restore_with_capacities(
+        &self,
+        snapshot: &WorldSnapshot<N>,
+        capacities: Option<StorageCapacities>,
+    ) -> RestoredHandles {
+        self.wait_step_done();
+        self.clear_world(snapshot.gravity, snapshot.time_step, capacities);
+
+        let shape_server = ShapeNpServer::new(self.storages.clone());
+        let body_server = RBodyNpServer::new(self.storages.clone());
+        let area_server = AreaNpServer::new(self.storages.clone());
+        let joint_server = JointNpServer::new(self.storages.clone());
+
+        let mut handles = RestoredHandles {
+            shapes: Vec::with_capacity(snapshot.shapes.len()),
+            bodies: Vec::with_capacity(snapshot.bodies.len()),
+            areas: Vec::with_capacity(snapshot.areas.len()),
+            joints: Vec::with_capacity(snapshot.joints.len()),
+        };
+
+        for s in snapshot.shapes.iter() {
+            let handle = shape_server.create(&ShapeDesc::from(&s.desc));
+            handles
+                .shapes
+                .push((store_key_to_shape_tag(from_snapshot_key(s.key)), handle));
+        }
+
+        let find_shape = |handles: &RestoredHandles, key: Option<SnapshotKey>| {
+            key.and_then(|key| {
+                let tag = store_key_to_shape_tag(from_snapshot_key(key));
+                handles
+                    .shapes
+                    .iter()
+                    .find(|(old_tag, _)| *old_tag == tag)
+                    .map(|(_, handle)| handle.get())
+            })
+        };
+
+        for b in snapshot.bodies.iter() {
+            let mut desc = RigidBodyDesc::default();
+            desc.mode = b.mode.into();
+            desc.mass = b.mass;
+            desc.friction = b.friction;
+            desc.bounciness = b.bounciness;
+            desc.belong_to = groups_from_snapshot(&b.belong_to);
+            desc.collide_with = groups_from_snapshot(&b.collide_with);
+            desc.lock_translation_x = b.lock_translation[0];
+            desc.lock_translation_y = b.lock_translation[1];
+            desc.lock_translation_z = b.lock_translation[2];
+            desc.lock_rotation_x = b.lock_rotation[0];
+            desc.lock_rotation_y = b.lock_rotation[1];
+            desc.lock_rotation_z = b.lock_rotation[2];
+            desc.contacts_to_report = b.contacts_to_report;
+
+            let handle = body_server.create(&desc);
+            body_server.set_transform(handle.get(), &b.transform);
+            body_server.set_linear_velocity(handle.get(), &b.linear_velocity);
+            body_server.set_angular_velocity(handle.get(), &b.angular_velocity);
+            body_server.set_shape(handle.get(), find_shape(&handles, b.shape));
+            body_server.set_lod_shape(handle.get(), find_shape(&handles, b.lod_shape));
+            body_server.set_depenetration(handle.get(), b.depenetration);
+            body_server.set_carried_by_platforms(handle.get(), b.carried_by_platforms);
+            body_server.set_report_static_contacts(handle.get(), b.report_static_contacts);
+            body_server.set_extended_groups(handle.get(), b.extended_groups);
+            body_server
+                .set_collider_tag(handle.get(), b.collider_tag.as_ref().map(ColliderTag::from));
+            self.restore_stable_id(rigid_tag_to_store_key(handle.get()), b.stable_id);
+
+            if b.sleeping {
+                let bodies = self.storages.bodies_r();
+                if let Some(mut body) = bodies.get_body(rigid_tag_to_store_key(handle.get())) {
+                    body.np_body.deactivate();
+                }
+            }
+
+            handles
+                .bodies
+                .push((store_key_to_rigid_tag(from_snapshot_key(b.key)), handle));
+        }
+
+        for a in snapshot.areas.iter() {
+            let mut desc = AreaDesc::default();
+            desc.belong_to = groups_from_snapshot(&a.belong_to);
+            desc.collide_with = groups_from_snapshot(&a.collide_with);
+
+            let handle = area_server.create(&desc);
+            area_server.set_transform(handle.get(), &a.transform);
+            area_server.set_shape(handle.get(), find_shape(&handles, a.shape));
+            area_server.set_extended_groups(handle.get(), a.extended_groups);
+            if let Some(margin) = a.proximity_margin {
+                area_server.set_proximity_margin(handle.get(), margin);
+            }
+            self.restore_stable_id(area_tag_to_store_key(handle.get()), a.stable_id);
+
+            handles
+                .areas
+                .push((store_key_to_area_tag(from_snapshot_key(a.key)), handle));
+        }
+
+        for j in snapshot.joints.iter() {
+            let desc = match j.desc {
+                JointDescSnapshot::Fixed => JointDesc::Fixed,
+            };
+            let initial_position = match j.initial_position {
+                Some(pos) => JointPosition::Exact(pos),
+                None => JointPosition::Middle,
+            };
+
+            let handle = match (&j.local_anchors, &j.suspension_axes) {
+                (Some([anchor_0, anchor_1]), Some([slide_axis, axle])) => {
+                    joint_server.create_suspension(anchor_0, anchor_1, slide_axis, axle)
+                }
+                (Some([anchor_0, anchor_1]), None) => {
+                    joint_server.create_with_anchors(&desc, anchor_0, anchor_1)
+                }
+                _ => joint_server.create(&desc, initial_position),
+            };
+            for body_key in j.body_0.iter().chain(j.body_1.iter()) {
+                let tag = store_key_to_rigid_tag(from_snapshot_key(*body_key));
+                if let Some((_, body_handle)) =
+                    handles.bodies.iter().find(|(old_tag, _)| *old_tag == tag)
+                {
+                    joint_server.insert_rigid_body(handle.get(), body_handle.get());
+                }
+            }
+
+            handles
+                .joints
+                .push((store_key_to_joint_tag(from_snapshot_key(j.key)), handle));
+        }
+
+        self.storages
+            .bodies_w()
+            .reserve_stable_ids(snapshot.next_stable_id);
+
+        handles
+    }
+
+    /// Gives back to the restored body its `StableId`; the snapshots taken before the ids were
+    /// introduced don't have them, so the body keeps the new one.
+    fn restore_stable_id(&self, key: StoreKey, stable_id: StableId) {
+        if stable_id != StableId::default() {
+            self.storages.bodies_w().set_stable_id(key, stable_id);
+        }
+    }
+
+    /// Drops all the objects and rebuilds the `GeometricalWorld` and the `MechanicalWorld`.
+    ///
+    /// The integration parameters of the `MechanicalWorld` are preserved.
+    /// When `capacities` is some, the storages are replaced with new ones of that capacities.
+    fn clear_world(
+        &self,
+        gravity: Vector3<N>,
+        time_step: N,
+        capacities: Option<StorageCapacities>,
+    ) {
+        let mut mw = self.mechanical_world.write().unwrap();
+        let mut gw = self.geometrical_world.write().unwrap();
+
+        {
+            // The objects are dropped all together, so the pending ones are no more meaningful.
+            let gc = self.storages.gc();
+            let mut gc = gc.write().unwrap();
+            gc.bodies.clear();
+            gc.areas.clear();
+            gc.shapes.clear();
+            gc.joints.clear();
+        }
+
+        // The objects are removed one by one, so the keys of the new objects never match the ones
+        // of the dropped objects.
+        self.storages.joints_w().clear();
+        self.storages.colliders_w().clear();
+        self.storages.bodies_w().clear();
+        self.storages.force_generator_w().clear();
+        self.storages.bodies_info().clear();
+        self.storages.shapes_w().clear();
+        self.storages.watch_contacts_w().clear();
+        self.storages.static_moves_w().clear();
+        self.storages.soft_bodies_gc_w().clear();
+
+        if let Some(capacities) = capacities {
+            self.storages.shrink_to(capacities);
+        }
+
+        let integration_parameters = mw.integration_parameters.clone();
+        *mw = MechanicalWorld::new(gravity);
+        mw.integration_parameters = integration_parameters;
+        mw.set_timestep(time_step);
+        self.friction_model.read().unwrap().install(&mut mw);
+        *gw = self.new_geometrical_world();
+
+        // The recorded keys are no more valid.
+        self.clear_rollback();
+        self.areas_with_events.lock().unwrap().clear();
+        *self.transforms_snapshot.write().unwrap() = Arc::new(TransformsSnapshot::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{Isometry3, Vector3};
+    use amethyst_physics::servers::{
+        AreaDesc, AreaPhysicsServerTrait, JointDesc, JointPhysicsServerTrait, JointPosition,
+        RBodyPhysicsServerTrait, RigidBodyDesc, ShapeDesc, ShapePhysicsServerTrait,
+    };
+
+    use super::ColliderTagSnapshot;
+    use crate::{
+        conversors::joint_tag_to_store_key, test_world::TestWorld, utils::UserData, ColliderTag,
+        ExtendedGroups, ForceGeneratorNpServer, WindDesc,
+    };
+
+    #[test]
+    fn restored_world_simulates_like_the_original() {
+        let test = TestWorld::<f32>::new();
+
+        let _ground = test.create_ground();
+        let shape = test.shapes.create(&ShapeDesc::Sphere { radius: 0.5 });
+        let body_a = test.bodies.create(&RigidBodyDesc::default());
+        let body_b = test.bodies.create(&RigidBodyDesc::default());
+        test.bodies.set_shape(body_a.get(), Some(shape.get()));
+        test.bodies.set_shape(body_b.get(), Some(shape.get()));
+        test.bodies
+            .set_transform(body_a.get(), &Isometry3::translation(0.0, 2.0, 0.0));
+        test.bodies
+            .set_transform(body_b.get(), &Isometry3::translation(1.5, 2.0, 0.0));
+        test.bodies
+            .set_linear_velocity(body_b.get(), &Vector3::new(0.0, 0.0, 1.0));
+        let joint = test.joints.create(&JointDesc::Fixed, JointPosition::Middle);
+        test.joints.insert_rigid_body(joint.get(), body_a.get());
+        test.joints.insert_rigid_body(joint.get(), body_b.get());
+        let area = test.areas.create(&AreaDesc::default());
+        test.areas.set_shape(area.get(), Some(shape.get()));
+        test.areas
+            .set_transform(area.get(), &Isometry3::translation(0.0, 5.0, 0.0));
+
+        // The bodies are still in the air, so there are no contacts to warm start.
+        test.step_n(5);
+
+        let restored = TestWorld::<f32>::new();
+        let handles = restored.world.restore(&test.world.snapshot());
+        assert_eq!(handles.shapes.len(), 1);
+        assert_eq!(handles.joints.len(), 1);
+        assert_eq!(handles.areas.len(), 1);
+        let (_, restored_area) = &handles.areas[0];
+        assert_eq!(
+            restored.areas.transform(restored_area.get()),
+            test.areas.transform(area.get())
+        );
+
+        test.step_n(60);
+        restored.step_n(60);
+
+        assert_eq!(handles.bodies.len(), 3);
+        for (tag, restored_body) in handles.bodies.iter() {
+            let restored_body = restored_body.get();
+            restored.assert_position(restored_body, &test.position(*tag), 1.0e-4);
+            restored.assert_linear_velocity(
+                restored_body,
+                &test.bodies.linear_velocity(*tag),
+                1.0e-4,
+            );
+            let angular_velocity = restored.bodies.angular_velocity(restored_body)
+                - test.bodies.angular_velocity(*tag);
+            assert!(angular_velocity.norm() <= 1.0e-4);
+        }
+    }
+
+    #[test]
+    fn body_settings_restored() {
+        let test = TestWorld::<f32>::new();
+
+        let shape = test.shapes.create(&ShapeDesc::Sphere { radius: 0.5 });
+        let lod_shape = test.shapes.create(&ShapeDesc::Sphere { radius: 0.4 });
+        let body = test.bodies.create(&RigidBodyDesc::default());
+        test.bodies.set_shape(body.get(), Some(shape.get()));
+        test.bodies.set_lod_shape(body.get(), Some(lod_shape.get()));
+        test.bodies.set_depenetration(body.get(), Some(0.1));
+        test.bodies.set_carried_by_platforms(body.get(), true);
+        test.bodies.set_report_static_contacts(body.get(), true);
+        test.bodies
+            .set_extended_groups(body.get(), ExtendedGroups::new(&[40], &[41]));
+        test.bodies
+            .set_collider_tag(body.get(), Some(ColliderTag::Name("hitbox")));
+
+        let restored = TestWorld::<f32>::new();
+        let handles = restored.world.restore(&test.world.snapshot());
+        let restored_shape = |tag| {
+            handles
+                .shapes
+                .iter()
+                .find(|(old_tag, _)| *old_tag == tag)
+                .map(|(_, handle)| handle.get())
+        };
+        let restored_body = handles.bodies[0].1.get();
+
+        assert_eq!(
+            restored.bodies.lod_shape(restored_body),
+            restored_shape(lod_shape.get())
+        );
+        assert_eq!(restored.bodies.depenetration(restored_body), Some(0.1));
+        assert!(restored.bodies.is_carried_by_platforms(restored_body));
+        assert!(restored.bodies.reports_static_contacts(restored_body));
+        assert_eq!(
+            restored.bodies.extended_groups(restored_body),
+            ExtendedGroups::new(&[40], &[41])
+        );
+        // The broad phase reads the groups from the collider user data.
+        let user_data = restored
+            .bodies
+            .with_np_collider(restored_body, |collider| {
+                collider
+                    .user_data()
+                    .and_then(|data| data.downcast_ref::<UserData>())
+                    .cloned()
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            user_data.extended_groups(),
+            ExtendedGroups::new(&[40], &[41])
+        );
+        assert_eq!(
+            restored.bodies.collider_tag(restored_body),
+            Some(ColliderTag::Name("hitbox"))
+        );
+        assert_eq!(user_data.collider_tag(), Some(ColliderTag::Name("hitbox")));
+    }
+
+    #[test]
+    fn area_settings_restored() {
+        let test = TestWorld::<f32>::new();
+
+        let shape = test.shapes.create(&ShapeDesc::Sphere { radius: 0.5 });
+        let area = test.areas.create(&AreaDesc::default());
+        test.areas.set_shape(area.get(), Some(shape.get()));
+        test.areas
+            .set_extended_groups(area.get(), ExtendedGroups::new(&[40], &[41]));
+        test.areas.set_proximity_margin(area.get(), 0.3);
+
+        let restored = TestWorld::<f32>::new();
+        let handles = restored.world.restore(&test.world.snapshot());
+        let restored_area = handles.areas[0].1.get();
+
+        assert_eq!(
+            restored.areas.extended_groups(restored_area),
+            ExtendedGroups::new(&[40], &[41])
+        );
+        assert_eq!(restored.areas.proximity_margin(restored_area), Some(0.3));
+    }
+
+    #[test]
+    fn suspension_joint_restored() {
+        let test = TestWorld::<f32>::new();
+
+        let body_a = test.bodies.create(&RigidBodyDesc::default());
+        let body_b = test.bodies.create(&RigidBodyDesc::default());
+        let anchor = Isometry3::translation(0.0, -1.0, 0.0);
+        let joint = test.joints.create_suspension(
+            &anchor,
+            &Isometry3::identity(),
+            &Vector3::y(),
+            &Vector3::x(),
+        );
+        test.joints.insert_rigid_body(joint.get(), body_a.get());
+        test.joints.insert_rigid_body(joint.get(), body_b.get());
+
+        let restored = TestWorld::<f32>::new();
+        let handles = restored.world.restore(&test.world.snapshot());
+        let (_, restored_joint) = &handles.joints[0];
+        let joints = restored.storages.joints_r();
+        let restored_joint = joints
+            .get_joint(joint_tag_to_store_key(restored_joint.get()))
+            .unwrap();
+        assert_eq!(
+            restored_joint.local_anchors,
+            Some([anchor, Isometry3::identity()])
+        );
+        assert_eq!(
+            restored_joint.suspension_axes,
+            Some([Vector3::y(), Vector3::x()])
+        );
+        assert!(restored_joint.np_joint.is_some());
+    }
+
+    #[test]
+    fn restore_drops_the_force_generators() {
+        let test = TestWorld::<f32>::new();
+        let force_generators = ForceGeneratorNpServer::new(test.storages.clone());
+
+        force_generators.create_wind(&WindDesc::default());
+        assert_eq!(test.storages.usage().force_generators.len, 1);
+
+        let snapshot = test.world.snapshot();
+        let _handles = test.world.restore(&snapshot);
+        assert_eq!(test.storages.usage().force_generators.len, 0);
+    }
+
+    #[test]
+    fn collider_tag_names_shared_by_the_threads() {
+        let to_name = || match ColliderTag::from(&ColliderTagSnapshot::Name("wheel".to_owned())) {
+            ColliderTag::Name(name) => name.as_ptr() as usize,
+            ColliderTag::Id(_) => unreachable!(),
+        };
+        let here = to_name();
+        let there = std::thread::spawn(to_name).join().unwrap();
+        assert_eq!(here, there);
+    }
+}